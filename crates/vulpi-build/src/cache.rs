@@ -0,0 +1,121 @@
+//! Incremental compilation. Every module that compiles without diagnostics leaves its interfaces
+//! and its lowered code inside of the build directory, so the next compilations can skip the
+//! modules whose sources and dependencies did not change. A module is only reused while the
+//! interfaces of its imports are the same that it was checked against.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use vulpi_ir::inline;
+use vulpi_location::FileId;
+use vulpi_resolver::{dependencies::Dependencies, Module};
+use vulpi_syntax::lambda;
+use vulpi_typer::module::Exported;
+use vulpi_vfs::path::Path;
+
+/// Modules stored by other versions of the compiler are never reused.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A module that was compiled before.
+#[derive(Serialize, Deserialize)]
+pub struct Compiled {
+    version: String,
+
+    /// The modification time of the source when it was compiled, as seconds and nanoseconds.
+    modified: (i64, u32),
+
+    /// The hash of the source, so touching a file without changing it does not invalidate it.
+    hash: u64,
+
+    pub dependencies: Dependencies,
+
+    /// The fingerprint of the interfaces of the module.
+    pub fingerprint: u64,
+
+    /// The fingerprints of the interfaces of the imported modules when the module was checked.
+    pub imports: Vec<(Path, u64)>,
+
+    /// The namespace built by the resolver.
+    pub module: Module,
+
+    /// Everything that the type checker declared for the module and its submodules.
+    pub interfaces: Exported,
+
+    /// The module lowered to the intermediate representation.
+    pub program: lambda::Program,
+}
+
+fn hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hashes the interfaces of a module, so the modules that import it can tell if it changed. The
+/// maps of the interfaces are sorted by the JSON values, so the same interfaces always have the
+/// same fingerprint.
+pub fn fingerprint(interfaces: &Exported) -> u64 {
+    serde_json::to_value(interfaces)
+        .map(|value| hash(&value.to_string()))
+        .unwrap_or_default()
+}
+
+impl Compiled {
+    pub fn new(
+        modified: FileTime,
+        source: &str,
+        dependencies: Dependencies,
+        imports: Vec<(Path, u64)>,
+        module: Module,
+        interfaces: Exported,
+        program: lambda::Program,
+    ) -> Self {
+        Self {
+            version: VERSION.to_string(),
+            modified: (modified.unix_seconds(), modified.nanoseconds()),
+            hash: hash(source),
+            dependencies,
+            fingerprint: fingerprint(&interfaces),
+            imports,
+            module,
+            interfaces,
+            program,
+        }
+    }
+
+    /// Checks if the source did not change since the module was compiled. The source is only
+    /// read when the modification time is different.
+    pub fn is_fresh(&self, modified: FileTime, source: impl FnOnce() -> Option<String>) -> bool {
+        if self.version != VERSION {
+            return false;
+        }
+
+        if self.modified == (modified.unix_seconds(), modified.nanoseconds()) {
+            return true;
+        }
+
+        source().is_some_and(|source| hash(&source) == self.hash)
+    }
+    /// The spans of the program point to the id that the source had in the compilation that
+    /// stored it, so they are moved to the id that it has now.
+    pub fn relocate(&mut self, file: FileId) {
+        let relocate = Rc::new(move |expr: &mut lambda::ExprKind| {
+            if let lambda::ExprKind::Application(_, _, Some(span)) = expr {
+                span.file = file;
+            }
+        });
+
+        for (_, decl) in &mut self.program.lets {
+            if let Some(span) = &mut decl.span {
+                span.file = file;
+            }
+
+            inline::traverse(&mut decl.body, relocate.clone());
+        }
+    }
+}
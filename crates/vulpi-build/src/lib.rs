@@ -1,32 +1,120 @@
 //! Facilities to build a entire crate of vulpi files. This module is responsible for building the
 //! crate from the source files and resolving the modules.
 
-use std::{collections::HashMap, path::PathBuf, fs::File, rc::Rc, cell::RefCell};
+use std::{collections::{HashMap, HashSet}, path::PathBuf, rc::Rc, cell::RefCell};
 
 use resw::Writer;
 use vulpi_intern::Symbol;
-use vulpi_ir::{transform, inline, dead_code, uncurry};
+use vulpi_ir::{transform::{self, Transform}, inline, dead_code, uncurry, perceus, print, simplify, unsupported};
 use vulpi_location::{FileId, Span};
-use vulpi_report::Report;
+use vulpi_report::{Diagnostic, Report};
 
 use vulpi_resolver::{
     cycle::DepHolder,
     dependencies::{self, Dependencies},
+    index::Index,
     Context, Module,
 };
 
-use vulpi_show::Show;
-use vulpi_syntax::concrete::tree::Program;
-use vulpi_typer::declare::{Programs, Declare};
+use vulpi_syntax::{concrete::tree::Program, elaborated, lambda, r#abstract::Qualified};
+use vulpi_typer::{declare::{Programs, Declare}, module::Exported, real::Real, Type};
 use vulpi_vfs::{path::Path, FileSystem};
 
+use cache::Compiled;
+
+pub mod cache;
+pub mod manifest;
+pub mod memory;
 pub mod real;
+pub mod repl;
+pub mod source_map;
 
 pub enum Interface {
-    Compiled(Module, Dependencies),
+    Compiled(Box<Compiled>),
     Uncompiled(Program),
 }
 
+/// The result of running the front-end of the compiler over a project.
+pub struct Checked {
+    pub programs: Vec<elaborated::Program<Type<Real>>>,
+
+    /// Where every name was used and declared.
+    pub names: vulpi_resolver::index::Index,
+
+    /// The types that were inferred for expressions and bindings.
+    pub types: vulpi_typer::index::Index,
+}
+
+/// The top-level definitions of a compiled project by the name that they have in the generated
+/// JavaScript, so errors at runtime can be traced back to the source.
+#[derive(Default)]
+pub struct Symbols {
+    names: HashMap<String, (Qualified, Option<Span>)>,
+}
+
+impl Symbols {
+    fn add(&mut self, name: &Qualified, decl: &lambda::LetDecl) {
+        // The uncurrying pass creates a copy of the function with the `uncurried` suffix.
+        let original = Qualified {
+            path: name.path.clone(),
+            name: name
+                .name
+                .get()
+                .strip_suffix(".uncurried")
+                .map(Symbol::intern)
+                .unwrap_or_else(|| name.name.clone()),
+        };
+
+        self.names
+            .insert(name.mangle(), (original, decl.span.clone()));
+    }
+
+    /// Finds the definition that generated a JavaScript function. Definitions created by the
+    /// compiler have no place.
+    pub fn get(&self, name: &str) -> Option<&(Qualified, Option<Span>)> {
+        // The bodies of the functions that call each other through a trampoline have the `$step`
+        // suffix.
+        self.names
+            .get(name)
+            .or_else(|| self.names.get(name.strip_suffix("$step")?))
+    }
+}
+
+/// A module that was checked from its source, with everything that is needed to store it in the
+/// cache.
+struct Fresh {
+    path: Path,
+    file: FileId,
+    dependencies: Dependencies,
+    module: Module,
+    interfaces: Exported,
+
+    /// The fingerprints of the interfaces of the imported modules.
+    imports: Vec<(Path, u64)>,
+}
+
+/// The output of the front-end together with the modules that were loaded from the cache.
+struct Front {
+    checked: Checked,
+
+    /// The lowered programs of the modules that were not checked again.
+    cached: Vec<(Path, lambda::Program)>,
+
+    /// The modules that were checked, in the same order as the programs.
+    fresh: Vec<Fresh>,
+}
+
+/// A module together with all of its submodules.
+fn with_submodules(module: &Module) -> Vec<Module> {
+    let mut modules = vec![module.clone()];
+
+    for submodule in module.submodules() {
+        modules.extend(with_submodules(&submodule));
+    }
+
+    modules
+}
+
 pub struct ProjectCompiler<FS: FileSystem> {
     pub name: Symbol,
     pub fs: FS,
@@ -47,75 +135,253 @@ impl<FS: FileSystem> ProjectCompiler<FS> {
         vulpi_parser::parse(self.reporter.clone(), id, &source)
     }
 
+    /// Loads the module in `path` from the cache if its source in `source` did not change since
+    /// it was stored.
+    fn cached(&mut self, path: &Path, source: FS::Path) -> Option<Compiled> {
+        let modified = self.fs.modification_time(source.clone()).ok()?;
+
+        let id = self.fs.load(self.fs.from_cached_path(path.clone())).ok()?;
+        let content = self.fs.read(id);
+        self.fs.unload(id).ok()?;
+
+        let mut compiled: Compiled = serde_json::from_str(&content.ok()?).ok()?;
+
+        let fresh = compiled.is_fresh(modified, || {
+            let id = self.fs.load(source.clone()).ok()?;
+            self.fs.read(id).ok()
+        });
+
+        if !fresh {
+            return None;
+        }
+
+        compiled.relocate(self.fs.load(source).ok()?);
+
+        Some(compiled)
+    }
+
+    /// Stores a module that was checked without diagnostics, so the next compilations can skip
+    /// it. Failing to store it only makes the next compilation slower, so errors are ignored.
+    fn store(&mut self, fresh: Fresh, program: &lambda::Program) -> Option<()> {
+        if !self.reporter.diagnostics(fresh.file).is_empty() {
+            return None;
+        }
+
+        let source = self.fs.path(fresh.file).ok()?.clone();
+        let modified = self.fs.modification_time(source).ok()?;
+        let content = self.fs.read(fresh.file).ok()?;
+
+        let compiled = Compiled::new(
+            modified,
+            &content,
+            fresh.dependencies,
+            fresh.imports,
+            fresh.module,
+            fresh.interfaces,
+            program.clone(),
+        );
+
+        let path = self.fs.from_cached_path(fresh.path);
+
+        let id = match self.fs.load(path.clone()) {
+            Ok(id) => id,
+            Err(_) => self.fs.create(path).ok()?,
+        };
+
+        self.fs
+            .store(id, serde_json::to_string(&compiled).ok()?)
+            .ok()?;
+        self.fs.write(id).ok()?;
+        self.fs.unload(id).ok()
+    }
+
+    /// Finds every module that is imported, directly or not, by `deps`. When `incremental` is
+    /// set, the modules are loaded from the cache when possible.
     pub fn find_dependencies(
         &mut self,
         bag: &mut HashMap<Path, (Interface, Dependencies)>,
         deps: Dependencies,
+        incremental: bool,
     ) {
         for (path, span) in deps.imported {
             if !bag.contains_key(&path) {
-                if let Some(id) = self.load(span.clone(), self.fs.from_src_path(path.clone())) {
+                let source = self.fs.from_src_path(path.clone());
+
+                let cached = if incremental {
+                    self.cached(&path, source.clone())
+                } else {
+                    None
+                };
+
+                if let Some(compiled) = cached {
+                    let deps = compiled.dependencies.clone();
+                    bag.insert(
+                        path.clone(),
+                        (Interface::Compiled(Box::new(compiled)), deps.clone()),
+                    );
+                    self.find_dependencies(bag, deps, incremental);
+                } else if let Some(id) = self.load(span.clone(), source) {
                     let program = self.parse(id);
                     let deps = dependencies::dependencies(self.name.clone(), &program);
                     bag.insert(path.clone(), (Interface::Uncompiled(program), deps.clone()));
-                    self.find_dependencies(bag, deps);
+                    self.find_dependencies(bag, deps, incremental);
                 }
             }
         }
     }
 
-    pub fn compile(&mut self, module: Symbol, path: FS::Path, output: PathBuf) {
-        // TODO: Fix this error :( I can't now because it would require changes
-        // to the vulpi-report module. Good luck Sofia from the future!
+    /// Parses again every cached module that imports a module that is not cached, or whose
+    /// interfaces changed since the module was checked, until only modules with cached
+    /// dependencies are left in the cache. Modules whose sources cannot be loaded anymore are
+    /// removed, so the modules that import them report it.
+    fn invalidate(
+        &mut self,
+        bag: &mut HashMap<Path, (Interface, Dependencies)>,
+        root: &Path,
+        source: FS::Path,
+    ) {
+        loop {
+            let stale: Vec<_> = bag
+                .iter()
+                .filter(|(_, (interface, deps))| match interface {
+                    Interface::Compiled(compiled) => {
+                        deps.imported.iter().any(|(path, _)| {
+                            !matches!(bag.get(path), Some((Interface::Compiled(_), _)))
+                        }) || compiled.imports.iter().any(|(path, fingerprint)| {
+                            matches!(
+                                bag.get(path),
+                                Some((Interface::Compiled(other), _))
+                                    if other.fingerprint != *fingerprint
+                            )
+                        })
+                    }
+                    Interface::Uncompiled(_) => false,
+                })
+                .map(|(path, _)| path.clone())
+                .collect();
+
+            if stale.is_empty() {
+                break;
+            }
 
-        let root = self.fs.load(path).unwrap();
-        let parsed = self.parse(root);
+            for path in stale {
+                let source = if &path == root {
+                    source.clone()
+                } else {
+                    self.fs.from_src_path(path.clone())
+                };
 
-        let path = Path {
-            segments: vec![module.clone(), Symbol::intern("Main")],
-        };
+                let Ok(id) = self.fs.load(source) else {
+                    bag.remove(&path);
+                    continue;
+                };
+
+                let program = self.parse(id);
 
+                bag.get_mut(&path).unwrap().0 = Interface::Uncompiled(program);
+            }
+        }
+    }
+
+    /// Runs the front-end of the compiler (parsing, resolution and type checking) over the file
+    /// in `path` and every module that it depends on. The file is registered as the module
+    /// `root`. All the diagnostics are sent to the reporter and the elaborated programs are
+    /// returned together with the indexes that were built along the way. Every module is checked
+    /// from its source, so the indexes know about all of them.
+    pub fn check(&mut self, root: Path, path: FS::Path) -> Checked {
+        self.front(root, path, false).checked
+    }
+
+    fn front(&mut self, root: Path, path: FS::Path, incremental: bool) -> Front {
         let mut bag = HashMap::new();
-        let deps = dependencies::dependencies(self.name.clone(), &parsed);
-        bag.insert(path.clone(), (Interface::Uncompiled(parsed), deps.clone()));
 
-        self.find_dependencies(&mut bag, deps);
+        let cached = if incremental {
+            self.cached(&root, path.clone())
+        } else {
+            None
+        };
+
+        let deps = if let Some(compiled) = cached {
+            let deps = compiled.dependencies.clone();
+            bag.insert(
+                root.clone(),
+                (Interface::Compiled(Box::new(compiled)), deps.clone()),
+            );
+            deps
+        } else {
+            // TODO: Fix this error :( I can't now because it would require changes
+            // to the vulpi-report module. Good luck Sofia from the future!
+            let id = self.fs.load(path.clone()).unwrap();
+            let parsed = self.parse(id);
+
+            let deps = dependencies::dependencies(self.name.clone(), &parsed);
+            bag.insert(root.clone(), (Interface::Uncompiled(parsed), deps.clone()));
+            deps
+        };
+
+        self.find_dependencies(&mut bag, deps, incremental);
+        self.invalidate(&mut bag, &root, path);
 
         let mut modules = HashMap::new();
+        let mut cached = vec![];
+        let mut fingerprints = HashMap::new();
 
         let available: Rc<RefCell<HashMap<Path, Module>>> = Default::default();
+        let index: Rc<RefCell<Index>> = Default::default();
 
         for (path, (program, deps)) in bag {
             match program {
-                Interface::Compiled(module, _) => {
-                    modules.insert(path, (module, None, deps));
+                Interface::Compiled(compiled) => {
+                    let compiled = *compiled;
+                    fingerprints.insert(path.clone(), compiled.fingerprint);
+                    cached.push((path.clone(), compiled.interfaces, compiled.program));
+                    modules.insert(path, (compiled.module, None, deps));
                 }
                 Interface::Uncompiled(parsed) => {
-                    let context = Context::new(available.clone(), path.clone(), self.reporter.clone());
+                    let file = parsed.eof.value.span.file;
+
+                    let context = Context::new(
+                        available.clone(),
+                        index.clone(),
+                        path.clone(),
+                        self.reporter.clone(),
+                    );
                     let solved = vulpi_resolver::resolve(&context, parsed);
                     modules.insert(
                         path,
-                        (context.module.clone(), Some((context, solved)), deps),
+                        (context.module.clone(), Some((file, context, solved)), deps),
                     );
                 }
             }
         }
 
+        // The submodules of the modules that were resolved are already available, but the ones
+        // loaded from the cache are not.
         for (module, _, _) in modules.values() {
-            let path = module.name().clone();
-            let mut borrow_mut = available.borrow_mut();
-            borrow_mut.insert(path, module.clone());
+            for module in with_submodules(module) {
+                let path = module.name().clone();
+                let mut borrow_mut = available.borrow_mut();
+                borrow_mut.insert(path, module);
+            }
         }
 
         let mut programs = vec![];
+        let mut checked = vec![];
 
         let mut dep = DepHolder::default();
 
-        for (_, ctx, _) in modules.into_values() {
-            if let Some((ctx, resolver)) = ctx {
+        // Paths of submodules are imported too but they are not files, so only the modules that
+        // were found are kept as the dependencies that invalidate a module in the cache.
+        let found: HashSet<_> = modules.keys().cloned().collect();
+
+        for (path, (module, ctx, mut deps)) in modules {
+            if let Some((file, ctx, resolver)) = ctx {
                 let program = resolver.eval(ctx.clone());
                 dep.register(&program);
                 programs.push(program);
+
+                deps.imported.retain(|(path, _)| found.contains(path));
+                checked.push((path, file, deps, module));
             }
         }
 
@@ -124,26 +390,275 @@ impl<FS: FileSystem> ProjectCompiler<FS> {
         let mut ctx = vulpi_typer::Context::new(self.reporter.clone());
         let env = vulpi_typer::Env::default();
 
+        let cached = cached
+            .into_iter()
+            .map(|(path, interfaces, program)| {
+                ctx.modules.import(interfaces);
+                (path, program)
+            })
+            .collect();
+
         let programs = Programs(programs);
-        println!("{}", programs.0[0].show());
 
         Declare::declare(&programs, (&mut ctx, env.clone()));
         let programs = Declare::define(&programs, (&mut ctx, env));
 
-        
-        if !self.reporter.has_errors() {
-            let mut res = transform::Transform::transform(&vulpi_ir::transform::Programs(programs), &mut Default::default());
-            
-            uncurry::uncurry(&mut res);
-            inline::inline(&mut res);
+        let mut fresh: Vec<_> = if incremental {
+            checked
+                .into_iter()
+                .map(|(path, file, dependencies, module)| {
+                    let names: Vec<_> = with_submodules(&module)
+                        .iter()
+                        .map(|module| module.name().symbol())
+                        .collect();
+
+                    let interfaces = ctx.modules.export(&names);
+                    fingerprints.insert(path.clone(), cache::fingerprint(&interfaces));
+
+                    Fresh {
+                        interfaces,
+                        path,
+                        file,
+                        dependencies,
+                        module,
+                        imports: vec![],
+                    }
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        for fresh in &mut fresh {
+            fresh.imports = fresh
+                .dependencies
+                .imported
+                .iter()
+                .filter_map(|(path, _)| Some((path.clone(), *fingerprints.get(path)?)))
+                .collect();
+        }
+
+        Front {
+            checked: Checked {
+                programs,
+                names: index.take(),
+                types: ctx.index,
+            },
+            cached,
+            fresh,
+        }
+    }
+
+    /// Checks and lowers the project. The modules that did not change since the last compilation
+    /// are loaded from the build directory instead of being checked again, and the ones that were
+    /// checked without diagnostics are stored there. The programs come together with the paths
+    /// of their modules and nothing is returned if there are errors.
+    fn lower(&mut self, root: Path, path: FS::Path) -> Option<(Vec<Path>, Vec<lambda::Program>)> {
+        let Front {
+            checked,
+            cached,
+            fresh,
+        } = self.front(root, path, true);
+
+        if self.reporter.has_errors() {
+            return None;
+        }
+
+        let mut context = transform::Context::default();
+
+        for (_, program) in &cached {
+            for (name, (def, size)) in &program.definitions {
+                context.add_constructor(name.clone(), def.clone(), *size);
+            }
+        }
+
+        let mut res = transform::Programs(checked.programs).transform(&mut context);
+        let mut paths = vec![];
+
+        for (fresh, program) in fresh.into_iter().zip(&res) {
+            paths.push(fresh.path.clone());
+            self.store(fresh, program);
+        }
+
+        let (cached_paths, cached): (Vec<_>, Vec<_>) = cached.into_iter().unzip();
+
+        paths.extend(cached_paths);
+        res.extend(cached);
+
+        uncurry::uncurry(&mut res);
+        inline::inline(&mut res);
+        simplify::simplify(&mut res);
+
+        Some((paths, res))
+    }
+
+    /// Writes the generated code to `output` together with its source map, that goes next to it
+    /// with the `.map` extension.
+    fn write(&self, code: Vec<u8>, spans: &[Span], output: &std::path::Path)
+    where
+        FS::Path: AsRef<std::path::Path>,
+    {
+        let code = String::from_utf8(code).unwrap();
+        let (mut code, mappings) = vulpi_js::source_map::extract(&code, spans);
+
+        let mut map = output.to_path_buf().into_os_string();
+        map.push(".map");
+        let map = PathBuf::from(map);
+
+        if let Some(name) = map.file_name() {
+            code.push_str(&format!(
+                "\n//# sourceMappingURL={}\n",
+                name.to_string_lossy()
+            ));
+        }
+
+        std::fs::write(&map, source_map::generate(&self.fs, output, mappings)).unwrap();
+        std::fs::write(output, code).unwrap();
+    }
+
+    /// Compiles the project to a single JavaScript file in `output`. The `root` is the module in
+    /// the file at `path`, where the compilation starts. The source map of the output is written
+    /// next to it, with the `.map` extension.
+    pub fn compile(&mut self, root: Path, path: FS::Path, output: PathBuf) -> Symbols
+    where
+        FS::Path: AsRef<std::path::Path>,
+    {
+        let mut symbols = Symbols::default();
+
+        if let Some((_, mut res)) = self.lower(root, path) {
             dead_code::dead_code_remove(&mut res);
-            
-            let js = vulpi_js::Transform::transform(vulpi_js::Programs(res), &mut Default::default());
-            let f = File::create(output).unwrap();
-            let mut w = Writer::new(f);
 
-            w.write_program(&js).unwrap();
+            for program in &res {
+                for (name, decl) in &program.lets {
+                    symbols.add(name, decl);
+                }
+            }
+
+            let mut context = vulpi_js::Context::with_source_map();
+            let js = vulpi_js::Transform::transform(vulpi_js::Programs(res), &mut context);
+
+            let mut code = vec![];
+            Writer::new(&mut code).write_program(&js).unwrap();
+
+            self.write(code, &context.take_spans(), &output);
         }
-        
+
+        symbols
+    }
+
+    /// Compiles the project to one ES module for each module of the project, in the directory
+    /// `output`. The module `A.B` goes to `A/B.mjs` and exports its public declarations, and the
+    /// code that all the modules share goes to `runtime.mjs`.
+    pub fn compile_esm(&mut self, root: Path, path: FS::Path, output: PathBuf) -> Symbols
+    where
+        FS::Path: AsRef<std::path::Path>,
+    {
+        let mut symbols = Symbols::default();
+
+        if let Some((paths, mut res)) = self.lower(root, path) {
+            dead_code::dead_code_remove_private(&mut res);
+
+            for program in &res {
+                for (name, decl) in &program.lets {
+                    symbols.add(name, decl);
+                }
+            }
+
+            let modules = paths.iter().map(Path::symbol).zip(res).collect();
+
+            let mut context = vulpi_js::Context::with_source_map();
+            let js = vulpi_js::Transform::transform(vulpi_js::esm::Modules(modules), &mut context);
+
+            let mut code = vec![];
+            Writer::new(&mut code).write_program(&js.runtime).unwrap();
+            std::fs::create_dir_all(&output).unwrap();
+            std::fs::write(output.join(vulpi_js::esm::RUNTIME), code).unwrap();
+
+            for module in js.modules {
+                let file = output.join(vulpi_js::esm::file(&module.name));
+
+                if let Some(parent) = file.parent() {
+                    std::fs::create_dir_all(parent).unwrap();
+                }
+
+                let mut code = vec![];
+                let mut writer = Writer::new(&mut code);
+                writer.write_program(&module.program).unwrap();
+
+                self.write(code, &module.spans, &file);
+            }
+        }
+
+        symbols
+    }
+
+    /// Compiles the project to C in the file `output`, and writes the runtime that it includes
+    /// to the same directory.
+    pub fn compile_c(&mut self, root: Path, path: FS::Path, output: PathBuf) {
+        if let Some((_, mut res)) = self.lower(root, path) {
+            dead_code::dead_code_remove(&mut res);
+
+            let code = match vulpi_c::generate(res) {
+                Ok(code) => code,
+                Err(errors) => {
+                    for error in errors {
+                        self.reporter.report(Diagnostic::new(error));
+                    }
+                    return;
+                }
+            };
+
+            let runtime = output.with_file_name(vulpi_c::RUNTIME);
+
+            std::fs::write(runtime, vulpi_c::RUNTIME_CODE).unwrap();
+            std::fs::write(output, code).unwrap();
+        }
+    }
+
+    /// Checks and lowers the project to run it with an interpreter, and returns the programs
+    /// together with the name of the `main` of the module `root`. Nothing is returned if there are
+    /// errors, or if the declarations that can run use effects, because the interpreter has no
+    /// handlers.
+    pub fn eval(
+        &mut self,
+        root: Path,
+        path: FS::Path,
+    ) -> Option<(Qualified, Vec<lambda::Program>)> {
+        let main = Qualified {
+            path: root.symbol(),
+            name: Symbol::intern("main"),
+        };
+
+        let (_, mut res) = self.lower(root, path)?;
+        dead_code::dead_code_remove(&mut res);
+
+        let errors = unsupported::effects_in(&res, "the interpreter");
+
+        if !errors.is_empty() {
+            for error in errors {
+                self.reporter.report(Diagnostic::new(error));
+            }
+            return None;
+        }
+
+        Some((main, res))
+    }
+
+    /// Lowers the project and counts the references of its values, and returns the lambda IR of
+    /// every module as text, to inspect the result. Nothing is returned if there are errors.
+    pub fn emit_lambda(&mut self, root: Path, path: FS::Path) -> Option<String> {
+        let (paths, mut res) = self.lower(root, path)?;
+
+        dead_code::dead_code_remove(&mut res);
+        let borrowed = perceus::perceus(&mut res);
+
+        let mut out = String::new();
+
+        for (path, program) in paths.iter().zip(&res) {
+            out.push_str(&format!("// module {}\n\n", path.symbol().get()));
+            out.push_str(&print::program(program, &borrowed));
+        }
+
+        Some(out)
     }
 }
@@ -0,0 +1,243 @@
+//! The manifest of a package. It lives in the `vulpi.toml` file at the root of the package and
+//! declares the name of the package, where its sources are, where the output goes and the other
+//! local packages that it depends on.
+//!
+//! ```toml
+//! [package]
+//! name = "App"
+//! entry = "Main"
+//! source = "src"
+//! output = "build"
+//!
+//! [dependencies]
+//! Yal = { path = "../yal" }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+/// The name of the file of the manifest.
+pub const FILE_NAME: &str = "vulpi.toml";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub package: Package,
+
+    /// The packages that this one depends on. The key is the name of the package, that is the
+    /// first segment of the paths that import its modules.
+    #[serde(default)]
+    pub dependencies: HashMap<String, Dependency>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Package {
+    pub name: String,
+
+    /// The module that contains the `main` function, relative to the package.
+    #[serde(default = "default_entry")]
+    pub entry: String,
+
+    /// The directory of the sources, relative to the manifest.
+    #[serde(default = "default_source")]
+    pub source: PathBuf,
+
+    /// The directory of the compiled files, relative to the manifest.
+    #[serde(default = "default_output")]
+    pub output: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dependency {
+    /// The directory of the package, relative to the manifest that depends on it.
+    pub path: PathBuf,
+}
+
+fn default_entry() -> String {
+    "Main".to_string()
+}
+
+fn default_source() -> PathBuf {
+    PathBuf::from(".")
+}
+
+fn default_output() -> PathBuf {
+    PathBuf::from("build")
+}
+
+pub enum Error {
+    NotFound(PathBuf),
+    Invalid(PathBuf, toml::de::Error),
+    InvalidName(PathBuf, String),
+    WrongName(PathBuf, String, String),
+    Conflict(String, PathBuf, PathBuf),
+    Cycle(Vec<String>),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound(path) => write!(f, "cannot find '{}'", path.display()),
+            Error::Invalid(path, err) => {
+                write!(f, "invalid manifest '{}': {}", path.display(), err)
+            }
+            Error::InvalidName(path, name) => write!(
+                f,
+                "the package name '{}' in '{}' is not a module name",
+                name,
+                path.display()
+            ),
+            Error::WrongName(path, expected, found) => write!(
+                f,
+                "expected the package in '{}' to be called '{}' but it's called '{}'",
+                path.display(),
+                expected,
+                found
+            ),
+            Error::Conflict(name, first, second) => write!(
+                f,
+                "there are two packages called '{}', in '{}' and in '{}'",
+                name,
+                first.display(),
+                second.display()
+            ),
+            Error::Cycle(names) => write!(
+                f,
+                "the packages depend on each other: {}",
+                names.join(" -> ")
+            ),
+        }
+    }
+}
+
+/// Package names are the first segment of module paths, so they have to look like one.
+fn is_module_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars.next().is_some_and(|x| x.is_uppercase())
+        && chars.all(|x| x.is_alphanumeric() || matches!(x, '_' | '\''))
+}
+
+impl Manifest {
+    /// Reads the manifest of the package in the directory `root`.
+    pub fn load(root: &Path) -> Result<Manifest, Error> {
+        let path = root.join(FILE_NAME);
+        let content = fs::read_to_string(&path).map_err(|_| Error::NotFound(path.clone()))?;
+        let manifest: Manifest =
+            toml::from_str(&content).map_err(|err| Error::Invalid(path.clone(), err))?;
+
+        if !is_module_name(&manifest.package.name) {
+            return Err(Error::InvalidName(path, manifest.package.name));
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// A package together with the directory where it was found.
+pub struct Located {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Located {
+    pub fn name(&self) -> &str {
+        &self.manifest.package.name
+    }
+
+    pub fn source(&self) -> PathBuf {
+        let source = self.root.join(&self.manifest.package.source);
+        source.canonicalize().unwrap_or(source)
+    }
+
+    pub fn output(&self) -> PathBuf {
+        self.root.join(&self.manifest.package.output)
+    }
+}
+
+/// A package and every package that it depends on, directly or not.
+pub struct Graph {
+    pub root: Located,
+    pub dependencies: Vec<Located>,
+}
+
+impl Graph {
+    /// Reads the manifest in the directory `root` and the manifests of all of its dependencies.
+    pub fn load(root: &Path) -> Result<Graph, Error> {
+        let root = root
+            .canonicalize()
+            .map_err(|_| Error::NotFound(root.to_path_buf()))?;
+        let manifest = Manifest::load(&root)?;
+
+        let mut found = HashMap::new();
+        let mut stack = vec![manifest.package.name.clone()];
+        let mut dependencies = vec![];
+
+        found.insert(manifest.package.name.clone(), root.clone());
+
+        collect(&root, &manifest, &mut found, &mut stack, &mut dependencies)?;
+
+        Ok(Graph {
+            root: Located { root, manifest },
+            dependencies,
+        })
+    }
+}
+
+fn collect(
+    root: &Path,
+    manifest: &Manifest,
+    found: &mut HashMap<String, PathBuf>,
+    stack: &mut Vec<String>,
+    dependencies: &mut Vec<Located>,
+) -> Result<(), Error> {
+    for (name, dependency) in &manifest.dependencies {
+        let path = root.join(&dependency.path);
+        let path = path.canonicalize().map_err(|_| Error::NotFound(path))?;
+
+        if let Some(place) = stack.iter().position(|x| x == name) {
+            let mut cycle = stack[place..].to_vec();
+            cycle.push(name.clone());
+            return Err(Error::Cycle(cycle));
+        }
+
+        if let Some(other) = found.get(name) {
+            if *other != path {
+                return Err(Error::Conflict(name.clone(), other.clone(), path));
+            }
+
+            continue;
+        }
+
+        let dependency = Manifest::load(&path)?;
+
+        if dependency.package.name != *name {
+            return Err(Error::WrongName(
+                path,
+                name.clone(),
+                dependency.package.name,
+            ));
+        }
+
+        found.insert(name.clone(), path.clone());
+
+        stack.push(name.clone());
+        collect(&path, &dependency, found, stack, dependencies)?;
+        stack.pop();
+
+        dependencies.push(Located {
+            root: path,
+            manifest: dependency,
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! An in-memory file system. Files are read from the disk only the first time that they are
+//! loaded and every change after that lives only in memory, so tools like the language server can
+//! check buffers that were not saved yet.
+
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use filetime::FileTime;
+use vulpi_intern::Symbol;
+use vulpi_location::FileId;
+use vulpi_vfs::{path::Path, Error};
+
+use super::FileSystem;
+
+pub struct MemoryFileSystem {
+    project_root: PathBuf,
+    build_root: PathBuf,
+    root: Symbol,
+    packages: HashMap<Symbol, PathBuf>,
+    file_map: HashMap<FileId, (PathBuf, String)>,
+    path_map: HashMap<PathBuf, FileId>,
+    counter: usize,
+}
+
+impl MemoryFileSystem {
+    pub fn new(root: Symbol, project_root: PathBuf, build: PathBuf) -> Self {
+        Self {
+            root,
+            project_root,
+            build_root: build,
+            packages: HashMap::new(),
+            file_map: HashMap::new(),
+            path_map: HashMap::new(),
+            counter: 0,
+        }
+    }
+
+    /// Makes the modules of another package available under its name, so `Name.Module` is read
+    /// from the `Module` file inside of the `source` directory.
+    pub fn add_package(&mut self, name: Symbol, source: PathBuf) {
+        self.packages.insert(name, source);
+    }
+
+    /// Normalizes a path so the same file always gets the same [FileId]. Files that only exist in
+    /// memory cannot be canonicalized, so they are just joined with the project root.
+    pub fn get_path(&self, path: PathBuf) -> PathBuf {
+        let path = self.project_root.clone().join(path);
+        path.canonicalize().unwrap_or(path)
+    }
+
+    /// Replaces the content of a file with a new one, creating the file if it was not loaded yet.
+    pub fn open(&mut self, path: PathBuf, content: String) -> FileId {
+        let path = self.get_path(path);
+
+        if let Some(id) = self.path_map.get(&path) {
+            self.file_map.insert(*id, (path, content));
+            return *id;
+        }
+
+        let id = self.new_id();
+
+        self.file_map.insert(id, (path.clone(), content));
+        self.path_map.insert(path, id);
+
+        id
+    }
+
+    /// Forgets the in-memory content of a file, so the next load reads it from the disk again.
+    pub fn close(&mut self, path: PathBuf) {
+        let path = self.get_path(path);
+
+        if let Some(id) = self.path_map.remove(&path) {
+            self.file_map.remove(&id);
+        }
+    }
+
+    /// All the files that are currently loaded.
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &PathBuf)> {
+        self.file_map.iter().map(|(id, (path, _))| (*id, path))
+    }
+
+    fn new_id(&mut self) -> FileId {
+        let id = FileId(self.counter);
+        self.counter += 1;
+        id
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    type Path = PathBuf;
+
+    fn load(&mut self, path: PathBuf) -> Result<FileId, Error> {
+        let path = self.get_path(path);
+
+        if let Some(id) = self.path_map.get(&path) {
+            return Ok(*id);
+        }
+
+        let content =
+            fs::read_to_string(path.clone()).map_err(|_| Error::NotFound(path.clone()))?;
+
+        let id = self.new_id();
+
+        self.file_map.insert(id, (path.clone(), content));
+        self.path_map.insert(path, id);
+
+        Ok(id)
+    }
+
+    fn unload(&mut self, id: FileId) -> Result<(), Error> {
+        let (path, _) = self.file_map.remove(&id).ok_or(Error::NotFoundId)?;
+        self.path_map.remove(&path);
+        Ok(())
+    }
+
+    fn store(&mut self, id: FileId, content: String) -> Result<(), Error> {
+        let file = self.file_map.get_mut(&id).ok_or(Error::NotFoundId)?;
+        file.1 = content;
+        Ok(())
+    }
+
+    fn read(&self, id: FileId) -> Result<String, Error> {
+        let file = self.file_map.get(&id).ok_or(Error::NotFoundId)?;
+        Ok(file.1.clone())
+    }
+
+    fn create(&mut self, path: PathBuf) -> Result<FileId, Error> {
+        let path = self.get_path(path);
+
+        if self.path_map.contains_key(&path) || path.exists() {
+            return Err(Error::AlreadyExists);
+        }
+
+        let id = self.new_id();
+
+        self.file_map.insert(id, (path.clone(), String::new()));
+        self.path_map.insert(path, id);
+
+        Ok(id)
+    }
+
+    fn write(&mut self, id: FileId) -> Result<(), Error> {
+        // Nothing is written to the disk, the content already lives in memory.
+        if self.file_map.contains_key(&id) {
+            Ok(())
+        } else {
+            Err(Error::NotFoundId)
+        }
+    }
+
+    fn delete(&mut self, id: FileId) -> Result<(), Error> {
+        self.unload(id)
+    }
+
+    fn path(&self, id: FileId) -> Result<&PathBuf, Error> {
+        let file = self.file_map.get(&id).ok_or(Error::NotFoundId)?;
+        Ok(&file.0)
+    }
+
+    fn modification_time(&self, path: PathBuf) -> Result<FileTime, Error> {
+        let metadata = fs::metadata(path.clone()).map_err(|_| Error::NotFound(path.clone()))?;
+
+        Ok(FileTime::from_last_modification_time(&metadata))
+    }
+
+    fn from_cached_path(&self, path: Path) -> Self::Path {
+        let mut path = path.to_pathbuf(self.build_root.clone());
+        path.set_extension("vpi");
+        path
+    }
+
+    fn from_src_path(&self, path: Path) -> Self::Path {
+        if self.root == path.segments[0] {
+            path.shift().to_pathbuf(self.project_root.clone())
+        } else if let Some(source) = self.packages.get(&path.segments[0]) {
+            path.shift().to_pathbuf(source.clone())
+        } else {
+            path.to_pathbuf(self.project_root.clone())
+        }
+    }
+}
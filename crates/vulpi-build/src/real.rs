@@ -11,6 +11,7 @@ pub struct RealFileSystem {
     project_root: PathBuf,
     build_root: PathBuf,
     root: Symbol,
+    packages: HashMap<Symbol, PathBuf>,
     file_map: HashMap<FileId, (PathBuf, String)>,
     path_map: HashMap<PathBuf, FileId>,
     counter: usize,
@@ -22,12 +23,19 @@ impl RealFileSystem {
             root,
             project_root,
             build_root: build,
+            packages: HashMap::new(),
             file_map: HashMap::new(),
             path_map: HashMap::new(),
             counter: 0,
         }
     }
 
+    /// Makes the modules of another package available under its name, so `Name.Module` is read
+    /// from the `Module` file inside of the `source` directory.
+    pub fn add_package(&mut self, name: Symbol, source: PathBuf) {
+        self.packages.insert(name, source);
+    }
+
     pub fn get_path(&self, path: PathBuf) -> Result<PathBuf, Error> {
         let path = &self.project_root.clone().join(path);
         path.canonicalize()
@@ -60,12 +68,15 @@ impl FileSystem for RealFileSystem {
     }
 
     fn unload(&mut self, id: FileId) -> Result<(), Error> {
-        self.file_map.remove(&id).ok_or(Error::NotFoundId)?;
+        let (path, _) = self.file_map.remove(&id).ok_or(Error::NotFoundId)?;
+        self.path_map.remove(&path);
         Ok(())
     }
 
-    fn store(&mut self, _id: FileId, _content: String) -> Result<(), Error> {
-        todo!()
+    fn store(&mut self, id: FileId, content: String) -> Result<(), Error> {
+        let file = self.file_map.get_mut(&id).ok_or(Error::NotFoundId)?;
+        file.1 = content;
+        Ok(())
     }
 
     fn read(&self, id: FileId) -> Result<String, Error> {
@@ -74,7 +85,8 @@ impl FileSystem for RealFileSystem {
     }
 
     fn create(&mut self, path: PathBuf) -> Result<FileId, Error> {
-        let path = self.get_path(path)?;
+        // The file does not exist yet, so the path cannot be canonicalized.
+        let path = self.project_root.clone().join(path);
 
         if path.exists() {
             return Err(Error::AlreadyExists);
@@ -91,6 +103,10 @@ impl FileSystem for RealFileSystem {
 
     fn write(&mut self, id: FileId) -> Result<(), Error> {
         if let Some((path, content)) = self.file_map.get(&id) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|_| Error::NotFound(path.clone()))?;
+            }
+
             fs::write(path, content).map_err(|_| Error::NotFound(path.clone()))?;
             Ok(())
         } else {
@@ -119,12 +135,16 @@ impl FileSystem for RealFileSystem {
     }
 
     fn from_cached_path(&self, path: Path) -> Self::Path {
-        path.to_pathbuf(self.build_root.clone())
+        let mut path = path.to_pathbuf(self.build_root.clone());
+        path.set_extension("vpi");
+        path
     }
 
     fn from_src_path(&self, path: Path) -> Self::Path {
         if self.root == path.segments[0] {
             path.shift().to_pathbuf(self.project_root.clone())
+        } else if let Some(source) = self.packages.get(&path.segments[0]) {
+            path.shift().to_pathbuf(source.clone())
         } else {
             path.to_pathbuf(self.project_root.clone())
         }
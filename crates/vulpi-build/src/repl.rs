@@ -0,0 +1,609 @@
+//! The state of the REPL. Every input is checked against everything that was typed before it by
+//! extending the namespace of the resolver and the interfaces of the type checker, so nothing is
+//! checked twice.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    rc::Rc,
+};
+
+use resw::Writer;
+use serde_json::{json, Map, Value};
+use vulpi_intern::Symbol;
+use vulpi_ir::{
+    dead_code, inline, simplify,
+    transform::{self, Transform},
+    uncurry,
+};
+use vulpi_location::{FileId, Span};
+use vulpi_report::Severity;
+use vulpi_resolver::{
+    dependencies::{self, Dependencies},
+    index::Index,
+    Context, Module, Namespace,
+};
+use vulpi_syntax::{
+    elaborated,
+    lambda::{self, ConsDef},
+    r#abstract::{self as abs, Qualified},
+};
+use vulpi_typer::{
+    declare::{Declare, Programs},
+    module::{Def, Interface as TypeInterface, Modules},
+    real::Real,
+    Type, TypeKind,
+};
+use vulpi_vfs::{path::Path, FileSystem};
+
+use crate::{memory::MemoryFileSystem, with_submodules, Interface, ProjectCompiler};
+
+pub struct Session {
+    pub compiler: ProjectCompiler<MemoryFileSystem>,
+
+    /// The directory where the inputs are said to be, so they are shown close to the project.
+    root: PathBuf,
+
+    /// The resolver context of the module that holds the inputs.
+    resolver: Context,
+    available: Rc<RefCell<HashMap<Path, Module>>>,
+    index: Rc<RefCell<Index>>,
+    loaded: HashSet<Path>,
+
+    typer: vulpi_typer::Context,
+    lowering: transform::Context,
+
+    /// Everything that was declared until now, lowered to the intermediate representation.
+    programs: Vec<lambda::Program>,
+
+    inputs: usize,
+}
+
+/// A declaration together with its pretty printed type.
+pub type Declared = (Qualified, String);
+
+/// Prints a value like it's written in the source code. The type of the value is described by the
+/// objects that [Describe] makes, and `$types` has the layouts of the types that they mention.
+const SHOW: &str = r#"
+function $resolve(type, args) {
+    switch (type.kind) {
+        case "var": return args[type.index];
+        case "tuple": return { kind: "tuple", args: type.args.map(x => $resolve(x, args)) };
+        case "data": return { kind: "data", name: type.name, args: type.args.map(x => $resolve(x, args)) };
+        default: return type;
+    }
+}
+
+function $show(type, value, depth) {
+    switch (type.kind) {
+        case "function":
+            return "<function>";
+        case "tuple":
+            if (type.args.length == 0) return "()";
+            return "(" + type.args.map((x, i) => $show(x, value[i], 1)).join(", ") + ")";
+        case "data": {
+            let data = $types[type.name];
+            let show = (x, field, depth) => $show($resolve(x, type.args), field, depth);
+
+            if (data.layout == "record") {
+                return "{ " + data.fields.map(([name, x]) => name + " = " + show(x, value[name], 1)).join(", ") + " }";
+            }
+
+            let [name, args] = data.layout == "enumerated" ? data.constructors[value]
+                : data.layout == "heavy" ? data.constructors[value.tag]
+                : data.constructors[0];
+
+            let fields = data.layout == "newtype" ? [value] : value;
+
+            if (args.length == 0) return name;
+
+            let shown = name + " " + args.map((x, i) => show(x, fields[i], 2)).join(" ");
+            return depth == 2 ? "(" + shown + ")" : shown;
+        }
+        default:
+            if (typeof value == "function") return "<function>";
+            if (typeof value == "string" && depth > 0) return JSON.stringify(value);
+            return String(value);
+    }
+}
+"#;
+
+/// Describes types for the `$show` function of [SHOW]. Types that are defined by the user are
+/// described by name, and their layouts after the lowering are collected in `types`.
+struct Describe<'a> {
+    modules: &'a mut Modules,
+    definitions: HashMap<Qualified, ConsDef>,
+    types: Map<String, Value>,
+}
+
+impl<'a> Describe<'a> {
+    /// Describes a type whose bound variables up to `binders` are the parameters of the type
+    /// that is being defined. Types that can't be described are shown like the engine shows them.
+    fn typ(&mut self, typ: &Type<Real>, binders: usize) -> Value {
+        let opaque = json!({ "kind": "opaque" });
+        let typ = typ.unfold();
+
+        match typ.as_ref() {
+            TypeKind::Arrow(_) | TypeKind::Qualified(_, _) => json!({ "kind": "function" }),
+            TypeKind::Tuple(types) => {
+                let args: Vec<_> = types.iter().map(|x| self.typ(x, binders)).collect();
+                json!({ "kind": "tuple", "args": args })
+            }
+            TypeKind::Bound(vulpi_typer::Index(index)) if *index < binders => {
+                json!({ "kind": "var", "index": binders - 1 - index })
+            }
+            TypeKind::Variable(_) | TypeKind::Application(_, _) => {
+                let mut head = typ.clone();
+                let mut args = vec![];
+
+                while let TypeKind::Application(left, right) = head.clone().as_ref() {
+                    args.push(right.clone());
+                    head = left.unfold();
+                }
+
+                match head.as_ref() {
+                    TypeKind::Variable(name) if self.define(name) => {
+                        let args: Vec<_> =
+                            args.iter().rev().map(|x| self.typ(x, binders)).collect();
+                        json!({ "kind": "data", "name": name.to_string(), "args": args })
+                    }
+                    _ => opaque,
+                }
+            }
+            _ => opaque,
+        }
+    }
+
+    /// Adds the layout of a type to `types`. It returns false if the values of the type have no
+    /// layout that can be shown, like the ones of abstract types.
+    fn define(&mut self, name: &Qualified) -> bool {
+        let key = name.to_string();
+
+        if self.types.contains_key(&key) {
+            return true;
+        }
+
+        let layout = match self.modules.typ(name).def {
+            Def::Enum(constructors) => {
+                let layout = match constructors.first().and_then(|x| self.definitions.get(x)) {
+                    Some(ConsDef::Enumerated(_, _)) => "enumerated",
+                    Some(ConsDef::Heavy(_, _, _)) => "heavy",
+                    Some(ConsDef::NewType) => "newtype",
+                    Some(ConsDef::Tuple) => "tuple",
+                    None => return false,
+                };
+
+                // Recursive types find their own name while the constructors are described.
+                self.types.insert(key.clone(), Value::Null);
+
+                let constructors: Vec<_> = constructors
+                    .iter()
+                    .map(|cons| {
+                        let (typ, _, _) = self.modules.constructor(cons);
+                        let (binders, typ) = strip_foralls(typ);
+
+                        let mut args = typ.arrow_spine();
+                        args.pop();
+
+                        let args: Vec<_> = args.iter().map(|x| self.typ(x, binders)).collect();
+                        json!([display(cons), args])
+                    })
+                    .collect();
+
+                json!({ "layout": layout, "constructors": constructors })
+            }
+            Def::Record(fields) => {
+                self.types.insert(key.clone(), Value::Null);
+
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|field| {
+                        let (binders, typ) = strip_foralls(self.modules.field(field));
+                        json!([field.name.get(), self.typ(&typ, binders)])
+                    })
+                    .collect();
+
+                json!({ "layout": "record", "fields": fields })
+            }
+            _ => return false,
+        };
+
+        self.types.insert(key, layout);
+        true
+    }
+}
+
+/// Removes the binders of the parameters of a type from the type of one of its constructors or
+/// fields, and returns how many there were.
+fn strip_foralls(typ: Type<Real>) -> (usize, Type<Real>) {
+    let mut binders = 0;
+    let mut typ = typ.unfold();
+
+    while let TypeKind::Forall(forall) = typ.clone().as_ref() {
+        binders += 1;
+        typ = forall.body.unfold();
+    }
+
+    (binders, typ)
+}
+
+/// The name of a constructor together with the name of its type, like `Option.Some`.
+fn display(cons: &Qualified) -> String {
+    let path = cons.path.get();
+    let typ = path.rsplit('.').next().unwrap_or_default();
+    format!("{}.{}", typ, cons.name.get())
+}
+
+/// Everything that an input changes before it is type checked, so an input with errors can be
+/// undone and does not leave names without definitions behind.
+struct Snapshot {
+    namespace: Namespace,
+    available: HashMap<Path, Module>,
+    loaded: HashSet<Path>,
+    interfaces: HashMap<Symbol, TypeInterface>,
+}
+
+impl Session {
+    /// Creates a session for the package `name` whose sources are inside of `root`. The inputs
+    /// are declared in the `Repl` module of the package.
+    pub fn new(name: Symbol, root: PathBuf) -> Self {
+        let compiler = ProjectCompiler {
+            fs: MemoryFileSystem::new(name.clone(), root.clone(), root.join("build")),
+            reporter: vulpi_report::hash_reporter(),
+            name: name.clone(),
+        };
+
+        let path = Path {
+            segments: vec![name, Symbol::intern("Repl")],
+        };
+
+        let available: Rc<RefCell<HashMap<Path, Module>>> = Default::default();
+        let index: Rc<RefCell<Index>> = Default::default();
+
+        let resolver = Context::new(
+            available.clone(),
+            index.clone(),
+            path.clone(),
+            compiler.reporter.clone(),
+        );
+
+        available.borrow_mut().insert(path, resolver.module.clone());
+
+        Self {
+            typer: vulpi_typer::Context::new(compiler.reporter.clone()),
+            compiler,
+            root,
+            resolver,
+            available,
+            index,
+            loaded: HashSet::new(),
+            lowering: Default::default(),
+            programs: vec![],
+            inputs: 0,
+        }
+    }
+
+    /// Checks if a source file of the package exists, like the `Prelude`.
+    pub fn exists(&self, path: Path) -> bool {
+        self.compiler.fs.from_src_path(path).is_file()
+    }
+
+    /// Forgets the diagnostics that were already shown.
+    pub fn clear(&mut self) {
+        let files: Vec<_> = self.compiler.fs.files().map(|(id, _)| id).collect();
+
+        for id in files {
+            self.compiler.reporter.clear(id);
+        }
+    }
+
+    fn errored(&self) -> bool {
+        self.compiler
+            .reporter
+            .all_diagnostics()
+            .iter()
+            .any(|x| matches!(x.severity(), Severity::Error))
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            namespace: self.resolver.module.borrow().clone(),
+            available: self.available.borrow().clone(),
+            loaded: self.loaded.clone(),
+            interfaces: self.typer.modules.modules.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        *self.resolver.module.borrow_mut() = snapshot.namespace;
+        *self.available.borrow_mut() = snapshot.available;
+        self.loaded = snapshot.loaded;
+        self.typer.modules.modules = snapshot.interfaces;
+    }
+
+    /// Stores an input in memory so the diagnostics can show it.
+    fn input(&mut self, source: &str) -> FileId {
+        self.inputs += 1;
+
+        let path = self.root.join(format!("<repl {}>", self.inputs));
+        self.compiler.fs.open(path, source.to_string())
+    }
+
+    /// Loads, resolves and declares the modules in `deps` and the ones that they import. Modules
+    /// that were loaded by other inputs are skipped.
+    fn load(&mut self, mut deps: Dependencies) -> Vec<abs::Program> {
+        deps.imported
+            .retain(|(path, _)| !self.loaded.contains(path));
+
+        let mut bag = HashMap::new();
+        self.compiler.find_dependencies(&mut bag, deps, false);
+
+        let mut resolved = vec![];
+
+        for (path, (interface, _)) in bag {
+            let Interface::Uncompiled(parsed) = interface else {
+                continue;
+            };
+
+            if !self.loaded.insert(path.clone()) {
+                continue;
+            }
+
+            let context = Context::new(
+                self.available.clone(),
+                self.index.clone(),
+                path,
+                self.compiler.reporter.clone(),
+            );
+
+            let solver = vulpi_resolver::resolve(&context, parsed);
+            resolved.push((context, solver));
+        }
+
+        for (context, _) in &resolved {
+            for module in with_submodules(&context.module) {
+                let path = module.name().clone();
+                self.available.borrow_mut().insert(path, module);
+            }
+        }
+
+        resolved
+            .into_iter()
+            .map(|(context, solver)| solver.eval(context))
+            .collect()
+    }
+
+    /// Type checks the programs against everything that was declared before and lowers them if
+    /// there are no errors.
+    fn check(&mut self, programs: Vec<abs::Program>) -> bool {
+        let env = vulpi_typer::Env::default();
+        let programs = Programs(programs);
+
+        Declare::declare(&programs, (&mut self.typer, env.clone()));
+        let elaborated: Vec<elaborated::Program<_>> =
+            Declare::define(&programs, (&mut self.typer, env));
+
+        if self.errored() {
+            return false;
+        }
+
+        let lowered = transform::Programs(elaborated).transform(&mut self.lowering);
+
+        // Declaring a name again replaces the old definition.
+        let declared: HashSet<_> = lowered
+            .iter()
+            .flat_map(|program| program.lets.iter().map(|(name, _)| name.clone()))
+            .collect();
+
+        for program in &mut self.programs {
+            program.lets.retain(|(name, _)| !declared.contains(name));
+        }
+
+        self.programs.extend(lowered);
+
+        true
+    }
+
+    /// Adds top level declarations to the session and returns the types of the values that were
+    /// declared.
+    pub fn declare(&mut self, source: &str) -> Option<Vec<Declared>> {
+        let snapshot = self.snapshot();
+        let id = self.input(source);
+
+        let parsed = vulpi_parser::parse(self.compiler.reporter.clone(), id, source);
+        let deps = dependencies::dependencies(self.compiler.name.clone(), &parsed);
+
+        let mut programs = self.load(deps);
+
+        let solver = vulpi_resolver::resolve(&self.resolver, parsed);
+
+        for module in with_submodules(&self.resolver.module) {
+            let path = module.name().clone();
+            self.available.borrow_mut().insert(path, module);
+        }
+
+        let program = solver.eval(self.resolver.clone());
+
+        let names: Vec<_> = program
+            .lets
+            .iter()
+            .map(|x| x.signature.name.clone())
+            .collect();
+
+        programs.push(program);
+
+        if !self.check(programs) {
+            self.restore(snapshot);
+            return None;
+        }
+
+        Some(
+            names
+                .into_iter()
+                .map(|name| {
+                    let typ = self.signature(&name);
+                    (name, typ)
+                })
+                .collect(),
+        )
+    }
+
+    /// Checks an expression and returns its type together with a JavaScript program that prints
+    /// its value.
+    pub fn evaluate(&mut self, source: &str) -> Option<(String, String)> {
+        let id = self.input(source);
+        let expr = vulpi_parser::parse_expr(self.compiler.reporter.clone(), id, source)?;
+
+        let name = Qualified {
+            path: self.resolver.module.name().symbol(),
+            name: Symbol::intern(&format!("it${}", self.inputs)),
+        };
+
+        let (expr, constant) = vulpi_resolver::expr::transform_constant(
+            &mut self.resolver.clone(),
+            name.clone(),
+            *expr,
+        );
+
+        let decl = abs::LetDecl {
+            signature: abs::LetSignature {
+                span: expr.span.clone(),
+                visibility: abs::Visibility::Private,
+                name: name.clone(),
+                binders: vec![],
+                ret: None,
+            },
+            body: vec![abs::PatternArm {
+                patterns: vec![],
+                expr,
+                guard: None,
+            }],
+            constant,
+        };
+
+        let program = abs::Program {
+            lets: vec![decl],
+            ..Default::default()
+        };
+
+        if !self.check(vec![program]) {
+            return None;
+        }
+
+        let result = (self.signature(&name), self.javascript(&name));
+
+        // The value is only printed once, so later inputs don't run the expression again.
+        for program in &mut self.programs {
+            program.lets.retain(|(other, _)| *other != name);
+        }
+
+        Some(result)
+    }
+
+    /// Infers the type of an expression without evaluating it.
+    pub fn type_of(&mut self, source: &str) -> Option<String> {
+        let id = self.input(source);
+        let expr = vulpi_parser::parse_expr(self.compiler.reporter.clone(), id, source)?;
+        let expr = vulpi_resolver::expr::transform(&mut self.resolver.clone(), *expr);
+
+        let env = vulpi_typer::Env::default();
+        let typ = self.typer.infer_expr(env.clone(), &expr);
+
+        (!self.errored()).then(|| typ.show(&env).to_string())
+    }
+
+    /// Infers the kind of a type.
+    pub fn kind_of(&mut self, source: &str) -> Option<String> {
+        let id = self.input(source);
+        let typ = vulpi_parser::parse_type(self.compiler.reporter.clone(), id, source)?;
+        let typ = vulpi_resolver::transform_type(&self.resolver, *typ);
+
+        let env = vulpi_typer::Env::default();
+        let kind = self.typer.infer_kind(env.clone(), &typ);
+
+        (!self.errored()).then(|| kind.show(&env).to_string())
+    }
+
+    /// Describes the declarations of a module, loading it if it was not loaded yet. Without a
+    /// name, it describes the declarations of the session.
+    pub fn browse(&mut self, module: &str) -> Option<Vec<String>> {
+        let path = if module.is_empty() {
+            self.resolver.module.name().clone()
+        } else {
+            let path = Path {
+                segments: module.split('.').map(Symbol::intern).collect(),
+            };
+
+            let snapshot = self.snapshot();
+            let programs = self.load(Dependencies {
+                declared: vec![],
+                imported: vec![(path.clone(), Span::default())],
+                opened: vec![],
+            });
+
+            if !programs.is_empty() && !self.check(programs) {
+                self.restore(snapshot);
+                return None;
+            }
+
+            path
+        };
+
+        let interface = self.typer.modules.modules.get(&path.symbol())?;
+
+        let lines = interface
+            .describe()
+            .into_iter()
+            .filter(|line| !line.contains('$'))
+            .collect();
+
+        Some(lines)
+    }
+
+    fn signature(&mut self, name: &Qualified) -> String {
+        let typ = self.typer.modules.let_decl(name).signature();
+        typ.show(&vulpi_typer::Env::default()).to_string()
+    }
+
+    /// Generates the code of everything that was declared together with a statement that prints
+    /// the value of `result` according to its type.
+    fn javascript(&mut self, result: &Qualified) -> String {
+        let mut programs = self.programs.clone();
+
+        let mut describe = Describe {
+            definitions: programs
+                .iter()
+                .flat_map(|program| &program.definitions)
+                .map(|(name, (def, _))| (name.clone(), def.clone()))
+                .collect(),
+            modules: &mut self.typer.modules,
+            types: Map::new(),
+        };
+
+        let typ = describe.modules.let_decl(result).signature();
+        let (_, typ) = strip_foralls(typ);
+        let typ = describe.typ(&typ, 0);
+        let types = Value::Object(describe.types);
+
+        uncurry::uncurry(&mut programs);
+        inline::inline(&mut programs);
+        simplify::simplify(&mut programs);
+        dead_code::dead_code_remove(&mut programs);
+
+        let js =
+            vulpi_js::Transform::transform(vulpi_js::Programs(programs), &mut Default::default());
+
+        let mut code = vec![];
+        Writer::new(&mut code).write_program(&js).unwrap();
+
+        let mut code = String::from_utf8(code).unwrap();
+        code.push_str(SHOW);
+        code.push_str(&format!("\nlet $types = {types};\n"));
+        code.push_str(&format!(
+            "console.log($show({typ}, {}, 0));\n",
+            result.mangle()
+        ));
+        code
+    }
+}
@@ -0,0 +1,130 @@
+//! Builds the source map that goes alongside the compiled JavaScript, so stack traces and
+//! breakpoints point to the lines of the `.vp` files.
+
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::Serialize;
+use vulpi_js::source_map::{self, Mapping, Segment};
+use vulpi_location::FileId;
+use vulpi_vfs::FileSystem;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SourceMap {
+    version: u8,
+    file: String,
+    sources: Vec<String>,
+    sources_content: Vec<String>,
+    names: Vec<String>,
+    mappings: String,
+}
+
+/// The places where the lines of a source start, to find the line and column of a byte.
+struct Source {
+    index: usize,
+    content: String,
+    lines: Vec<usize>,
+}
+
+impl Source {
+    fn new(index: usize, content: String) -> Self {
+        let lines = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            index,
+            content,
+            lines,
+        }
+    }
+
+    /// The line and the column in UTF-16 units of a byte of the source.
+    fn position(&self, byte: usize) -> Option<(usize, usize)> {
+        let line = self.lines.partition_point(|start| *start <= byte) - 1;
+        let text = self.content.get(self.lines[line]..byte)?;
+        Some((line, text.encode_utf16().count()))
+    }
+}
+
+/// The path of `path` relative to the directory `dir`, with the separators of URLs.
+fn relative(path: &Path, dir: &Path) -> String {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+
+    let path: Vec<_> = path.components().collect();
+    let dir: Vec<_> = dir.components().collect();
+
+    let common = path.iter().zip(&dir).take_while(|(x, y)| x == y).count();
+
+    let mut relative = PathBuf::new();
+
+    for _ in common..dir.len() {
+        relative.push(Component::ParentDir);
+    }
+
+    relative.extend(&path[common..]);
+
+    relative
+        .components()
+        .map(|x| x.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Generates the source map of the JavaScript file in `output` from the places that were found
+/// in it.
+pub fn generate<FS: FileSystem>(fs: &FS, output: &Path, mappings: Vec<Mapping>) -> String
+where
+    FS::Path: AsRef<Path>,
+{
+    let dir = output.parent().unwrap_or(Path::new("."));
+
+    let mut sources: HashMap<FileId, Option<Source>> = HashMap::new();
+    let mut map = SourceMap {
+        version: 3,
+        file: output
+            .file_name()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        sources: vec![],
+        sources_content: vec![],
+        names: vec![],
+        mappings: String::new(),
+    };
+
+    let mut segments = vec![];
+
+    for mapping in mappings {
+        let source = sources.entry(mapping.span.file).or_insert_with(|| {
+            let path = fs.path(mapping.span.file).ok()?;
+            let content = fs.read(mapping.span.file).ok()?;
+
+            map.sources.push(relative(path.as_ref(), dir));
+            map.sources_content.push(content.clone());
+
+            Some(Source::new(map.sources.len() - 1, content))
+        });
+
+        let Some(source) = source else {
+            continue;
+        };
+
+        if let Some((line, column)) = source.position(mapping.span.start.0) {
+            segments.push(Segment {
+                line: mapping.line,
+                column: mapping.column,
+                source: source.index,
+                source_line: line,
+                source_column: column,
+            });
+        }
+    }
+
+    map.mappings = source_map::encode(segments);
+
+    serde_json::to_string(&map).unwrap()
+}
@@ -0,0 +1,968 @@
+//! Generation of C from the lambda IR, to build native executables with the C compiler of the
+//! system. The generated file includes a small runtime, that is written next to it.
+//!
+//! Values are small integers, marked by their lowest bit, or pointers to objects in the heap. The
+//! constructors follow the [ConsDef](vulpi_syntax::lambda::ConsDef) that the lowering gave them:
+//! the ones of enumerations are integers, the ones with fields are objects with the number of the
+//! constructor in their header, and newtypes are erased. Functions inside of other ones become C
+//! functions that receive the variables that they capture in an environment, and the top-level
+//! functions are called directly when all of their arguments are given.
+//!
+//! The memory is managed with the instructions that the [Perceus](vulpi_ir::perceus) pass adds.
+//! Calls of a function to itself in tail position become jumps to its start, and the other calls
+//! in tail position are left to the C compiler, that turns them into jumps when it optimizes.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    rc::Rc,
+};
+
+use vulpi_intern::Symbol;
+use vulpi_ir::{
+    inline,
+    perceus::{self, Borrowed},
+    unsupported::{self, Unsupported, UnsupportedKind},
+};
+use vulpi_location::Span;
+use vulpi_syntax::{
+    elaborated::LiteralKind,
+    lambda::{Case, ExprKind, Program, Stmt, TagType, Tree},
+    r#abstract::Qualified,
+};
+
+/// The name of the backend in its errors.
+const BACKEND: &str = "the C backend";
+
+/// The file of the runtime, that the generated code includes.
+pub const RUNTIME: &str = "vulpi.h";
+
+/// The code of the [RUNTIME] file.
+pub const RUNTIME_CODE: &str = include_str!("runtime.h");
+
+/// The functions of the runtime that implement externals, by the binding of the external,
+/// together with their number of parameters.
+const PRIMITIVES: &[(&str, &str, usize)] = &[
+    ("add", "vulpi_add", 2),
+    ("sub", "vulpi_sub", 2),
+    ("concat", "vulpi_concat", 2),
+    ("eq", "vulpi_eq", 2),
+    ("1 - eq", "vulpi_neq", 2),
+    ("id", "vulpi_id", 1),
+    ("console.log", "vulpi_log", 1),
+    ("process.exit", "vulpi_exit", 1),
+];
+
+/// A top-level declaration.
+enum Global {
+    /// A function together with its parameters.
+    Function(Vec<Symbol>),
+    Constant,
+
+    /// An external together with its binding, the place of the binding and the primitive that
+    /// implements it, if there is one.
+    External(Symbol, Span, Option<(&'static str, usize)>),
+}
+
+/// Replaces the characters that can't be in C identifiers.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Writes a text as a C string.
+fn string(text: &str) -> String {
+    let mut out = String::from("\"");
+
+    for byte in text.bytes() {
+        match byte {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'?' => out.push_str("\\?"),
+            b' '..=b'~' => out.push(byte as char),
+            _ => write!(out, "\\{byte:03o}").unwrap(),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+/// The arguments that come after the fixed ones of a variadic function of the runtime.
+fn rest(args: &[String]) -> String {
+    args.iter().map(|x| format!(", {x}")).collect()
+}
+
+/// The C function that is being generated.
+#[derive(Default)]
+struct Function {
+    code: String,
+    indent: usize,
+
+    /// The names of the variables in C.
+    locals: HashMap<Symbol, String>,
+    counter: usize,
+
+    /// The top-level function together with its parameters. Its calls to itself in tail position
+    /// jump to its start.
+    current: Option<(Qualified, Vec<String>)>,
+    looped: bool,
+}
+
+struct Generator<'a> {
+    globals: HashMap<Qualified, Global>,
+
+    /// The names of the top-level declarations in C.
+    names: HashMap<Qualified, String>,
+    borrowed: &'a Borrowed,
+
+    /// The fields of records, by their number.
+    fields: Vec<Symbol>,
+
+    /// The top-level functions and externals that are used as values, so they need closures.
+    closures: Vec<Qualified>,
+
+    prototypes: String,
+    statics: String,
+    definitions: String,
+
+    lambdas: usize,
+    literals: usize,
+
+    function: Function,
+
+    /// The externals without a primitive that are used.
+    errors: Vec<Unsupported>,
+}
+
+impl<'a> Generator<'a> {
+    fn line(&mut self, line: impl AsRef<str>) {
+        let indent = "    ".repeat(self.function.indent + 1);
+        writeln!(self.function.code, "{indent}{}", line.as_ref()).unwrap();
+    }
+
+    fn fresh(&mut self, name: &str) -> String {
+        self.function.counter += 1;
+
+        let name = sanitize(name);
+        let name = match name.trim_start_matches('_') {
+            "" => "v",
+            name => name,
+        };
+
+        format!("{name}_{}", self.function.counter)
+    }
+
+    /// Stores the result of C code in a new variable, so the order of evaluation is kept.
+    fn bind(&mut self, code: String) -> String {
+        let name = self.fresh("t");
+        self.line(format!("value {name} = {code};"));
+        name
+    }
+
+    fn declare(&mut self, name: &Symbol) -> String {
+        let local = self.fresh(&name.get());
+        self.function.locals.insert(name.clone(), local.clone());
+        local
+    }
+
+    fn local(&self, name: &Symbol) -> String {
+        match self.function.locals.get(name) {
+            Some(local) => local.clone(),
+            None => panic!("the variable '{}' is not bound", name.get()),
+        }
+    }
+
+    fn field(&mut self, name: &Symbol) -> usize {
+        match self.fields.iter().position(|x| x == name) {
+            Some(place) => place,
+            None => {
+                self.fields.push(name.clone());
+                self.fields.len() - 1
+            }
+        }
+    }
+
+    /// Runs `f` in a new scope, so the variables that it binds are forgotten after it.
+    fn scope<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let locals = self.function.locals.clone();
+        let result = f(self);
+        self.function.locals = locals;
+        result
+    }
+
+    /// A static variable that holds the value of a literal once it's created.
+    fn cell(&mut self) -> String {
+        self.literals += 1;
+        let name = format!("literal_{}", self.literals);
+        writeln!(self.statics, "static value {name};").unwrap();
+        name
+    }
+
+    fn literal(&mut self, literal: &LiteralKind) -> String {
+        match literal {
+            LiteralKind::Integer(x) => format!("vulpi_int({})", x.get().replace('_', "")),
+            LiteralKind::Unit => "vulpi_unit".to_string(),
+            LiteralKind::Float(x) => {
+                let cell = self.cell();
+                format!("vulpi_float_literal(&{cell}, {})", x.get().replace('_', ""))
+            }
+            // Characters are strings, like in the JavaScript backend.
+            LiteralKind::String(x) | LiteralKind::Char(x) => {
+                let cell = self.cell();
+                let text = x.get();
+                format!(
+                    "vulpi_string_literal(&{cell}, {}, {})",
+                    string(&text),
+                    text.len()
+                )
+            }
+        }
+    }
+
+    fn global(&mut self, name: &Qualified) -> String {
+        let global = &self.names[name];
+
+        if let Some(Global::Constant) = self.globals.get(name) {
+            return self.bind(format!("get_{global}()"));
+        }
+
+        let closure = format!("closure_{global}()");
+
+        if !self.closures.contains(name) {
+            self.closures.push(name.clone());
+        }
+
+        closure
+    }
+
+    fn values(&mut self, exprs: &[Box<ExprKind>]) -> Vec<String> {
+        exprs.iter().map(|x| self.value(x)).collect()
+    }
+
+    /// The C function that is called directly when a function is applied to some arguments.
+    fn direct(&self, func: &ExprKind, args: usize) -> Option<String> {
+        let ExprKind::Function(name) = func else {
+            return None;
+        };
+
+        match self.globals.get(name) {
+            Some(Global::Function(params)) if params.len() == args => {
+                Some(format!("fn_{}", self.names[name]))
+            }
+            Some(Global::External(_, _, Some((primitive, arity)))) if *arity == args => {
+                Some(primitive.to_string())
+            }
+            _ => None,
+        }
+    }
+
+    fn call(&mut self, func: &ExprKind, args: &[Box<ExprKind>]) -> String {
+        if let Some(direct) = self.direct(func, args.len()) {
+            let args = self.values(args);
+            return format!("{direct}({})", args.join(", "));
+        }
+
+        let func = self.value(func);
+        let args = self.values(args);
+
+        match args.len() {
+            0 => format!("vulpi_apply({func}, 0, NULL)"),
+            1..=3 => format!("vulpi_apply{}({func}{})", args.len(), rest(&args)),
+            _ => format!(
+                "vulpi_apply({func}, {}, (value[]){{{}}})",
+                args.len(),
+                args.join(", ")
+            ),
+        }
+    }
+
+    /// Lifts a function to the top level. The variables that it captures are borrowed from its
+    /// environment and the parameters are owned, like the Perceus pass expects.
+    fn lambda(&mut self, expr: &ExprKind, params: &[Symbol], body: &ExprKind) -> String {
+        let mut captured: Vec<_> = perceus::free_vars(expr).into_iter().collect();
+        captured.sort_by_key(|x| x.get());
+
+        let values: Vec<_> = captured.iter().map(|x| self.local(x)).collect();
+
+        self.lambdas += 1;
+        let name = format!("lambda_{}", self.lambdas);
+        let outer = std::mem::take(&mut self.function);
+
+        for (i, var) in captured.iter().enumerate() {
+            let local = self.declare(var);
+            self.line(format!("value {local} = env[{i}];"));
+        }
+
+        for (i, param) in params.iter().enumerate() {
+            let local = self.declare(param);
+            self.line(format!("value {local} = args[{i}];"));
+        }
+
+        self.ret(body);
+
+        let function = std::mem::replace(&mut self.function, outer);
+        let signature = format!("static value {name}(value *env, value *args)");
+
+        writeln!(self.prototypes, "{signature};").unwrap();
+        write!(self.definitions, "{signature} {{\n{}}}\n\n", function.code).unwrap();
+
+        self.bind(format!(
+            "vulpi_closure({name}, {}, {}{})",
+            params.len(),
+            values.len(),
+            rest(&values)
+        ))
+    }
+
+    /// Generates the code that computes an expression and returns the variable or the constant
+    /// that holds its result.
+    fn value(&mut self, expr: &ExprKind) -> String {
+        match expr {
+            ExprKind::Variable(name) => self.local(name),
+            ExprKind::Function(name) | ExprKind::Constructor(name) => self.global(name),
+            ExprKind::Literal(literal) => self.literal(literal),
+            ExprKind::Lambda(params, body) => self.lambda(expr, params, body),
+            ExprKind::Application(func, args, _) => {
+                let call = self.call(func, args);
+                self.bind(call)
+            }
+            ExprKind::Object(tag, args) => {
+                let args = self.values(args);
+                self.bind(format!(
+                    "vulpi_object({tag}, {}{})",
+                    args.len(),
+                    rest(&args)
+                ))
+            }
+            ExprKind::Reuse(token, tag, args) => {
+                let token = self.local(token);
+                let args = self.values(args);
+                self.bind(format!(
+                    "vulpi_reuse({token}, {tag}, {}{})",
+                    args.len(),
+                    rest(&args)
+                ))
+            }
+            ExprKind::Tuple(args) => {
+                let args = self.values(args);
+                self.bind(format!("vulpi_object(0, {}{})", args.len(), rest(&args)))
+            }
+            ExprKind::Projection(field, expr) => {
+                let record = self.value(expr);
+                let field = self.field(&field.name);
+                self.bind(format!("vulpi_field({record}, {field})"))
+            }
+            ExprKind::Access(expr, place) => {
+                let object = self.value(expr);
+                self.bind(format!("vulpi_at({object}, {place})"))
+            }
+            ExprKind::RecordInstance(_, fields) => {
+                let fields = self.fields(fields);
+                self.bind(format!("vulpi_record({}{})", fields.len(), rest(&fields)))
+            }
+            ExprKind::RecordUpdate(_, expr, fields) => {
+                let record = self.value(expr);
+                let fields = self.fields(fields);
+                self.bind(format!(
+                    "vulpi_update({record}, {}{})",
+                    fields.len(),
+                    rest(&fields)
+                ))
+            }
+            ExprKind::Block(stmts) => self.scope(|this| match stmts.split_last() {
+                Some((Stmt::Expr(last), stmts)) => {
+                    for stmt in stmts {
+                        this.stmt(stmt);
+                    }
+                    this.value(last)
+                }
+                _ => {
+                    for stmt in stmts {
+                        this.stmt(stmt);
+                    }
+                    "vulpi_unit".to_string()
+                }
+            }),
+            ExprKind::Switch(result, tree, actions) => {
+                let result = self.fresh(&result.get());
+                self.line(format!("value {result};"));
+
+                self.tree(tree, actions, &mut |this, action| {
+                    let value = this.value(action);
+                    this.line(format!("{result} = {value};"));
+                });
+
+                result
+            }
+            ExprKind::Computation(_)
+            | ExprKind::Perform(_)
+            | ExprKind::Handle(_, _, _, _, _)
+            | ExprKind::Request(_, _, _) => {
+                // Only the operations of the effects, that the compiler creates, get here. The
+                // declarations that use them are rejected before.
+                self.bind(
+                    "(vulpi_panic(\"effects are not supported by the C backend\"), vulpi_unit)"
+                        .to_string(),
+                )
+            }
+        }
+    }
+
+    /// The pairs of field numbers and values of a record.
+    fn fields(&mut self, fields: &[(Symbol, Box<ExprKind>)]) -> Vec<String> {
+        fields
+            .iter()
+            .map(|(name, expr)| {
+                let value = self.value(expr);
+                format!("{}, {value}", self.field(name))
+            })
+            .collect()
+    }
+
+    /// Generates the code that returns the result of an expression from the current function.
+    fn ret(&mut self, expr: &ExprKind) {
+        match expr {
+            ExprKind::Block(stmts) => self.scope(|this| match stmts.split_last() {
+                Some((Stmt::Expr(last), stmts)) => {
+                    for stmt in stmts {
+                        this.stmt(stmt);
+                    }
+                    this.ret(last)
+                }
+                _ => {
+                    for stmt in stmts {
+                        this.stmt(stmt);
+                    }
+                    this.line("return vulpi_unit;")
+                }
+            }),
+            ExprKind::Switch(_, tree, actions) => {
+                self.tree(tree, actions, &mut |this, action| this.ret(action))
+            }
+            ExprKind::Application(func, args, _) if self.is_recursive(func, args.len()) => {
+                self.jump(args)
+            }
+            _ => {
+                let value = self.value(expr);
+                self.line(format!("return {value};"));
+            }
+        }
+    }
+
+    fn is_recursive(&self, func: &ExprKind, args: usize) -> bool {
+        match (func, &self.function.current) {
+            (ExprKind::Function(name), Some((current, params))) => {
+                name == current && params.len() == args
+            }
+            _ => false,
+        }
+    }
+
+    /// Assigns the arguments of a call of the current function to its parameters and jumps to
+    /// its start.
+    fn jump(&mut self, args: &[Box<ExprKind>]) {
+        let (_, params) = self.function.current.clone().unwrap();
+        let args = self.values(args);
+
+        let changed: Vec<_> = params
+            .iter()
+            .zip(args)
+            .filter(|(param, arg)| *param != arg)
+            .collect();
+
+        if let [(param, arg)] = &changed[..] {
+            self.line(format!("{param} = {arg};"));
+        } else {
+            // The arguments can use the parameters, so all of them are computed before the
+            // parameters change.
+            let next: Vec<_> = changed
+                .iter()
+                .map(|(_, arg)| self.bind(arg.clone()))
+                .collect();
+
+            for ((param, _), next) in changed.iter().zip(next) {
+                self.line(format!("{param} = {next};"));
+            }
+        }
+
+        self.line("goto start;");
+        self.function.looped = true;
+    }
+
+    fn branch(&mut self, stmts: &[Stmt]) {
+        self.function.indent += 1;
+
+        self.scope(|this| {
+            for stmt in stmts {
+                this.stmt(stmt);
+            }
+        });
+
+        self.function.indent -= 1;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Let(name, expr) => {
+                let value = self.value(expr);
+                let local = self.declare(name);
+                self.line(format!("value {local} = {value};"));
+            }
+            Stmt::Expr(expr) => {
+                self.value(expr);
+            }
+            Stmt::Dup(name) => self.line(format!("vulpi_dup({});", self.local(name))),
+            Stmt::Drop(name) => self.line(format!("vulpi_drop({});", self.local(name))),
+            Stmt::Decref(name) => self.line(format!("vulpi_decref({});", self.local(name))),
+            Stmt::Free(name) => self.line(format!("vulpi_free({});", self.local(name))),
+            Stmt::Token(token, object) => {
+                let object = object.as_ref().map_or("0".to_string(), |x| self.local(x));
+
+                match self.function.locals.get(token).cloned() {
+                    Some(token) => self.line(format!("{token} = {object};")),
+                    None => {
+                        let token = self.declare(token);
+                        self.line(format!("value {token} = {object};"));
+                    }
+                }
+            }
+            Stmt::Unique(name, unique, shared) => {
+                // The tokens are set inside of the branches and used after them.
+                for stmt in unique.iter().chain(shared) {
+                    if let Stmt::Token(token, _) = stmt {
+                        if !self.function.locals.contains_key(token) {
+                            let token = self.declare(token);
+                            self.line(format!("value {token} = 0;"));
+                        }
+                    }
+                }
+
+                self.line(format!("if (vulpi_is_unique({})) {{", self.local(name)));
+                self.branch(unique);
+                self.line("} else {");
+                self.branch(shared);
+                self.line("}");
+            }
+        }
+    }
+
+    /// Generates a decision tree. The actions are generated by `leaf`.
+    fn tree(
+        &mut self,
+        tree: &Tree,
+        actions: &[Box<ExprKind>],
+        leaf: &mut dyn FnMut(&mut Self, &ExprKind),
+    ) {
+        let (scrutinee, branches, default) = match tree {
+            Tree::Leaf(action) => return self.scope(|this| leaf(this, &actions[*action])),
+            Tree::Switch(scrutinee, branches, default) => (scrutinee, branches, default),
+        };
+
+        let value = self.value(scrutinee);
+
+        let Some((_, tag, first)) = branches.first() else {
+            if let Some(default) = default {
+                self.tree(default, actions, leaf);
+            }
+            return;
+        };
+
+        let labels: Option<Vec<_>> = branches
+            .iter()
+            .map(|(case, tag, _)| label(tag, case))
+            .collect();
+        let tests: Option<Vec<_>> = branches
+            .iter()
+            .map(|(case, _, _)| test(case, &value))
+            .collect();
+
+        if let Some(labels) = labels {
+            let test = match tag {
+                TagType::Field(_) => format!("vulpi_tag({value})"),
+                _ => format!("vulpi_unbox({value})"),
+            };
+
+            self.line(format!("switch ({test}) {{"));
+
+            for ((_, _, tree), label) in branches.iter().zip(labels) {
+                self.line(format!("case {label}: {{"));
+                self.case(Some(tree), actions, leaf);
+            }
+
+            self.line("default: {");
+            self.case(default.as_deref(), actions, leaf);
+            self.line("}");
+        } else if let Some(tests) = tests {
+            for (i, ((_, _, tree), test)) in branches.iter().zip(tests).enumerate() {
+                let prefix = if i == 0 { "" } else { "} else " };
+                self.line(format!("{prefix}if ({test}) {{"));
+                self.subtree(Some(tree), actions, leaf);
+            }
+
+            self.line("} else {");
+            self.subtree(default.as_deref(), actions, leaf);
+            self.line("}");
+        } else {
+            // Tuples, records and the only constructor of a type always match.
+            self.tree(first, actions, leaf);
+        }
+    }
+
+    /// Generates the tree of a branch with one more level of indentation. A missing tree means
+    /// that no pattern matched.
+    fn subtree(
+        &mut self,
+        tree: Option<&Tree>,
+        actions: &[Box<ExprKind>],
+        leaf: &mut dyn FnMut(&mut Self, &ExprKind),
+    ) {
+        self.function.indent += 1;
+
+        match tree {
+            Some(tree) => self.tree(tree, actions, leaf),
+            None => self.line("vulpi_unreachable();"),
+        }
+
+        self.function.indent -= 1;
+    }
+
+    /// Generates the body of a case of a `switch`, that was already opened.
+    fn case(
+        &mut self,
+        tree: Option<&Tree>,
+        actions: &[Box<ExprKind>],
+        leaf: &mut dyn FnMut(&mut Self, &ExprKind),
+    ) {
+        self.subtree(tree, actions, leaf);
+        self.function.indent += 1;
+        self.line("break;");
+        self.function.indent -= 1;
+        self.line("}");
+    }
+
+    fn declaration(&mut self, name: &Qualified, body: &ExprKind) {
+        let global = self.names[name].clone();
+        self.function = Function::default();
+
+        if let ExprKind::Lambda(params, body) = body {
+            let params: Vec<_> = params.iter().map(|x| self.declare(x)).collect();
+            self.function.current = Some((name.clone(), params.clone()));
+            self.ret(body);
+
+            let function = std::mem::take(&mut self.function);
+
+            let params = if params.is_empty() {
+                "void".to_string()
+            } else {
+                params
+                    .iter()
+                    .map(|x| format!("value {x}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+
+            let signature = format!("static value fn_{global}({params})");
+            let start = if function.looped { "start:;\n" } else { "" };
+
+            writeln!(self.prototypes, "{signature};").unwrap();
+            write!(
+                self.definitions,
+                "{signature} {{\n{start}{}}}\n\n",
+                function.code
+            )
+            .unwrap();
+        } else {
+            self.ret(body);
+
+            let function = std::mem::take(&mut self.function);
+
+            writeln!(self.prototypes, "static value get_{global}(void);").unwrap();
+            writeln!(self.statics, "static value val_{global};").unwrap();
+
+            write!(
+                self.definitions,
+                "static value init_{global}(void) {{\n{}}}\n\n\
+                 static value get_{global}(void) {{\n    \
+                     if (val_{global} == 0) {{\n        \
+                         val_{global} = vulpi_immortal(init_{global}());\n    \
+                     }}\n\n    \
+                     return val_{global};\n\
+                 }}\n\n",
+                function.code
+            )
+            .unwrap();
+        }
+    }
+
+    /// Generates the closure of a top-level function or external that is used as a value. The
+    /// closures own their arguments, so the ones that the function borrows are dropped after it
+    /// returns.
+    fn closure(&mut self, name: &Qualified) {
+        let global = &self.names[name];
+
+        let (arity, body) = match &self.globals[name] {
+            Global::Function(params) => {
+                let args: Vec<_> = (0..params.len()).map(|i| format!("args[{i}]")).collect();
+                let mut body = format!("    value result = fn_{global}({});\n", args.join(", "));
+
+                for (param, arg) in params.iter().zip(&args) {
+                    if self.borrowed.get(name).is_some_and(|x| x.contains(param)) {
+                        writeln!(body, "    vulpi_drop({arg});").unwrap();
+                    }
+                }
+
+                body.push_str("    return result;\n");
+                (params.len(), body)
+            }
+            Global::External(_, _, Some((primitive, arity))) => {
+                let args: Vec<_> = (0..*arity).map(|i| format!("args[{i}]")).collect();
+                (
+                    *arity,
+                    format!("    return {primitive}({});\n", args.join(", ")),
+                )
+            }
+            Global::External(binding, span, None) => {
+                self.errors.push(Unsupported {
+                    backend: BACKEND,
+                    span: span.clone(),
+                    kind: UnsupportedKind::External(binding.clone()),
+                });
+                return;
+            }
+            Global::Constant => unreachable!(),
+        };
+
+        writeln!(self.prototypes, "static value closure_{global}(void);").unwrap();
+
+        write!(
+            self.definitions,
+            "static value entry_{global}(value *env, value *args) {{\n{body}}}\n\n\
+             static value closure_{global}(void) {{\n    \
+                 static value closure = 0;\n\n    \
+                 if (closure == 0) {{\n        \
+                     closure = vulpi_immortal(vulpi_closure(entry_{global}, {arity}, 0));\n    \
+                 }}\n\n    \
+                 return closure;\n\
+             }}\n\n"
+        )
+        .unwrap();
+    }
+}
+
+/// The label of a case of a C `switch` over the tag or the value of the scrutinee.
+fn label(tag: &TagType, case: &Case) -> Option<String> {
+    match (tag, case) {
+        (TagType::Field(id) | TagType::Number(id), Case::Constructor(_, _)) => Some(id.to_string()),
+        (_, Case::Literal(literal)) => match &**literal {
+            LiteralKind::Integer(x) => Some(x.get().replace('_', "")),
+            LiteralKind::Unit => Some("0".to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The condition that checks if the scrutinee is equal to a literal that can't be the label of a
+/// case.
+fn test(case: &Case, value: &str) -> Option<String> {
+    let Case::Literal(literal) = case else {
+        return None;
+    };
+
+    match &**literal {
+        LiteralKind::String(x) | LiteralKind::Char(x) => {
+            let text = x.get();
+            Some(format!(
+                "vulpi_string_equals({value}, {}, {})",
+                string(&text),
+                text.len()
+            ))
+        }
+        LiteralKind::Float(x) => Some(format!(
+            "vulpi_float_equals({value}, {})",
+            x.get().replace('_', "")
+        )),
+        _ => None,
+    }
+}
+
+/// Joins the applications of a partially applied function into one, like `add(x)(1)` into
+/// `add(x, 1)`, so the top-level functions and the primitives are called directly. It only joins
+/// the arguments that the function takes, because a function that is not given all of them does
+/// nothing, so the order of the effects stays the same.
+fn saturate(expr: &mut ExprKind, arities: &HashMap<Qualified, usize>) {
+    if !matches!(expr, ExprKind::Application(_, _, _)) {
+        return;
+    }
+
+    let mut head = std::mem::replace(expr, ExprKind::Block(vec![]));
+    let mut applications = vec![];
+
+    while let ExprKind::Application(func, args, span) = head {
+        applications.push((args, span));
+        head = *func;
+    }
+
+    let arity = match &head {
+        ExprKind::Function(name) => arities.get(name).copied().unwrap_or(0),
+        _ => 0,
+    };
+
+    let mut applications = applications.into_iter().rev();
+    let (mut args, mut span) = applications.next().unwrap();
+    let mut func = Box::new(head);
+    let mut joining = true;
+
+    for (next, next_span) in applications {
+        if joining && args.len() + next.len() <= arity {
+            args.extend(next);
+        } else {
+            func = Box::new(ExprKind::Application(func, args, span));
+            args = next;
+            joining = false;
+        }
+
+        span = next_span;
+    }
+
+    *expr = ExprKind::Application(func, args, span);
+}
+
+/// Generates the C code of the programs of a project. The code includes the [RUNTIME] file, and
+/// its `main` evaluates the top-level constants in the order that they were declared. The
+/// programs go through the Perceus pass here, so no other pass can run after it.
+///
+/// Effects and the externals that have no primitive can't be compiled, so they are returned as
+/// errors instead.
+pub fn generate(mut programs: Vec<Program>) -> Result<String, Vec<Unsupported>> {
+    let errors = unsupported::effects_in(&programs, BACKEND);
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut globals = HashMap::new();
+    let mut names = HashMap::new();
+    let mut used = HashSet::new();
+
+    for program in &programs {
+        let externals = program.externals.iter().map(|external| {
+            let primitive = PRIMITIVES
+                .iter()
+                .find(|(binding, _, _)| *binding == external.binding.get())
+                .map(|(_, primitive, arity)| (*primitive, *arity));
+
+            let binding = external.binding.clone();
+            let global = Global::External(binding, external.span.clone(), primitive);
+            (&external.name, global)
+        });
+
+        let lets = program.lets.iter().map(|(name, decl)| match &*decl.body {
+            ExprKind::Lambda(params, _) => (name, Global::Function(params.clone())),
+            _ => (name, Global::Constant),
+        });
+
+        for (name, global) in externals.chain(lets) {
+            let base = sanitize(&name.to_string());
+            let mut unique = base.clone();
+            let mut counter = 0;
+
+            while !used.insert(unique.clone()) {
+                counter += 1;
+                unique = format!("{base}_{counter}");
+            }
+
+            names.insert(name.clone(), unique);
+            globals.insert(name.clone(), global);
+        }
+    }
+
+    let arities: HashMap<_, _> = globals
+        .iter()
+        .filter_map(|(name, global)| match global {
+            Global::Function(params) => Some((name.clone(), params.len())),
+            Global::External(_, _, Some((_, arity))) => Some((name.clone(), *arity)),
+            _ => None,
+        })
+        .collect();
+
+    let arities = Rc::new(move |expr: &mut ExprKind| saturate(expr, &arities));
+
+    for program in &mut programs {
+        for (_, decl) in &mut program.lets {
+            inline::traverse(&mut decl.body, arities.clone());
+        }
+    }
+
+    let borrowed = perceus::perceus(&mut programs);
+
+    let mut generator = Generator {
+        globals,
+        names,
+        borrowed: &borrowed,
+        fields: vec![],
+        closures: vec![],
+        prototypes: String::new(),
+        statics: String::new(),
+        definitions: String::new(),
+        lambdas: 0,
+        literals: 0,
+        function: Function::default(),
+        errors: vec![],
+    };
+
+    let mut constants = vec![];
+
+    for program in &programs {
+        for (name, decl) in &program.lets {
+            if !matches!(&*decl.body, ExprKind::Lambda(_, _)) {
+                constants.push(generator.names[name].clone());
+            }
+
+            generator.declaration(name, &decl.body);
+        }
+    }
+
+    let mut i = 0;
+
+    while i < generator.closures.len() {
+        let name = generator.closures[i].clone();
+        generator.closure(&name);
+        i += 1;
+    }
+
+    if !generator.errors.is_empty() {
+        return Err(generator.errors);
+    }
+
+    let mut out = format!("#include \"{RUNTIME}\"\n\n");
+
+    // The table ends with an empty name, so it's never empty.
+    out.push_str("const char *const vulpi_field_names[] = {");
+
+    for field in &generator.fields {
+        write!(out, "{}, ", string(&field.get())).unwrap();
+    }
+
+    out.push_str("\"\"};\n\n");
+
+    out.push_str(&generator.prototypes);
+    out.push('\n');
+
+    if !generator.statics.is_empty() {
+        out.push_str(&generator.statics);
+        out.push('\n');
+    }
+
+    out.push_str(&generator.definitions);
+    out.push_str("int main(void) {\n");
+
+    for constant in constants {
+        writeln!(out, "    get_{constant}();").unwrap();
+    }
+
+    out.push_str("    return 0;\n}\n");
+    Ok(out)
+}
@@ -0,0 +1,743 @@
+/* The runtime of the programs compiled by the C backend of Vulpi. It only needs the C standard
+ * library, so the generated files build with any C99 compiler. */
+
+#ifndef VULPI_H
+#define VULPI_H
+
+#include <stdarg.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+/* A value is a small integer, marked by its lowest bit, or a pointer to an object. Constructors
+ * without fields of enumerations and units are small integers too. */
+typedef intptr_t value;
+
+#define vulpi_int(n) ((value)(((uintptr_t)(intptr_t)(n) << 1) | 1))
+#define vulpi_unbox(v) ((intptr_t)(v) >> 1)
+#define vulpi_unit vulpi_int(0)
+
+enum vulpi_kind {
+    VULPI_DATA,
+    VULPI_CLOSURE,
+    VULPI_STRING,
+    VULPI_FLOAT,
+    VULPI_RECORD
+};
+
+/* Every object starts with a header. The objects whose count is zero are never freed, like the
+ * values of the top-level constants. */
+typedef struct {
+    uint32_t rc;
+    uint16_t kind;
+
+    /* The constructor of data and the number of parameters of closures. */
+    uint16_t tag;
+
+    /* The number of values that the object holds. */
+    uint32_t size;
+} vulpi_header;
+
+/* Constructors with fields and tuples. Records hold pairs of field numbers and values. */
+typedef struct {
+    vulpi_header header;
+    value fields[];
+} vulpi_data;
+
+typedef value (*vulpi_code)(value *env, value *args);
+
+typedef struct {
+    vulpi_header header;
+    vulpi_code code;
+    value env[];
+} vulpi_closure_t;
+
+typedef struct {
+    vulpi_header header;
+    size_t length;
+    char bytes[];
+} vulpi_string_t;
+
+typedef struct {
+    vulpi_header header;
+    double number;
+} vulpi_float_t;
+
+/* The names of the fields of records by their numbers. It's defined by the generated code. */
+extern const char *const vulpi_field_names[];
+
+#define VULPI_HEADER(v) ((vulpi_header *)(v))
+
+#if defined(__GNUC__)
+#define VULPI_NORETURN __attribute__((noreturn))
+#define VULPI_NOINLINE __attribute__((noinline))
+#else
+#define VULPI_NORETURN
+#define VULPI_NOINLINE
+#endif
+
+static inline int vulpi_is_object(value v) {
+    return (v & 1) == 0;
+}
+
+static inline VULPI_NORETURN void vulpi_panic(const char *message) {
+    fflush(stdout);
+    fprintf(stderr, "[Error]: %s\n", message);
+    exit(1);
+}
+
+static inline VULPI_NORETURN void vulpi_unreachable(void) {
+    vulpi_panic("no pattern matched the value");
+}
+
+static inline void *vulpi_alloc(size_t bytes) {
+    void *memory = malloc(bytes);
+
+    if (memory == NULL) {
+        vulpi_panic("out of memory");
+    }
+
+    return memory;
+}
+
+static inline void vulpi_init(vulpi_header *header, uint16_t kind, uint16_t tag, uint32_t size) {
+    header->rc = 1;
+    header->kind = kind;
+    header->tag = tag;
+    header->size = size;
+}
+
+static inline value vulpi_immortal(value v) {
+    if (vulpi_is_object(v)) {
+        VULPI_HEADER(v)->rc = 0;
+    }
+
+    return v;
+}
+
+/* Reference counting */
+
+static inline void vulpi_dup(value v) {
+    if (vulpi_is_object(v) && VULPI_HEADER(v)->rc != 0) {
+        VULPI_HEADER(v)->rc++;
+    }
+}
+
+static inline value *vulpi_children(vulpi_header *header) {
+    switch (header->kind) {
+    case VULPI_DATA:
+    case VULPI_RECORD:
+        return ((vulpi_data *)header)->fields;
+    case VULPI_CLOSURE:
+        return ((vulpi_closure_t *)header)->env;
+    default:
+        return NULL;
+    }
+}
+
+/* Frees an object whose count reached zero together with the values that only it held. The
+ * objects are kept in a stack instead of being freed recursively, so long lists don't overflow
+ * the C stack. */
+static inline void vulpi_release(vulpi_header *object) {
+    static vulpi_header **stack = NULL;
+    static size_t capacity = 0;
+    size_t top = 0;
+
+    if (capacity == 0) {
+        capacity = 64;
+        stack = vulpi_alloc(capacity * sizeof(vulpi_header *));
+    }
+
+    stack[top++] = object;
+
+    while (top > 0) {
+        vulpi_header *header = stack[--top];
+        value *children = vulpi_children(header);
+
+        for (uint32_t i = 0; children != NULL && i < header->size; i++) {
+            value child = children[i];
+
+            if (!vulpi_is_object(child)) {
+                continue;
+            }
+
+            vulpi_header *inner = VULPI_HEADER(child);
+
+            if (inner->rc != 0 && --inner->rc == 0) {
+                if (top == capacity) {
+                    capacity *= 2;
+                    stack = realloc(stack, capacity * sizeof(vulpi_header *));
+
+                    if (stack == NULL) {
+                        vulpi_panic("out of memory");
+                    }
+                }
+
+                stack[top++] = inner;
+            }
+        }
+
+        free(header);
+    }
+}
+
+static inline void vulpi_drop(value v) {
+    if (vulpi_is_object(v) && VULPI_HEADER(v)->rc != 0 && --VULPI_HEADER(v)->rc == 0) {
+        vulpi_release(VULPI_HEADER(v));
+    }
+}
+
+/* Removes a reference from an object that has other ones. */
+static inline void vulpi_decref(value v) {
+    if (vulpi_is_object(v) && VULPI_HEADER(v)->rc != 0) {
+        VULPI_HEADER(v)->rc--;
+    }
+}
+
+/* Frees the memory of an object whose values were already dropped or moved. */
+static inline void vulpi_free(value v) {
+    free((void *)v);
+}
+
+static inline int vulpi_is_unique(value v) {
+    return vulpi_is_object(v) && VULPI_HEADER(v)->rc == 1;
+}
+
+/* Data */
+
+/* The constructors without fields of types that have other constructors with fields. They are
+ * shared, so they are never allocated again. */
+static inline value vulpi_nullary(uint16_t tag) {
+    static value shared[256];
+
+    if (tag < 256 && shared[tag] != 0) {
+        return shared[tag];
+    }
+
+    vulpi_data *data = vulpi_alloc(sizeof(vulpi_data));
+    vulpi_init(&data->header, VULPI_DATA, tag, 0);
+
+    if (tag < 256) {
+        shared[tag] = vulpi_immortal((value)data);
+    }
+
+    return (value)data;
+}
+
+static inline value vulpi_fill(vulpi_data *data, uint16_t tag, uint32_t size, va_list args) {
+    vulpi_init(&data->header, VULPI_DATA, tag, size);
+
+    for (uint32_t i = 0; i < size; i++) {
+        data->fields[i] = va_arg(args, value);
+    }
+
+    return (value)data;
+}
+
+static inline value vulpi_object(uint16_t tag, uint32_t size, ...) {
+    if (size == 0) {
+        return vulpi_nullary(tag);
+    }
+
+    va_list args;
+    va_start(args, size);
+    vulpi_data *data = vulpi_alloc(sizeof(vulpi_data) + size * sizeof(value));
+    value result = vulpi_fill(data, tag, size, args);
+    va_end(args);
+
+    return result;
+}
+
+/* Creates an object in the memory of a reuse token, that is an object of the same size, or in
+ * new memory if the token is empty. */
+static inline value vulpi_reuse(value token, uint16_t tag, uint32_t size, ...) {
+    if (size == 0) {
+        return vulpi_nullary(tag);
+    }
+
+    vulpi_data *data = token != 0 ? (vulpi_data *)token
+                                  : vulpi_alloc(sizeof(vulpi_data) + size * sizeof(value));
+
+    va_list args;
+    va_start(args, size);
+    value result = vulpi_fill(data, tag, size, args);
+    va_end(args);
+
+    return result;
+}
+
+static inline uint16_t vulpi_tag(value v) {
+    return VULPI_HEADER(v)->tag;
+}
+
+static inline value vulpi_at(value v, uint32_t place) {
+    return ((vulpi_data *)v)->fields[place];
+}
+
+/* Records */
+
+/* Creates a record from pairs of field numbers and values. */
+static inline value vulpi_record(uint32_t size, ...) {
+    vulpi_data *data = vulpi_alloc(sizeof(vulpi_data) + 2 * size * sizeof(value));
+    vulpi_init(&data->header, VULPI_RECORD, 0, 2 * size);
+
+    va_list args;
+    va_start(args, size);
+
+    for (uint32_t i = 0; i < size; i++) {
+        data->fields[2 * i] = vulpi_int(va_arg(args, int));
+        data->fields[2 * i + 1] = va_arg(args, value);
+    }
+
+    va_end(args);
+
+    return (value)data;
+}
+
+static inline uint32_t vulpi_place(value record, int field) {
+    vulpi_data *data = (vulpi_data *)record;
+
+    for (uint32_t i = 0; i < data->header.size; i += 2) {
+        if (data->fields[i] == vulpi_int(field)) {
+            return i + 1;
+        }
+    }
+
+    vulpi_panic("the record does not have the field");
+    return 0;
+}
+
+static inline value vulpi_field(value record, int field) {
+    return ((vulpi_data *)record)->fields[vulpi_place(record, field)];
+}
+
+/* Copies a record with new values for some of its fields, given as pairs of field numbers and
+ * values. The record is borrowed. */
+static inline value vulpi_update(value record, uint32_t size, ...) {
+    vulpi_data *old = (vulpi_data *)record;
+    uint32_t length = old->header.size;
+
+    vulpi_data *data = vulpi_alloc(sizeof(vulpi_data) + length * sizeof(value));
+    vulpi_init(&data->header, VULPI_RECORD, 0, length);
+
+    for (uint32_t i = 0; i < length; i++) {
+        data->fields[i] = old->fields[i];
+        vulpi_dup(old->fields[i]);
+    }
+
+    va_list args;
+    va_start(args, size);
+
+    for (uint32_t i = 0; i < size; i++) {
+        uint32_t place = vulpi_place((value)data, va_arg(args, int));
+        vulpi_drop(data->fields[place]);
+        data->fields[place] = va_arg(args, value);
+    }
+
+    va_end(args);
+
+    return (value)data;
+}
+
+/* Strings and floats */
+
+static inline value vulpi_string(const char *bytes, size_t length) {
+    vulpi_string_t *string = vulpi_alloc(sizeof(vulpi_string_t) + length + 1);
+    vulpi_init(&string->header, VULPI_STRING, 0, 0);
+    string->length = length;
+    memcpy(string->bytes, bytes, length);
+    string->bytes[length] = '\0';
+    return (value)string;
+}
+
+static inline value vulpi_string_literal(value *cell, const char *bytes, size_t length) {
+    if (*cell == 0) {
+        *cell = vulpi_immortal(vulpi_string(bytes, length));
+    }
+
+    return *cell;
+}
+
+static inline int vulpi_is_kind(value v, uint16_t kind) {
+    return vulpi_is_object(v) && VULPI_HEADER(v)->kind == kind;
+}
+
+static inline int vulpi_string_equals(value v, const char *bytes, size_t length) {
+    vulpi_string_t *string = (vulpi_string_t *)v;
+    return vulpi_is_kind(v, VULPI_STRING) && string->length == length
+        && memcmp(string->bytes, bytes, length) == 0;
+}
+
+static inline value vulpi_float(double number) {
+    vulpi_float_t *boxed = vulpi_alloc(sizeof(vulpi_float_t));
+    vulpi_init(&boxed->header, VULPI_FLOAT, 0, 0);
+    boxed->number = number;
+    return (value)boxed;
+}
+
+static inline value vulpi_float_literal(value *cell, double number) {
+    if (*cell == 0) {
+        *cell = vulpi_immortal(vulpi_float(number));
+    }
+
+    return *cell;
+}
+
+static inline double vulpi_number(value v) {
+    if (vulpi_is_kind(v, VULPI_FLOAT)) {
+        return ((vulpi_float_t *)v)->number;
+    }
+
+    return (double)vulpi_unbox(v);
+}
+
+static inline int vulpi_float_equals(value v, double number) {
+    return vulpi_is_kind(v, VULPI_FLOAT) && ((vulpi_float_t *)v)->number == number;
+}
+
+/* Closures */
+
+static inline value vulpi_closure(vulpi_code code, uint16_t arity, uint32_t size, ...) {
+    vulpi_closure_t *closure = vulpi_alloc(sizeof(vulpi_closure_t) + size * sizeof(value));
+    vulpi_init(&closure->header, VULPI_CLOSURE, arity, size);
+    closure->code = code;
+
+    va_list args;
+    va_start(args, size);
+
+    for (uint32_t i = 0; i < size; i++) {
+        closure->env[i] = va_arg(args, value);
+    }
+
+    va_end(args);
+
+    return (value)closure;
+}
+
+static inline value vulpi_apply(value function, uint32_t count, value *args);
+
+/* The code of a partial application, whose environment has the function and the arguments that
+ * were already given. */
+static inline value vulpi_partial(value *env, value *args) {
+    vulpi_closure_t *closure =
+        (vulpi_closure_t *)((char *)env - offsetof(vulpi_closure_t, env));
+
+    uint32_t given = closure->header.size - 1;
+    uint32_t count = given + closure->header.tag;
+    value *all = vulpi_alloc(count * sizeof(value));
+
+    for (uint32_t i = 0; i < given; i++) {
+        all[i] = env[i + 1];
+        vulpi_dup(all[i]);
+    }
+
+    memcpy(all + given, args, closure->header.tag * sizeof(value));
+
+    value result = vulpi_apply(env[0], count, all);
+    free(all);
+    return result;
+}
+
+/* Calls a closure with owned arguments. The closure is borrowed. Closures that need more
+ * arguments become partial applications, and the result of closures that need less is called
+ * with the rest of them. */
+static inline value vulpi_apply(value function, uint32_t count, value *args) {
+    if (!vulpi_is_kind(function, VULPI_CLOSURE)) {
+        vulpi_panic("called a value that is not a function");
+    }
+
+    vulpi_closure_t *closure = (vulpi_closure_t *)function;
+    uint32_t arity = closure->header.tag;
+
+    if (count == arity) {
+        return closure->code(closure->env, args);
+    }
+
+    if (count < arity) {
+        vulpi_closure_t *partial =
+            vulpi_alloc(sizeof(vulpi_closure_t) + (count + 1) * sizeof(value));
+
+        vulpi_init(&partial->header, VULPI_CLOSURE, arity - count, count + 1);
+        partial->code = vulpi_partial;
+        partial->env[0] = function;
+        vulpi_dup(function);
+        memcpy(partial->env + 1, args, count * sizeof(value));
+
+        return (value)partial;
+    }
+
+    value next = closure->code(closure->env, args);
+    value result = vulpi_apply(next, count - arity, args + arity);
+    vulpi_drop(next);
+    return result;
+}
+
+/* Calls with a few arguments keep them outside of the function that calls, so the C compiler can
+ * turn its calls in tail position into jumps. */
+
+static VULPI_NOINLINE value vulpi_apply1(value function, value a) {
+    value args[] = {a};
+    return vulpi_apply(function, 1, args);
+}
+
+static VULPI_NOINLINE value vulpi_apply2(value function, value a, value b) {
+    value args[] = {a, b};
+    return vulpi_apply(function, 2, args);
+}
+
+static VULPI_NOINLINE value vulpi_apply3(value function, value a, value b, value c) {
+    value args[] = {a, b, c};
+    return vulpi_apply(function, 3, args);
+}
+
+/* Conversion to text */
+
+typedef struct {
+    char *bytes;
+    size_t length;
+    size_t capacity;
+} vulpi_buffer;
+
+static inline void vulpi_append(vulpi_buffer *buffer, const char *bytes, size_t length) {
+    if (buffer->length + length + 1 > buffer->capacity) {
+        buffer->capacity = 2 * (buffer->length + length + 1);
+        buffer->bytes = realloc(buffer->bytes, buffer->capacity);
+
+        if (buffer->bytes == NULL) {
+            vulpi_panic("out of memory");
+        }
+    }
+
+    memcpy(buffer->bytes + buffer->length, bytes, length);
+    buffer->length += length;
+    buffer->bytes[buffer->length] = '\0';
+}
+
+static inline void vulpi_append_text(vulpi_buffer *buffer, const char *text) {
+    vulpi_append(buffer, text, strlen(text));
+}
+
+/* Writes floats like JavaScript does, with the shortest text that reads back as the same
+ * number. */
+static inline void vulpi_show_float(vulpi_buffer *buffer, double number) {
+    char text[32];
+
+    if (number != number) {
+        vulpi_append_text(buffer, "NaN");
+        return;
+    }
+
+    if (number == 1.0 / 0.0 || number == -1.0 / 0.0) {
+        vulpi_append_text(buffer, number > 0 ? "Infinity" : "-Infinity");
+        return;
+    }
+
+    for (int precision = 1; precision <= 17; precision++) {
+        snprintf(text, sizeof(text), "%.*g", precision, number);
+
+        if (strtod(text, NULL) == number) {
+            break;
+        }
+    }
+
+    vulpi_append_text(buffer, text);
+}
+
+/* Writes a value. Strings are quoted when they are inside of other values. */
+static inline void vulpi_show(vulpi_buffer *buffer, value v, int nested) {
+    char text[32];
+
+    if (!vulpi_is_object(v)) {
+        snprintf(text, sizeof(text), "%lld", (long long)vulpi_unbox(v));
+        vulpi_append_text(buffer, text);
+        return;
+    }
+
+    vulpi_header *header = VULPI_HEADER(v);
+
+    switch (header->kind) {
+    case VULPI_STRING: {
+        vulpi_string_t *string = (vulpi_string_t *)v;
+
+        if (nested) {
+            vulpi_append_text(buffer, "\"");
+        }
+
+        vulpi_append(buffer, string->bytes, string->length);
+
+        if (nested) {
+            vulpi_append_text(buffer, "\"");
+        }
+
+        break;
+    }
+    case VULPI_FLOAT:
+        vulpi_show_float(buffer, ((vulpi_float_t *)v)->number);
+        break;
+    case VULPI_CLOSURE:
+        vulpi_append_text(buffer, "<function>");
+        break;
+    case VULPI_DATA: {
+        vulpi_data *data = (vulpi_data *)v;
+        snprintf(text, sizeof(text), "#%d[", header->tag);
+        vulpi_append_text(buffer, text);
+
+        for (uint32_t i = 0; i < header->size; i++) {
+            if (i > 0) {
+                vulpi_append_text(buffer, ", ");
+            }
+
+            vulpi_show(buffer, data->fields[i], 1);
+        }
+
+        vulpi_append_text(buffer, "]");
+        break;
+    }
+    case VULPI_RECORD: {
+        vulpi_data *data = (vulpi_data *)v;
+        vulpi_append_text(buffer, "{");
+
+        for (uint32_t i = 0; i < header->size; i += 2) {
+            vulpi_append_text(buffer, i > 0 ? ", " : " ");
+            vulpi_append_text(buffer, vulpi_field_names[vulpi_unbox(data->fields[i])]);
+            vulpi_append_text(buffer, " = ");
+            vulpi_show(buffer, data->fields[i + 1], 1);
+        }
+
+        vulpi_append_text(buffer, " }");
+        break;
+    }
+    }
+}
+
+/* Primitives. They implement the externals of the programs and own their arguments. */
+
+static inline value vulpi_concat(value left, value right) {
+    vulpi_buffer buffer = {NULL, 0, 0};
+    vulpi_show(&buffer, left, 0);
+    vulpi_show(&buffer, right, 0);
+
+    value result = vulpi_string(buffer.bytes == NULL ? "" : buffer.bytes, buffer.length);
+    free(buffer.bytes);
+
+    vulpi_drop(left);
+    vulpi_drop(right);
+    return result;
+}
+
+/* Adds numbers or joins strings, like the `+` of JavaScript. */
+static inline value vulpi_add(value left, value right) {
+    if (!vulpi_is_object(left) && !vulpi_is_object(right)) {
+        return vulpi_int(vulpi_unbox(left) + vulpi_unbox(right));
+    }
+
+    if (vulpi_is_kind(left, VULPI_STRING) || vulpi_is_kind(right, VULPI_STRING)) {
+        return vulpi_concat(left, right);
+    }
+
+    value result = vulpi_float(vulpi_number(left) + vulpi_number(right));
+    vulpi_drop(left);
+    vulpi_drop(right);
+    return result;
+}
+
+static inline value vulpi_sub(value left, value right) {
+    if (!vulpi_is_object(left) && !vulpi_is_object(right)) {
+        return vulpi_int(vulpi_unbox(left) - vulpi_unbox(right));
+    }
+
+    value result = vulpi_float(vulpi_number(left) - vulpi_number(right));
+    vulpi_drop(left);
+    vulpi_drop(right);
+    return result;
+}
+
+/* Compares values by their structure. Closures are only equal to themselves. */
+static inline int vulpi_equal(value left, value right) {
+    if (left == right) {
+        return 1;
+    }
+
+    if (!vulpi_is_object(left) || !vulpi_is_object(right)) {
+        return 0;
+    }
+
+    vulpi_header *a = VULPI_HEADER(left);
+    vulpi_header *b = VULPI_HEADER(right);
+
+    if (a->kind != b->kind) {
+        return 0;
+    }
+
+    switch (a->kind) {
+    case VULPI_STRING: {
+        vulpi_string_t *string = (vulpi_string_t *)left;
+        return vulpi_string_equals(right, string->bytes, string->length);
+    }
+    case VULPI_FLOAT:
+        return vulpi_float_equals(right, ((vulpi_float_t *)left)->number);
+    case VULPI_DATA:
+        if (a->tag != b->tag || a->size != b->size) {
+            return 0;
+        }
+
+        for (uint32_t i = 0; i < a->size; i++) {
+            if (!vulpi_equal(vulpi_at(left, i), vulpi_at(right, i))) {
+                return 0;
+            }
+        }
+
+        return 1;
+    case VULPI_RECORD:
+        if (a->size != b->size) {
+            return 0;
+        }
+
+        for (uint32_t i = 0; i < a->size; i += 2) {
+            int field = (int)vulpi_unbox(vulpi_at(left, i));
+
+            if (!vulpi_equal(vulpi_at(left, i + 1), vulpi_field(right, field))) {
+                return 0;
+            }
+        }
+
+        return 1;
+    default:
+        return 0;
+    }
+}
+
+static inline value vulpi_eq(value left, value right) {
+    int result = vulpi_equal(left, right);
+    vulpi_drop(left);
+    vulpi_drop(right);
+    return vulpi_int(result);
+}
+
+static inline value vulpi_neq(value left, value right) {
+    return vulpi_int(1 - vulpi_unbox(vulpi_eq(left, right)));
+}
+
+static inline value vulpi_id(value v) {
+    return v;
+}
+
+static inline value vulpi_log(value v) {
+    vulpi_buffer buffer = {NULL, 0, 0};
+    vulpi_show(&buffer, v, 0);
+    vulpi_append_text(&buffer, "\n");
+    fwrite(buffer.bytes, 1, buffer.length, stdout);
+    free(buffer.bytes);
+
+    vulpi_drop(v);
+    return vulpi_unit;
+}
+
+static inline value vulpi_exit(value code) {
+    fflush(stdout);
+    exit((int)vulpi_unbox(code));
+}
+
+#endif
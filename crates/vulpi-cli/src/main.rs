@@ -3,11 +3,68 @@
 
 use std::{backtrace::Backtrace, env, panic, path::PathBuf};
 
-use vulpi_build::real::RealFileSystem;
+use vulpi_build::{manifest::Graph, real::RealFileSystem, ProjectCompiler};
+use vulpi_eval::Interpreter;
 use vulpi_intern::Symbol;
-use vulpi_report::renderer::classic::Classic;
+use vulpi_report::{
+    codes,
+    hash::HashReporter,
+    renderer::{classic::Classic, json::Json, sarif::Sarif},
+    Report,
+};
+use vulpi_vfs::{path::Path, FileSystem};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+
+mod repl;
+mod run;
+
+/// The size of the stack of the thread that runs the interpreter.
+const EVAL_STACK_SIZE: usize = 1 << 30;
+
+/// How the diagnostics are written.
+#[derive(Clone, Copy, ValueEnum)]
+enum MessageFormat {
+    /// Colored messages with the source code, written to stderr.
+    Human,
+
+    /// One JSON object per diagnostic and line, written to stdout.
+    Json,
+
+    /// A single SARIF log, written to stdout.
+    Sarif,
+}
+
+/// How the generated JavaScript is organized.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ModuleFormat {
+    /// A single script where every declaration is global.
+    Script,
+
+    /// One ES module for each module of the package, written to a directory.
+    Esm,
+}
+
+/// What the compilation produces.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Emit {
+    /// The JavaScript of the program.
+    Js,
+
+    /// The lambda IR after the reference counting pass, written to stdout unless there is an
+    /// output file.
+    Lambda,
+}
+
+/// The language that a file is compiled to.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Target {
+    Js,
+
+    /// A C file that builds with the C compiler of the system, together with the runtime that it
+    /// includes.
+    C,
+}
 
 #[derive(Parser)]
 enum Cli {
@@ -15,11 +72,139 @@ enum Cli {
         package: String,
         file_name: String,
 
+        /// The output file, or the output directory for ES modules.
         #[clap(short, long)]
         output: Option<String>,
+
+        /// Treats warnings as errors, so they stop the compilation.
+        #[clap(short = 'W', long)]
+        deny_warnings: bool,
+
+        #[clap(long, value_enum, default_value = "human")]
+        message_format: MessageFormat,
+
+        #[clap(long, value_enum, default_value = "script")]
+        module_format: ModuleFormat,
+
+        #[clap(long, value_enum, default_value = "js")]
+        emit: Emit,
+
+        #[clap(long, value_enum, default_value = "js")]
+        target: Target,
+    },
+
+    /// Builds the package described by the `vulpi.toml` in the directory, together with the
+    /// packages that it depends on.
+    Build {
+        #[clap(default_value = ".")]
+        dir: PathBuf,
+
+        /// Treats warnings as errors, so they stop the compilation.
+        #[clap(short = 'W', long)]
+        deny_warnings: bool,
+
+        #[clap(long, value_enum, default_value = "human")]
+        message_format: MessageFormat,
+
+        #[clap(long, value_enum, default_value = "script")]
+        module_format: ModuleFormat,
+    },
+
+    /// Compiles a file to the build directory and runs it with a JavaScript engine.
+    Run {
+        file_name: String,
+
+        /// The name of the package. It defaults to the name of the current directory.
+        #[clap(short, long)]
+        package: Option<String>,
+
+        /// The engine that runs the program, like `node`, `deno` or `qjs`. It defaults to the
+        /// `VULPI_ENGINE` variable or to the first known engine found in the `PATH`.
+        #[clap(short, long)]
+        engine: Option<String>,
+
+        /// Treats warnings as errors, so they stop the compilation.
+        #[clap(short = 'W', long)]
+        deny_warnings: bool,
+
+        #[clap(long, value_enum, default_value = "human")]
+        message_format: MessageFormat,
+
+        /// The arguments of the program.
+        #[clap(last = true)]
+        args: Vec<String>,
+    },
+
+    /// Checks a file and runs its `main` with the interpreter, without a JavaScript engine. The
+    /// interpreter has no handlers, so programs that use effects are rejected.
+    Eval {
+        file_name: String,
+
+        /// The name of the package. It defaults to the name of the current directory.
+        #[clap(short, long)]
+        package: Option<String>,
+
+        /// Treats warnings as errors, so they stop the compilation.
+        #[clap(short = 'W', long)]
+        deny_warnings: bool,
+
+        #[clap(long, value_enum, default_value = "human")]
+        message_format: MessageFormat,
+    },
+
+    /// Starts an interactive session that checks declarations and evaluates expressions.
+    Repl {
+        /// The name of the package. It defaults to the name of the current directory.
+        #[clap(short, long)]
+        package: Option<String>,
+
+        /// The engine that evaluates the expressions. It's chosen like in the `run` command.
+        #[clap(short, long)]
+        engine: Option<String>,
+    },
+
+    /// Prints the long explanation of an error code, like `E0302`.
+    Explain { code: String },
+
+    /// Starts the language server over stdio.
+    Lsp {
+        #[clap(short, long)]
+        package: Option<String>,
     },
 }
 
+fn reporter(deny_warnings: bool) -> Report {
+    let mut reporter = HashReporter::new();
+
+    if deny_warnings {
+        reporter = reporter.deny_warnings();
+    }
+
+    Report::new(reporter)
+}
+
+/// Writes the diagnostics of the compilation and exits if there are errors.
+fn report(compiler: &ProjectCompiler<RealFileSystem>, message_format: MessageFormat, cwd: PathBuf) {
+    let fs = &compiler.fs;
+    let mut stdout = std::io::stdout();
+
+    match message_format {
+        MessageFormat::Human => compiler.reporter.to_stderr(Classic::new(fs, cwd)),
+        MessageFormat::Json => compiler
+            .reporter
+            .to_json_lines(Json::new(fs, cwd), &mut stdout)
+            .unwrap(),
+        MessageFormat::Sarif => compiler
+            .reporter
+            .to_sarif(Sarif::new(fs, cwd), &mut stdout)
+            .unwrap(),
+    }
+
+    if compiler.reporter.has_errors() {
+        std::process::exit(1);
+    }
+}
+
 fn main() {
     panic::set_hook(Box::new(|e| {
         eprintln!(
@@ -44,29 +229,296 @@ fn main() {
             file_name,
             package,
             output,
+            deny_warnings,
+            message_format,
+            module_format,
+            emit,
+            target,
         } => {
             let cwd = env::current_dir().unwrap();
 
             let name = Symbol::intern(&package);
 
-            let output = output.unwrap_or_else(|| {
-                format!("{}.js", file_name.split(".").next().unwrap().to_string())
+            let mut compiler = ProjectCompiler {
+                fs: RealFileSystem::new(name.clone(), cwd.clone(), cwd.clone().join("build")),
+                reporter: reporter(deny_warnings),
+                name: name.clone(),
+            };
+
+            let root = Path {
+                segments: vec![name.clone(), Symbol::intern("Main")],
+            };
+
+            let file = PathBuf::from(&file_name);
+
+            if emit == Emit::Lambda {
+                let lambda = compiler.emit_lambda(root, file);
+                report(&compiler, message_format, cwd);
+
+                let lambda = lambda.unwrap_or_default();
+
+                match output {
+                    Some(output) => std::fs::write(output, lambda).unwrap(),
+                    None => print!("{}", lambda),
+                }
+
+                return;
+            }
+
+            if target == Target::C {
+                let output =
+                    output.unwrap_or_else(|| format!("{}.c", file_name.split('.').next().unwrap()));
+
+                compiler.compile_c(root, file, PathBuf::from(output));
+                report(&compiler, message_format, cwd);
+                return;
+            }
+
+            let output = output.unwrap_or_else(|| match module_format {
+                ModuleFormat::Script => {
+                    format!("{}.js", file_name.split('.').next().unwrap())
+                }
+                ModuleFormat::Esm => "build".to_string(),
             });
 
-            let mut compiler = vulpi_build::ProjectCompiler {
-                fs: RealFileSystem::new(name.clone(), cwd.clone(), cwd.clone().join("build")),
-                reporter: vulpi_report::hash_reporter(),
+            let output = PathBuf::from(output);
+
+            match module_format {
+                ModuleFormat::Script => {
+                    compiler.compile(root, file, output);
+                }
+                ModuleFormat::Esm => {
+                    if let Err(err) = std::fs::create_dir_all(&output) {
+                        eprintln!("[Error]: cannot create '{}': {}", output.display(), err);
+                        std::process::exit(1);
+                    }
+
+                    compiler.compile_esm(root, file, output);
+                }
+            }
+
+            report(&compiler, message_format, cwd);
+        }
+        Cli::Build {
+            dir,
+            deny_warnings,
+            message_format,
+            module_format,
+        } => {
+            let graph = match Graph::load(&dir) {
+                Ok(graph) => graph,
+                Err(err) => {
+                    eprintln!("[Error]: {}", err);
+                    std::process::exit(1);
+                }
+            };
+
+            let package = &graph.root;
+            let name = Symbol::intern(package.name());
+            let output = package.output();
+
+            let mut fs = RealFileSystem::new(name.clone(), package.source(), output.clone());
+
+            for dependency in &graph.dependencies {
+                fs.add_package(Symbol::intern(dependency.name()), dependency.source());
+            }
+
+            let mut compiler = ProjectCompiler {
+                fs,
+                reporter: reporter(deny_warnings),
                 name: name.clone(),
             };
 
-            compiler.compile(
-                name.clone(),
-                PathBuf::from(file_name),
-                PathBuf::from(output),
-            );
+            let entry = package
+                .manifest
+                .package
+                .entry
+                .split('.')
+                .map(Symbol::intern);
+
+            let root = Path {
+                segments: std::iter::once(name.clone()).chain(entry).collect(),
+            };
 
-            let ctx = Classic::new(&compiler.fs, cwd.clone());
-            compiler.reporter.to_stderr(ctx)
+            let file = compiler.fs.from_src_path(root.clone());
+
+            if let Err(err) = std::fs::create_dir_all(&output) {
+                eprintln!("[Error]: cannot create '{}': {}", output.display(), err);
+                std::process::exit(1);
+            }
+
+            match module_format {
+                ModuleFormat::Script => {
+                    compiler.compile(root, file, output.join(format!("{}.js", name.get())));
+                }
+                ModuleFormat::Esm => {
+                    compiler.compile_esm(root, file, output);
+                }
+            }
+
+            report(&compiler, message_format, env::current_dir().unwrap());
+        }
+        Cli::Run {
+            file_name,
+            package,
+            engine,
+            deny_warnings,
+            message_format,
+            args,
+        } => {
+            let cwd = env::current_dir().unwrap();
+
+            let package = package
+                .or_else(|| cwd.file_name().map(|x| x.to_string_lossy().to_string()))
+                .unwrap_or_else(|| "Main".to_string());
+
+            let name = Symbol::intern(&package);
+
+            let engine = match run::engine(engine) {
+                Ok(engine) => engine,
+                Err(err) => {
+                    eprintln!("[Error]: {}", err);
+                    std::process::exit(1);
+                }
+            };
+
+            let file = PathBuf::from(file_name);
+            let build = cwd.join("build");
+
+            let mut compiler = ProjectCompiler {
+                fs: RealFileSystem::new(name.clone(), cwd.clone(), build.clone()),
+                reporter: reporter(deny_warnings),
+                name: name.clone(),
+            };
+
+            let module = file
+                .with_extension("")
+                .components()
+                .map(|x| Symbol::intern(&x.as_os_str().to_string_lossy()))
+                .collect::<Vec<_>>();
+
+            let root = Path {
+                segments: std::iter::once(name.clone()).chain(module).collect(),
+            };
+
+            if let Err(err) = std::fs::create_dir_all(&build) {
+                eprintln!("[Error]: cannot create '{}': {}", build.display(), err);
+                std::process::exit(1);
+            }
+
+            let output = build.join(format!("{}.js", root.segments.last().unwrap().get()));
+            let symbols = compiler.compile(root, file, output.clone());
+
+            report(&compiler, message_format, cwd.clone());
+
+            match run::run(&engine, &output, &args, &symbols, &compiler.fs, &cwd) {
+                Ok(code) => std::process::exit(code),
+                Err(err) => {
+                    eprintln!("[Error]: {}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Cli::Eval {
+            file_name,
+            package,
+            deny_warnings,
+            message_format,
+        } => {
+            // The interpreter recurses when the program does, so it runs in a thread with a
+            // bigger stack than the main one. The symbols are interned by each thread, so all of
+            // them are created there.
+            let thread = std::thread::Builder::new()
+                .stack_size(EVAL_STACK_SIZE)
+                .spawn(move || {
+                    let cwd = env::current_dir().unwrap();
+
+                    let package = package
+                        .or_else(|| cwd.file_name().map(|x| x.to_string_lossy().to_string()))
+                        .unwrap_or_else(|| "Main".to_string());
+
+                    let name = Symbol::intern(&package);
+
+                    let file = PathBuf::from(file_name);
+                    let build = cwd.join("build");
+
+                    let mut compiler = ProjectCompiler {
+                        fs: RealFileSystem::new(name.clone(), cwd.clone(), build.clone()),
+                        reporter: reporter(deny_warnings),
+                        name: name.clone(),
+                    };
+
+                    let module = file
+                        .with_extension("")
+                        .components()
+                        .map(|x| Symbol::intern(&x.as_os_str().to_string_lossy()))
+                        .collect::<Vec<_>>();
+
+                    let root = Path {
+                        segments: std::iter::once(name.clone()).chain(module).collect(),
+                    };
+
+                    if let Err(err) = std::fs::create_dir_all(&build) {
+                        eprintln!("[Error]: cannot create '{}': {}", build.display(), err);
+                        std::process::exit(1);
+                    }
+
+                    let program = compiler.eval(root, file);
+                    report(&compiler, message_format, cwd);
+
+                    let Some((main, programs)) = program else {
+                        return;
+                    };
+
+                    let mut stdout = std::io::stdout();
+
+                    match Interpreter::new(&programs, &mut stdout).run(&main) {
+                        Ok(()) => (),
+                        Err(vulpi_eval::Error::Exit(code)) => std::process::exit(code),
+                        Err(err) => {
+                            eprintln!("[Error]: {}", err);
+                            std::process::exit(1);
+                        }
+                    }
+                })
+                .unwrap();
+
+            if thread.join().is_err() {
+                std::process::exit(101);
+            }
+        }
+        Cli::Repl { package, engine } => {
+            let cwd = env::current_dir().unwrap();
+
+            let package = package
+                .or_else(|| cwd.file_name().map(|x| x.to_string_lossy().to_string()))
+                .unwrap_or_else(|| "Main".to_string());
+
+            let engine = match run::engine(engine) {
+                Ok(engine) => engine,
+                Err(err) => {
+                    eprintln!("[Error]: {}", err);
+                    std::process::exit(1);
+                }
+            };
+
+            repl::Repl::new(Symbol::intern(&package), engine, cwd).start();
+        }
+        Cli::Explain { code } => {
+            let explanation = codes::parse(&code).and_then(codes::explain);
+
+            let Some(explanation) = explanation else {
+                eprintln!("[Error]: '{}' is not an error code", code);
+                std::process::exit(1);
+            };
+
+            print!("{}", explanation);
+        }
+        Cli::Lsp { package } => {
+            if let Err(err) = vulpi_lsp::start(package) {
+                eprintln!("[Error]: language server stopped: {}", err);
+                std::process::exit(1);
+            }
         }
     }
 }
@@ -0,0 +1,206 @@
+//! The interactive loop of `vulpi repl`. Declarations are added to the session and expressions
+//! are compiled to JavaScript and evaluated by an engine.
+
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    process::Command,
+};
+
+use vulpi_build::repl::Session;
+use vulpi_intern::Symbol;
+use vulpi_report::renderer::classic::Classic;
+
+const HELP: &str = "\
+Expressions are evaluated and printed together with their types. Declarations like `let`, `type`
+and `use` are added to the session.
+
+Commands:
+  :type <expr>       Shows the type of an expression without evaluating it.
+  :kind <type>       Shows the kind of a type.
+  :browse [module]   Lists the declarations of a module, or the ones of the session.
+  :{ ... :}          Reads an input that spans many lines.
+  :help              Shows this message.
+  :quit              Leaves the REPL.
+";
+
+/// The keywords that start a top level declaration.
+const DECLARATIONS: &[&str] = &[
+    "let", "type", "use", "impl", "trait", "effect", "mod", "external", "pub",
+];
+
+fn is_declaration(input: &str) -> bool {
+    let first = input.split_whitespace().next().unwrap_or_default();
+    input.starts_with('#') || DECLARATIONS.contains(&first)
+}
+
+pub struct Repl {
+    session: Session,
+    engine: PathBuf,
+    cwd: PathBuf,
+}
+
+impl Repl {
+    pub fn new(name: Symbol, engine: PathBuf, cwd: PathBuf) -> Self {
+        let mut repl = Self {
+            session: Session::new(name, cwd.clone()),
+            engine,
+            cwd,
+        };
+
+        let prelude = vulpi_vfs::path::Path {
+            segments: vec![Symbol::intern("Prelude")],
+        };
+
+        if repl.session.exists(prelude) {
+            repl.declare("use Prelude");
+            repl.report();
+        }
+
+        repl
+    }
+
+    /// Renders the diagnostics of the last input and forgets them.
+    fn report(&mut self) {
+        let classic = Classic::new(&self.session.compiler.fs, self.cwd.clone());
+        self.session.compiler.reporter.to_stderr(classic);
+        self.session.clear();
+    }
+
+    fn declare(&mut self, input: &str) {
+        if let Some(declared) = self.session.declare(input) {
+            for (name, typ) in declared {
+                println!("{} : {}", name.name.get(), typ);
+            }
+        }
+    }
+
+    fn evaluate(&mut self, input: &str) {
+        let Some((typ, code)) = self.session.evaluate(input) else {
+            return;
+        };
+
+        match self.execute(&code) {
+            Ok(value) => println!("{} : {}", value.trim_end(), typ),
+            Err(err) => eprintln!("[Error]: {}", err),
+        }
+    }
+
+    /// Runs the generated code and returns what it printed.
+    fn execute(&self, code: &str) -> Result<String, String> {
+        let build = self.cwd.join("build");
+        let file = build.join("repl.js");
+
+        fs::create_dir_all(&build)
+            .and_then(|_| fs::write(&file, code))
+            .map_err(|err| format!("cannot write '{}': {}", file.display(), err))?;
+
+        let mut command = Command::new(&self.engine);
+
+        if self.engine.file_stem().is_some_and(|x| x == "deno") {
+            command.arg("run");
+        }
+
+        let output = command
+            .arg(&file)
+            .output()
+            .map_err(|err| format!("cannot start '{}': {}", self.engine.display(), err))?;
+
+        io::stderr().write_all(&output.stderr).ok();
+
+        if !output.status.success() {
+            return Err("the evaluation threw an exception".to_string());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn browse(&mut self, module: &str) {
+        match self.session.browse(module) {
+            Some(lines) => lines.iter().for_each(|line| println!("{}", line)),
+            None if self.session.compiler.reporter.all_diagnostics().is_empty() => {
+                eprintln!("[Error]: cannot find the module '{}'", module)
+            }
+            None => (),
+        }
+    }
+
+    /// Runs a single input, that can be a command, a declaration or an expression. Returns
+    /// `false` when the REPL should stop.
+    fn input(&mut self, input: &str) -> bool {
+        let input = input.trim();
+
+        if let Some(command) = input.strip_prefix(':') {
+            let (command, rest) = command
+                .split_once(char::is_whitespace)
+                .unwrap_or((command, ""));
+            let rest = rest.trim();
+
+            match command {
+                "type" | "t" => {
+                    if let Some(typ) = self.session.type_of(rest) {
+                        println!("{} : {}", rest, typ);
+                    }
+                }
+                "kind" | "k" => {
+                    if let Some(kind) = self.session.kind_of(rest) {
+                        println!("{} : {}", rest, kind);
+                    }
+                }
+                "browse" | "b" => self.browse(rest),
+                "help" | "h" | "?" => print!("{}", HELP),
+                "quit" | "q" => return false,
+                _ => eprintln!("[Error]: unknown command ':{}', try ':help'", command),
+            }
+        } else if is_declaration(input) {
+            self.declare(input);
+        } else if !input.is_empty() {
+            self.evaluate(input);
+        }
+
+        self.report();
+        true
+    }
+
+    /// Reads inputs until the end of the standard input or until `:quit`.
+    pub fn start(&mut self) {
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+
+        loop {
+            prompt("> ");
+
+            let Some(Ok(line)) = lines.next() else {
+                break;
+            };
+
+            let input = if line.trim() == ":{" {
+                let mut block = vec![];
+
+                loop {
+                    prompt("| ");
+
+                    match lines.next() {
+                        Some(Ok(line)) if line.trim() == ":}" => break,
+                        Some(Ok(line)) => block.push(line),
+                        _ => break,
+                    }
+                }
+
+                block.join("\n")
+            } else {
+                line
+            };
+
+            if !self.input(&input) {
+                break;
+            }
+        }
+    }
+}
+
+fn prompt(prompt: &str) {
+    print!("{}", prompt);
+    io::stdout().flush().ok();
+}
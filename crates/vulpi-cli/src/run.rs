@@ -0,0 +1,139 @@
+//! Runs the compiled JavaScript with an engine that is installed in the machine.
+
+use std::{
+    env,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use vulpi_build::{real::RealFileSystem, Symbols};
+use vulpi_report::renderer::LineGuide;
+use vulpi_vfs::FileSystem;
+
+/// The environment variable that chooses the engine when it's not given in the command line.
+pub const ENGINE_VAR: &str = "VULPI_ENGINE";
+
+/// The engines that are searched in the `PATH`, in order of preference.
+const ENGINES: &[&str] = &["node", "deno", "qjs"];
+
+/// Finds the executable of an engine. Names without a separator are searched in the `PATH`.
+fn find(engine: &str) -> Option<PathBuf> {
+    if engine.contains(std::path::MAIN_SEPARATOR) {
+        let path = PathBuf::from(engine);
+        return path.is_file().then_some(path);
+    }
+
+    let paths = env::var_os("PATH")?;
+
+    env::split_paths(&paths)
+        .map(|dir| dir.join(engine))
+        .find(|path| path.is_file())
+}
+
+/// Chooses the engine from the command line, the [ENGINE_VAR] variable or the first one of the
+/// known engines that is installed.
+pub fn engine(engine: Option<String>) -> Result<PathBuf, String> {
+    let engine = engine.or_else(|| env::var(ENGINE_VAR).ok());
+
+    match engine {
+        Some(engine) => find(&engine).ok_or_else(|| format!("cannot find the engine '{}'", engine)),
+        None => ENGINES.iter().find_map(|x| find(x)).ok_or_else(|| {
+            format!(
+                "cannot find a JavaScript engine, install one of {} or set {}",
+                ENGINES.join(", "),
+                ENGINE_VAR
+            )
+        }),
+    }
+}
+
+/// Runs a JavaScript file and returns its exit code. The output of the engine goes straight to
+/// the terminal, but the error output is also read to find the functions in the stack trace of an
+/// uncaught exception.
+pub fn run(
+    engine: &Path,
+    file: &Path,
+    args: &[String],
+    symbols: &Symbols,
+    fs: &RealFileSystem,
+    cwd: &Path,
+) -> Result<i32, String> {
+    let mut command = Command::new(engine);
+
+    // Deno needs a subcommand to run a script and Node only reads the source maps when asked.
+    match engine.file_stem().and_then(|x| x.to_str()) {
+        Some("deno") => {
+            command.arg("run");
+        }
+        Some("node") => {
+            command.arg("--enable-source-maps");
+        }
+        _ => (),
+    }
+
+    let mut child = command
+        .arg(file)
+        .args(args)
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("cannot start '{}': {}", engine.display(), err))?;
+
+    let mut frames = vec![];
+
+    if let Some(stderr) = child.stderr.take() {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            eprintln!("{}", line);
+
+            if let Some(name) = frame(&line) {
+                frames.push(name.to_string());
+            }
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|err| format!("cannot wait for '{}': {}", engine.display(), err))?;
+
+    let code = status.code().unwrap_or(1);
+
+    if code != 0 {
+        if let Some(location) = frames.iter().find_map(|x| locate(x, symbols, fs, cwd)) {
+            eprintln!("[Error]: uncaught exception in {}", location);
+        }
+    }
+
+    Ok(code)
+}
+
+/// Gets the name of the function in a line of a stack trace like `    at Main$main (file.js:1:2)`.
+fn frame(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix("at ")?;
+    rest.split([' ', '(']).next().filter(|x| !x.is_empty())
+}
+
+/// Describes the definition that generated a JavaScript function, with its place in the source
+/// when it's known.
+fn locate(name: &str, symbols: &Symbols, fs: &RealFileSystem, cwd: &Path) -> Option<String> {
+    let (qualified, span) = symbols.get(name)?;
+    let name = qualified.to_string();
+
+    let place = span.as_ref().and_then(|span| {
+        let path = fs.path(span.file).ok()?;
+        let content = fs.read(span.file).ok()?;
+        let (line, column) = LineGuide::new(&content).to_line_and_column(span.start.clone())?;
+        let relative = path.strip_prefix(cwd).unwrap_or(path);
+
+        Some(format!(
+            "{}:{}:{}",
+            relative.display(),
+            line + 1,
+            column + 1
+        ))
+    });
+
+    match place {
+        Some(place) => Some(format!("'{}' at {}", name, place)),
+        None => Some(format!("'{}'", name)),
+    }
+}
@@ -0,0 +1,446 @@
+//! A tree-walking interpreter of the lambda IR, to run programs without compiling them to
+//! JavaScript. The externals are implemented by the [primitives] of their bindings, and the
+//! top-level constants are evaluated the first time that they are used.
+//!
+//! Calls in tail position replace the expression that is being evaluated instead of using the
+//! stack of Rust, so loops written as recursive functions run in constant space. Effects are not
+//! supported, and the programs that use them are rejected by the compiler before they run.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io::Write,
+    rc::Rc,
+};
+
+use vulpi_intern::Symbol;
+use vulpi_syntax::{
+    elaborated::LiteralKind,
+    lambda::{Case, ExprKind, Program, Stmt, TagType, Tree},
+    r#abstract::Qualified,
+};
+
+pub mod primitives;
+mod value;
+
+pub use value::{Closure, Function, Value};
+
+pub enum Error {
+    /// The program asked to stop with an exit code.
+    Exit(i32),
+    Output(std::io::Error),
+
+    /// A primitive was given values that it can't work with.
+    Primitive(&'static str, String),
+
+    /// An external whose binding has no primitive was used.
+    External(String),
+    NotFound(String),
+
+    /// A constant needs its own value to be evaluated.
+    Cycle(String),
+
+    /// No pattern matched the value.
+    Unmatched(String),
+
+    /// A value was used in a way that its type does not allow.
+    Invalid(String),
+    Effects,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Exit(code) => write!(f, "the program exited with the code {}", code),
+            Error::Output(err) => write!(f, "cannot write the output: {}", err),
+            Error::Primitive(binding, args) => {
+                write!(f, "the primitive '{}' cannot take {}", binding, args)
+            }
+            Error::External(binding) => {
+                write!(
+                    f,
+                    "the external '{}' is not available in the interpreter",
+                    binding
+                )
+            }
+            Error::NotFound(name) => write!(f, "cannot find the declaration '{}'", name),
+            Error::Cycle(name) => write!(f, "the constant '{}' depends on itself", name),
+            Error::Unmatched(value) => write!(f, "no pattern matches the value {}", value),
+            Error::Invalid(message) => write!(f, "{}", message),
+            Error::Effects => write!(f, "effects are not supported by the interpreter"),
+        }
+    }
+}
+
+struct Scope<'a> {
+    name: Symbol,
+    value: Value<'a>,
+    next: Env<'a>,
+}
+
+/// The variables that an expression can see. Binding a variable keeps the previous environment,
+/// so closures capture it without copying.
+#[derive(Clone, Default)]
+pub(crate) struct Env<'a>(Option<Rc<Scope<'a>>>);
+
+impl<'a> Env<'a> {
+    fn bind(&self, name: Symbol, value: Value<'a>) -> Self {
+        Env(Some(Rc::new(Scope {
+            name,
+            value,
+            next: self.clone(),
+        })))
+    }
+
+    fn get(&self, name: &Symbol) -> &Value<'a> {
+        let mut env = self;
+
+        while let Some(scope) = &env.0 {
+            if scope.name == *name {
+                return &scope.value;
+            }
+            env = &scope.next;
+        }
+
+        panic!("the variable '{}' is not bound", name.get())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Global<'a> {
+    Let(&'a ExprKind),
+    External(&'a Symbol),
+}
+
+/// What is left to do after a function is given its arguments.
+enum Next<'a> {
+    Done(Value<'a>),
+
+    /// Evaluates the body of a closure in its environment.
+    Jump(Env<'a>, &'a ExprKind),
+}
+
+pub struct Interpreter<'a> {
+    globals: HashMap<&'a Qualified, Global<'a>>,
+
+    /// The values of the top-level declarations that were already evaluated.
+    values: HashMap<Qualified, Value<'a>>,
+    evaluating: HashSet<Qualified>,
+
+    /// Where the programs write their output.
+    out: &'a mut dyn Write,
+}
+
+fn literal<'a>(literal: &LiteralKind) -> Value<'a> {
+    match literal {
+        LiteralKind::Integer(x) => {
+            let x = x.get().replace('_', "");
+            x.parse()
+                .map(Value::Int)
+                .unwrap_or_else(|_| Value::Float(x.parse().unwrap_or(f64::NAN)))
+        }
+        LiteralKind::Float(x) => Value::Float(x.get().replace('_', "").parse().unwrap_or(f64::NAN)),
+        // Characters are strings, like in the JavaScript backend.
+        LiteralKind::String(x) | LiteralKind::Char(x) => Value::String(x.get().into()),
+        LiteralKind::Unit => Value::unit(),
+    }
+}
+
+/// Checks if a value matches the case of a branch.
+fn matches(value: &Value, case: &Case, tag: &TagType) -> bool {
+    match (case, tag, value) {
+        (Case::Constructor(_, _), TagType::Field(id), Value::Object(tag, _)) => tag == id,
+        (Case::Constructor(_, _), TagType::Number(id), Value::Int(x)) => *x == *id as i64,
+        (Case::Literal(lit), _, value) => literal(lit) == *value,
+        // Tuples, records and the only constructor of a type always match.
+        (Case::Tuple(_) | Case::Record(_, _), _, _)
+        | (Case::Constructor(_, _), TagType::None, _) => true,
+        _ => false,
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(programs: &'a [Program], out: &'a mut dyn Write) -> Self {
+        let mut globals = HashMap::new();
+
+        for program in programs {
+            for external in &program.externals {
+                globals.insert(&external.name, Global::External(&external.binding));
+            }
+
+            for (name, decl) in &program.lets {
+                globals.insert(name, Global::Let(&decl.body));
+            }
+        }
+
+        Self {
+            globals,
+            values: HashMap::new(),
+            evaluating: HashSet::new(),
+            out,
+        }
+    }
+
+    /// Evaluates `main` and applies it to the unit if it's a function.
+    pub fn run(&mut self, main: &Qualified) -> Result<(), Error> {
+        let main = self.get(main)?;
+
+        if let Value::Function(_) = main {
+            self.apply(main, vec![Value::unit()])?;
+        }
+
+        self.out.flush().map_err(Error::Output)
+    }
+
+    /// The value of a top-level declaration. Constants are evaluated only once.
+    pub fn get(&mut self, name: &Qualified) -> Result<Value<'a>, Error> {
+        if let Some(value) = self.values.get(name) {
+            return Ok(value.clone());
+        }
+
+        let value = match self.globals.get(name).copied() {
+            Some(Global::Let(body)) => {
+                if !self.evaluating.insert(name.clone()) {
+                    return Err(Error::Cycle(name.to_string()));
+                }
+
+                let value = self.eval(Env::default(), body);
+                self.evaluating.remove(name);
+                value?
+            }
+            Some(Global::External(binding)) => match primitives::find(&binding.get()) {
+                Some(primitive) => Value::Function(Rc::new(Function::Primitive(primitive))),
+                None => return Err(Error::External(binding.get())),
+            },
+            None => return Err(Error::NotFound(name.to_string())),
+        };
+
+        self.values.insert(name.clone(), value.clone());
+        Ok(value)
+    }
+
+    /// Calls a function with some arguments.
+    pub fn apply(&mut self, func: Value<'a>, args: Vec<Value<'a>>) -> Result<Value<'a>, Error> {
+        let next = self.enter(func, args)?;
+        self.finish(next)
+    }
+
+    fn finish(&mut self, next: Next<'a>) -> Result<Value<'a>, Error> {
+        match next {
+            Next::Done(value) => Ok(value),
+            Next::Jump(env, body) => self.eval(env, body),
+        }
+    }
+
+    /// Gives the arguments to a function. Functions that are given less arguments than they take
+    /// wait for the others, and the result of the ones that are given more is called with the
+    /// rest.
+    fn enter(&mut self, mut func: Value<'a>, mut args: Vec<Value<'a>>) -> Result<Next<'a>, Error> {
+        loop {
+            let Value::Function(function) = &func else {
+                return Err(Error::Invalid(format!("cannot call the value {}", func)));
+            };
+
+            let function = function.clone();
+            let arity = function.arity();
+
+            if args.len() < arity {
+                let partial = Function::Partial(func, args);
+                return Ok(Next::Done(Value::Function(Rc::new(partial))));
+            }
+
+            let rest = args.split_off(arity);
+
+            let next = match &*function {
+                Function::Closure(closure) => {
+                    let env = closure
+                        .params
+                        .iter()
+                        .zip(args)
+                        .fold(closure.env.clone(), |env, (param, arg)| {
+                            env.bind(param.clone(), arg)
+                        });
+
+                    Next::Jump(env, closure.body)
+                }
+                Function::Primitive(primitive) => Next::Done((primitive.run)(self.out, args)?),
+                Function::Partial(inner, given) => {
+                    func = inner.clone();
+                    args = given.iter().cloned().chain(args).chain(rest).collect();
+                    continue;
+                }
+            };
+
+            if rest.is_empty() {
+                return Ok(next);
+            }
+
+            func = self.finish(next)?;
+            args = rest;
+        }
+    }
+
+    fn values(
+        &mut self,
+        env: &Env<'a>,
+        exprs: &'a [Box<ExprKind>],
+    ) -> Result<Vec<Value<'a>>, Error> {
+        exprs.iter().map(|x| self.eval(env.clone(), x)).collect()
+    }
+
+    fn fields(
+        &mut self,
+        env: &Env<'a>,
+        fields: &'a [(Symbol, Box<ExprKind>)],
+    ) -> Result<Vec<(Symbol, Value<'a>)>, Error> {
+        fields
+            .iter()
+            .map(|(name, expr)| Ok((name.clone(), self.eval(env.clone(), expr)?)))
+            .collect()
+    }
+
+    /// Evaluates an expression. The expressions in tail position are evaluated in the same loop.
+    fn eval(&mut self, mut env: Env<'a>, mut expr: &'a ExprKind) -> Result<Value<'a>, Error> {
+        loop {
+            match expr {
+                ExprKind::Lambda(params, body) => {
+                    let closure = Closure { params, body, env };
+
+                    return Ok(Value::Function(Rc::new(Function::Closure(closure))));
+                }
+                ExprKind::Application(func, args, _) => {
+                    let func = self.eval(env.clone(), func)?;
+                    let args = self.values(&env, args)?;
+
+                    match self.enter(func, args)? {
+                        Next::Done(value) => return Ok(value),
+                        Next::Jump(next, body) => {
+                            env = next;
+                            expr = body;
+                        }
+                    }
+                }
+                ExprKind::Variable(name) => return Ok(env.get(name).clone()),
+                ExprKind::Constructor(name) | ExprKind::Function(name) => return self.get(name),
+                ExprKind::Object(tag, args) | ExprKind::Reuse(_, tag, args) => {
+                    return Ok(Value::Object(*tag, self.values(&env, args)?.into()))
+                }
+                ExprKind::Tuple(args) => {
+                    return Ok(Value::Object(0, self.values(&env, args)?.into()))
+                }
+                ExprKind::Projection(field, expr) => {
+                    return match self.eval(env, expr)? {
+                        Value::Record(fields) => fields
+                            .iter()
+                            .find(|(name, _)| *name == field.name)
+                            .map(|(_, value)| value.clone())
+                            .ok_or_else(|| {
+                                Error::Invalid(format!(
+                                    "the record has no field '{}'",
+                                    field.name.get()
+                                ))
+                            }),
+                        value => Err(Error::Invalid(format!(
+                            "the value {} is not a record",
+                            value
+                        ))),
+                    }
+                }
+                ExprKind::Access(expr, place) => {
+                    return match self.eval(env, expr)? {
+                        Value::Object(_, fields) if *place < fields.len() => {
+                            Ok(fields[*place].clone())
+                        }
+                        value => Err(Error::Invalid(format!(
+                            "the value {} has no field {}",
+                            value, place
+                        ))),
+                    }
+                }
+                ExprKind::Block(stmts) => match stmts.split_last() {
+                    Some((Stmt::Expr(last), stmts)) => {
+                        for stmt in stmts {
+                            env = self.stmt(env, stmt)?;
+                        }
+                        expr = last;
+                    }
+                    _ => {
+                        for stmt in stmts {
+                            env = self.stmt(env, stmt)?;
+                        }
+                        return Ok(Value::unit());
+                    }
+                },
+                ExprKind::Literal(lit) => return Ok(literal(lit)),
+                ExprKind::RecordInstance(_, fields) => {
+                    return Ok(Value::Record(self.fields(&env, fields)?.into()))
+                }
+                ExprKind::RecordUpdate(_, expr, fields) => {
+                    let Value::Record(record) = self.eval(env.clone(), expr)? else {
+                        return Err(Error::Invalid("only records can be updated".to_string()));
+                    };
+
+                    let mut record = record.to_vec();
+
+                    for (name, value) in self.fields(&env, fields)? {
+                        match record.iter_mut().find(|(field, _)| *field == name) {
+                            Some((_, field)) => *field = value,
+                            None => record.push((name, value)),
+                        }
+                    }
+
+                    return Ok(Value::Record(record.into()));
+                }
+                ExprKind::Switch(_, tree, actions) => {
+                    let action = self.tree(&env, tree)?;
+                    expr = &actions[action];
+                }
+                ExprKind::Computation(_)
+                | ExprKind::Perform(_)
+                | ExprKind::Handle(_, _, _, _, _)
+                | ExprKind::Request(_, _, _) => return Err(Error::Effects),
+            }
+        }
+    }
+
+    fn stmt(&mut self, env: Env<'a>, stmt: &'a Stmt) -> Result<Env<'a>, Error> {
+        match stmt {
+            Stmt::Let(name, expr) => {
+                let value = self.eval(env.clone(), expr)?;
+                Ok(env.bind(name.clone(), value))
+            }
+            Stmt::Expr(expr) => {
+                self.eval(env.clone(), expr)?;
+                Ok(env)
+            }
+            // The memory is managed by Rust, so objects are never reused.
+            Stmt::Unique(_, _, shared) => shared
+                .iter()
+                .try_fold(env, |env, stmt| self.stmt(env, stmt)),
+            Stmt::Dup(_) | Stmt::Drop(_) | Stmt::Decref(_) | Stmt::Free(_) | Stmt::Token(_, _) => {
+                Ok(env)
+            }
+        }
+    }
+
+    /// Finds the action of a decision tree that matches the values of the variables.
+    fn tree(&mut self, env: &Env<'a>, mut tree: &'a Tree) -> Result<usize, Error> {
+        loop {
+            let (scrutinee, branches, default) = match tree {
+                Tree::Leaf(action) => return Ok(*action),
+                Tree::Switch(scrutinee, branches, default) => (scrutinee, branches, default),
+            };
+
+            let value = self.eval(env.clone(), scrutinee)?;
+            let branch = branches
+                .iter()
+                .find(|(case, tag, _)| matches(&value, case, tag));
+
+            tree = match (branch, default) {
+                (Some((_, _, next)), _) => next,
+                (None, Some(default)) => default,
+                (None, None) => return Err(Error::Unmatched(value.to_string())),
+            };
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! The functions that implement the externals of the programs, by their binding. They do what the
+//! JavaScript that the bindings name does, for the values that Vulpi programs can give them.
+
+use std::io::Write;
+
+use crate::{value::Value, Error};
+
+pub struct Primitive {
+    /// The binding of the externals that the primitive implements, like `console.log`.
+    pub binding: &'static str,
+    pub arity: usize,
+    pub run: for<'a> fn(&mut dyn Write, Vec<Value<'a>>) -> Result<Value<'a>, Error>,
+}
+
+pub const PRIMITIVES: &[Primitive] = &[
+    Primitive {
+        binding: "add",
+        arity: 2,
+        run: add,
+    },
+    Primitive {
+        binding: "sub",
+        arity: 2,
+        run: sub,
+    },
+    Primitive {
+        binding: "concat",
+        arity: 2,
+        run: concat,
+    },
+    Primitive {
+        binding: "eq",
+        arity: 2,
+        run: |_, args| Ok(Value::bool(args[0] == args[1])),
+    },
+    Primitive {
+        binding: "1 - eq",
+        arity: 2,
+        run: |_, args| Ok(Value::bool(args[0] != args[1])),
+    },
+    Primitive {
+        binding: "id",
+        arity: 1,
+        run: |_, mut args| Ok(args.remove(0)),
+    },
+    Primitive {
+        binding: "console.log",
+        arity: 1,
+        run: log,
+    },
+    Primitive {
+        binding: "process.exit",
+        arity: 1,
+        run: exit,
+    },
+];
+
+/// Finds the primitive that implements the externals with a binding.
+pub fn find(binding: &str) -> Option<&'static Primitive> {
+    PRIMITIVES.iter().find(|x| x.binding == binding)
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(x) => Some(*x as f64),
+        Value::Float(x) => Some(*x),
+        _ => None,
+    }
+}
+
+/// Adds numbers or joins strings, like the `+` of JavaScript.
+fn add<'a>(_: &mut dyn Write, args: Vec<Value<'a>>) -> Result<Value<'a>, Error> {
+    match (&args[0], &args[1]) {
+        (Value::Int(x), Value::Int(y)) => Ok(Value::Int(x.wrapping_add(*y))),
+        (Value::String(_), _) | (_, Value::String(_)) => {
+            Ok(Value::String(format!("{}{}", args[0], args[1]).into()))
+        }
+        (x, y) => match (number(x), number(y)) {
+            (Some(x), Some(y)) => Ok(Value::Float(x + y)),
+            _ => Err(Error::Primitive("add", format!("{x}, {y}"))),
+        },
+    }
+}
+
+fn sub<'a>(_: &mut dyn Write, args: Vec<Value<'a>>) -> Result<Value<'a>, Error> {
+    match (&args[0], &args[1]) {
+        (Value::Int(x), Value::Int(y)) => Ok(Value::Int(x.wrapping_sub(*y))),
+        (x, y) => match (number(x), number(y)) {
+            (Some(x), Some(y)) => Ok(Value::Float(x - y)),
+            _ => Err(Error::Primitive("sub", format!("{x}, {y}"))),
+        },
+    }
+}
+
+fn concat<'a>(_: &mut dyn Write, args: Vec<Value<'a>>) -> Result<Value<'a>, Error> {
+    Ok(Value::String(format!("{}{}", args[0], args[1]).into()))
+}
+
+fn log<'a>(out: &mut dyn Write, args: Vec<Value<'a>>) -> Result<Value<'a>, Error> {
+    writeln!(out, "{}", args[0]).map_err(Error::Output)?;
+    Ok(Value::unit())
+}
+
+fn exit<'a>(out: &mut dyn Write, args: Vec<Value<'a>>) -> Result<Value<'a>, Error> {
+    out.flush().map_err(Error::Output)?;
+
+    match args[0] {
+        Value::Int(code) => Err(Error::Exit(code as i32)),
+        ref code => Err(Error::Primitive("process.exit", code.to_string())),
+    }
+}
@@ -0,0 +1,125 @@
+//! The values that the interpreter works with.
+
+use std::{fmt::Display, rc::Rc};
+
+use vulpi_intern::Symbol;
+use vulpi_syntax::lambda::ExprKind;
+
+use crate::{primitives::Primitive, Env};
+
+/// A function that was created by a lambda, together with the variables that it can see.
+pub struct Closure<'a> {
+    pub(crate) params: &'a [Symbol],
+    pub(crate) body: &'a ExprKind,
+    pub(crate) env: Env<'a>,
+}
+
+pub enum Function<'a> {
+    Closure(Closure<'a>),
+    Primitive(&'static Primitive),
+
+    /// A function that was given less arguments than it takes.
+    Partial(Value<'a>, Vec<Value<'a>>),
+}
+
+impl<'a> Function<'a> {
+    /// The number of arguments that the function needs to run.
+    pub fn arity(&self) -> usize {
+        match self {
+            Function::Closure(closure) => closure.params.len(),
+            Function::Primitive(primitive) => primitive.arity,
+            Function::Partial(Value::Function(function), args) => function.arity() - args.len(),
+            Function::Partial(_, _) => 0,
+        }
+    }
+}
+
+/// A value of a program. Constructors are laid out like the lowering says in their
+/// [ConsDef](vulpi_syntax::lambda::ConsDef): the ones of enumerations are integers, the ones with
+/// fields are objects with the number of the constructor, and tuples are objects with the number
+/// zero.
+#[derive(Clone)]
+pub enum Value<'a> {
+    Int(i64),
+    Float(f64),
+    String(Rc<str>),
+    Object(usize, Rc<[Value<'a>]>),
+    Record(Rc<[(Symbol, Value<'a>)]>),
+    Function(Rc<Function<'a>>),
+}
+
+impl<'a> Value<'a> {
+    pub fn unit() -> Self {
+        Value::Int(0)
+    }
+
+    pub fn bool(value: bool) -> Self {
+        Value::Int(value as i64)
+    }
+
+    /// Writes the value like the C backend does. Strings are quoted when they are inside of other
+    /// values.
+    fn show(&self, f: &mut std::fmt::Formatter<'_>, nested: bool) -> std::fmt::Result {
+        match self {
+            Value::Int(x) => write!(f, "{x}"),
+            Value::Float(x) if x.is_infinite() => {
+                write!(f, "{}", if *x > 0.0 { "Infinity" } else { "-Infinity" })
+            }
+            Value::Float(x) => write!(f, "{x}"),
+            Value::String(x) if nested => write!(f, "{x:?}"),
+            Value::String(x) => write!(f, "{x}"),
+            Value::Object(tag, fields) => {
+                write!(f, "#{tag}[")?;
+
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    field.show(f, true)?;
+                }
+
+                write!(f, "]")
+            }
+            Value::Record(fields) => {
+                write!(f, "{{")?;
+
+                for (i, (name, field)) in fields.iter().enumerate() {
+                    write!(f, "{}{} = ", if i > 0 { ", " } else { " " }, name.get())?;
+                    field.show(f, true)?;
+                }
+
+                write!(f, " }}")
+            }
+            Value::Function(_) => write!(f, "<function>"),
+        }
+    }
+}
+
+impl<'a> Display for Value<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.show(f, false)
+    }
+}
+
+impl<'a> PartialEq for Value<'a> {
+    /// Compares values by their structure. Functions are only equal to themselves.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(x), Value::Int(y)) => x == y,
+            (Value::Float(x), Value::Float(y)) => x == y,
+            (Value::Int(x), Value::Float(y)) | (Value::Float(y), Value::Int(x)) => *x as f64 == *y,
+            (Value::String(x), Value::String(y)) => x == y,
+            (Value::Object(x, xs), Value::Object(y, ys)) => x == y && xs == ys,
+            (Value::Record(xs), Value::Record(ys)) => {
+                xs.len() == ys.len()
+                    && xs.iter().all(|(name, x)| {
+                        ys.iter()
+                            .find(|(other, _)| other == name)
+                            .is_some_and(|(_, y)| x == y)
+                    })
+            }
+            (Value::Function(x), Value::Function(y)) => Rc::ptr_eq(x, y),
+            _ => false,
+        }
+    }
+}
@@ -46,6 +46,41 @@ impl Symbol {
     }
 }
 
+/// Symbols are stored as their strings, so they can be interned again by other runs of the
+/// compiler. Generated symbols only make sense inside the run that created them and keep their id.
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Symbol::Generated(n) => serializer.serialize_u64(*n as u64),
+            Symbol::Interned(_) => serializer.serialize_str(&self.get()),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Symbol;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a string or the id of a generated symbol")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, n: u64) -> Result<Symbol, E> {
+                Ok(Symbol::Generated(n as usize))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, string: &str) -> Result<Symbol, E> {
+                Ok(Symbol::intern(string))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
 impl Show for Symbol {
     fn show(&self) -> vulpi_show::TreeDisplay {
         vulpi_show::TreeDisplay::label(&format!("Symbol: {}", self.get()))
@@ -4,7 +4,7 @@ use petgraph::{graph::DiGraph, stable_graph::NodeIndex};
 use vulpi_intern::Symbol;
 use vulpi_syntax::{
     lambda::{self, LetDecl, Program},
-    r#abstract::Qualified,
+    r#abstract::{Qualified, Visibility},
 };
 
 pub struct Context {
@@ -34,7 +34,7 @@ impl Check for lambda::ExprKind {
     fn check(&mut self, ctx: &mut Context) {
         match self {
             lambda::ExprKind::Lambda(_, expr) => expr.check(ctx),
-            lambda::ExprKind::Application(expr, args) => {
+            lambda::ExprKind::Application(expr, args, _) => {
                 expr.check(ctx);
                 for arg in args {
                     arg.check(ctx);
@@ -54,7 +54,7 @@ impl Check for lambda::ExprKind {
 
                 ctx.graph.add_edge(*current, node, ());
             }
-            lambda::ExprKind::Object(_, args) => {
+            lambda::ExprKind::Object(_, args) | lambda::ExprKind::Reuse(_, _, args) => {
                 for arg in args {
                     arg.check(ctx);
                 }
@@ -66,6 +66,7 @@ impl Check for lambda::ExprKind {
                     match stmt {
                         lambda::Stmt::Let(_, expr) => expr.check(ctx),
                         lambda::Stmt::Expr(expr) => expr.check(ctx),
+                        _ => {}
                     }
                 }
             }
@@ -91,6 +92,17 @@ impl Check for lambda::ExprKind {
                     action.check(ctx);
                 }
             }
+            lambda::ExprKind::Computation(expr) => expr.check(ctx),
+            lambda::ExprKind::Perform(expr) => expr.check(ctx),
+            lambda::ExprKind::Handle(_, body, handler, _, _) => {
+                body.check(ctx);
+                handler.check(ctx);
+            }
+            lambda::ExprKind::Request(_, _, args) => {
+                for arg in args {
+                    arg.check(ctx);
+                }
+            }
         }
     }
 }
@@ -131,11 +143,12 @@ pub fn is_constant(expr: &lambda::Expr) -> bool {
 pub fn has_no_side_effects(expr: &lambda::Expr) -> bool {
     match &**expr {
         lambda::ExprKind::Lambda(_, _) => true,
-        lambda::ExprKind::Application(_, _) => false,
+        lambda::ExprKind::Application(_, _, _) => false,
         lambda::ExprKind::Variable(_) => true,
         lambda::ExprKind::Constructor(_) => true,
         lambda::ExprKind::Function(_) => true,
         lambda::ExprKind::Object(_, _) => true,
+        lambda::ExprKind::Reuse(_, _, _) => true,
         lambda::ExprKind::Projection(_, expr) => has_no_side_effects(expr),
         lambda::ExprKind::Access(expr, _) => has_no_side_effects(expr),
         lambda::ExprKind::Block(_) => true,
@@ -144,10 +157,14 @@ pub fn has_no_side_effects(expr: &lambda::Expr) -> bool {
         lambda::ExprKind::RecordUpdate(_, _, _) => false,
         lambda::ExprKind::Tuple(args) => args.iter().all(has_no_side_effects),
         lambda::ExprKind::Switch(_, _, _) => false,
+        lambda::ExprKind::Computation(_) => true,
+        lambda::ExprKind::Perform(_) => false,
+        lambda::ExprKind::Handle(_, _, _, _, _) => true,
+        lambda::ExprKind::Request(_, _, _) => false,
     }
 }
 
-pub fn remove_lets(program: &mut Program, ctx: &mut Context) {
+pub fn remove_lets(program: &mut Program, ctx: &mut Context, keep_public: bool) {
     program.lets = mem::take(&mut program.lets)
         .into_iter()
         .filter(|(name, body)| {
@@ -158,15 +175,26 @@ pub fn remove_lets(program: &mut Program, ctx: &mut Context) {
                 != 0
                 || (is_constant(&body.body) && body.is_in_source_code)
                 || !has_no_side_effects(&body.body)
+                || (keep_public && body.visibility == Visibility::Public)
         })
         .collect();
 }
 
-pub fn dead_code_remove(programs: &mut Vec<Program>) {
+fn remove(programs: &mut Vec<Program>, keep_public: bool) {
     let mut ctx = Context::default();
     programs.check(&mut ctx);
 
     for program in programs {
-        remove_lets(program, &mut ctx);
+        remove_lets(program, &mut ctx, keep_public);
     }
 }
+
+pub fn dead_code_remove(programs: &mut Vec<Program>) {
+    remove(programs, false)
+}
+
+/// Removes the unused declarations except for the public ones, that can be used by code that
+/// imports the modules.
+pub fn dead_code_remove_private(programs: &mut Vec<Program>) {
+    remove(programs, true)
+}
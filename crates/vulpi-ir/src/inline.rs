@@ -2,11 +2,13 @@ use std::{collections::{HashMap, HashSet}, rc::Rc, cell::RefCell};
 
 use petgraph::{stable_graph::NodeIndex, graph::DiGraph, visit::EdgeRef};
 use vulpi_intern::Symbol;
+use vulpi_location::Span;
 use vulpi_show::Show;
 use vulpi_syntax::{lambda::{self, LetDecl, Program}, r#abstract::Qualified};
 
 pub struct Context<'a> {
-    vars: HashMap<Qualified, Vec<&'a mut lambda::ExprKind>>,
+    /// The places where each declaration is used, together with the declaration that uses it.
+    vars: HashMap<Qualified, Vec<(Qualified, &'a mut lambda::ExprKind)>>,
     nodes: HashMap<Qualified, NodeIndex>,
     graph: DiGraph<(), ()>,
     should_inline: HashMap<Qualified, lambda::ExprKind>,
@@ -42,7 +44,7 @@ impl Transform for lambda::ExprKind {
     fn transform<'a>(&'a mut self, ctx: &mut Context<'a>) {
         match self {
             lambda::ExprKind::Lambda(_, expr) => expr.transform(ctx),
-            lambda::ExprKind::Application(expr, args) => {
+            lambda::ExprKind::Application(expr, args, _) => {
                 expr.transform(ctx);
                 for arg in args {
                     arg.transform(ctx);
@@ -56,9 +58,10 @@ impl Transform for lambda::ExprKind {
 
                 ctx.graph.add_edge(*current, node, ());
 
-                ctx.vars.entry(c.clone()).or_default().push(self);
+                let current = ctx.current.clone();
+                ctx.vars.entry(c.clone()).or_default().push((current, self));
             }
-            lambda::ExprKind::Object(_, args) => {
+            lambda::ExprKind::Object(_, args) | lambda::ExprKind::Reuse(_, _, args) => {
                 for arg in args {
                     arg.transform(ctx);
                 }
@@ -70,6 +73,7 @@ impl Transform for lambda::ExprKind {
                     match stmt {
                         lambda::Stmt::Let(_, expr) => expr.transform(ctx),
                         lambda::Stmt::Expr(expr) => expr.transform(ctx),
+                        _ => {}
                     }
                 }
             }
@@ -95,6 +99,17 @@ impl Transform for lambda::ExprKind {
                     action.transform(ctx);
                 }
             }
+            lambda::ExprKind::Computation(expr) => expr.transform(ctx),
+            lambda::ExprKind::Perform(expr) => expr.transform(ctx),
+            lambda::ExprKind::Handle(_, body, handler, _, _) => {
+                body.transform(ctx);
+                handler.transform(ctx);
+            }
+            lambda::ExprKind::Request(_, _, args) => {
+                for arg in args {
+                    arg.transform(ctx);
+                }
+            }
         }
     }
 }
@@ -142,7 +157,7 @@ pub fn traverse<F: Fn(&mut lambda::ExprKind) -> ()>(expr: &mut lambda::ExprKind,
         lambda::ExprKind::Lambda(_, body) => {
             traverse(body, f)
         },
-        lambda::ExprKind::Application(func, args) => {
+        lambda::ExprKind::Application(func, args, _) => {
             traverse(func, f.clone());
             for arg in args {
                 traverse(arg, f.clone());
@@ -151,7 +166,7 @@ pub fn traverse<F: Fn(&mut lambda::ExprKind) -> ()>(expr: &mut lambda::ExprKind,
         lambda::ExprKind::Variable(_) => {}
         lambda::ExprKind::Constructor(_) |
         lambda::ExprKind::Function(_) => {}
-        lambda::ExprKind::Object(_, args) => {
+        lambda::ExprKind::Object(_, args) | lambda::ExprKind::Reuse(_, _, args) => {
             for arg in args {
                 traverse(arg, f.clone());
             }
@@ -163,6 +178,7 @@ pub fn traverse<F: Fn(&mut lambda::ExprKind) -> ()>(expr: &mut lambda::ExprKind,
                 match stmt {
                     lambda::Stmt::Let(_, expr) => traverse(expr, f.clone()),
                     lambda::Stmt::Expr(expr) => traverse(expr, f.clone()),
+                    _ => {}
                 }
             }
         }
@@ -188,7 +204,17 @@ pub fn traverse<F: Fn(&mut lambda::ExprKind) -> ()>(expr: &mut lambda::ExprKind,
                 traverse(action, f.clone());
             }
         }
-    
+        lambda::ExprKind::Computation(expr) => traverse(expr, f.clone()),
+        lambda::ExprKind::Perform(expr) => traverse(expr, f.clone()),
+        lambda::ExprKind::Handle(_, body, handler, _, _) => {
+            traverse(body, f.clone());
+            traverse(handler, f.clone());
+        }
+        lambda::ExprKind::Request(_, _, args) => {
+            for arg in args {
+                traverse(arg, f.clone());
+            }
+        }
     }
 }
 
@@ -203,11 +229,12 @@ pub fn traverse_programs<F: Fn(&mut lambda::ExprKind) -> ()>(programs: &mut Vec<
 
 pub fn is_complex(expr: &lambda::ExprKind) -> bool {
     match expr {
-        lambda::ExprKind::Application(_, _) => true,
+        lambda::ExprKind::Application(_, _, _) => true,
         lambda::ExprKind::Constructor(_) => false,
         lambda::ExprKind::Variable(_) => false,
         lambda::ExprKind::Function(_) => false,
         lambda::ExprKind::Object(_, _) => true,
+        lambda::ExprKind::Reuse(_, _, _) => true,
         lambda::ExprKind::Lambda(_, body) => is_complex(body),
         lambda::ExprKind::Projection(_, _) => true,
         lambda::ExprKind::Access(_, _) => true,
@@ -219,12 +246,17 @@ pub fn is_complex(expr: &lambda::ExprKind) -> bool {
         
         lambda::ExprKind::Block(_) => true,
         lambda::ExprKind::Switch(_, _, _) => false,
+
+        lambda::ExprKind::Computation(_) => true,
+        lambda::ExprKind::Perform(_) => true,
+        lambda::ExprKind::Handle(_, _, _, _, _) => true,
+        lambda::ExprKind::Request(_, _, _) => true,
     }
 }
 
 pub fn apply(expr: &mut lambda::ExprKind, changed: Rc<RefCell<bool>>) {
     match expr {
-        lambda::ExprKind::Application(func, args) => {
+        lambda::ExprKind::Application(func, args, _) => {
             match &mut **func { 
                 lambda::ExprKind::Lambda(params,ref mut body) => {
                     let mut subs = im_rc::HashMap::new();
@@ -258,7 +290,7 @@ pub fn substitute(expr: &mut lambda::ExprKind, mut subs: im_rc::HashMap<Symbol,
             }
             substitute(body, subs);
         }
-        lambda::ExprKind::Application(func, args) => {
+        lambda::ExprKind::Application(func, args, _) => {
             substitute(func, subs.clone());
             for arg in args {
                 substitute(arg, subs.clone());
@@ -271,7 +303,7 @@ pub fn substitute(expr: &mut lambda::ExprKind, mut subs: im_rc::HashMap<Symbol,
         }
         lambda::ExprKind::Constructor(_) => {}
         lambda::ExprKind::Function(_) => {}
-        lambda::ExprKind::Object(_, args) => {
+        lambda::ExprKind::Object(_, args) | lambda::ExprKind::Reuse(_, _, args) => {
             for arg in args {
                 substitute(arg, subs.clone());
             }
@@ -287,6 +319,7 @@ pub fn substitute(expr: &mut lambda::ExprKind, mut subs: im_rc::HashMap<Symbol,
                 match stmt {
                     lambda::Stmt::Let(_, expr) => substitute(expr, subs.clone()),
                     lambda::Stmt::Expr(expr) => substitute(expr, subs.clone()),
+                    _ => {}
                 }
             }
         }
@@ -312,6 +345,17 @@ pub fn substitute(expr: &mut lambda::ExprKind, mut subs: im_rc::HashMap<Symbol,
                 substitute(action, subs.clone());
             }
         }
+        lambda::ExprKind::Computation(expr) => substitute(expr, subs),
+        lambda::ExprKind::Perform(expr) => substitute(expr, subs),
+        lambda::ExprKind::Handle(_, body, handler, _, _) => {
+            substitute(body, subs.clone());
+            substitute(handler, subs);
+        }
+        lambda::ExprKind::Request(_, _, args) => {
+            for arg in args {
+                substitute(arg, subs.clone());
+            }
+        }
     }
 }
 
@@ -321,7 +365,7 @@ pub fn are_complex(exprs: &[Box<lambda::ExprKind>]) -> bool {
 
 pub fn should_inline(expr: &lambda::ExprKind) -> bool {
     match expr {
-        lambda::ExprKind::Application(func, args) => {
+        lambda::ExprKind::Application(func, args, _) => {
             !is_complex(func) && !are_complex(args)
         }
         lambda::ExprKind::Constructor(_) => true,
@@ -329,6 +373,7 @@ pub fn should_inline(expr: &lambda::ExprKind) -> bool {
         lambda::ExprKind::Function(_) => true,
         lambda::ExprKind::Literal(_) => true,
         lambda::ExprKind::Object(_, args) => !are_complex(args), 
+        lambda::ExprKind::Reuse(_, _, _) => false,
         lambda::ExprKind::Lambda(_, body) => should_inline(body),
         lambda::ExprKind::Projection(_, e) => !is_complex(e),
         lambda::ExprKind::Access(e, _) => !is_complex(e),
@@ -339,6 +384,11 @@ pub fn should_inline(expr: &lambda::ExprKind) -> bool {
         
         lambda::ExprKind::Block(_) => false,
         lambda::ExprKind::Switch(_, _, _) => false,
+
+        lambda::ExprKind::Computation(_) => false,
+        lambda::ExprKind::Perform(_) => false,
+        lambda::ExprKind::Handle(_, _, _, _, _) => false,
+        lambda::ExprKind::Request(_, _, _) => false,
     }
 }
 
@@ -372,8 +422,36 @@ pub fn remove_recursive_inline_marks(ctx: &mut Context) {
     }
 }
 
+/// Adds the constants that an inlined declaration uses to the constants of the declarations where
+/// it was inlined, because they are evaluated in order and now use them directly.
+fn inherit_constants(programs: &mut [Program], inlined: Vec<(Qualified, Qualified)>) {
+    let constants: HashMap<Qualified, HashMap<Qualified, Span>> = programs
+        .iter()
+        .flat_map(|program| &program.lets)
+        .filter_map(|(name, decl)| Some((name.clone(), decl.constants.clone()?)))
+        .collect();
+
+    for program in programs {
+        for (name, decl) in &mut program.lets {
+            let Some(own) = &mut decl.constants else {
+                continue;
+            };
+
+            for (owner, inlined) in &inlined {
+                if owner == name {
+                    if let Some(other) = constants.get(inlined) {
+                        own.extend(other.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub fn inline(programs: &mut Vec<Program>) {
     loop {
+        let mut inlined = vec![];
+
         let changed = {
             let mut ctx = Default::default();
             programs.transform(&mut ctx);
@@ -382,8 +460,9 @@ pub fn inline(programs: &mut Vec<Program>) {
     
             for (name, value) in ctx.should_inline {
                 if let Some(exprs) = ctx.vars.get_mut(&name) {
-                    for expr in exprs {
+                    for (owner, expr) in exprs {
                         **expr = value.clone();
+                        inlined.push((owner.clone(), name.clone()));
                         ctx.changed = true;
                     }
                 }
@@ -392,6 +471,8 @@ pub fn inline(programs: &mut Vec<Program>) {
             ctx.changed
         };
 
+        inherit_constants(programs, inlined);
+
         let changed = Rc::new(RefCell::new(changed));
 
         traverse_programs(programs, |x| {
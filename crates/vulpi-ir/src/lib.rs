@@ -6,3 +6,7 @@ pub mod pattern;
 pub mod inline;
 pub mod dead_code;
 pub mod uncurry;
+pub mod perceus;
+pub mod simplify;
+pub mod print;
+pub mod unsupported;
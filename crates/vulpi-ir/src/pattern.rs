@@ -5,8 +5,9 @@ use std::collections::HashSet;
 use vulpi_intern::Symbol;
 use vulpi_macros::Show;
 use vulpi_syntax::{
-    elaborated::{PatApplication, Pattern, PatternKind},
+    elaborated::{PatApplication, PatRecord, Pattern, PatternKind},
     lambda::{Expr, Case},
+    r#abstract::Qualified,
 };
 
 #[derive(Default, Show)]
@@ -20,6 +21,7 @@ pub struct Problem {
 pub enum Index {
     Cons(usize),
     Tuple(usize),
+    Field(Qualified),
 }
 
 #[derive(Clone, Show)]
@@ -37,7 +39,17 @@ impl Occurrence {
 pub enum Tree {
     Fail,
     Leaf(usize, Vec<Occurrence>),
-    Switch(Occurrence, Vec<(Case, Tree)>),
+    Switch(Occurrence, Vec<(Case, Tree)>, Option<Box<Tree>>),
+}
+
+/// The number of sub patterns that a case has.
+fn arity(case: &Case) -> usize {
+    match case {
+        Case::Literal(_) => 0,
+        Case::Tuple(size) => *size,
+        Case::Constructor(_, size) => *size,
+        Case::Record(_, fields) => fields.len(),
+    }
 }
 
 pub fn specialize(ocur: &Occurrence, case: Case) -> Vec<Occurrence> {
@@ -45,6 +57,10 @@ pub fn specialize(ocur: &Occurrence, case: Case) -> Vec<Occurrence> {
         Case::Literal(_) => vec![],
         Case::Tuple(size) => (0..size).map(|x| ocur.with(Index::Tuple(x))).collect(),
         Case::Constructor(_, size) => (0..size).map(|x| ocur.with(Index::Cons(x))).collect(),
+        Case::Record(_, fields) => fields
+            .into_iter()
+            .map(|x| ocur.with(Index::Field(x)))
+            .collect(),
     }
 }
 
@@ -67,14 +83,20 @@ impl Row {
 
         match (case, *self.0[0].clone()) {
             (_, Error) => unreachable!(),
-            (_, Wildcard) => Some(self.shift()),
-            (_, Variable(_)) => Some(self.shift()),
+            (_, Or(_)) => unreachable!("or-patterns are expanded before compilation"),
+            (case, Wildcard | Variable(_)) => {
+                let wildcards = vec![Box::new(Wildcard); arity(&case)];
+                Some(self.join(Row(wildcards)))
+            }
 
             (Case::Literal(l), Literal(r)) if l == r => Some(self.shift()),
             (Case::Constructor(l, _), Application(PatApplication { func, args })) if l == func => {
                 Some(self.join(Row(args)))
             }
             (Case::Tuple(x), Tuple(y)) if x == y.len() => Some(self.join(Row(y))),
+            (Case::Record(l, _), Record(PatRecord { name, fields })) if l == name => {
+                Some(self.join(Row(fields.into_iter().map(|x| x.1).collect())))
+            }
 
             _ => None,
         }
@@ -147,7 +169,7 @@ impl Problem {
             }
         }
 
-        problem.occurrences = self.occurrences.clone();
+        problem.occurrences = self.occurrences[1..].to_vec();
 
         problem
     }
@@ -172,7 +194,10 @@ impl Problem {
         self.matrix.iter().any(|x| {
             matches!(
                 &*x.0[column],
-                PatternKind::Literal(_) | PatternKind::Application(_) | PatternKind::Tuple(_)
+                PatternKind::Literal(_)
+                    | PatternKind::Application(_)
+                    | PatternKind::Tuple(_)
+                    | PatternKind::Record(_)
             )
         })
     }
@@ -191,6 +216,10 @@ impl Problem {
                 PatternKind::Tuple(x) => {
                     heads.insert(Case::Tuple(x.len()));
                 }
+                PatternKind::Record(PatRecord { name, fields }) => {
+                    let fields = fields.iter().map(|x| x.0.clone()).collect();
+                    heads.insert(Case::Record(name.clone(), fields));
+                }
                 _ => (),
             }
         }
@@ -220,6 +249,17 @@ impl Problem {
             let problem = self.swap(0, refutable);
             let heads = problem.head_patterns(0);
 
+            // Tuples and records have only one case, so there's nothing left for the default
+            // branch. Literals and constructors that are not in the heads go to the rows that
+            // start with a wildcard.
+            let default = match heads.iter().next() {
+                Some(Case::Tuple(_) | Case::Record(_, _)) => None,
+                _ => match problem.defaults().compile() {
+                    Tree::Fail => None,
+                    tree => Some(Box::new(tree)),
+                },
+            };
+
             let mut branches = vec![];
 
             for head in heads {
@@ -228,8 +268,74 @@ impl Problem {
                 branches.push((head, branch));
             }
 
-            Tree::Switch(problem.occurrences[0].clone(), branches)
+            Tree::Switch(problem.occurrences[0].clone(), branches, default)
+        }
+    }
+}
+
+/// Checks if a pattern contains an or-pattern.
+pub fn has_or(pat: &Pattern) -> bool {
+    match &**pat {
+        PatternKind::Or(_) => true,
+        PatternKind::Application(app) => app.args.iter().any(has_or),
+        PatternKind::Tuple(parts) => parts.iter().any(has_or),
+        PatternKind::Record(record) => record.fields.iter().any(|x| has_or(&x.1)),
+        _ => false,
+    }
+}
+
+/// Expands the or-patterns of a row into one row for each combination of alternatives, so the
+/// variables of each row are always bound by the same occurrences.
+pub fn alternatives(row: &[Pattern]) -> Vec<Vec<Pattern>> {
+    row.iter().fold(vec![vec![]], |rows, pat| {
+        let alternatives = pattern_alternatives(pat);
+
+        rows.into_iter()
+            .flat_map(|row| {
+                alternatives.iter().map(move |alt| {
+                    let mut row = row.clone();
+                    row.push(alt.clone());
+                    row
+                })
+            })
+            .collect()
+    })
+}
+
+fn pattern_alternatives(pat: &Pattern) -> Vec<Pattern> {
+    match &**pat {
+        PatternKind::Or(or) => {
+            let mut alternatives = pattern_alternatives(&or.left);
+            alternatives.extend(pattern_alternatives(&or.right));
+            alternatives
+        }
+        PatternKind::Application(app) => alternatives(&app.args)
+            .into_iter()
+            .map(|args| {
+                Box::new(PatternKind::Application(PatApplication {
+                    func: app.func.clone(),
+                    args,
+                }))
+            })
+            .collect(),
+        PatternKind::Tuple(parts) => alternatives(parts)
+            .into_iter()
+            .map(|parts| Box::new(PatternKind::Tuple(parts)))
+            .collect(),
+        PatternKind::Record(record) => {
+            let (names, pats): (Vec<_>, Vec<_>) = record.fields.iter().cloned().unzip();
+
+            alternatives(&pats)
+                .into_iter()
+                .map(|pats| {
+                    Box::new(PatternKind::Record(PatRecord {
+                        name: record.name.clone(),
+                        fields: names.iter().cloned().zip(pats).collect(),
+                    }))
+                })
+                .collect()
         }
+        _ => vec![pat.clone()],
     }
 }
 
@@ -254,6 +360,11 @@ pub fn pattern_binders(expr: Expr, pat: &Pattern) -> Vec<(Occurrence, Symbol)> {
                     bind(part, ocur.with(Index::Tuple(i)), binders);
                 }
             }
+            PatternKind::Record(record) => {
+                for (field, pat) in &record.fields {
+                    bind(pat, ocur.with(Index::Field(field.clone())), binders);
+                }
+            }
             _ => (),
         }
     }
@@ -0,0 +1,1090 @@
+//! Precise reference counting with the Perceus algorithm. Every variable owns a reference to its
+//! value, that is passed along by the last use of the variable and dropped as soon as the variable
+//! is not used anymore, so the memory is freed as early as possible. Backends that don't have a
+//! garbage collector use the instructions added by this pass to manage the memory.
+//!
+//! The parameters of the top-level functions that are only inspected are borrowed, so the caller
+//! keeps the reference and neither of them counts it. The values matched against constructors
+//! drop their fields only when the object is freed, and the memory of an object that is freed
+//! right before an object of the same size is created is reused for it.
+
+use std::collections::{HashMap, HashSet};
+
+use vulpi_intern::Symbol;
+use vulpi_syntax::{
+    lambda::{self, ConsDef, Expr, ExprKind, Program, Stmt, Tree},
+    r#abstract::Qualified,
+};
+
+/// The parameters of the top-level functions that are borrowed, by function.
+pub type Borrowed = HashMap<Qualified, HashSet<Symbol>>;
+
+/// Adds the free variables of an expression to `free`.
+fn free_in(expr: &ExprKind, bound: &HashSet<Symbol>, free: &mut HashSet<Symbol>) {
+    let mut var = |name: &Symbol| {
+        if !bound.contains(name) {
+            free.insert(name.clone());
+        }
+    };
+
+    match expr {
+        ExprKind::Variable(name) => var(name),
+        ExprKind::Reuse(token, _, args) => {
+            var(token);
+            for arg in args {
+                free_in(arg, bound, free);
+            }
+        }
+        ExprKind::Lambda(params, body) => {
+            let mut bound = bound.clone();
+            bound.extend(params.iter().cloned());
+            free_in(body, &bound, free);
+        }
+        ExprKind::Block(stmts) => free_in_stmts(stmts, &mut bound.clone(), free),
+        ExprKind::Switch(_, tree, actions) => {
+            free_in_tree(tree, bound, free);
+            for action in actions {
+                free_in(action, bound, free);
+            }
+        }
+        _ => {
+            for child in children(expr) {
+                free_in(child, bound, free);
+            }
+        }
+    }
+}
+
+fn free_in_stmts(stmts: &[Stmt], bound: &mut HashSet<Symbol>, free: &mut HashSet<Symbol>) {
+    for stmt in stmts {
+        free_in_stmt(stmt, bound, free);
+    }
+}
+
+fn free_in_stmt(stmt: &Stmt, bound: &mut HashSet<Symbol>, free: &mut HashSet<Symbol>) {
+    let mut var = |name: &Symbol, bound: &HashSet<Symbol>| {
+        if !bound.contains(name) {
+            free.insert(name.clone());
+        }
+    };
+
+    match stmt {
+        Stmt::Let(name, expr) => {
+            free_in(expr, bound, free);
+            bound.insert(name.clone());
+        }
+        Stmt::Expr(expr) => free_in(expr, bound, free),
+        Stmt::Dup(name) | Stmt::Drop(name) | Stmt::Decref(name) | Stmt::Free(name) => {
+            var(name, bound)
+        }
+        Stmt::Token(token, Some(name)) => {
+            var(name, bound);
+            bound.insert(token.clone());
+        }
+        Stmt::Token(token, None) => {
+            bound.insert(token.clone());
+        }
+        Stmt::Unique(name, unique, shared) => {
+            var(name, bound);
+            free_in_stmts(unique, &mut bound.clone(), free);
+            free_in_stmts(shared, &mut bound.clone(), free);
+        }
+    }
+}
+
+fn free_in_tree(tree: &Tree, bound: &HashSet<Symbol>, free: &mut HashSet<Symbol>) {
+    if let Tree::Switch(scrutinee, branches, default) = tree {
+        free_in(scrutinee, bound, free);
+
+        for (_, _, tree) in branches {
+            free_in_tree(tree, bound, free);
+        }
+
+        if let Some(tree) = default {
+            free_in_tree(tree, bound, free);
+        }
+    }
+}
+
+/// The variables that an expression uses without binding them.
+pub fn free_vars(expr: &ExprKind) -> HashSet<Symbol> {
+    let mut free = HashSet::new();
+    free_in(expr, &HashSet::new(), &mut free);
+    free
+}
+
+/// The expressions inside of an expression that don't bind variables.
+pub(crate) fn children(expr: &ExprKind) -> Vec<&Expr> {
+    match expr {
+        ExprKind::Lambda(_, body) => vec![body],
+        ExprKind::Application(func, args, _) => std::iter::once(func).chain(args).collect(),
+        ExprKind::Object(_, args)
+        | ExprKind::Reuse(_, _, args)
+        | ExprKind::Tuple(args)
+        | ExprKind::Request(_, _, args) => args.iter().collect(),
+        ExprKind::Projection(_, expr)
+        | ExprKind::Access(expr, _)
+        | ExprKind::Computation(expr)
+        | ExprKind::Perform(expr) => vec![expr],
+        ExprKind::Block(stmts) => stmts
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::Let(_, expr) | Stmt::Expr(expr) => Some(expr),
+                _ => None,
+            })
+            .collect(),
+        ExprKind::RecordInstance(_, fields) => fields.iter().map(|(_, expr)| expr).collect(),
+        ExprKind::RecordUpdate(_, expr, fields) => std::iter::once(expr)
+            .chain(fields.iter().map(|(_, expr)| expr))
+            .collect(),
+        ExprKind::Switch(_, _, actions) => actions.iter().collect(),
+        ExprKind::Handle(_, body, handler, _, _) => vec![body, handler],
+        ExprKind::Variable(_)
+        | ExprKind::Constructor(_)
+        | ExprKind::Function(_)
+        | ExprKind::Literal(_) => vec![],
+    }
+}
+
+/// The variable that a field access reads from, like `x` in `x[1][0]`. Reading a field borrows
+/// the value.
+pub(crate) fn root(expr: &ExprKind) -> Option<&Symbol> {
+    match expr {
+        ExprKind::Variable(name) => Some(name),
+        ExprKind::Access(expr, _) | ExprKind::Projection(_, expr) => root(expr),
+        _ => None,
+    }
+}
+
+/// If an expression reads a field of a variable.
+fn is_field(expr: &ExprKind) -> bool {
+    root(expr).is_some() && !matches!(expr, ExprKind::Variable(_))
+}
+
+/// Expressions whose values are not counted.
+fn is_atom(expr: &ExprKind) -> bool {
+    matches!(
+        expr,
+        ExprKind::Function(_) | ExprKind::Constructor(_) | ExprKind::Literal(_)
+    )
+}
+
+/// The objects created by the compiled constructors, by the variables that they were matched
+/// with, for each action of a switch. An action only knows what all the paths to it know.
+fn known_objects(
+    tree: &Tree,
+    definitions: &HashMap<Qualified, (ConsDef, usize)>,
+    actions: usize,
+) -> Vec<HashMap<Symbol, usize>> {
+    fn go(
+        tree: &Tree,
+        definitions: &HashMap<Qualified, (ConsDef, usize)>,
+        path: &mut HashMap<Symbol, usize>,
+        known: &mut Vec<Option<HashMap<Symbol, usize>>>,
+    ) {
+        match tree {
+            Tree::Leaf(i) => {
+                known[*i] = Some(match known[*i].take() {
+                    None => path.clone(),
+                    Some(before) => before
+                        .into_iter()
+                        .filter(|(name, size)| path.get(name) == Some(size))
+                        .collect(),
+                });
+            }
+            Tree::Switch(scrutinee, branches, default) => {
+                for (case, _, tree) in branches {
+                    let object = match (&**scrutinee, case) {
+                        (ExprKind::Variable(name), lambda::Case::Constructor(cons, _)) => {
+                            match definitions.get(cons) {
+                                Some((ConsDef::Heavy(_, _, size), _)) => Some((name, *size)),
+                                _ => None,
+                            }
+                        }
+                        _ => None,
+                    };
+
+                    if let Some((name, size)) = object {
+                        let before = path.insert(name.clone(), size);
+                        go(tree, definitions, path, known);
+
+                        match before {
+                            Some(size) => path.insert(name.clone(), size),
+                            None => path.remove(name),
+                        };
+                    } else {
+                        go(tree, definitions, path, known);
+                    }
+                }
+
+                if let Some(tree) = default {
+                    go(tree, definitions, path, known);
+                }
+            }
+        }
+    }
+
+    let mut known = vec![None; actions];
+    go(tree, definitions, &mut HashMap::new(), &mut known);
+    known.into_iter().map(Option::unwrap_or_default).collect()
+}
+
+/// If an object of a size is always created when an expression runs. The code inside of
+/// functions, handlers and branches may not run, so it's not searched.
+fn allocates(expr: &ExprKind, size: usize) -> bool {
+    match expr {
+        ExprKind::Object(_, args) if args.len() == size => true,
+        ExprKind::Lambda(_, _)
+        | ExprKind::Computation(_)
+        | ExprKind::Handle(_, _, _, _, _)
+        | ExprKind::Switch(_, _, _) => false,
+        _ => children(expr).into_iter().any(|x| allocates(x, size)),
+    }
+}
+
+/// Turns the first object of a size that [allocates] finds into the reuse of a token.
+fn reuse(expr: &mut ExprKind, size: usize, token: &Symbol) -> bool {
+    match expr {
+        ExprKind::Object(tag, args) if args.len() == size => {
+            *expr = ExprKind::Reuse(token.clone(), *tag, std::mem::take(args));
+            true
+        }
+        ExprKind::Lambda(_, _)
+        | ExprKind::Computation(_)
+        | ExprKind::Handle(_, _, _, _, _)
+        | ExprKind::Switch(_, _, _) => false,
+        ExprKind::Application(func, args, _) => {
+            reuse(func, size, token) || args.iter_mut().any(|x| reuse(x, size, token))
+        }
+        ExprKind::Object(_, args)
+        | ExprKind::Reuse(_, _, args)
+        | ExprKind::Tuple(args)
+        | ExprKind::Request(_, _, args) => args.iter_mut().any(|x| reuse(x, size, token)),
+        ExprKind::Projection(_, expr) | ExprKind::Access(expr, _) | ExprKind::Perform(expr) => {
+            reuse(expr, size, token)
+        }
+        ExprKind::Block(stmts) => stmts
+            .iter_mut()
+            .any(|stmt| reuse_in_stmt(stmt, size, token)),
+        ExprKind::RecordInstance(_, fields) => {
+            fields.iter_mut().any(|(_, x)| reuse(x, size, token))
+        }
+        ExprKind::RecordUpdate(_, expr, fields) => {
+            reuse(expr, size, token) || fields.iter_mut().any(|(_, x)| reuse(x, size, token))
+        }
+        ExprKind::Variable(_)
+        | ExprKind::Constructor(_)
+        | ExprKind::Function(_)
+        | ExprKind::Literal(_) => false,
+    }
+}
+
+fn reuse_in_stmt(stmt: &mut Stmt, size: usize, token: &Symbol) -> bool {
+    match stmt {
+        Stmt::Let(_, expr) | Stmt::Expr(expr) => reuse(expr, size, token),
+        _ => false,
+    }
+}
+
+/// Adds statements before an expression.
+pub(crate) fn prepend(mut stmts: Vec<Stmt>, expr: Expr) -> Expr {
+    if stmts.is_empty() {
+        return expr;
+    }
+
+    match *expr {
+        ExprKind::Block(rest) => stmts.extend(rest),
+        expr => stmts.push(Stmt::Expr(Box::new(expr))),
+    }
+
+    Box::new(ExprKind::Block(stmts))
+}
+
+fn sorted(names: impl IntoIterator<Item = Symbol>) -> Vec<Symbol> {
+    let mut names: Vec<_> = names.into_iter().collect();
+    names.sort_by_key(|x| x.get());
+    names
+}
+
+/// Infers which parameters of the top-level functions are owned. A parameter is owned if its
+/// reference is kept after the function returns, if it's passed to an owned parameter, if it's
+/// passed in a tail call to a parameter that can't be borrowed without losing the tail call, or if
+/// its memory can be reused. The other ones are borrowed.
+struct Inference<'a> {
+    params: HashMap<Qualified, (Vec<Symbol>, Vec<bool>)>,
+    definitions: &'a HashMap<Qualified, (ConsDef, usize)>,
+
+    /// The parameters of the current function whose references are kept.
+    owned: HashSet<Symbol>,
+
+    /// The parameters that have to be owned because of the tail calls to them.
+    tail: Vec<(Qualified, usize)>,
+
+    /// The variables that hold fields of the borrowed parameters, so they are borrowed too.
+    fields: HashSet<Symbol>,
+
+    current: Qualified,
+}
+
+impl<'a> Inference<'a> {
+    fn is_borrowed(&self, name: &Symbol) -> bool {
+        let (params, modes) = &self.params[&self.current];
+        self.fields.contains(name)
+            || params
+                .iter()
+                .zip(modes)
+                .any(|(x, owned)| x == name && !owned)
+    }
+
+    fn collect(&mut self, expr: &ExprKind, owned: bool, tail: bool) {
+        match expr {
+            ExprKind::Variable(name) => {
+                if owned {
+                    self.owned.insert(name.clone());
+                }
+            }
+            // The captured variables are kept by the closure.
+            ExprKind::Lambda(_, _) | ExprKind::Computation(_) => {
+                self.owned.extend(free_vars(expr));
+            }
+            ExprKind::Application(func, args, _) => {
+                let modes = match &**func {
+                    ExprKind::Function(name) => self
+                        .params
+                        .get(name)
+                        .filter(|(params, _)| params.len() == args.len())
+                        .map(|(_, modes)| modes.clone()),
+                    _ => None,
+                };
+
+                let Some(modes) = modes else {
+                    self.collect(func, false, false);
+                    for arg in args {
+                        self.collect(arg, true, false);
+                    }
+                    return;
+                };
+
+                let ExprKind::Function(name) = &**func else {
+                    unreachable!()
+                };
+
+                for (i, (arg, owned)) in args.iter().zip(modes).enumerate() {
+                    self.collect(arg, owned, false);
+
+                    if let ExprKind::Variable(var) = &**arg {
+                        if tail && !owned && !self.is_borrowed(var) {
+                            self.tail.push((name.clone(), i));
+                        }
+                    }
+                }
+            }
+            ExprKind::Projection(_, expr) | ExprKind::Access(expr, _) => {
+                self.collect(expr, false, false)
+            }
+            ExprKind::RecordUpdate(_, expr, fields) => {
+                self.collect(expr, false, false);
+                for (_, field) in fields {
+                    self.collect(field, true, false);
+                }
+            }
+            ExprKind::Block(stmts) => {
+                for (i, stmt) in stmts.iter().enumerate() {
+                    match stmt {
+                        Stmt::Expr(expr) if i == stmts.len() - 1 => self.collect(expr, owned, tail),
+                        Stmt::Let(name, expr) => {
+                            if is_field(expr) && root(expr).is_some_and(|x| self.is_borrowed(x)) {
+                                self.fields.insert(name.clone());
+                            }
+
+                            self.collect(expr, true, false)
+                        }
+                        Stmt::Expr(expr) => self.collect(expr, true, false),
+                        _ => {}
+                    }
+                }
+            }
+            ExprKind::Switch(_, tree, actions) => {
+                let known = known_objects(tree, self.definitions, actions.len());
+
+                for (action, known) in actions.iter().zip(known) {
+                    self.collect(action, owned, tail);
+
+                    for (name, size) in known {
+                        if allocates(action, size) {
+                            self.owned.insert(name);
+                        }
+                    }
+                }
+            }
+            _ => {
+                for child in children(expr) {
+                    self.collect(child, true, false);
+                }
+            }
+        }
+    }
+}
+
+/// The functions that are used as values, so they are called like closures, that own all of
+/// their parameters.
+fn escaping(
+    expr: &ExprKind,
+    params: &HashMap<Qualified, (Vec<Symbol>, Vec<bool>)>,
+    found: &mut HashSet<Qualified>,
+) {
+    match expr {
+        ExprKind::Function(name) => {
+            found.insert(name.clone());
+        }
+        ExprKind::Application(func, args, _) => {
+            let called = match &**func {
+                ExprKind::Function(name) => params
+                    .get(name)
+                    .is_some_and(|(params, _)| params.len() == args.len()),
+                _ => false,
+            };
+
+            if !called {
+                escaping(func, params, found);
+            }
+
+            for arg in args {
+                escaping(arg, params, found);
+            }
+        }
+        _ => {
+            for child in children(expr) {
+                escaping(child, params, found);
+            }
+        }
+    }
+}
+
+fn infer(
+    programs: &[Program],
+    definitions: &HashMap<Qualified, (ConsDef, usize)>,
+) -> HashMap<Qualified, (Vec<Symbol>, Vec<bool>)> {
+    let mut params = HashMap::new();
+
+    for program in programs {
+        for (name, decl) in &program.lets {
+            if let ExprKind::Lambda(names, _) = &*decl.body {
+                params.insert(name.clone(), (names.clone(), vec![false; names.len()]));
+            }
+        }
+    }
+
+    let mut found = HashSet::new();
+
+    for program in programs {
+        for (_, decl) in &program.lets {
+            escaping(&decl.body, &params, &mut found);
+        }
+    }
+
+    for name in found {
+        if let Some((_, modes)) = params.get_mut(&name) {
+            modes.fill(true);
+        }
+    }
+
+    let mut inference = Inference {
+        params,
+        definitions,
+        owned: HashSet::new(),
+        tail: vec![],
+        fields: HashSet::new(),
+        current: Qualified {
+            path: Symbol::intern(""),
+            name: Symbol::intern(""),
+        },
+    };
+
+    loop {
+        let mut changed = false;
+
+        for program in programs {
+            for (name, decl) in &program.lets {
+                let ExprKind::Lambda(_, body) = &*decl.body else {
+                    continue;
+                };
+
+                inference.current = name.clone();
+                inference.collect(body, true, true);
+
+                let owned = std::mem::take(&mut inference.owned);
+                let tail = std::mem::take(&mut inference.tail);
+                inference.fields.clear();
+
+                let (params, modes) = inference.params.get_mut(name).unwrap();
+
+                for (param, mode) in params.iter().zip(modes.iter_mut()) {
+                    if !*mode && owned.contains(param) {
+                        *mode = true;
+                        changed = true;
+                    }
+                }
+
+                for (func, i) in tail {
+                    let mode = &mut inference.params.get_mut(&func).unwrap().1[i];
+
+                    if !*mode {
+                        *mode = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    inference.params
+}
+
+/// Inserts the instructions that count references into a function.
+struct Context<'a> {
+    params: &'a HashMap<Qualified, (Vec<Symbol>, Vec<bool>)>,
+    definitions: &'a HashMap<Qualified, (ConsDef, usize)>,
+
+    /// The variables whose references have to be passed along or dropped.
+    owned: HashSet<Symbol>,
+
+    /// The variables whose references belong to someone else, like the borrowed parameters and
+    /// the variables captured by a closure.
+    borrowed: HashSet<Symbol>,
+
+    counter: usize,
+}
+
+impl<'a> Context<'a> {
+    fn new_var(&mut self) -> Symbol {
+        self.counter += 1;
+        Symbol::intern(&format!("$rc{}", self.counter))
+    }
+
+    /// Drops the owned variables that are not needed anymore.
+    fn dead(&mut self, needed: impl Fn(&Symbol) -> bool) -> Vec<Stmt> {
+        let dead: Vec<_> = self.owned.iter().filter(|x| !needed(x)).cloned().collect();
+
+        for name in &dead {
+            self.owned.remove(name);
+        }
+
+        sorted(dead).into_iter().map(Stmt::Drop).collect()
+    }
+
+    /// Takes the reference of a variable if this is its last use.
+    fn consume(&mut self, name: &Symbol, live: &HashSet<Symbol>) -> bool {
+        !live.contains(name) && self.owned.remove(name)
+    }
+
+    fn function(&mut self, owned: HashSet<Symbol>, borrowed: HashSet<Symbol>, body: Expr) -> Expr {
+        let owned = std::mem::replace(&mut self.owned, owned);
+        let borrowed = std::mem::replace(&mut self.borrowed, borrowed);
+
+        let body = self.expr(body, &HashSet::new());
+
+        self.owned = owned;
+        self.borrowed = borrowed;
+
+        body
+    }
+
+    /// Transforms an expression whose result is owned. It consumes the owned variables that are
+    /// not in `live` and keeps the ones that are.
+    fn expr(&mut self, expr: Expr, live: &HashSet<Symbol>) -> Expr {
+        let free = free_vars(&expr);
+        let drops = self.dead(|x| free.contains(x) || live.contains(x));
+        let expr = self.node(*expr, live);
+        prepend(drops, expr)
+    }
+
+    fn node(&mut self, expr: ExprKind, live: &HashSet<Symbol>) -> Expr {
+        match expr {
+            ExprKind::Variable(name) => {
+                if self.consume(&name, live) {
+                    Box::new(ExprKind::Variable(name))
+                } else {
+                    prepend(
+                        vec![Stmt::Dup(name.clone())],
+                        Box::new(ExprKind::Variable(name)),
+                    )
+                }
+            }
+            ExprKind::Lambda(params, body) => {
+                let captured = self.capture(&ExprKind::Lambda(params.clone(), body.clone()), live);
+                let owned = params.iter().cloned().collect();
+                let body = self.function(owned, captured.1, body);
+                prepend(captured.0, Box::new(ExprKind::Lambda(params, body)))
+            }
+            ExprKind::Computation(body) => {
+                let captured = self.capture(&body, live);
+                let body = self.function(HashSet::new(), captured.1, body);
+                prepend(captured.0, Box::new(ExprKind::Computation(body)))
+            }
+            ExprKind::Application(func, args, span) => {
+                let modes = match &*func {
+                    ExprKind::Function(name) => self
+                        .params
+                        .get(name)
+                        .filter(|(params, _)| params.len() == args.len())
+                        .map(|(_, modes)| modes.clone()),
+                    _ => None,
+                };
+
+                let modes = modes.unwrap_or_else(|| vec![true; args.len()]);
+
+                let children = std::iter::once((func, false))
+                    .chain(args.into_iter().zip(modes))
+                    .collect();
+
+                self.compound(children, live, false, |mut results| {
+                    let func = results.remove(0);
+                    ExprKind::Application(func, results, span)
+                })
+            }
+            ExprKind::Object(tag, args) => {
+                let children = args.into_iter().map(|x| (x, true)).collect();
+                self.compound(children, live, false, |args| ExprKind::Object(tag, args))
+            }
+            ExprKind::Reuse(token, tag, args) => {
+                let children = args.into_iter().map(|x| (x, true)).collect();
+                self.compound(children, live, false, |args| {
+                    ExprKind::Reuse(token, tag, args)
+                })
+            }
+            ExprKind::Tuple(args) => {
+                let children = args.into_iter().map(|x| (x, true)).collect();
+                self.compound(children, live, false, ExprKind::Tuple)
+            }
+            ExprKind::Request(effect, id, args) => {
+                let children = args.into_iter().map(|x| (x, true)).collect();
+                self.compound(children, live, false, |args| {
+                    ExprKind::Request(effect, id, args)
+                })
+            }
+            ExprKind::RecordInstance(name, fields) => {
+                let (names, fields): (Vec<_>, Vec<_>) = fields.into_iter().unzip();
+                let children = fields.into_iter().map(|x| (x, true)).collect();
+                self.compound(children, live, false, |fields| {
+                    ExprKind::RecordInstance(name, names.into_iter().zip(fields).collect())
+                })
+            }
+            ExprKind::RecordUpdate(name, expr, fields) => {
+                let (names, fields): (Vec<_>, Vec<_>) = fields.into_iter().unzip();
+                let children = std::iter::once((expr, false))
+                    .chain(fields.into_iter().map(|x| (x, true)))
+                    .collect();
+
+                self.compound(children, live, false, |mut fields| {
+                    let expr = fields.remove(0);
+                    ExprKind::RecordUpdate(name, expr, names.into_iter().zip(fields).collect())
+                })
+            }
+            ExprKind::Projection(field, expr) => {
+                self.compound(vec![(expr, false)], live, true, |mut exprs| {
+                    ExprKind::Projection(field, exprs.remove(0))
+                })
+            }
+            ExprKind::Access(expr, place) => {
+                self.compound(vec![(expr, false)], live, true, |mut exprs| {
+                    ExprKind::Access(exprs.remove(0), place)
+                })
+            }
+            ExprKind::Perform(expr) => {
+                self.compound(vec![(expr, true)], live, false, |mut exprs| {
+                    ExprKind::Perform(exprs.remove(0))
+                })
+            }
+            ExprKind::Handle(effect, body, handler, effectful, span) => self.compound(
+                vec![(body, true), (handler, true)],
+                live,
+                false,
+                |mut exprs| {
+                    let handler = exprs.remove(1);
+                    ExprKind::Handle(effect, exprs.remove(0), handler, effectful, span)
+                },
+            ),
+            ExprKind::Block(stmts) => self.block(stmts, live),
+            ExprKind::Switch(result, tree, actions) => {
+                let known = known_objects(&tree, self.definitions, actions.len());
+                let before = self.owned.clone();
+
+                let actions = actions
+                    .into_iter()
+                    .zip(known)
+                    .map(|(action, known)| {
+                        self.owned = before.clone();
+                        let action = self.expr(action, live);
+                        self.specialize(action, &known)
+                    })
+                    .collect();
+
+                self.owned = before.into_iter().filter(|x| live.contains(x)).collect();
+
+                Box::new(ExprKind::Switch(result, tree, actions))
+            }
+            expr @ (ExprKind::Constructor(_) | ExprKind::Function(_) | ExprKind::Literal(_)) => {
+                Box::new(expr)
+            }
+        }
+    }
+
+    /// Gives the references of the variables captured by a closure to it. Returns the
+    /// duplications of the variables that are still used outside of it, and the variables that
+    /// are borrowed inside of it.
+    fn capture(
+        &mut self,
+        closure: &ExprKind,
+        live: &HashSet<Symbol>,
+    ) -> (Vec<Stmt>, HashSet<Symbol>) {
+        let captured = sorted(free_vars(closure));
+        let mut dups = vec![];
+
+        for name in &captured {
+            if !self.consume(name, live) {
+                dups.push(Stmt::Dup(name.clone()));
+            }
+        }
+
+        (dups, captured.into_iter().collect())
+    }
+
+    /// Transforms an expression made of other ones, that are evaluated in order before it runs.
+    /// The owned children give their references to it, and the borrowed ones keep them until it
+    /// finishes, so the variables whose last use is a borrowed child are dropped after it. If
+    /// `read` is set, the result is borrowed from a child, so it's duplicated.
+    fn compound(
+        &mut self,
+        children: Vec<(Expr, bool)>,
+        live: &HashSet<Symbol>,
+        read: bool,
+        build: impl FnOnce(Vec<Expr>) -> ExprKind,
+    ) -> Expr {
+        let mut after = live.clone();
+
+        for (child, owned) in &children {
+            if let (false, Some(name)) = (owned, root(child)) {
+                after.insert(name.clone());
+            }
+        }
+
+        // The borrowed children that are not variables are computed before the expression, so
+        // the other ones are too, in the same order.
+        let hoist = children
+            .iter()
+            .any(|(child, owned)| !owned && root(child).is_none() && !is_atom(child));
+
+        let mut lives = vec![];
+        let mut needed = after;
+
+        for (child, _) in children.iter().rev() {
+            lives.push(needed.clone());
+            free_in(child, &HashSet::new(), &mut needed);
+        }
+
+        lives.reverse();
+
+        let mut stmts = vec![];
+        let mut results = vec![];
+        let mut drops = vec![];
+
+        for ((child, owned), child_live) in children.into_iter().zip(lives) {
+            if is_atom(&child) {
+                results.push(child);
+                continue;
+            }
+
+            if !owned {
+                if let Some(name) = root(&child) {
+                    if !live.contains(name) && self.owned.contains(name) && !drops.contains(name) {
+                        drops.push(name.clone());
+                    }
+
+                    results.push(child);
+                    continue;
+                }
+            }
+
+            let simple = matches!(&*child, ExprKind::Variable(_));
+            let result = self.expr(child, &child_live);
+
+            if !owned || (hoist && !simple) {
+                let name = self.new_var();
+                stmts.push(Stmt::Let(name.clone(), result));
+                results.push(Box::new(ExprKind::Variable(name.clone())));
+
+                if !owned {
+                    drops.push(name);
+                }
+            } else {
+                results.push(result);
+            }
+        }
+
+        for name in &drops {
+            self.owned.remove(name);
+        }
+
+        let expr = Box::new(build(results));
+
+        if drops.is_empty() && !read {
+            return prepend(stmts, expr);
+        }
+
+        let name = self.new_var();
+        stmts.push(Stmt::Let(name.clone(), expr));
+
+        if read {
+            stmts.push(Stmt::Dup(name.clone()));
+        }
+
+        stmts.extend(drops.into_iter().map(Stmt::Drop));
+        stmts.push(Stmt::Expr(Box::new(ExprKind::Variable(name))));
+
+        Box::new(ExprKind::Block(stmts))
+    }
+
+    fn block(&mut self, stmts: Vec<Stmt>, live: &HashSet<Symbol>) -> Expr {
+        // The variables that are used after each statement.
+        let mut lives = vec![];
+        let mut needed = live.clone();
+
+        for stmt in stmts.iter().rev() {
+            lives.push(needed.clone());
+
+            let mut bound = HashSet::new();
+            let mut free = HashSet::new();
+            free_in_stmt(stmt, &mut bound, &mut free);
+
+            needed.retain(|x| !bound.contains(x));
+            needed.extend(free);
+        }
+
+        lives.reverse();
+
+        let last = stmts.len().saturating_sub(1);
+        let ends_with_expr = matches!(stmts.last(), Some(Stmt::Expr(_)));
+        let mut result = vec![];
+
+        for (i, (stmt, after)) in stmts.into_iter().zip(lives).enumerate() {
+            let mut free = HashSet::new();
+            free_in_stmt(&stmt, &mut HashSet::new(), &mut free);
+            result.extend(self.dead(|x| after.contains(x) || free.contains(x)));
+
+            match stmt {
+                // The fields of a borrowed value are borrowed too, because it lives longer than
+                // them.
+                Stmt::Let(name, expr) if is_field(&expr) => {
+                    let borrowed = root(&expr).is_some_and(|x| self.borrowed.contains(x));
+                    result.push(Stmt::Let(name.clone(), expr));
+
+                    if borrowed {
+                        self.borrowed.insert(name);
+                    } else {
+                        result.push(Stmt::Dup(name.clone()));
+                        self.owned.insert(name);
+                    }
+                }
+                Stmt::Let(name, expr) => {
+                    let expr = self.expr(expr, &after);
+
+                    // Wildcards are never used, so they get a name that is not repeated.
+                    let name = if name.get() == "_" {
+                        self.new_var()
+                    } else {
+                        name
+                    };
+
+                    result.push(Stmt::Let(name.clone(), expr));
+                    self.owned.insert(name);
+                }
+                Stmt::Expr(expr) if i == last => match *self.expr(expr, &after) {
+                    ExprKind::Block(stmts) => result.extend(stmts),
+                    expr => result.push(Stmt::Expr(Box::new(expr))),
+                },
+                // The value of an expression in the middle of a block is not used.
+                Stmt::Expr(expr) => {
+                    let expr = self.expr(expr, &after);
+                    let name = self.new_var();
+                    result.push(Stmt::Let(name.clone(), expr));
+                    self.owned.insert(name);
+                }
+                stmt => result.push(stmt),
+            }
+        }
+
+        if !ends_with_expr {
+            result.extend(self.dead(|x| live.contains(x)));
+        }
+
+        Box::new(ExprKind::Block(result))
+    }
+
+    /// Specializes the drops of the objects that an action knows the constructor of. The fields
+    /// that were duplicated after being read from the object are only duplicated if the object
+    /// is shared, because otherwise the references of the object are moved to them, and the
+    /// memory is reused if an object of the same size is created later.
+    fn specialize(&mut self, action: Expr, known: &HashMap<Symbol, usize>) -> Expr {
+        let ExprKind::Block(mut stmts) = *action else {
+            return action;
+        };
+
+        let mut i = 0;
+
+        while i < stmts.len() {
+            let Stmt::Drop(name) = &stmts[i] else {
+                i += 1;
+                continue;
+            };
+
+            let Some(&size) = known.get(name) else {
+                i += 1;
+                continue;
+            };
+
+            let name = name.clone();
+            let mut fields: Vec<Option<Symbol>> = vec![None; size];
+
+            for (j, stmt) in stmts[..i].iter().enumerate() {
+                let Stmt::Let(field, expr) = stmt else {
+                    continue;
+                };
+
+                let ExprKind::Access(object, place) = &**expr else {
+                    continue;
+                };
+
+                if !matches!(&**object, ExprKind::Variable(x) if *x == name) || *place >= size {
+                    continue;
+                }
+
+                // The duplication can only move if the field is not used before the drop.
+                let uses: Vec<_> = stmts[j + 1..i]
+                    .iter()
+                    .filter(|x| {
+                        let mut free = HashSet::new();
+                        free_in_stmt(x, &mut HashSet::new(), &mut free);
+                        free.contains(field)
+                    })
+                    .collect();
+
+                if let [Stmt::Dup(x)] = &uses[..] {
+                    if x == field {
+                        fields[*place] = Some(field.clone());
+                    }
+                }
+            }
+
+            let mut unique = vec![];
+            let mut shared = vec![];
+
+            for (place, field) in fields.iter().enumerate() {
+                match field {
+                    Some(field) => shared.push(Stmt::Dup(field.clone())),
+                    None => {
+                        let value = self.new_var();
+                        let object = Box::new(ExprKind::Variable(name.clone()));
+                        unique.push(Stmt::Let(
+                            value.clone(),
+                            Box::new(ExprKind::Access(object, place)),
+                        ));
+                        unique.push(Stmt::Drop(value));
+                    }
+                }
+            }
+
+            shared.push(Stmt::Decref(name.clone()));
+
+            let token = self.new_var();
+            let reused = stmts[i + 1..]
+                .iter_mut()
+                .any(|stmt| reuse_in_stmt(stmt, size, &token));
+
+            if reused {
+                unique.push(Stmt::Token(token.clone(), Some(name.clone())));
+                shared.push(Stmt::Token(token, None));
+            } else {
+                unique.push(Stmt::Free(name.clone()));
+            }
+
+            stmts[i] = Stmt::Unique(name, unique, shared);
+
+            let moved: HashSet<_> = fields.into_iter().flatten().collect();
+            let before = stmts.len();
+
+            stmts = stmts
+                .into_iter()
+                .enumerate()
+                .filter(|(j, stmt)| *j > i || !matches!(stmt, Stmt::Dup(x) if moved.contains(x)))
+                .map(|(_, stmt)| stmt)
+                .collect();
+
+            i = i + 1 - (before - stmts.len());
+        }
+
+        Box::new(ExprKind::Block(stmts))
+    }
+}
+
+/// Inserts the instructions that count references into the programs and returns the borrowed
+/// parameters of their functions. It has to run after all the other passes, because they don't
+/// keep the instructions right.
+pub fn perceus(programs: &mut [Program]) -> Borrowed {
+    let mut definitions = HashMap::new();
+
+    for program in programs.iter() {
+        definitions.extend(program.definitions.clone());
+    }
+
+    let params = infer(programs, &definitions);
+
+    let mut ctx = Context {
+        params: &params,
+        definitions: &definitions,
+        owned: HashSet::new(),
+        borrowed: HashSet::new(),
+        counter: 0,
+    };
+
+    for program in programs.iter_mut() {
+        for (name, decl) in &mut program.lets {
+            let body = std::mem::replace(&mut decl.body, Box::new(ExprKind::Block(vec![])));
+
+            decl.body = match (*body, params.get(name)) {
+                (ExprKind::Lambda(names, body), Some((_, modes))) => {
+                    let (owned, borrowed): (Vec<_>, Vec<_>) = names
+                        .iter()
+                        .cloned()
+                        .zip(modes)
+                        .partition(|(_, owned)| **owned);
+
+                    let owned = owned.into_iter().map(|(x, _)| x).collect();
+                    let borrowed = borrowed.into_iter().map(|(x, _)| x).collect();
+
+                    let body = ctx.function(owned, borrowed, body);
+                    Box::new(ExprKind::Lambda(names, body))
+                }
+                (body, _) => ctx.function(HashSet::new(), HashSet::new(), Box::new(body)),
+            };
+        }
+    }
+
+    params
+        .into_iter()
+        .map(|(name, (names, modes))| {
+            let borrowed = names
+                .into_iter()
+                .zip(modes)
+                .filter(|(_, owned)| !owned)
+                .map(|(x, _)| x)
+                .collect();
+
+            (name, borrowed)
+        })
+        .collect()
+}
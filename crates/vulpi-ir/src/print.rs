@@ -0,0 +1,260 @@
+//! Prints the lambda IR as text, to inspect what the passes do to a program. The borrowed
+//! parameters found by the [Perceus](crate::perceus) pass are written with a `&` before them.
+
+use std::fmt::Write;
+
+use vulpi_intern::Symbol;
+use vulpi_syntax::{
+    elaborated::LiteralKind,
+    lambda::{Case, ExprKind, Program, Stmt, Tree},
+};
+
+use crate::perceus::Borrowed;
+
+struct Printer<'a> {
+    out: String,
+    indent: usize,
+    borrowed: &'a Borrowed,
+}
+
+fn names(names: &[Symbol]) -> String {
+    names.iter().map(|x| x.get()).collect::<Vec<_>>().join(", ")
+}
+
+impl<'a> Printer<'a> {
+    fn line(&mut self) {
+        self.out.push('\n');
+        self.out.push_str(&"  ".repeat(self.indent));
+    }
+
+    fn list(&mut self, exprs: &[Box<ExprKind>]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr);
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        self.out.push('{');
+        self.indent += 1;
+
+        for stmt in stmts {
+            self.line();
+            self.stmt(stmt);
+        }
+
+        self.indent -= 1;
+        self.line();
+        self.out.push('}');
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Let(name, expr) => {
+                write!(self.out, "let {} = ", name.get()).unwrap();
+                self.expr(expr);
+            }
+            Stmt::Expr(expr) => self.expr(expr),
+            Stmt::Dup(name) => write!(self.out, "dup {}", name.get()).unwrap(),
+            Stmt::Drop(name) => write!(self.out, "drop {}", name.get()).unwrap(),
+            Stmt::Decref(name) => write!(self.out, "decref {}", name.get()).unwrap(),
+            Stmt::Free(name) => write!(self.out, "free {}", name.get()).unwrap(),
+            Stmt::Token(token, Some(name)) => {
+                write!(self.out, "token {} = {}", token.get(), name.get()).unwrap()
+            }
+            Stmt::Token(token, None) => write!(self.out, "token {} = none", token.get()).unwrap(),
+            Stmt::Unique(name, unique, shared) => {
+                write!(self.out, "if unique {} ", name.get()).unwrap();
+                self.stmts(unique);
+                self.out.push_str(" else ");
+                self.stmts(shared);
+            }
+        }
+    }
+
+    fn tree(&mut self, tree: &Tree, actions: &[Box<ExprKind>]) {
+        match tree {
+            Tree::Leaf(i) => self.expr(&actions[*i]),
+            Tree::Switch(scrutinee, branches, default) => {
+                self.out.push_str("switch ");
+                self.expr(scrutinee);
+                self.out.push_str(" {");
+                self.indent += 1;
+
+                for (case, _, tree) in branches {
+                    self.line();
+
+                    match case {
+                        Case::Tuple(size) => write!(self.out, "tuple {size}").unwrap(),
+                        Case::Constructor(name, _) | Case::Record(name, _) => {
+                            self.out.push_str(&name.name.get())
+                        }
+                        Case::Literal(literal) => self.literal(literal),
+                    }
+
+                    self.out.push_str(" => ");
+                    self.tree(tree, actions);
+                }
+
+                if let Some(tree) = default {
+                    self.line();
+                    self.out.push_str("_ => ");
+                    self.tree(tree, actions);
+                }
+
+                self.indent -= 1;
+                self.line();
+                self.out.push('}');
+            }
+        }
+    }
+
+    fn literal(&mut self, literal: &LiteralKind) {
+        match literal {
+            LiteralKind::String(x) => write!(self.out, "{:?}", x.get()).unwrap(),
+            LiteralKind::Integer(x) | LiteralKind::Float(x) => self.out.push_str(&x.get()),
+            LiteralKind::Char(x) => write!(self.out, "'{}'", x.get()).unwrap(),
+            LiteralKind::Unit => self.out.push_str("()"),
+        }
+    }
+
+    fn fields(&mut self, fields: &[(Symbol, Box<ExprKind>)]) {
+        for (i, (name, expr)) in fields.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            write!(self.out, " {} = ", name.get()).unwrap();
+            self.expr(expr);
+        }
+        self.out.push_str(" }");
+    }
+
+    fn expr(&mut self, expr: &ExprKind) {
+        match expr {
+            ExprKind::Lambda(params, body) => {
+                write!(self.out, "fn({}) => ", names(params)).unwrap();
+                self.expr(body);
+            }
+            ExprKind::Application(func, args, _) => {
+                self.expr(func);
+                self.out.push('(');
+                self.list(args);
+                self.out.push(')');
+            }
+            ExprKind::Variable(name) => self.out.push_str(&name.get()),
+            ExprKind::Constructor(name) | ExprKind::Function(name) => {
+                self.out.push_str(&name.to_string())
+            }
+            ExprKind::Object(tag, args) => {
+                write!(self.out, "#{tag}[").unwrap();
+                self.list(args);
+                self.out.push(']');
+            }
+            ExprKind::Reuse(token, tag, args) => {
+                write!(self.out, "reuse {} #{tag}[", token.get()).unwrap();
+                self.list(args);
+                self.out.push(']');
+            }
+            ExprKind::Projection(field, expr) => {
+                self.expr(expr);
+                write!(self.out, ".{}", field.name.get()).unwrap();
+            }
+            ExprKind::Access(expr, place) => {
+                self.expr(expr);
+                write!(self.out, "[{place}]").unwrap();
+            }
+            ExprKind::Block(stmts) => self.stmts(stmts),
+            ExprKind::Literal(literal) => self.literal(literal),
+            ExprKind::RecordInstance(name, fields) => {
+                write!(self.out, "{} {{", name.to_string()).unwrap();
+                self.fields(fields);
+            }
+            ExprKind::RecordUpdate(name, expr, fields) => {
+                write!(self.out, "{} {{ ", name.to_string()).unwrap();
+                self.expr(expr);
+                self.out.push_str(" with");
+                self.fields(fields);
+            }
+            ExprKind::Tuple(args) => {
+                self.out.push('(');
+                self.list(args);
+                self.out.push(')');
+            }
+            ExprKind::Switch(_, tree, actions) => self.tree(tree, actions),
+            ExprKind::Computation(body) => {
+                self.out.push_str("computation ");
+                self.expr(body);
+            }
+            ExprKind::Perform(expr) => {
+                self.out.push_str("perform ");
+                self.expr(expr);
+            }
+            ExprKind::Handle(effect, body, handler, _, _) => {
+                write!(self.out, "handle {} ", effect.to_string()).unwrap();
+                self.expr(body);
+                self.out.push_str(" with ");
+                self.expr(handler);
+            }
+            ExprKind::Request(effect, id, args) => {
+                write!(self.out, "request {}#{id}(", effect.to_string()).unwrap();
+                self.list(args);
+                self.out.push(')');
+            }
+        }
+    }
+
+    fn program(&mut self, program: &Program) {
+        for external in &program.externals {
+            writeln!(
+                self.out,
+                "external {} = {:?}",
+                external.name.to_string(),
+                external.binding.get()
+            )
+            .unwrap();
+        }
+
+        if !program.externals.is_empty() {
+            self.out.push('\n');
+        }
+
+        for (name, decl) in &program.lets {
+            write!(self.out, "let {} = ", name.to_string()).unwrap();
+
+            match (&*decl.body, self.borrowed.get(name)) {
+                (ExprKind::Lambda(params, body), Some(borrowed)) => {
+                    let params: Vec<_> = params
+                        .iter()
+                        .map(|x| {
+                            if borrowed.contains(x) {
+                                format!("&{}", x.get())
+                            } else {
+                                x.get()
+                            }
+                        })
+                        .collect();
+
+                    write!(self.out, "fn({}) => ", params.join(", ")).unwrap();
+                    self.expr(body);
+                }
+                (body, _) => self.expr(body),
+            }
+
+            self.out.push_str("\n\n");
+        }
+    }
+}
+
+/// Prints the declarations of a program.
+pub fn program(program: &Program, borrowed: &Borrowed) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        indent: 0,
+        borrowed,
+    };
+
+    printer.program(program);
+    printer.out
+}
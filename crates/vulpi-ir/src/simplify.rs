@@ -0,0 +1,831 @@
+//! Simplification of the lambda IR after inlining. The rules run over all the declarations until
+//! none of them changes the program:
+//!
+//! - A lambda that is called right away binds its arguments to its parameters (beta reduction).
+//! - The statements of blocks that are called, bound by a `let` or run as statements are moved to
+//!   the block around them (let floating).
+//! - A switch over an object or a literal that is known takes the branch that matches it
+//!   (case-of-known-constructor), and the fields of known objects are read directly.
+//! - A switch over the result of another switch whose actions are known goes inside of the other
+//!   one (case-of-case). The actions that more than one branch goes to become join points, that
+//!   are functions, so they are not copied.
+//! - The `let`s of variables are replaced by the variables, and the `let`s of pure expressions
+//!   that are not used are removed.
+//!
+//! The variables of the statements that are moved get new names, so they never capture others.
+
+use std::collections::{HashSet, VecDeque};
+
+use vulpi_intern::Symbol;
+use vulpi_syntax::{
+    elaborated::LiteralKind,
+    lambda::{Case, Expr, ExprKind, Program, Stmt, TagType, Tree},
+};
+
+use crate::perceus::{children, prepend, root};
+
+/// The most times that the rules run over the program, in case they never stop changing it.
+const MAX_ROUNDS: usize = 16;
+
+/// The new names of variables.
+type Subs = im_rc::HashMap<Symbol, Symbol>;
+
+/// The values that are known by the variables that hold them.
+type Env = im_rc::HashMap<Symbol, Known>;
+
+/// What is known about the value of a variable.
+#[derive(Clone)]
+enum Known {
+    /// An object with its tag and the fields that are atoms, that can be copied.
+    Object(usize, Vec<Option<ExprKind>>),
+    Literal(LiteralKind),
+}
+
+impl Known {
+    fn mentions(&self, name: &Symbol) -> bool {
+        match self {
+            Known::Object(_, fields) => fields
+                .iter()
+                .flatten()
+                .any(|field| matches!(field, ExprKind::Variable(other) if other == name)),
+            Known::Literal(_) => false,
+        }
+    }
+}
+
+fn unit() -> Expr {
+    Box::new(ExprKind::Literal(Box::new(LiteralKind::Unit)))
+}
+
+fn children_mut(expr: &mut ExprKind) -> Vec<&mut Expr> {
+    match expr {
+        ExprKind::Lambda(_, body) => vec![body],
+        ExprKind::Application(func, args, _) => std::iter::once(func).chain(args).collect(),
+        ExprKind::Object(_, args)
+        | ExprKind::Reuse(_, _, args)
+        | ExprKind::Tuple(args)
+        | ExprKind::Request(_, _, args) => args.iter_mut().collect(),
+        ExprKind::Projection(_, expr)
+        | ExprKind::Access(expr, _)
+        | ExprKind::Computation(expr)
+        | ExprKind::Perform(expr) => vec![expr],
+        ExprKind::Block(stmts) => stmts
+            .iter_mut()
+            .filter_map(|stmt| match stmt {
+                Stmt::Let(_, expr) | Stmt::Expr(expr) => Some(expr),
+                _ => None,
+            })
+            .collect(),
+        ExprKind::RecordInstance(_, fields) => fields.iter_mut().map(|(_, expr)| expr).collect(),
+        ExprKind::RecordUpdate(_, expr, fields) => std::iter::once(expr)
+            .chain(fields.iter_mut().map(|(_, expr)| expr))
+            .collect(),
+        ExprKind::Switch(_, _, actions) => actions.iter_mut().collect(),
+        ExprKind::Handle(_, body, handler, _, _) => vec![body, handler],
+        ExprKind::Variable(_)
+        | ExprKind::Constructor(_)
+        | ExprKind::Function(_)
+        | ExprKind::Literal(_) => vec![],
+    }
+}
+
+/// Expressions that can be copied without changing what the program does.
+fn atom(expr: &ExprKind) -> Option<ExprKind> {
+    match expr {
+        ExprKind::Variable(_)
+        | ExprKind::Literal(_)
+        | ExprKind::Function(_)
+        | ExprKind::Constructor(_) => Some(expr.clone()),
+        _ => None,
+    }
+}
+
+/// Expressions that don't do anything besides creating their values, so they can be removed if
+/// the values are not used.
+fn is_pure(expr: &ExprKind) -> bool {
+    match expr {
+        ExprKind::Variable(_)
+        | ExprKind::Literal(_)
+        | ExprKind::Function(_)
+        | ExprKind::Constructor(_)
+        | ExprKind::Lambda(_, _) => true,
+        ExprKind::Object(_, args) | ExprKind::Tuple(args) => args.iter().all(|arg| is_pure(arg)),
+        ExprKind::RecordInstance(_, fields) => fields.iter().all(|(_, expr)| is_pure(expr)),
+        ExprKind::RecordUpdate(_, expr, fields) => {
+            is_pure(expr) && fields.iter().all(|(_, expr)| is_pure(expr))
+        }
+        ExprKind::Access(expr, _) | ExprKind::Projection(_, expr) => is_pure(expr),
+        _ => false,
+    }
+}
+
+/// If a variable appears anywhere in an expression, even where another variable with the same
+/// name hides it.
+fn mentions(expr: &ExprKind, name: &Symbol) -> bool {
+    match expr {
+        ExprKind::Variable(other) => other == name,
+        ExprKind::Reuse(token, _, args) => token == name || args.iter().any(|x| mentions(x, name)),
+        ExprKind::Block(stmts) => stmts.iter().any(|stmt| mentions_stmt(stmt, name)),
+        ExprKind::Switch(_, tree, actions) => {
+            mentions_tree(tree, name) || actions.iter().any(|action| mentions(action, name))
+        }
+        _ => children(expr)
+            .into_iter()
+            .any(|child| mentions(child, name)),
+    }
+}
+
+fn mentions_stmt(stmt: &Stmt, name: &Symbol) -> bool {
+    match stmt {
+        Stmt::Let(_, expr) | Stmt::Expr(expr) => mentions(expr, name),
+        Stmt::Dup(other) | Stmt::Drop(other) | Stmt::Decref(other) | Stmt::Free(other) => {
+            other == name
+        }
+        Stmt::Token(token, other) => token == name || other.as_ref() == Some(name),
+        Stmt::Unique(other, unique, shared) => {
+            other == name
+                || unique
+                    .iter()
+                    .chain(shared)
+                    .any(|stmt| mentions_stmt(stmt, name))
+        }
+    }
+}
+
+fn mentions_tree(tree: &Tree, name: &Symbol) -> bool {
+    match tree {
+        Tree::Leaf(_) => false,
+        Tree::Switch(scrutinee, branches, default) => {
+            mentions(scrutinee, name)
+                || branches
+                    .iter()
+                    .any(|(_, _, tree)| mentions_tree(tree, name))
+                || default
+                    .as_ref()
+                    .is_some_and(|tree| mentions_tree(tree, name))
+        }
+    }
+}
+
+/// If an expression binds a variable with a name anywhere.
+fn binds(expr: &ExprKind, name: &Symbol) -> bool {
+    match expr {
+        ExprKind::Lambda(params, body) => params.contains(name) || binds(body, name),
+        ExprKind::Block(stmts) => stmts.iter().any(|stmt| binds_stmt(stmt, name)),
+        _ => children(expr).into_iter().any(|child| binds(child, name)),
+    }
+}
+
+fn binds_stmt(stmt: &Stmt, name: &Symbol) -> bool {
+    match stmt {
+        Stmt::Let(other, expr) => other == name || binds(expr, name),
+        Stmt::Expr(expr) => binds(expr, name),
+        Stmt::Token(token, _) => token == name,
+        Stmt::Unique(_, unique, shared) => unique
+            .iter()
+            .chain(shared)
+            .any(|stmt| binds_stmt(stmt, name)),
+        _ => false,
+    }
+}
+
+/// Gives new names to the free variables of an expression.
+fn rename(expr: &mut ExprKind, subs: &Subs) {
+    if subs.is_empty() {
+        return;
+    }
+
+    match expr {
+        ExprKind::Variable(name) => {
+            if let Some(new) = subs.get(name) {
+                *name = new.clone();
+            }
+        }
+        ExprKind::Reuse(token, _, args) => {
+            if let Some(new) = subs.get(token) {
+                *token = new.clone();
+            }
+
+            for arg in args {
+                rename(arg, subs);
+            }
+        }
+        ExprKind::Lambda(params, body) => {
+            let mut subs = subs.clone();
+
+            for param in params.iter() {
+                subs.remove(param);
+            }
+
+            rename(body, &subs)
+        }
+        ExprKind::Block(stmts) => {
+            let mut subs = subs.clone();
+
+            for stmt in stmts {
+                rename_stmt(stmt, &mut subs);
+            }
+        }
+        ExprKind::Switch(_, tree, actions) => {
+            rename_tree(tree, subs);
+
+            for action in actions {
+                rename(action, subs);
+            }
+        }
+        _ => {
+            for child in children_mut(expr) {
+                rename(child, subs);
+            }
+        }
+    }
+}
+
+/// Renames the variables of a statement. The variable that a `let` binds hides the ones that
+/// would be renamed in the statements after it.
+fn rename_stmt(stmt: &mut Stmt, subs: &mut Subs) {
+    let get = |name: &mut Symbol, subs: &Subs| {
+        if let Some(new) = subs.get(name) {
+            *name = new.clone();
+        }
+    };
+
+    match stmt {
+        Stmt::Let(name, expr) => {
+            rename(expr, subs);
+            subs.remove(name);
+        }
+        Stmt::Expr(expr) => rename(expr, subs),
+        Stmt::Dup(name) | Stmt::Drop(name) | Stmt::Decref(name) | Stmt::Free(name) => {
+            get(name, subs)
+        }
+        Stmt::Token(token, name) => {
+            get(token, subs);
+
+            if let Some(name) = name {
+                get(name, subs);
+            }
+        }
+        Stmt::Unique(name, unique, shared) => {
+            get(name, subs);
+
+            for stmt in unique.iter_mut().chain(shared) {
+                rename_stmt(stmt, subs);
+            }
+        }
+    }
+}
+
+fn rename_tree(tree: &mut Tree, subs: &Subs) {
+    if let Tree::Switch(scrutinee, branches, default) = tree {
+        rename(scrutinee, subs);
+
+        for (_, _, tree) in branches {
+            rename_tree(tree, subs);
+        }
+
+        if let Some(tree) = default {
+            rename_tree(tree, subs);
+        }
+    }
+}
+
+/// Removes the statements of a block that ends with an expression, and leaves the expression in
+/// its place.
+fn unblock(expr: &mut ExprKind) -> Option<Vec<Stmt>> {
+    match expr {
+        ExprKind::Block(stmts) if matches!(stmts.last(), Some(Stmt::Expr(_))) => {
+            let mut stmts = std::mem::take(stmts);
+
+            if let Some(Stmt::Expr(last)) = stmts.pop() {
+                *expr = *last;
+            }
+
+            Some(stmts)
+        }
+        _ => None,
+    }
+}
+
+/// Forgets the values that a variable that is bound again can't hold anymore.
+fn forget(env: &mut Env, name: &Symbol) {
+    env.remove(name);
+    env.retain(|_, known| !known.mentions(name));
+}
+
+fn value_of(expr: &ExprKind, env: &Env) -> Option<Known> {
+    match expr {
+        ExprKind::Variable(name) => env.get(name).cloned(),
+        ExprKind::Literal(literal) => Some(Known::Literal((**literal).clone())),
+        ExprKind::Object(tag, args) => Some(Known::Object(
+            *tag,
+            args.iter().map(|arg| atom(arg)).collect(),
+        )),
+        ExprKind::Tuple(args) => Some(Known::Object(0, args.iter().map(|arg| atom(arg)).collect())),
+        ExprKind::Access(expr, place) => match value_of(expr, env)? {
+            Known::Object(_, fields) => value_of(fields.get(*place)?.as_ref()?, env),
+            Known::Literal(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// The value of a literal as a number, if the literal is a number.
+fn number(literal: &LiteralKind) -> Option<f64> {
+    match literal {
+        LiteralKind::Integer(x) | LiteralKind::Float(x) => x.get().replace('_', "").parse().ok(),
+        _ => None,
+    }
+}
+
+fn literal_eq(left: &LiteralKind, right: &LiteralKind) -> Option<bool> {
+    match (left, right) {
+        (LiteralKind::String(x), LiteralKind::String(y))
+        | (LiteralKind::Char(x), LiteralKind::Char(y)) => Some(x == y),
+        (LiteralKind::Unit, LiteralKind::Unit) => Some(true),
+        _ => Some(number(left)? == number(right)?),
+    }
+}
+
+/// If a value matches a case, or [None] if it's not known.
+fn matches(case: &Case, tag: &TagType, value: Option<&Known>) -> Option<bool> {
+    match (case, tag, value) {
+        (Case::Literal(literal), _, Some(Known::Literal(value))) => literal_eq(literal, value),
+        (Case::Literal(_), _, _) => None,
+        (Case::Tuple(_) | Case::Record(_, _), _, _)
+        | (Case::Constructor(_, _), TagType::None, _) => Some(true),
+        (Case::Constructor(_, _), TagType::Field(id), Some(Known::Object(tag, _))) => {
+            Some(tag == id)
+        }
+        (Case::Constructor(_, _), TagType::Number(id), Some(Known::Literal(value))) => {
+            Some(number(value)? == *id as f64)
+        }
+        _ => None,
+    }
+}
+
+/// The branch of a switch that a known scrutinee takes. It's `Some(None)` if it takes the
+/// default one.
+fn choose(
+    scrutinee: &ExprKind,
+    branches: &[(Case, TagType, Tree)],
+    default: bool,
+    env: &Env,
+) -> Option<Option<usize>> {
+    let value = value_of(scrutinee, env);
+
+    for (i, (case, tag, _)) in branches.iter().enumerate() {
+        if matches(case, tag, value.as_ref())? {
+            return Some(Some(i));
+        }
+    }
+
+    default.then_some(None)
+}
+
+/// The action that a tree goes to, if all of the switches in the way are known.
+fn decide(mut tree: &Tree, env: &Env) -> Option<usize> {
+    loop {
+        match tree {
+            Tree::Leaf(action) => return Some(*action),
+            Tree::Switch(scrutinee, branches, default) => {
+                tree = match choose(scrutinee, branches, default.is_some(), env)? {
+                    Some(branch) => &branches[branch].2,
+                    None => default.as_deref()?,
+                }
+            }
+        }
+    }
+}
+
+fn leaves(tree: &Tree, actions: &mut HashSet<usize>) {
+    match tree {
+        Tree::Leaf(action) => {
+            actions.insert(*action);
+        }
+        Tree::Switch(_, branches, default) => {
+            for (_, _, tree) in branches {
+                leaves(tree, actions);
+            }
+
+            if let Some(tree) = default {
+                leaves(tree, actions);
+            }
+        }
+    }
+}
+
+/// If all the switches of a tree look at a variable or at its fields.
+fn inspects(tree: &Tree, name: &Symbol) -> bool {
+    match tree {
+        Tree::Leaf(_) => true,
+        Tree::Switch(scrutinee, branches, default) => {
+            root(scrutinee) == Some(name)
+                && branches.iter().all(|(_, _, tree)| inspects(tree, name))
+                && default.as_ref().is_none_or(|tree| inspects(tree, name))
+        }
+    }
+}
+
+/// The value that an expression ends with, if it's known.
+fn result_of(expr: &ExprKind, env: &Env) -> Option<Known> {
+    match expr {
+        ExprKind::Block(stmts) => match stmts.last()? {
+            Stmt::Expr(expr) => result_of(expr, env),
+            _ => None,
+        },
+        expr => value_of(expr, env),
+    }
+}
+
+#[derive(Default)]
+struct Simplifier {
+    counter: usize,
+    changed: bool,
+}
+
+impl Simplifier {
+    /// Creates a new name for a variable that is based on the name of another one.
+    fn fresh(&mut self, name: &Symbol) -> Symbol {
+        self.counter += 1;
+
+        let name = name.get();
+        let base = name
+            .split('$')
+            .next()
+            .filter(|x| !x.is_empty())
+            .unwrap_or("v");
+
+        Symbol::intern(&format!("{base}$s{}", self.counter))
+    }
+
+    /// Gives new names to the variables that the statements of a block bind, in the statements
+    /// and in the expression that the block ends with.
+    fn float(&mut self, stmts: &mut [Stmt], last: &mut ExprKind) {
+        let mut subs = Subs::new();
+
+        for stmt in stmts.iter_mut() {
+            match stmt {
+                Stmt::Let(name, expr) => {
+                    rename(expr, &subs);
+
+                    let new = self.fresh(name);
+                    subs.insert(name.clone(), new.clone());
+                    *name = new;
+                }
+                stmt => rename_stmt(stmt, &mut subs),
+            }
+        }
+
+        rename(last, &subs);
+    }
+
+    fn expr(&mut self, expr: &mut ExprKind, env: &Env) {
+        match expr {
+            ExprKind::Lambda(params, body) => {
+                let mut env = env.clone();
+
+                for param in params.iter() {
+                    forget(&mut env, param);
+                }
+
+                self.expr(body, &env)
+            }
+            ExprKind::Application(_, _, _) => self.application(expr, env),
+            ExprKind::Access(inner, place) => {
+                let place = *place;
+                self.expr(inner, env);
+
+                if let Some(Known::Object(_, fields)) = value_of(inner, env) {
+                    if let Some(Some(field)) = fields.get(place) {
+                        *expr = field.clone();
+                        self.changed = true;
+                    }
+                }
+            }
+            ExprKind::Block(stmts) => {
+                let mut stmts = self.block(std::mem::take(stmts), env);
+
+                match stmts.pop() {
+                    Some(Stmt::Expr(last)) if stmts.is_empty() => {
+                        *expr = *last;
+                        self.changed = true;
+                    }
+                    Some(last) => {
+                        stmts.push(last);
+                        *expr = ExprKind::Block(stmts);
+                    }
+                    None => *expr = ExprKind::Block(stmts),
+                }
+            }
+            ExprKind::Switch(_, tree, actions) => {
+                self.tree(tree, env);
+
+                if let Tree::Leaf(action) = tree {
+                    let action = std::mem::replace(&mut actions[*action], unit());
+                    *expr = *action;
+                    self.changed = true;
+                    self.expr(expr, env);
+                } else {
+                    let mut reachable = HashSet::new();
+                    leaves(tree, &mut reachable);
+
+                    for (i, action) in actions.iter_mut().enumerate() {
+                        if reachable.contains(&i) {
+                            self.expr(action, env);
+                        } else if !matches!(&**action, ExprKind::Literal(literal) if matches!(**literal, LiteralKind::Unit))
+                        {
+                            *action = unit();
+                            self.changed = true;
+                        }
+                    }
+                }
+            }
+            _ => {
+                for child in children_mut(expr) {
+                    self.expr(child, env);
+                }
+            }
+        }
+    }
+
+    fn application(&mut self, expr: &mut ExprKind, env: &Env) {
+        let ExprKind::Application(func, args, span) = expr else {
+            return;
+        };
+
+        self.expr(func, env);
+
+        for arg in args.iter_mut() {
+            self.expr(arg, env);
+        }
+
+        if let ExprKind::Lambda(params, body) = &mut **func {
+            if params.len() == args.len() {
+                let mut subs = Subs::new();
+                let mut stmts = Vec::new();
+
+                for (param, arg) in params.iter().zip(std::mem::take(args)) {
+                    let name = self.fresh(param);
+                    subs.insert(param.clone(), name.clone());
+                    stmts.push(Stmt::Let(name, arg));
+                }
+
+                let mut body = std::mem::replace(body, unit());
+                rename(&mut body, &subs);
+
+                *expr = *prepend(stmts, body);
+                self.changed = true;
+            }
+        } else if let Some(mut stmts) = unblock(func) {
+            self.float(&mut stmts, func);
+
+            let call = ExprKind::Application(
+                std::mem::replace(func, unit()),
+                std::mem::take(args),
+                span.take(),
+            );
+            stmts.push(Stmt::Expr(Box::new(call)));
+
+            *expr = ExprKind::Block(stmts);
+            self.changed = true;
+        }
+    }
+
+    /// Takes the branches of the switches of a tree whose scrutinees are known.
+    fn tree(&mut self, tree: &mut Tree, env: &Env) {
+        while let Tree::Switch(scrutinee, branches, default) = tree {
+            let next = match choose(scrutinee, branches, default.is_some(), env) {
+                Some(Some(branch)) => branches.swap_remove(branch).2,
+                Some(None) => *default.take().unwrap(),
+                None => {
+                    for (_, _, tree) in branches {
+                        self.tree(tree, env);
+                    }
+
+                    if let Some(tree) = default {
+                        self.tree(tree, env);
+                    }
+
+                    return;
+                }
+            };
+
+            *tree = next;
+            self.changed = true;
+        }
+    }
+
+    fn block(&mut self, stmts: Vec<Stmt>, env: &Env) -> Vec<Stmt> {
+        let mut env = env.clone();
+        let mut pending: VecDeque<_> = stmts.into();
+        let mut out = Vec::new();
+
+        while let Some(stmt) = pending.pop_front() {
+            match stmt {
+                Stmt::Let(name, mut expr) => {
+                    self.expr(&mut expr, &env);
+
+                    if let Some(mut stmts) = unblock(&mut expr) {
+                        self.float(&mut stmts, &mut expr);
+                        pending.push_front(Stmt::Let(name, expr));
+
+                        for stmt in stmts.into_iter().rev() {
+                            pending.push_front(stmt);
+                        }
+
+                        self.changed = true;
+                        continue;
+                    }
+
+                    if let ExprKind::Variable(other) = &*expr {
+                        if *other != name && !pending.iter().any(|x| binds_stmt(x, other)) {
+                            let mut subs = Subs::unit(name, other.clone());
+
+                            for stmt in pending.iter_mut() {
+                                rename_stmt(stmt, &mut subs);
+                            }
+
+                            self.changed = true;
+                            continue;
+                        }
+                    }
+
+                    let known = value_of(&expr, &env);
+                    forget(&mut env, &name);
+
+                    if let Some(known) = known.filter(|known| !known.mentions(&name)) {
+                        env.insert(name.clone(), known);
+                    }
+
+                    out.push(Stmt::Let(name, expr));
+                }
+                Stmt::Expr(mut expr) => {
+                    self.expr(&mut expr, &env);
+
+                    if let Some(mut stmts) = unblock(&mut expr) {
+                        self.float(&mut stmts, &mut expr);
+                        pending.push_front(Stmt::Expr(expr));
+
+                        for stmt in stmts.into_iter().rev() {
+                            pending.push_front(stmt);
+                        }
+
+                        self.changed = true;
+                        continue;
+                    }
+
+                    out.push(Stmt::Expr(expr));
+                }
+                stmt => out.push(stmt),
+            }
+        }
+
+        self.case_of_case(&mut out, &env);
+        self.prune(&mut out);
+
+        out
+    }
+
+    /// Moves a switch at the end of a block over the result of the switch before it into the
+    /// actions of that switch, when they end with known values.
+    fn case_of_case(&mut self, stmts: &mut Vec<Stmt>, env: &Env) {
+        let [.., Stmt::Let(name, inner), Stmt::Expr(outer)] = stmts.as_slice() else {
+            return;
+        };
+
+        let (ExprKind::Switch(_, inner_tree, inner_actions), ExprKind::Switch(_, outer_tree, _)) =
+            (&**inner, &**outer)
+        else {
+            return;
+        };
+
+        if !inspects(outer_tree, name) || mentions(inner, name) {
+            return;
+        }
+
+        let mut reachable: Vec<_> = {
+            let mut reachable = HashSet::new();
+            leaves(inner_tree, &mut reachable);
+            reachable.into_iter().collect()
+        };
+
+        reachable.sort();
+
+        let mut targets = Vec::new();
+
+        for &action in &reachable {
+            let Some(known) = result_of(&inner_actions[action], env) else {
+                return;
+            };
+
+            let mut env = env.clone();
+            env.insert(name.clone(), known);
+
+            let Some(target) = decide(outer_tree, &env) else {
+                return;
+            };
+
+            targets.push((action, target));
+        }
+
+        let Some(Stmt::Expr(outer)) = stmts.pop() else {
+            return;
+        };
+
+        let Some(Stmt::Let(name, inner)) = stmts.pop() else {
+            return;
+        };
+
+        let (ExprKind::Switch(result, tree, mut actions), ExprKind::Switch(_, _, outer_actions)) =
+            (*inner, *outer)
+        else {
+            return;
+        };
+
+        let mut joins = im_rc::HashMap::new();
+
+        for (_, target) in &targets {
+            if joins.contains_key(target) {
+                continue;
+            }
+
+            if targets.iter().filter(|(_, other)| other == target).count() > 1 {
+                let param = self.fresh(&name);
+                let join = self.fresh(&Symbol::intern("join"));
+
+                let mut body = outer_actions[*target].clone();
+                rename(&mut body, &Subs::unit(name.clone(), param.clone()));
+
+                let lambda = ExprKind::Lambda(vec![param], body);
+                stmts.push(Stmt::Let(join.clone(), Box::new(lambda)));
+                joins.insert(*target, join);
+            }
+        }
+
+        for (action, target) in targets {
+            let value = std::mem::replace(&mut actions[action], unit());
+
+            actions[action] = match joins.get(&target) {
+                Some(join) => Box::new(ExprKind::Application(
+                    Box::new(ExprKind::Variable(join.clone())),
+                    vec![value],
+                    None,
+                )),
+                None => {
+                    let new = self.fresh(&name);
+
+                    let mut body = outer_actions[target].clone();
+                    rename(&mut body, &Subs::unit(name.clone(), new.clone()));
+
+                    Box::new(ExprKind::Block(vec![
+                        Stmt::Let(new, value),
+                        Stmt::Expr(body),
+                    ]))
+                }
+            };
+        }
+
+        stmts.push(Stmt::Expr(Box::new(ExprKind::Switch(
+            result, tree, actions,
+        ))));
+        self.changed = true;
+    }
+
+    /// Removes the pure statements whose values are not used.
+    fn prune(&mut self, stmts: &mut Vec<Stmt>) {
+        for i in (0..stmts.len().saturating_sub(1)).rev() {
+            let unused = match &stmts[i] {
+                Stmt::Let(name, expr) => {
+                    is_pure(expr) && !stmts[i + 1..].iter().any(|x| mentions_stmt(x, name))
+                }
+                Stmt::Expr(expr) => is_pure(expr),
+                _ => false,
+            };
+
+            if unused {
+                stmts.remove(i);
+                self.changed = true;
+            }
+        }
+    }
+}
+
+/// Simplifies the declarations of the programs until nothing changes.
+pub fn simplify(programs: &mut [Program]) {
+    let mut simplifier = Simplifier::default();
+
+    for _ in 0..MAX_ROUNDS {
+        simplifier.changed = false;
+
+        for program in programs.iter_mut() {
+            for (_, decl) in &mut program.lets {
+                simplifier.expr(&mut decl.body, &Env::new());
+            }
+        }
+
+        if !simplifier.changed {
+            break;
+        }
+    }
+}
@@ -1,14 +1,20 @@
 //! This module compiles a TypedTree to a Vulpi IR tree called Lambda. This is the first step in
 //! lowering the AST to a form that is easier to work with for code generation.
 
-use std::{cell::RefCell, collections::HashMap, rc::Rc, vec};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    vec,
+};
 
 use vulpi_intern::Symbol;
+use vulpi_location::Spanned;
 
 use vulpi_syntax::{
     elaborated::*,
     lambda::{self, Case, ConsDef, Stmt, TagType},
-    r#abstract::Qualified,
+    r#abstract::{Qualified, Visibility},
 };
 
 use crate::pattern;
@@ -39,6 +45,7 @@ pub struct Context {
     constructors: Rc<RefCell<HashMap<Qualified, (ConsDef, usize)>>>,
     vars: im_rc::HashMap<Symbol, usize>,
     types: im_rc::HashMap<Qualified, TypeDef>,
+    instances: Rc<RefCell<HashSet<Qualified>>>,
 }
 
 impl Context {
@@ -106,6 +113,12 @@ impl Context {
         self.constructors.borrow().get(name).cloned().unwrap().0
     }
 
+    /// Takes the instances that were used since the last call, so they can be added as
+    /// dependencies of the declaration that uses them.
+    pub fn take_instances(&mut self) -> HashSet<Qualified> {
+        self.instances.take()
+    }
+
     pub fn is_newtype(&self, name: &Qualified) -> bool {
         if let Some(cons) = self.constructors.borrow().get(name) {
             matches!(cons, (ConsDef::NewType, 1))
@@ -115,10 +128,35 @@ impl Context {
     }
 }
 
+fn translate_dictionary(context: &mut Context, dictionary: &Dictionary) -> lambda::Expr {
+    match dictionary {
+        Dictionary::Variable(name) => {
+            Box::new(lambda::ExprKind::Variable(context.find_var(name.clone())))
+        }
+        Dictionary::Instance(name, args) => {
+            context.instances.borrow_mut().insert(name.clone());
+
+            args.iter().fold(
+                Box::new(lambda::ExprKind::Function(name.clone())),
+                |acc, arg| {
+                    let arg = translate_dictionary(context, arg);
+                    Box::new(lambda::ExprKind::Application(acc, vec![arg], None))
+                },
+            )
+        }
+        Dictionary::Super(dictionary, place) => Box::new(lambda::ExprKind::Access(
+            translate_dictionary(context, dictionary),
+            *place,
+        )),
+        Dictionary::Error => unreachable!(),
+    }
+}
+
 fn translate_occurence(occ: pattern::Occurrence) -> lambda::Expr {
     occ.1.into_iter().fold(occ.0, |acc, x| match x {
         pattern::Index::Cons(i) => Box::new(lambda::ExprKind::Access(acc, i)),
         pattern::Index::Tuple(i) => Box::new(lambda::ExprKind::Access(acc, i)),
+        pattern::Index::Field(field) => Box::new(lambda::ExprKind::Projection(field, acc)),
     })
 }
 
@@ -130,6 +168,7 @@ fn translate_case_to_tagged_expr(context: &mut Context, case: Case) -> TagType {
             ConsDef::NewType => TagType::None,
             ConsDef::Tuple => TagType::None,
         },
+        Case::Record(_, _) => TagType::None,
         Case::Literal(_) => TagType::None,
         Case::Tuple(_) => TagType::Size,
     }
@@ -162,8 +201,15 @@ fn translate_tree(
         match tree {
             pattern::Tree::Fail => unreachable!(),
             pattern::Tree::Leaf(i, _) => lambda::Tree::Leaf(i),
-            pattern::Tree::Switch(occ, cases) => {
-                if cases.len() == 1 {
+            pattern::Tree::Switch(occ, cases, default) => {
+                // The only constructor of a type always matches, so the default is never used.
+                let complete = matches!(
+                    &cases[..],
+                    [(Case::Constructor(name, _), _)]
+                        if matches!(context.get_constructor(name), ConsDef::NewType | ConsDef::Tuple)
+                );
+
+                if cases.len() == 1 && (default.is_none() || complete) {
                     translate(context, cases[0].1.clone())
                 } else {
                     let branches = cases
@@ -177,7 +223,9 @@ fn translate_tree(
                         })
                         .collect();
 
-                    lambda::Tree::Switch(translate_occurence(occ), branches)
+                    let default = default.map(|tree| Box::new(translate(context, *tree)));
+
+                    lambda::Tree::Switch(translate_occurence(occ), branches, default)
                 }
             }
         }
@@ -186,7 +234,7 @@ fn translate_tree(
     match tree {
         pattern::Tree::Fail => unreachable!(),
         pattern::Tree::Leaf(i, _) => actions[i].clone(),
-        pattern::Tree::Switch(_, _) => {
+        pattern::Tree::Switch(_, _, _) => {
             let tree = translate(context, tree);
             Box::new(lambda::ExprKind::Switch(
                 context.new_var("r".to_string()),
@@ -313,6 +361,18 @@ fn compile_match(
     context.scope(|context| compile_match_with_names(context, scrutinee_names, arms, actions))
 }
 
+/// Compiles a binding whose pattern has alternatives as a match with a single arm.
+fn compile_alternatives(
+    context: &mut Context,
+    scrutinee: Expr<Type<Real>>,
+    pattern: &Pattern,
+    action: Expr<Type<Real>>,
+) -> lambda::Expr {
+    let arms = pattern::alternatives(std::slice::from_ref(pattern));
+    let actions = vec![action; arms.len()];
+    compile_match(context, vec![scrutinee], arms, actions)
+}
+
 pub trait Transform {
     type Out;
     fn transform<'a>(&self, context: &mut Context) -> Self::Out;
@@ -351,6 +411,15 @@ impl Transform for Expr<Type<Real>> {
 
     fn transform<'a>(&self, context: &mut Context) -> Self::Out {
         match &*self.data {
+            ExprKind::Lambda(lambda) if pattern::has_or(&lambda.param) => {
+                context.scope(|context| {
+                    let name = context.new_var("v".to_string());
+                    let arms = pattern::alternatives(std::slice::from_ref(&lambda.param));
+                    let actions = vec![lambda.body.clone(); arms.len()];
+                    let body = compile_match_with_names(context, vec![name.clone()], arms, actions);
+                    Box::new(lambda::ExprKind::Lambda(vec![name], body))
+                })
+            }
             ExprKind::Lambda(lambda) => context.scope(|context| {
                 let arms = vec![lambda.param.clone()];
                 let scrutinee = vec![generate_pattern_name(context, &lambda.param).0];
@@ -374,7 +443,11 @@ impl Transform for Expr<Type<Real>> {
             ExprKind::Application(app) => {
                 let func = app.func.transform(context);
                 let arg = app.args.transform(context);
-                Box::new(lambda::ExprKind::Application(func, vec![arg]))
+                Box::new(lambda::ExprKind::Application(
+                    func,
+                    vec![arg],
+                    Some(self.span.clone()),
+                ))
             }
             ExprKind::Variable(var) => {
                 Box::new(lambda::ExprKind::Variable(context.find_var(var.clone())))
@@ -386,6 +459,12 @@ impl Transform for Expr<Type<Real>> {
                 field.field.clone(),
                 field.expr.transform(context),
             )),
+            ExprKind::Let(let_expr) if pattern::has_or(&let_expr.pattern) => compile_alternatives(
+                context,
+                let_expr.body.clone(),
+                &let_expr.pattern,
+                let_expr.next.clone(),
+            ),
             ExprKind::Let(let_expr) => {
                 let arms = vec![let_expr.pattern.clone()];
                 let scrutinee = vec![let_expr.body.clone()];
@@ -396,15 +475,40 @@ impl Transform for Expr<Type<Real>> {
             ExprKind::When(when_expr) => {
                 let (actions, patterns): (Vec<_>, Vec<_>) = when_expr
                     .arms
-                    .clone()
-                    .into_iter()
-                    .map(|x| (x.expr, x.patterns))
+                    .iter()
+                    .flat_map(|x| {
+                        pattern::alternatives(&x.patterns)
+                            .into_iter()
+                            .map(|row| (x.expr.clone(), row))
+                    })
                     .unzip();
 
                 compile_match(context, when_expr.scrutinee.clone(), patterns, actions)
             }
             ExprKind::Do(sttms) => context.scope(|context| {
-                sttms.into_iter().for_each(|x| x.transform(context));
+                for (i, sttm) in sttms.iter().enumerate() {
+                    match sttm {
+                        // The rest of the block depends on the alternative that matched.
+                        SttmKind::Let(let_) if pattern::has_or(&let_.pattern) => {
+                            let rest = Spanned::new(
+                                Box::new(ExprKind::Do(sttms[i + 1..].to_vec())),
+                                self.span.clone(),
+                            );
+
+                            let expr = compile_alternatives(
+                                context,
+                                let_.expr.clone(),
+                                &let_.pattern,
+                                rest,
+                            );
+
+                            context.add_upwards(Stmt::Expr(expr));
+                            break;
+                        }
+                        _ => sttm.transform(context),
+                    }
+                }
+
                 let statements = context.drain_upwards();
                 Box::new(lambda::ExprKind::Block(statements))
             }),
@@ -434,6 +538,28 @@ impl Transform for Expr<Type<Real>> {
                 let t = t.exprs.transform(context);
                 Box::new(lambda::ExprKind::Tuple(t))
             }
+            ExprKind::Dictionary(evidence) => translate_dictionary(context, &evidence.get()),
+            ExprKind::Computation(expr) => context.scope(|context| {
+                let body = expr.transform(context);
+                let mut upwards = context.drain_upwards();
+
+                if upwards.is_empty() {
+                    Box::new(lambda::ExprKind::Computation(body))
+                } else {
+                    upwards.push(Stmt::Expr(body));
+                    Box::new(lambda::ExprKind::Computation(Box::new(
+                        lambda::ExprKind::Block(upwards),
+                    )))
+                }
+            }),
+            ExprKind::Perform(expr) => Box::new(lambda::ExprKind::Perform(expr.transform(context))),
+            ExprKind::Handle(handle) => Box::new(lambda::ExprKind::Handle(
+                handle.effect.clone(),
+                handle.body.transform(context),
+                handle.handler.transform(context),
+                handle.effectful,
+                Some(handle.handler.span.clone()),
+            )),
             ExprKind::Error => unreachable!(),
         }
     }
@@ -472,16 +598,33 @@ impl Transform for (Qualified, LetDecl<Type<Real>>) {
             .1
             .body
             .iter()
-            .map(|x| (x.expr.clone(), x.patterns.clone()))
+            .flat_map(|x| {
+                pattern::alternatives(&x.patterns)
+                    .into_iter()
+                    .map(|row| (x.expr.clone(), row))
+            })
             .unzip();
 
+        context.take_instances();
+
         let expr = compile_match_with_names(&mut context, new_names.clone(), patterns, actions);
 
+        // Constants are evaluated in order, so they have to come after the dictionaries that
+        // they use.
+        let instances = context.take_instances();
+
+        let constants = self.1.constants.clone().map(|mut constants| {
+            constants.extend(instances.into_iter().map(|x| (x, Default::default())));
+            constants
+        });
+
         let mut upwards = context.drain_upwards();
 
         if upwards.is_empty() {
             lambda::LetDecl {
                 name: self.0.clone(),
+                span: Some(self.1.span.clone()),
+                visibility: self.1.visibility.clone(),
                 body: binders
                     .into_iter()
                     .chain(new_names)
@@ -489,13 +632,15 @@ impl Transform for (Qualified, LetDecl<Type<Real>>) {
                         Box::new(lambda::ExprKind::Lambda(vec![name], acc))
                     }),
                 is_in_source_code: true,
-                constants: self.1.constants.clone(),
+                constants: constants.clone(),
             }
         } else {
             upwards.push(Stmt::Expr(expr));
 
             lambda::LetDecl {
                 name: self.0.clone(),
+                span: Some(self.1.span.clone()),
+                visibility: self.1.visibility.clone(),
                 body: binders
                     .into_iter()
                     .chain(new_names)
@@ -503,7 +648,7 @@ impl Transform for (Qualified, LetDecl<Type<Real>>) {
                         Box::new(lambda::ExprKind::Lambda(vec![name], acc))
                     }),
                 is_in_source_code: true,
-                constants: self.1.constants.clone(),
+                constants,
             }
         }
     }
@@ -605,7 +750,11 @@ impl Transform for Program<Type<Real>> {
                 .externals
                 .clone()
                 .into_iter()
-                .map(|(x, y)| (x, y.binding.clone()))
+                .map(|(name, external)| lambda::ExternalDecl {
+                    name,
+                    binding: external.binding,
+                    span: external.span,
+                })
                 .collect(),
             definitions,
             commands: self.commands.clone(),
@@ -626,9 +775,11 @@ impl Transform for Programs {
 
         for (i, program) in self.0.iter().enumerate() {
             for (name, external) in &program.externals {
-                programs[i]
-                    .externals
-                    .push((name.clone(), external.binding.clone()));
+                programs[i].externals.push(lambda::ExternalDecl {
+                    name: name.clone(),
+                    binding: external.binding.clone(),
+                    span: external.span.clone(),
+                });
             }
 
             programs[i].commands.extend(program.commands.clone());
@@ -650,6 +801,32 @@ impl Transform for Programs {
                     .lets
                     .push((name.clone(), derive_let_from_constructor(name, names, def)))
             }
+
+            // The requests are only built by the runtime, so their constructors are not defined
+            // as functions.
+            if program.types.contains_key(&request_type()) {
+                let def = ConsDef::Heavy(request_type(), 0, 1);
+                context.add_constructor(return_constructor(), def.clone(), 1);
+                programs[i]
+                    .definitions
+                    .insert(return_constructor(), (def, 1));
+            }
+
+            for (name, effect) in &program.effects {
+                for (id, (operation, size)) in effect.operations.iter().enumerate() {
+                    let def = ConsDef::Heavy(name.clone(), id + 1, size + 1);
+                    context.add_constructor(operation.clone(), def.clone(), size + 1);
+
+                    // Kept with the other definitions so programs that were compiled before can
+                    // register them again.
+                    programs[i]
+                        .definitions
+                        .insert(operation.clone(), (def, size + 1));
+
+                    let let_decl = derive_let_from_operation(context, name, id, operation, *size);
+                    programs[i].lets.push((operation.clone(), let_decl));
+                }
+            }
         }
 
         for (i, program) in self.0.iter().enumerate() {
@@ -657,12 +834,152 @@ impl Transform for Programs {
                 let let_expr = (name.clone(), type_expr.clone()).transform(context);
                 programs[i].lets.push((name.clone(), let_expr));
             }
+
+            for decl in program.traits.values() {
+                programs[i]
+                    .lets
+                    .extend(derive_lets_from_trait(context, decl));
+            }
+
+            for decl in program.instances.values() {
+                let let_decl = derive_let_from_instance(context, decl);
+                programs[i].lets.push((decl.name.clone(), let_decl));
+            }
         }
 
         programs
     }
 }
 
+/// Each method of a trait turns into a function that takes the method out of the dictionary.
+/// Dictionaries are tuples with the dictionaries of the super traits followed by the methods.
+fn derive_lets_from_trait(
+    context: &mut Context,
+    decl: &TraitDecl,
+) -> Vec<(Qualified, lambda::LetDecl)> {
+    decl.methods
+        .iter()
+        .enumerate()
+        .map(|(i, method)| {
+            let dictionary = context.new_var("dict".to_string());
+
+            let body = Box::new(lambda::ExprKind::Lambda(
+                vec![dictionary.clone()],
+                Box::new(lambda::ExprKind::Access(
+                    Box::new(lambda::ExprKind::Variable(dictionary)),
+                    decl.supers + i,
+                )),
+            ));
+
+            (
+                method.clone(),
+                lambda::LetDecl {
+                    name: method.clone(),
+                    span: None,
+                    visibility: Visibility::Private,
+                    body,
+                    constants: None,
+                    is_in_source_code: false,
+                },
+            )
+        })
+        .collect()
+}
+
+/// The dictionary of an instance is a function of the dictionaries of its context, or a constant
+/// if it has no context.
+fn derive_let_from_instance(context: &mut Context, decl: &InstanceDecl) -> lambda::LetDecl {
+    let mut context = decl
+        .context
+        .iter()
+        .fold(context.clone(), |acc, name| acc.add_var_local(name.clone()));
+
+    context.take_instances();
+
+    let mut elements = decl
+        .supers
+        .iter()
+        .map(|dictionary| translate_dictionary(&mut context, dictionary))
+        .collect::<Vec<_>>();
+
+    for method in &decl.methods {
+        elements.push(decl.context.iter().fold(
+            Box::new(lambda::ExprKind::Function(method.clone())),
+            |acc, name| {
+                let arg = Box::new(lambda::ExprKind::Variable(context.find_var(name.clone())));
+                Box::new(lambda::ExprKind::Application(acc, vec![arg], None))
+            },
+        ));
+    }
+
+    let body = Box::new(lambda::ExprKind::Tuple(elements));
+
+    let constants = if decl.context.is_empty() {
+        let mut dependencies = context.take_instances();
+        dependencies.extend(decl.methods.iter().cloned());
+
+        Some(
+            dependencies
+                .into_iter()
+                .map(|x| (x, Default::default()))
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    lambda::LetDecl {
+        name: decl.name.clone(),
+        span: None,
+        visibility: Visibility::Private,
+        body: decl.context.iter().rfold(body, |acc, name| {
+            Box::new(lambda::ExprKind::Lambda(
+                vec![context.find_var(name.clone())],
+                acc,
+            ))
+        }),
+        constants,
+        is_in_source_code: false,
+    }
+}
+
+/// Each operation of an effect turns into a function that creates a computation that sends the
+/// request of the operation. Requests have the same layout of the constructor of the operation
+/// without the continuation.
+fn derive_let_from_operation(
+    context: &mut Context,
+    effect: &Qualified,
+    id: usize,
+    name: &Qualified,
+    size: usize,
+) -> lambda::LetDecl {
+    let names: Vec<_> = (0..size)
+        .map(|_| context.new_var("v".to_string()))
+        .collect();
+
+    let body = Box::new(lambda::ExprKind::Computation(Box::new(
+        lambda::ExprKind::Request(
+            effect.clone(),
+            id + 1,
+            names
+                .iter()
+                .map(|x| Box::new(lambda::ExprKind::Variable(x.clone())))
+                .collect(),
+        ),
+    )));
+
+    lambda::LetDecl {
+        name: name.clone(),
+        span: None,
+        visibility: Visibility::Private,
+        body: names.into_iter().rfold(body, |acc, name| {
+            Box::new(lambda::ExprKind::Lambda(vec![name], acc))
+        }),
+        constants: None,
+        is_in_source_code: false,
+    }
+}
+
 fn derive_let_from_constructor(
     name: Qualified,
     names: Vec<Symbol>,
@@ -690,6 +1007,8 @@ fn derive_let_from_constructor(
 
     lambda::LetDecl {
         name: name.clone(),
+        span: None,
+        visibility: Visibility::Private,
         body: names.into_iter().rfold(body, |acc, name| {
             Box::new(lambda::ExprKind::Lambda(vec![name], acc))
         }),
@@ -1,8 +1,10 @@
+use std::collections::HashMap;
+
 use vulpi_intern::Symbol;
 use vulpi_syntax::{
     lambda::LetDecl,
     lambda::{self, Program},
-    r#abstract::Qualified,
+    r#abstract::{Qualified, Visibility},
 };
 
 pub fn accumulate_lambda_nodes<'a>(
@@ -31,20 +33,29 @@ pub fn create_big_lambda<'a>(
     }
 }
 
-pub fn uncurry_program(program: &mut Program) {
+/// Creates an uncurried copy of every function of the program and returns the name and the number
+/// of parameters of the copies.
+pub fn uncurry_program(program: &mut Program) -> HashMap<Qualified, (Qualified, usize)> {
     let mut new_lets = vec![];
+    let mut arities = HashMap::new();
 
     for (name, let_) in &mut program.lets {
         if let Some((params, body)) = create_big_lambda(&mut let_.body) {
+            let original = name.clone();
+
             let name = Qualified {
                 path: name.path.clone(),
                 name: Symbol::intern(&format!("{}.uncurried", name.name.get())),
             };
 
+            arities.insert(original, (name.clone(), params.len()));
+
             new_lets.push((
                 name.clone(),
                 LetDecl {
                     name: name.clone(),
+                    span: let_.span.clone(),
+                    visibility: Visibility::Private,
                     body: Box::new(lambda::ExprKind::Lambda(
                         params.clone(),
                         Box::new(body.clone()),
@@ -61,15 +72,57 @@ pub fn uncurry_program(program: &mut Program) {
                     .map(lambda::ExprKind::Variable)
                     .map(Box::new)
                     .collect(),
+                None,
             );
         }
     }
 
     program.lets.extend(new_lets);
+
+    arities
+}
+
+/// The function and the arguments of nested applications, from the first argument to the last.
+fn spine(expr: &lambda::ExprKind) -> (&lambda::ExprKind, Vec<lambda::Expr>) {
+    match expr {
+        lambda::ExprKind::Application(func, args, _) => {
+            let (func, mut spine_args) = spine(func);
+            spine_args.extend(args.iter().cloned());
+            (func, spine_args)
+        }
+        _ => (expr, vec![]),
+    }
+}
+
+/// Replaces the applications of a curried function to all of its arguments by a call to its
+/// uncurried copy. Recursive functions are not inlined, so otherwise they would call themselves
+/// through the curried wrapper and the tail calls would not be found.
+pub fn saturate(expr: &mut lambda::ExprKind, arities: &HashMap<Qualified, (Qualified, usize)>) {
+    let lambda::ExprKind::Application(_, _, span) = expr else {
+        return;
+    };
+
+    let span = span.clone();
+    let (func, args) = spine(expr);
+
+    let lambda::ExprKind::Function(name) = func else {
+        return;
+    };
+
+    if let Some((uncurried, arity)) = arities.get(name) {
+        if *arity == args.len() {
+            let func = Box::new(lambda::ExprKind::Function(uncurried.clone()));
+            *expr = lambda::ExprKind::Application(func, args, span);
+        }
+    }
 }
 
 pub fn uncurry(programs: &mut Vec<Program>) {
-    for program in programs {
-        uncurry_program(program);
+    let mut arities = HashMap::new();
+
+    for program in programs.iter_mut() {
+        arities.extend(uncurry_program(program));
     }
+
+    crate::inline::traverse_programs(programs, |expr| saturate(expr, &arities));
 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+//! Errors of the backends that can't run every program, like the C backend and the interpreter.
+//! They are reported before the code is generated, so nothing fails only when it runs.
+
+use vulpi_intern::Symbol;
+use vulpi_location::Span;
+use vulpi_report::{IntoDiagnostic, Text};
+use vulpi_syntax::lambda::{ExprKind, Program};
+
+use crate::perceus::children;
+
+pub enum UnsupportedKind {
+    /// Handlers and the computations that they run.
+    Effects,
+
+    /// An external whose binding has no implementation in the backend.
+    External(Symbol),
+}
+
+pub struct Unsupported {
+    /// The name of the backend, like `the C backend`.
+    pub backend: &'static str,
+    pub span: Span,
+    pub kind: UnsupportedKind,
+}
+
+impl IntoDiagnostic for Unsupported {
+    fn code(&self) -> Option<usize> {
+        let code = match self.kind {
+            UnsupportedKind::Effects => 400,
+            UnsupportedKind::External(_) => 401,
+        };
+
+        Some(code)
+    }
+
+    fn message(&self) -> Text {
+        match &self.kind {
+            UnsupportedKind::Effects => format!("effects are not supported by {}", self.backend),
+            UnsupportedKind::External(binding) => format!(
+                "the external '{}' is not supported by {}",
+                binding.get(),
+                self.backend
+            ),
+        }
+        .into()
+    }
+
+    fn severity(&self) -> vulpi_report::Severity {
+        vulpi_report::Severity::Error
+    }
+
+    fn location(&self) -> Span {
+        self.span.clone()
+    }
+}
+
+/// If an expression uses effects.
+fn uses_effects(expr: &ExprKind) -> bool {
+    match expr {
+        ExprKind::Handle(_, _, _, _, _)
+        | ExprKind::Computation(_)
+        | ExprKind::Perform(_)
+        | ExprKind::Request(_, _, _) => true,
+        _ => children(expr).into_iter().any(|child| uses_effects(child)),
+    }
+}
+
+/// The place of the first handler inside of an expression.
+fn handler(expr: &ExprKind) -> Option<&Span> {
+    match expr {
+        ExprKind::Handle(_, _, _, _, Some(span)) => Some(span),
+        _ => children(expr).into_iter().find_map(|child| handler(child)),
+    }
+}
+
+/// Reports the declarations of the source code that use effects, for the backends that can't run
+/// them. Each declaration is reported once, at its first handler or at its name. The operations of
+/// the effects are made by the compiler, and they are only used by declarations that are reported.
+pub fn effects_in(programs: &[Program], backend: &'static str) -> Vec<Unsupported> {
+    programs
+        .iter()
+        .flat_map(|program| &program.lets)
+        .filter_map(|(_, decl)| {
+            if !uses_effects(&decl.body) {
+                return None;
+            }
+
+            let span = handler(&decl.body).or(decl.span.as_ref())?;
+
+            Some(Unsupported {
+                backend,
+                span: span.clone(),
+                kind: UnsupportedKind::Effects,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,280 @@
+//! Generation of ES modules. Every module of the project becomes a `.mjs` file that exports its
+//! public declarations and imports the declarations of the other modules that it uses, so the
+//! output can be tree shaken by bundlers and used from JavaScript written by hand. The code of the
+//! `#javascript` commands and the runtime of effects go to a module that all of them import.
+
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use resast::{
+    decl::{
+        Decl, ExportSpecifier, ImportSpecifier, ModExport, ModImport, NamedExportDecl,
+        NormalImportSpec,
+    },
+    expr::{Lit, StringLit},
+    pat::Pat,
+    Ident, Program, ProgramPart,
+};
+use vulpi_intern::Symbol;
+use vulpi_ir::inline;
+use vulpi_location::Span;
+use vulpi_syntax::{
+    lambda,
+    r#abstract::{Qualified, Visibility},
+};
+
+use crate::{commands, order, tail_call::TRAMPOLINE_RUNTIME, Context, Transform, EFFECTS_RUNTIME};
+
+/// The file of the module with the code that is shared by all the other modules.
+pub const RUNTIME: &str = "runtime.mjs";
+
+/// The file of a module relative to the output directory, like `Data/List.mjs` for `Data.List`.
+pub fn file(module: &Symbol) -> String {
+    format!("{}.mjs", module.get().replace('.', "/"))
+}
+
+/// The specifier that imports the file `to` from the file `from`. Both of them are relative to
+/// the output directory.
+fn specifier(from: &str, to: &str) -> String {
+    let from: Vec<_> = from.split('/').collect();
+    let to: Vec<_> = to.split('/').collect();
+
+    let dir = &from[..from.len() - 1];
+    let common = dir
+        .iter()
+        .zip(&to[..to.len() - 1])
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let prefix = if common == dir.len() {
+        "./".to_string()
+    } else {
+        "../".repeat(dir.len() - common)
+    };
+
+    format!("{prefix}{}", to[common..].join("/"))
+}
+
+/// The name that a public declaration is exported with. Inside of the modules, the declarations
+/// keep their mangled names, that always have a `$`, so they never collide with these.
+fn exported_name(name: &Qualified) -> String {
+    name.name.get().replace('?', "INT").replace('!', "BANG")
+}
+
+/// Imports the `(imported, local)` pairs of names from a file.
+fn import<'a>(names: Vec<(String, String)>, source: String) -> ProgramPart<'a> {
+    let specifiers = names
+        .into_iter()
+        .map(|(imported, local)| NormalImportSpec {
+            local: Ident::new(local),
+            imported: Ident::new(imported),
+        })
+        .collect();
+
+    ProgramPart::Decl(Decl::Import(Box::new(ModImport {
+        specifiers: vec![ImportSpecifier::Normal(specifiers)],
+        source: Lit::String(StringLit::Double(Cow::Owned(source))),
+    })))
+}
+
+/// Exports the `(local, exported)` pairs of names.
+fn export<'a>(names: Vec<(String, String)>) -> ProgramPart<'a> {
+    let specifiers = names
+        .into_iter()
+        .map(|(local, exported)| ExportSpecifier {
+            local: Ident::new(local),
+            exported: Ident::new(exported),
+        })
+        .collect();
+
+    ProgramPart::Decl(Decl::Export(Box::new(ModExport::Named(
+        NamedExportDecl::Specifier(specifiers, None),
+    ))))
+}
+
+/// The names of the functions and variables declared at the top level.
+fn declared_names(parts: &[ProgramPart]) -> Vec<String> {
+    let mut names = vec![];
+
+    for part in parts {
+        match part {
+            ProgramPart::Decl(Decl::Func(func)) => {
+                names.extend(func.id.as_ref().map(|id| id.name.to_string()));
+            }
+            ProgramPart::Decl(Decl::Var(_, decls)) => {
+                for decl in decls {
+                    if let Pat::Ident(id) = &decl.id {
+                        names.push(id.name.to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    names
+}
+
+/// The declarations of other modules that are used by a program, in the order that they appear.
+fn imported(
+    module: &Symbol,
+    program: &mut lambda::Program,
+    owners: &HashMap<Qualified, Symbol>,
+) -> Vec<Qualified> {
+    let found = RefCell::new(vec![]);
+
+    let visit = Rc::new(|expr: &mut lambda::ExprKind| {
+        if let lambda::ExprKind::Function(name) | lambda::ExprKind::Constructor(name) = expr {
+            let foreign = owners.get(name).is_some_and(|owner| owner != module);
+
+            if foreign && !found.borrow().contains(name) {
+                found.borrow_mut().push(name.clone());
+            }
+        }
+    });
+
+    for (_, decl) in &mut program.lets {
+        inline::traverse(&mut decl.body, visit.clone());
+    }
+
+    drop(visit);
+    found.into_inner()
+}
+
+/// A generated module.
+pub struct Module<'a> {
+    /// The name of the Vulpi module, like `Data.List`.
+    pub name: Symbol,
+    pub program: Program<'a>,
+
+    /// The spans of the marked identifiers, when the code is generated with a source map.
+    pub spans: Vec<Span>,
+}
+
+pub struct Output<'a> {
+    /// The module that goes in the [RUNTIME] file.
+    pub runtime: Program<'a>,
+    pub modules: Vec<Module<'a>>,
+}
+
+/// The lowered programs of a project together with the names of their modules.
+pub struct Modules(pub Vec<(Symbol, lambda::Program)>);
+
+impl Transform for Modules {
+    type Out<'a> = Output<'a>;
+
+    fn transform<'a>(self, ctx: &mut Context<'a>) -> Self::Out<'a> {
+        let mut programs = self.0;
+        let mut owners = HashMap::new();
+
+        for (module, program) in &programs {
+            for external in &program.externals {
+                ctx.externals
+                    .insert(external.name.clone(), external.binding.clone());
+            }
+
+            for (name, _) in &program.lets {
+                owners.insert(name.clone(), module.clone());
+            }
+        }
+
+        let mut runtime = commands(programs.iter().map(|(_, program)| program));
+
+        let imports: Vec<_> = programs
+            .iter_mut()
+            .map(|(module, program)| imported(module, program, &owners))
+            .collect();
+
+        // Private declarations that are used by other modules are exported with their mangled
+        // names.
+        let used: HashSet<_> = imports.iter().flatten().cloned().collect();
+
+        let mut generated = vec![];
+
+        for ((module, program), imports) in programs.into_iter().zip(imports) {
+            let mut exports = vec![];
+
+            for (name, decl) in &program.lets {
+                if decl.visibility == Visibility::Public {
+                    exports.push((name.mangle(), exported_name(name)));
+                }
+
+                if used.contains(name) {
+                    exports.push((name.mangle(), name.mangle()));
+                }
+            }
+
+            let code = order(program.transform(ctx));
+            let spans = ctx.take_spans();
+
+            generated.push((module, imports, exports, code, spans));
+        }
+
+        if ctx.uses_effects {
+            let effects = ressa::Parser::new(EFFECTS_RUNTIME).unwrap();
+            runtime.extend(effects.flatten());
+        }
+
+        if ctx.uses_trampoline {
+            let trampoline = ressa::Parser::new(TRAMPOLINE_RUNTIME).unwrap();
+            runtime.extend(trampoline.flatten());
+        }
+
+        let shared = declared_names(&runtime);
+
+        if !shared.is_empty() {
+            let names = shared.iter().map(|x| (x.clone(), x.clone())).collect();
+            runtime.push(export(names));
+        }
+
+        let modules = generated
+            .into_iter()
+            .map(|(name, imports, exports, code, spans)| {
+                let path = file(&name);
+                let mut parts = vec![];
+
+                if !shared.is_empty() {
+                    let names = shared.iter().map(|x| (x.clone(), x.clone())).collect();
+                    parts.push(import(names, specifier(&path, RUNTIME)));
+                }
+
+                let mut sources: Vec<(Symbol, Vec<_>)> = vec![];
+
+                for name in imports {
+                    let owner = &owners[&name];
+                    let pair = (name.mangle(), name.mangle());
+
+                    match sources.iter_mut().find(|(source, _)| source == owner) {
+                        Some((_, names)) => names.push(pair),
+                        None => sources.push((owner.clone(), vec![pair])),
+                    }
+                }
+
+                for (source, names) in sources {
+                    parts.push(import(names, specifier(&path, &file(&source))));
+                }
+
+                parts.extend(code);
+
+                if !exports.is_empty() {
+                    parts.push(export(exports));
+                }
+
+                Module {
+                    name,
+                    program: Program::Mod(parts),
+                    spans,
+                }
+            })
+            .collect();
+
+        Output {
+            runtime: Program::Mod(runtime),
+            modules,
+        }
+    }
+}
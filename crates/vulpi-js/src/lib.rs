@@ -12,15 +12,124 @@ use vulpi_intern::Symbol;
 use vulpi_location::Span;
 use vulpi_syntax::{elaborated::LiteralKind, lambda, r#abstract::Qualified};
 
+pub mod esm;
+pub mod source_map;
+mod tail_call;
+
+/// The runtime of effects. Computations are generator functions that yield the requests of the
+/// operations, and handlers drive them until they finish or send a request of the handled effect.
+///
+/// Each resumption with `handle k () with h` runs the rest of the computation inside of the
+/// generator of the handler, so the generators nest once per handled operation. A computation that
+/// performs more operations than the stack is deep, like a `State` handler around a recursion of a
+/// hundred thousand steps, overflows the stack of Node.js.
+const EFFECTS_RUNTIME: &str = r#"
+function $request(effect, tag, args) {
+    return { effect: effect, tag: tag, args: args };
+}
+
+function* $resume(generator, input) {
+    let state = generator.next(input);
+    while (!state.done) {
+        state = generator.next(yield state.value);
+    }
+    return state.value;
+}
+
+function $handle(effect, body, handler, effectful) {
+    return function* () {
+        const generator = body();
+        let state = generator.next();
+        while (!state.done && state.value.effect !== effect) {
+            state = generator.next(yield state.value);
+        }
+        let request;
+        if (state.done) {
+            request = [state.value];
+            request.tag = 0;
+        } else {
+            const cont = function (value) {
+                return function* () {
+                    return yield* $resume(generator, value);
+                };
+            };
+            request = state.value.args.concat([cont]);
+            request.tag = state.value.tag;
+        }
+        const result = handler(request);
+        return effectful ? yield* result() : result;
+    };
+}
+
+function $run(computation) {
+    const state = computation().next();
+    if (!state.done) {
+        throw new Error("unhandled effect " + state.value.effect);
+    }
+    return state.value;
+}
+"#;
+
 /// The context used to generate new variable names and other things.
 #[derive(Default, Clone)]
 pub struct Context<'a> {
     upwards: Vec<Stmt<'a>>,
     scope: Vec<usize>,
     externals: HashMap<Qualified, Symbol>,
+
+    /// If the current function is a generator, so computations can be performed with `yield*`.
+    in_computation: bool,
+    uses_effects: bool,
+
+    tail: tail_call::State,
+    uses_trampoline: bool,
+
+    /// The spans of the marked identifiers, when the code is generated with a source map.
+    spans: Option<Vec<Span>>,
 }
 
 impl<'a> Context<'a> {
+    /// Creates a context that marks the generated code with the places in the source code, so
+    /// the written program has to go through [source_map::extract].
+    pub fn with_source_map() -> Self {
+        Self {
+            spans: Some(vec![]),
+            ..Default::default()
+        }
+    }
+
+    /// The spans of the marks that were put in the generated code.
+    pub fn take_spans(&mut self) -> Vec<Span> {
+        self.spans.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Creates an identifier that is marked with a span, if there's a source map.
+    fn marked_ident(&mut self, name: String, span: Option<Span>) -> Ident<'a> {
+        match (&mut self.spans, span) {
+            (Some(spans), Some(span)) => {
+                let name = source_map::mark(&name, spans.len());
+                spans.push(span);
+                Ident::new(name)
+            }
+            _ => Ident::new(name),
+        }
+    }
+
+    /// Marks the leftmost identifier of an expression, that is where a call starts.
+    fn mark(&mut self, mut expr: &mut Expr<'a>, span: Option<Span>) {
+        loop {
+            match expr {
+                Expr::Call(call) => expr = &mut call.callee,
+                Expr::Member(member) => expr = &mut member.object,
+                Expr::Ident(ident) => {
+                    *ident = self.marked_ident(ident.name.to_string(), span);
+                    return;
+                }
+                _ => return,
+            }
+        }
+    }
+
     pub fn add_upwards(&mut self, stmt: Stmt<'a>) {
         self.upwards.push(stmt);
     }
@@ -39,6 +148,38 @@ impl<'a> Context<'a> {
         self.scope.pop();
         result
     }
+
+    /// Runs a function in the body of a function that is a generator or not.
+    pub fn function_scope<F, T>(&mut self, generator: bool, f: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        let in_computation = std::mem::replace(&mut self.in_computation, generator);
+        let suspended = self.tail.suspend();
+        let result = self.scope(f);
+        self.tail.resume(suspended);
+        self.in_computation = in_computation;
+        result
+    }
+}
+
+fn call<'a>(name: &str, arguments: Vec<Expr<'a>>) -> Expr<'a> {
+    Expr::Call(CallExpr {
+        callee: Box::new(Expr::Ident(Ident::new(name.to_string()))),
+        arguments,
+    })
+}
+
+fn effect_name<'a>(effect: Qualified) -> Expr<'a> {
+    Expr::Lit(Lit::String(StringLit::Double(Cow::Owned(effect.mangle()))))
+}
+
+/// Yield expressions are wrapped in parenthesis because they have the lowest precedence.
+fn wrapped_yield(argument: Expr<'_>, delegate: bool) -> Expr<'_> {
+    Expr::Sequence(vec![Expr::Yield(YieldExpr {
+        argument: Some(Box::new(argument)),
+        delegate,
+    })])
 }
 
 pub trait Transform {
@@ -91,6 +232,8 @@ impl Transform for lambda::Stmt {
                 let e = *e.transform(ctx);
                 Stmt::Expr(e)
             }
+            // The garbage collector of the engine manages the memory.
+            _ => Stmt::Empty,
         }
     }
 }
@@ -107,7 +250,7 @@ impl Transform for (lambda::TagType, lambda::Case) {
             (lambda::TagType::Number(id), lambda::Case::Constructor(_, _)) => Expr::Lit(resast::expr::Lit::Number(
                 Cow::Owned(id.to_string()),
             )),
-            (lambda::TagType::Number(_), lambda::Case::Literal(l)) => match &*l {
+            (lambda::TagType::Number(_) | lambda::TagType::None, lambda::Case::Literal(l)) => match &*l {
                 LiteralKind::String(x) => Expr::Lit(resast::expr::Lit::String(
                     resast::expr::StringLit::Single(Cow::Owned(x.get())),
                 )),
@@ -131,9 +274,12 @@ impl Transform for lambda::ExprKind {
     type Out<'a> = Expr<'a>;
 
     fn transform<'a>(self, ctx: &mut Context<'a>) -> Self::Out<'a> {
+        // Only the expressions that pass the result along are in tail position too.
+        let tail = std::mem::take(&mut ctx.tail.in_tail);
+
         match self {
             lambda::ExprKind::Lambda(symbols, expr) => {
-                let (result, mut upwards) = ctx.scope(|ctx| {
+                let (result, mut upwards) = ctx.function_scope(false, |ctx| {
                     let result = *expr.transform(ctx);
                     (result, ctx.take_upwards())
                 });
@@ -164,13 +310,32 @@ impl Transform for lambda::ExprKind {
                     })
                 }
             }
-            lambda::ExprKind::Application(callee, args) => {
-                let callee = *callee.transform(ctx);
-                let args = args.transform(ctx);
+            lambda::ExprKind::Application(callee, args, span) => {
+                let name = match &*callee {
+                    lambda::ExprKind::Function(name) if tail => Some(name.clone()),
+                    _ => None,
+                };
+
+                let mut callee = *callee.transform(ctx);
+                let mut args: Vec<_> = args.transform(ctx).into_iter().map(|x| *x).collect();
+
+                if let Some(name) = name {
+                    let mut upwards = vec![];
+
+                    match ctx.tail.call(&name, args, &mut upwards) {
+                        Ok(call) => {
+                            ctx.upwards.extend(upwards);
+                            return call;
+                        }
+                        Err(unchanged) => args = unchanged,
+                    }
+                }
+
+                ctx.mark(&mut callee, span);
 
                 Expr::Call(CallExpr {
                     callee: Box::new(callee),
-                    arguments: args.into_iter().map(|x| *x).collect(),
+                    arguments: args,
                 })
             }
             lambda::ExprKind::Variable(name) => Expr::Ident(Ident::new(name.get())),
@@ -182,13 +347,15 @@ impl Transform for lambda::ExprKind {
                     Expr::Ident(Ident::new(x.mangle()))
                 }
             },
-            lambda::ExprKind::Object(id, args) => Expr::Call(CallExpr {
-                callee: Box::new(Expr::Ident(Ident::new("obj".to_string()))),
-                arguments: vec![
-                    Expr::Lit(Lit::Number(Cow::Owned(id.to_string()))),
-                    Expr::Array(args.transform(ctx).into_iter().map(|x| Some(*x)).collect()),
-                ],
-            }),
+            lambda::ExprKind::Object(id, args) | lambda::ExprKind::Reuse(_, id, args) => {
+                Expr::Call(CallExpr {
+                    callee: Box::new(Expr::Ident(Ident::new("obj".to_string()))),
+                    arguments: vec![
+                        Expr::Lit(Lit::Number(Cow::Owned(id.to_string()))),
+                        Expr::Array(args.transform(ctx).into_iter().map(|x| Some(*x)).collect()),
+                    ],
+                })
+            }
             lambda::ExprKind::Projection(field, obj) => Expr::Member(MemberExpr {
                 computed: false,
                 object: Box::new(*obj.transform(ctx)),
@@ -208,6 +375,7 @@ impl Transform for lambda::ExprKind {
                         let statement = statement.transform(ctx);
                         ctx.add_upwards(statement);
                     } else if let lambda::Stmt::Expr(e) = statement {
+                        ctx.tail.in_tail = tail;
                         return *e.transform(ctx);
                     }
                 }
@@ -278,19 +446,23 @@ impl Transform for lambda::ExprKind {
                     switch: lambda::Tree,
                     context: &mut Context<'a>,
                     actions: &[lambda::Expr],
+                    tail: bool,
                 ) -> Stmt<'a> {
                     match switch {
                         lambda::Tree::Leaf(x) => {
                             context.scope(|context| {
-                                    
+                                context.tail.in_tail = tail;
+
                                 let result = actions[x].clone().transform(context);
                                 let mut upwards = context.take_upwards();
 
-                                upwards.push(Stmt::Expr(Expr::Assign(AssignExpr {
-                                    operator: resast::AssignOp::Equal,
-                                    left: AssignLeft::Expr(Box::new(to_set)),
-                                    right: Box::new(*result),
-                                })));
+                                if !std::mem::take(&mut context.tail.jumped) {
+                                    upwards.push(Stmt::Expr(Expr::Assign(AssignExpr {
+                                        operator: resast::AssignOp::Equal,
+                                        left: AssignLeft::Expr(Box::new(to_set)),
+                                        right: Box::new(*result),
+                                    })));
+                                }
 
 
                                 Stmt::Block(resast::stmt::BlockStmt(
@@ -298,7 +470,7 @@ impl Transform for lambda::ExprKind {
                                 ))
                             })
                         }
-                        lambda::Tree::Switch(scrutinee, branches) => {
+                        lambda::Tree::Switch(scrutinee, branches, default) => {
                             let mut compiled_branches = vec![];
                             let mut tests = vec![];
 
@@ -316,6 +488,23 @@ impl Transform for lambda::ExprKind {
                                             tree,
                                             context,
                                             actions,
+                                            tail,
+                                        )),
+                                        ProgramPart::Stmt(Stmt::Break(None)),
+                                    ],
+                                })
+                            }
+
+                            if let Some(tree) = default {
+                                compiled_branches.push(SwitchCase {
+                                    test: None,
+                                    consequent: vec![
+                                        ProgramPart::Stmt(compile_switch(
+                                            to_set.clone(),
+                                            *tree,
+                                            context,
+                                            actions,
+                                            tail,
                                         )),
                                         ProgramPart::Stmt(Stmt::Break(None)),
                                     ],
@@ -335,12 +524,78 @@ impl Transform for lambda::ExprKind {
                     init: None,
                 }]));
 
-                let sttm = compile_switch(Expr::Ident(Ident::new(name.get())), tree, ctx, &actions);
+                let to_set = Expr::Ident(Ident::new(name.get()));
+                let sttm = compile_switch(to_set, tree, ctx, &actions, tail);
 
                 ctx.add_upwards(sttm);
 
                 Expr::Ident(Ident::new(name.get()))
             }
+            lambda::ExprKind::Computation(expr) => {
+                ctx.uses_effects = true;
+
+                let upwards = ctx.function_scope(true, |ctx| {
+                    let result = *expr.transform(ctx);
+                    let mut upwards = ctx.take_upwards();
+                    upwards.push(Stmt::Return(Some(result)));
+                    upwards
+                });
+
+                Expr::Func(Func {
+                    id: None,
+                    params: vec![],
+                    body: FuncBody(upwards.into_iter().map(ProgramPart::Stmt).collect()),
+                    generator: true,
+                    is_async: false,
+                })
+            }
+            lambda::ExprKind::Perform(expr) => {
+                ctx.uses_effects = true;
+
+                let computation = *expr.transform(ctx);
+
+                if ctx.in_computation {
+                    let call = Expr::Call(CallExpr {
+                        callee: Box::new(computation),
+                        arguments: vec![],
+                    });
+                    wrapped_yield(call, true)
+                } else {
+                    call("$run", vec![computation])
+                }
+            }
+            lambda::ExprKind::Handle(effect, body, handler, effectful, _) => {
+                ctx.uses_effects = true;
+
+                let body = *body.transform(ctx);
+                let handler = *handler.transform(ctx);
+
+                call(
+                    "$handle",
+                    vec![
+                        effect_name(effect),
+                        body,
+                        handler,
+                        Expr::Lit(Lit::Boolean(effectful)),
+                    ],
+                )
+            }
+            lambda::ExprKind::Request(effect, tag, args) => {
+                ctx.uses_effects = true;
+
+                let args = args.transform(ctx).into_iter().map(|x| Some(*x)).collect();
+
+                let request = call(
+                    "$request",
+                    vec![
+                        effect_name(effect),
+                        Expr::Lit(Lit::Number(Cow::Owned(tag.to_string()))),
+                        Expr::Array(args),
+                    ],
+                );
+
+                wrapped_yield(request, false)
+            }
         }
     }
 }
@@ -356,9 +611,8 @@ fn get_tag_accessor<'a>(
             object: Box::new(*scrutinee.clone().transform(context)),
             property: Box::new(Expr::Ident(Ident::new("tag".to_string()))),
         }),
-        lambda::TagType::Number(_) => *scrutinee.clone().transform(context),
+        lambda::TagType::Number(_) | lambda::TagType::None => *scrutinee.clone().transform(context),
         lambda::TagType::Size => todo!(),
-        lambda::TagType::None => todo!(),
     }
 }
 
@@ -368,12 +622,34 @@ impl Transform for lambda::LetDecl {
     fn transform<'a>(self, ctx: &mut Context<'a>) -> Self::Out<'a> {
         match *self.body {
             lambda::ExprKind::Lambda(param, body) => {
-                let transform = body.transform(ctx);
-                let mut upwards = ctx.take_upwards();
-                upwards.push(Stmt::Return(Some(*transform)));
+                let (upwards, looped) = ctx.function_scope(false, |ctx| {
+                    ctx.tail.enter(self.name.clone(), param.clone());
+
+                    let transform = body.transform(ctx);
+                    let mut upwards = ctx.take_upwards();
+
+                    if !ctx.tail.jumped {
+                        upwards.push(Stmt::Return(Some(*transform)));
+                    }
+
+                    (upwards, ctx.tail.leave())
+                });
+
+                let upwards = if looped {
+                    tail_call::looped(upwards)
+                } else {
+                    upwards
+                };
+
+                // Trampolined functions are called through an entry with their name.
+                let name = if ctx.tail.is_trampolined(&self.name) {
+                    tail_call::step(&self.name)
+                } else {
+                    self.name.mangle()
+                };
 
                 Decl::Func(Func {
-                    id: Some(Ident::new(self.name.clone().mangle())),
+                    id: Some(ctx.marked_ident(name, self.span)),
                     params: param.iter().map(|x| FuncArg::Pat(pat_ident(x.clone()))).collect(),
                     body: FuncBody(upwards.into_iter().map(ProgramPart::Stmt).collect()),
                     generator: false,
@@ -383,7 +659,7 @@ impl Transform for lambda::LetDecl {
             body => {
                 let body = body.transform(ctx);
                 Decl::Var(VarKind::Let, vec![VarDecl {
-                    id: pat_ident(Symbol::intern(&self.name.clone().mangle())),
+                    id: Pat::Ident(ctx.marked_ident(self.name.mangle(), self.span)),
                     init: Some(body),
                 }])
             }
@@ -392,77 +668,129 @@ impl Transform for lambda::LetDecl {
 }
 
 impl Transform for lambda::Program {
-    type Out<'a> = Vec<(Qualified, Vec<ProgramPart<'a>>, Option<HashMap<Qualified, Span>>)>;
+    type Out<'a> = Vec<Declared<'a>>;
 
-    fn transform<'a>(self, ctx: &mut Context<'a>) -> Self::Out<'a> {
+    fn transform<'a>(mut self, ctx: &mut Context<'a>) -> Self::Out<'a> {
         let mut decls = vec![];
-        
+
+        ctx.tail = tail_call::State::new(&mut self);
+
         for (_, let_decl) in self.lets {
             let name = let_decl.name.clone();
             let hash_map = let_decl.constants.clone();
+
+            let entry = match &*let_decl.body {
+                lambda::ExprKind::Lambda(params, _) if ctx.tail.is_trampolined(&name) => {
+                    Some(tail_call::entry(&name, params))
+                }
+                _ => None,
+            };
+
             let decl = let_decl.transform(ctx);
             let mut new_decls = ctx.take_upwards().into_iter().map(ProgramPart::Stmt).collect::<Vec<_>>();
             new_decls.push(ProgramPart::Decl(decl));
+
+            if let Some(entry) = entry {
+                ctx.uses_trampoline = true;
+                new_decls.push(ProgramPart::Decl(Decl::Func(entry)));
+            }
+
             decls.push((name, new_decls, hash_map));
         }
         
         decls
     }
 }
+/// A declaration together with the statements that it needs and the constants that it uses.
+type Declared<'a> = (
+    Qualified,
+    Vec<ProgramPart<'a>>,
+    Option<HashMap<Qualified, Span>>,
+);
+
+/// Puts the declarations after the constants that they use, because constants are evaluated in
+/// order.
+fn order(declared: Vec<Declared<'_>>) -> Vec<ProgramPart<'_>> {
+    let mut decls = HashMap::new();
+    let mut petgraph = DiGraph::new();
+    let mut nodes = HashMap::new();
+
+    for (name, decl, dependencies) in declared {
+        let from = *nodes
+            .entry(name.clone())
+            .or_insert_with(|| petgraph.add_node(()));
+
+        if let Some(dependencies) = dependencies {
+            for (to_, _) in dependencies {
+                let to = nodes.entry(to_).or_insert_with(|| petgraph.add_node(()));
+                petgraph.add_edge(from, *to, ());
+            }
+        }
+
+        decls.insert(name, decl);
+    }
+
+    let top_ = petgraph::algo::toposort(&petgraph, None).unwrap();
+    let inv_map = nodes.iter().map(|(k, v)| (v, k)).collect::<HashMap<_, _>>();
+
+    top_.iter()
+        .rev()
+        .filter_map(|x| decls.get(inv_map[x]).cloned())
+        .flatten()
+        .collect()
+}
+
+/// The code of the `#javascript` commands of the programs.
+fn commands<'a, 'b>(programs: impl Iterator<Item = &'b lambda::Program>) -> Vec<ProgramPart<'a>> {
+    let mut parts = Vec::new();
+
+    for program in programs {
+        for (result, command) in &program.commands {
+            if command.get() == "javascript" {
+                let js = ressa::Parser::new(result.get_static()).unwrap();
+                for part in js.flatten() {
+                    parts.push(part.clone());
+                }
+            }
+        }
+    }
+
+    parts
+}
+
 pub struct Programs(pub Vec<lambda::Program>);
 
 impl Transform for Programs {
     type Out<'a> = Program<'a>;
 
     fn transform<'a>(self, ctx: &mut Context<'a>) -> Self::Out<'a> {
-        let mut decls = HashMap::new();
-        let mut petgraph = DiGraph::new();
-        let mut nodes = HashMap::new();
-        let mut parts = Vec::new();
-
         for program in &self.0 {
-            for (name, symbol) in &program.externals {
-                ctx.externals.insert(name.clone(), symbol.clone());
+            for external in &program.externals {
+                ctx.externals
+                    .insert(external.name.clone(), external.binding.clone());
             }
         }
 
-        for program in &self.0 {
-            for (result, command) in &program.commands {
-                if command.get() == "javascript" {
-                    let js = ressa::Parser::new(&result.get_static()).unwrap();
-                    for part in js.flatten() {
-                        parts.push(part.clone());
-                    }
-                }
-            }
-        }
-        
-        for program in self.0 {
-            for (name, decl, dependencies) in program.transform(ctx) {
-                let from = nodes.entry(name.clone()).or_insert_with(|| {
-                    petgraph.add_node(())
-                }).clone();
-
-                if let Some(dependencies) = dependencies {
-                    for (to_, _) in dependencies {
-                        let to = nodes.entry(to_.clone()).or_insert_with(|| {
-                            petgraph.add_node(())
-                        });
-                        petgraph.add_edge(from, *to, ());
-                    }
-                }
+        let mut parts = commands(self.0.iter());
 
-                decls.insert(name, decl);
-            } 
-        }
+        let declared = self
+            .0
+            .into_iter()
+            .flat_map(|program| program.transform(ctx))
+            .collect();
 
-        let top_ = petgraph::algo::toposort(&petgraph, None).unwrap();
-        let inv_map = nodes.iter().map(|(k, v)| (v, k)).collect::<HashMap<_, _>>();
+        let ordered_expr = order(declared);
 
-        let ordered_expr = top_.iter().rev().filter_map(|x| {
-            decls.get(&inv_map[x].clone()).cloned()
-        }).flatten().collect::<Vec<_>>();
+        if ctx.uses_effects {
+            let runtime = ressa::Parser::new(EFFECTS_RUNTIME).unwrap();
+            parts.extend(runtime.flatten());
+        }
+
+        if ctx.uses_trampoline {
+            let runtime = ressa::Parser::new(tail_call::TRAMPOLINE_RUNTIME).unwrap();
+            parts.extend(runtime.flatten());
+        }
 
         Program::Script(parts.into_iter().chain(ordered_expr.into_iter()).collect())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,144 @@
+//! Source maps for the generated code. The writer of [resast] does not tell where it writes each
+//! node, so the generator puts a marker with the index of a span in front of the identifiers that
+//! start a call or a declaration, and the written code is scanned for them afterwards. The
+//! mappings are encoded as described in the [Source Map v3] format.
+//!
+//! [Source Map v3]: https://sourcemaps.info/spec.html
+
+use vulpi_location::Span;
+
+/// Surrounds the index of a span inside of an identifier. It cannot appear in the code written
+/// from a program, so the markers are never confused with it.
+pub const MARKER: char = '\u{1}';
+
+/// A place in the generated code that came from a place in the source code. Lines and columns
+/// start at zero and columns are counted in UTF-16 units, like in the format.
+pub struct Mapping {
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+}
+
+/// Marks an identifier with the index of a span.
+pub fn mark(name: &str, index: usize) -> String {
+    format!("{MARKER}{index}{MARKER}{name}")
+}
+
+/// Removes the markers from the written code and returns where each one of them was.
+pub fn extract(code: &str, spans: &[Span]) -> (String, Vec<Mapping>) {
+    let mut result = String::with_capacity(code.len());
+    let mut mappings = vec![];
+
+    let mut line = 0;
+    let mut column = 0;
+
+    let mut chars = code.chars();
+
+    while let Some(char) = chars.next() {
+        match char {
+            MARKER => {
+                let index: String = chars.by_ref().take_while(|x| *x != MARKER).collect();
+
+                if let Some(span) = index.parse().ok().and_then(|x: usize| spans.get(x)) {
+                    mappings.push(Mapping {
+                        line,
+                        column,
+                        span: span.clone(),
+                    });
+                }
+            }
+            '\n' => {
+                result.push(char);
+                line += 1;
+                column = 0;
+            }
+            _ => {
+                result.push(char);
+                column += char.len_utf16();
+            }
+        }
+    }
+
+    (result, mappings)
+}
+
+/// A mapping together with the place that it points to. The source is an index in the list of
+/// sources of the map.
+pub struct Segment {
+    pub line: usize,
+    pub column: usize,
+    pub source: usize,
+    pub source_line: usize,
+    pub source_column: usize,
+}
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Writes a number as a base 64 variable length quantity with the sign in the lowest bit.
+fn vlq(out: &mut String, value: isize) {
+    let mut value = if value < 0 {
+        ((-value as usize) << 1) | 1
+    } else {
+        (value as usize) << 1
+    };
+
+    loop {
+        let mut digit = value & 0b11111;
+        value >>= 5;
+
+        if value > 0 {
+            digit |= 0b100000;
+        }
+
+        out.push(BASE64[digit] as char);
+
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+/// Encodes the `mappings` field of a source map. Every field of a segment is relative to the
+/// same field in the segment before it, and the generated column restarts at each line.
+pub fn encode(mut segments: Vec<Segment>) -> String {
+    segments.sort_by_key(|x| (x.line, x.column));
+
+    let mut out = String::new();
+
+    let mut line = 0;
+    let mut column = 0;
+    let mut source = 0;
+    let mut source_line = 0;
+    let mut source_column = 0;
+
+    for (i, segment) in segments.iter().enumerate() {
+        if segment.line > line {
+            for _ in line..segment.line {
+                out.push(';');
+            }
+
+            line = segment.line;
+            column = 0;
+        } else if i > 0 {
+            out.push(',');
+        }
+
+        vlq(&mut out, segment.column as isize - column as isize);
+        vlq(&mut out, segment.source as isize - source as isize);
+        vlq(
+            &mut out,
+            segment.source_line as isize - source_line as isize,
+        );
+        vlq(
+            &mut out,
+            segment.source_column as isize - source_column as isize,
+        );
+
+        column = segment.column;
+        source = segment.source;
+        source_line = segment.source_line;
+        source_column = segment.source_column;
+    }
+
+    out
+}
@@ -0,0 +1,336 @@
+//! Elimination of tail calls. A function that calls itself in tail position becomes a loop that
+//! assigns the arguments to the parameters and starts again. Functions that call each other in
+//! tail position return a bounce with the next call instead, and a trampoline outside of them
+//! performs the calls until one of them returns a value, so the stack does not grow.
+
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use petgraph::graph::DiGraph;
+use resast::{
+    decl::VarDecl,
+    expr::{AssignExpr, AssignLeft, CallExpr, Expr, NewExpr},
+    pat::Pat,
+    stmt::{BlockStmt, Stmt, WhileStmt},
+    AssignOp, Func, FuncArg, FuncBody, Ident, ProgramPart,
+};
+use vulpi_intern::Symbol;
+use vulpi_ir::inline;
+use vulpi_syntax::{lambda, r#abstract::Qualified};
+
+/// The runtime of the trampoline.
+pub const TRAMPOLINE_RUNTIME: &str = r#"
+function $Bounce(fn, args) {
+    this.fn = fn;
+    this.args = args;
+}
+
+function $trampoline(result) {
+    while (result instanceof $Bounce) {
+        result = result.fn.apply(null, result.args);
+    }
+    return result;
+}
+"#;
+
+/// The tail calls of the functions of a program and of the function that is being generated.
+#[derive(Default, Clone)]
+pub struct State {
+    /// The functions that return bounces, by their number of parameters.
+    trampolined: HashMap<Qualified, usize>,
+
+    /// The functions whose calls to themselves become loops.
+    loops: HashSet<Qualified>,
+
+    /// The function whose body is being generated together with its parameters.
+    current: Option<(Qualified, Vec<Symbol>)>,
+
+    /// If the expression that is being generated is the result of the current function.
+    pub in_tail: bool,
+
+    /// If the last expression in tail position became a jump to the start of the loop, so its
+    /// value must not be used.
+    pub jumped: bool,
+
+    /// If the current function jumps to the start of the loop.
+    looped: bool,
+}
+
+/// The function that was being generated before a function inside of it.
+pub struct Suspended {
+    current: Option<(Qualified, Vec<Symbol>)>,
+    in_tail: bool,
+}
+
+/// The functions of `arities` that are called with all of their arguments in tail position.
+fn tail_calls(
+    expr: &lambda::ExprKind,
+    arities: &HashMap<Qualified, usize>,
+    calls: &mut Vec<Qualified>,
+) {
+    match expr {
+        lambda::ExprKind::Application(func, args, _) => {
+            if let lambda::ExprKind::Function(name) = &**func {
+                if arities.get(name) == Some(&args.len()) {
+                    calls.push(name.clone());
+                }
+            }
+        }
+        lambda::ExprKind::Block(stmts) => {
+            if let Some(lambda::Stmt::Expr(expr)) = stmts.last() {
+                tail_calls(expr, arities, calls);
+            }
+        }
+        lambda::ExprKind::Switch(_, _, actions) => {
+            for action in actions {
+                tail_calls(action, arities, calls);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// If an expression creates functions. They would see the parameters change if the function
+/// that creates them became a loop.
+fn has_closures(expr: &mut lambda::ExprKind) -> bool {
+    let found = Rc::new(Cell::new(false));
+    let inner = found.clone();
+
+    inline::traverse(
+        expr,
+        Rc::new(move |expr: &mut lambda::ExprKind| {
+            if matches!(
+                expr,
+                lambda::ExprKind::Lambda(_, _)
+                    | lambda::ExprKind::Computation(_)
+                    | lambda::ExprKind::Handle(_, _, _, _, _)
+            ) {
+                inner.set(true);
+            }
+        }),
+    );
+
+    found.get()
+}
+
+impl State {
+    /// Finds the functions of a program that call themselves or each other in tail position.
+    pub fn new(program: &mut lambda::Program) -> Self {
+        let mut arities = HashMap::new();
+
+        for (name, decl) in &program.lets {
+            if let lambda::ExprKind::Lambda(params, _) = &*decl.body {
+                arities.insert(name.clone(), params.len());
+            }
+        }
+
+        let mut graph = DiGraph::<Qualified, ()>::new();
+        let mut nodes = HashMap::new();
+
+        for name in arities.keys() {
+            nodes.insert(name.clone(), graph.add_node(name.clone()));
+        }
+
+        let mut closures = HashSet::new();
+
+        for (name, decl) in &mut program.lets {
+            let lambda::ExprKind::Lambda(_, body) = &mut *decl.body else {
+                continue;
+            };
+
+            let mut calls = vec![];
+            tail_calls(body, &arities, &mut calls);
+
+            for call in calls {
+                graph.add_edge(nodes[name], nodes[&call], ());
+            }
+
+            if has_closures(body) {
+                closures.insert(name.clone());
+            }
+        }
+
+        let mut state = State::default();
+
+        for component in petgraph::algo::tarjan_scc(&graph) {
+            let [node] = component[..] else {
+                for node in component {
+                    let name = &graph[node];
+                    state.trampolined.insert(name.clone(), arities[name]);
+                }
+                continue;
+            };
+
+            let name = &graph[node];
+
+            if !graph.contains_edge(node, node) {
+                continue;
+            }
+
+            if closures.contains(name) {
+                state.trampolined.insert(name.clone(), arities[name]);
+            }
+        }
+
+        for node in graph.node_indices() {
+            let name = &graph[node];
+
+            if graph.contains_edge(node, node) && !closures.contains(name) {
+                state.loops.insert(name.clone());
+            }
+        }
+
+        state
+    }
+
+    /// If a function returns bounces, so it has to be called through the trampoline.
+    pub fn is_trampolined(&self, name: &Qualified) -> bool {
+        self.trampolined.contains_key(name)
+    }
+
+    /// Starts the generation of the body of a function.
+    pub fn enter(&mut self, name: Qualified, params: Vec<Symbol>) {
+        self.current = Some((name, params));
+        self.in_tail = true;
+        self.looped = false;
+    }
+
+    /// Ends the generation of the body of a function and returns if it jumps to its start.
+    pub fn leave(&mut self) -> bool {
+        self.current = None;
+        self.in_tail = false;
+        self.jumped = false;
+        std::mem::take(&mut self.looped)
+    }
+
+    /// Forgets the current function while the body of a function inside of it is generated.
+    pub fn suspend(&mut self) -> Suspended {
+        Suspended {
+            current: self.current.take(),
+            in_tail: std::mem::take(&mut self.in_tail),
+        }
+    }
+
+    /// Goes back to the function that was suspended.
+    pub fn resume(&mut self, suspended: Suspended) {
+        self.current = suspended.current;
+        self.in_tail = suspended.in_tail;
+    }
+
+    /// Generates a call in tail position to a function with all of its arguments. A call to the
+    /// current function becomes a jump to its start, that is added to `upwards`, and a call
+    /// between trampolined functions becomes a bounce.
+    pub fn call<'a>(
+        &mut self,
+        func: &Qualified,
+        args: Vec<Expr<'a>>,
+        upwards: &mut Vec<Stmt<'a>>,
+    ) -> Result<Expr<'a>, Vec<Expr<'a>>> {
+        let Some((current, params)) = self.current.clone() else {
+            return Err(args);
+        };
+
+        if *func == current && self.loops.contains(&current) && params.len() == args.len() {
+            let changed: Vec<_> = params
+                .iter()
+                .zip(args)
+                .filter(|(param, arg)| !matches!(arg, Expr::Ident(x) if x.name == param.get()))
+                .collect();
+
+            if let [(param, arg)] = &changed[..] {
+                upwards.push(assign(param.get(), arg.clone()));
+            } else {
+                // The arguments can use the parameters, so all of them are evaluated before the
+                // parameters change.
+                for (param, arg) in &changed {
+                    upwards.push(Stmt::Var(vec![VarDecl {
+                        id: Pat::Ident(Ident::new(next(param))),
+                        init: Some(arg.clone()),
+                    }]));
+                }
+
+                for (param, _) in &changed {
+                    let value = Expr::Ident(Ident::new(next(param)));
+                    upwards.push(assign(param.get(), value));
+                }
+            }
+
+            upwards.push(Stmt::Continue(None));
+
+            self.jumped = true;
+            self.looped = true;
+
+            return Ok(Expr::Ident(Ident::new("undefined".to_string())));
+        }
+
+        if self.is_trampolined(&current) && self.trampolined.get(func) == Some(&args.len()) {
+            return Ok(Expr::New(NewExpr {
+                callee: Box::new(Expr::Ident(Ident::new("$Bounce".to_string()))),
+                arguments: vec![
+                    Expr::Ident(Ident::new(step(func))),
+                    Expr::Array(args.into_iter().map(Some).collect()),
+                ],
+            }));
+        }
+
+        Err(args)
+    }
+}
+
+/// The name of the variable that holds the next value of a parameter.
+fn next(param: &Symbol) -> String {
+    format!("{}$next", param.get())
+}
+
+fn assign(name: String, value: Expr<'_>) -> Stmt<'_> {
+    Stmt::Expr(Expr::Assign(AssignExpr {
+        operator: AssignOp::Equal,
+        left: AssignLeft::Expr(Box::new(Expr::Ident(Ident::new(name)))),
+        right: Box::new(value),
+    }))
+}
+
+/// The name of the function of a trampolined function that returns bounces.
+pub fn step(name: &Qualified) -> String {
+    format!("{}$step", name.mangle())
+}
+
+/// Puts the body of a function that jumps to its start inside of a loop.
+pub fn looped(body: Vec<Stmt<'_>>) -> Vec<Stmt<'_>> {
+    vec![Stmt::While(WhileStmt {
+        test: Expr::Lit(resast::expr::Lit::Boolean(true)),
+        body: Box::new(Stmt::Block(BlockStmt(
+            body.into_iter().map(ProgramPart::Stmt).collect(),
+        ))),
+    })]
+}
+
+/// The function that calls a trampolined function and runs the trampoline over its result.
+pub fn entry<'a>(name: &Qualified, params: &[Symbol]) -> Func<'a> {
+    let call = Expr::Call(CallExpr {
+        callee: Box::new(Expr::Ident(Ident::new(step(name)))),
+        arguments: params
+            .iter()
+            .map(|x| Expr::Ident(Ident::new(x.get())))
+            .collect(),
+    });
+
+    let trampoline = Expr::Call(CallExpr {
+        callee: Box::new(Expr::Ident(Ident::new("$trampoline".to_string()))),
+        arguments: vec![call],
+    });
+
+    Func {
+        id: Some(Ident::new(name.mangle())),
+        params: params
+            .iter()
+            .map(|x| FuncArg::Pat(Pat::Ident(Ident::new(x.get()))))
+            .collect(),
+        body: FuncBody(vec![ProgramPart::Stmt(Stmt::Return(Some(trampoline)))]),
+        generator: false,
+        is_async: false,
+    }
+}
@@ -15,6 +15,12 @@ pub struct Error {
 }
 
 impl IntoDiagnostic for Error {
+    fn code(&self) -> Option<usize> {
+        match self.message {
+            ErrorKind::UnfinishedString => Some(1),
+        }
+    }
+
     fn message(&self) -> vulpi_report::Text {
         match self.message {
             ErrorKind::UnfinishedString => vulpi_report::Text::from("unfinished string literal"),
@@ -86,6 +86,14 @@ pub struct State {
     layout: Vec<usize>,
     lex_state: LexState,
     reporter: Report,
+
+    /// The number of braces that are open inside of each string interpolation that is being
+    /// lexed. A '}' closes the interpolation when there's no brace open.
+    interpolation: Vec<usize>,
+
+    /// The strings of commands and external declarations are code of the target, so they are not
+    /// interpolated.
+    raw_string: bool,
 }
 
 /// The lexer struct that contains the input and the current state. This struct is the entry point
@@ -111,6 +119,8 @@ impl<'a> Lexer<'a> {
                 layout: vec![],
                 lex_state: LexState::Common,
                 reporter,
+                interpolation: vec![],
+                raw_string: false,
             },
         }
     }
@@ -249,7 +259,10 @@ impl<'a> Lexer<'a> {
             "in" => TokenData::In,
             "forall" => TokenData::Forall,
             "_" => TokenData::Wildcard,
-            "external" => TokenData::External,
+            "external" => {
+                self.state.raw_string = true;
+                TokenData::External
+            }
             "trait" => TokenData::Trait,
             "impl" => TokenData::Impl,
             _ => TokenData::LowerIdent,
@@ -278,12 +291,28 @@ impl<'a> Lexer<'a> {
         let result = if let Some(char) = self.advance() {
             match char {
                 '#' => {
+                    self.state.raw_string = true;
                     self.bump();
                     self.accumulate(|char| char.is_ascii_digit());
                     TokenData::Command
                 }
-                '{' => TokenData::LBrace,
-                '}' => TokenData::RBrace,
+                '{' => {
+                    if let Some(depth) = self.state.interpolation.last_mut() {
+                        *depth += 1;
+                    }
+                    TokenData::LBrace
+                }
+                '}' => match self.state.interpolation.last_mut() {
+                    Some(0) => {
+                        self.state.interpolation.pop();
+                        return self.string(TokenData::StringMiddle, TokenData::StringEnd);
+                    }
+                    Some(depth) => {
+                        *depth -= 1;
+                        TokenData::RBrace
+                    }
+                    None => TokenData::RBrace,
+                },
                 '(' => {
                     if let Some(')') = self.peekable.peek() {
                         self.advance();
@@ -377,7 +406,14 @@ impl<'a> Lexer<'a> {
                 ':' => TokenData::Colon,
                 ';' => TokenData::Semicolon,
                 ',' => TokenData::Comma,
-                '.' => TokenData::Dot,
+                '.' => {
+                    if let Some('.') = self.peekable.peek() {
+                        self.advance();
+                        TokenData::DotDot
+                    } else {
+                        TokenData::Dot
+                    }
+                }
                 '0'..='9' => {
                     self.accumulate(|char| char.is_ascii_digit());
                     if let Some('.') = self.peekable.peek() {
@@ -388,7 +424,7 @@ impl<'a> Lexer<'a> {
                         TokenData::Int
                     }
                 }
-                '"' => return self.string(),
+                '"' => return self.string(TokenData::StringStart, TokenData::String),
                 'A'..='Z' => {
                     self.accumulate(is_identifier_char);
                     TokenData::UpperIdent
@@ -27,6 +27,8 @@ impl<'a> Lexer<'a> {
             Some('\\') => '\\',
             Some('\'') => '\'',
             Some('"') => '"',
+            Some('{') => '{',
+            Some('}') => '}',
             _ => return None,
         };
 
@@ -35,7 +37,15 @@ impl<'a> Lexer<'a> {
         Some(result)
     }
 
-    pub(crate) fn string(&mut self) -> (TokenData, Symbol) {
+    /// Parses the text of a string until it ends or until an interpolation starts. The text that
+    /// comes after an interpolation is lexed with [TokenData::StringMiddle] and
+    /// [TokenData::StringEnd] as kinds, so the parser knows where the string finishes.
+    pub(crate) fn string(
+        &mut self,
+        interpolated: TokenData,
+        finished: TokenData,
+    ) -> (TokenData, Symbol) {
+        let raw = std::mem::take(&mut self.state.raw_string);
         let mut string = String::new();
 
         while let Some(c) = self.peekable.peek() {
@@ -49,6 +59,11 @@ impl<'a> Lexer<'a> {
                     }
                 }
                 '"' => break,
+                '{' if !raw => {
+                    self.advance();
+                    self.state.interpolation.push(0);
+                    return (interpolated, Symbol::intern(&string));
+                }
                 _ => {
                     string.push(self.advance().unwrap());
                 }
@@ -57,7 +72,7 @@ impl<'a> Lexer<'a> {
 
         if let Some('"') = self.peekable.peek() {
             self.advance();
-            (TokenData::String, Symbol::intern(&string))
+            (finished, Symbol::intern(&string))
         } else {
             self.report(ErrorKind::UnfinishedString);
             (TokenData::Error, Symbol::intern(&string))
@@ -3,14 +3,15 @@
 
 use std::fmt::Debug;
 
+use serde::{Deserialize, Serialize};
 use vulpi_show::{Show, TreeDisplay};
 
 /// A new-type for a usize. It's used to locate a byte inside a source code.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub struct Byte(pub usize);
 
 /// A span that locates a piece of data inside a source code.
-#[derive(Clone, Default)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Span {
     pub file: FileId,
     pub start: Byte,
@@ -112,5 +113,7 @@ impl<T> Spanned<T> {
 }
 
 /// The identifier of a file.
-#[derive(Clone, Default, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(
+    Clone, Default, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize,
+)]
 pub struct FileId(pub usize);
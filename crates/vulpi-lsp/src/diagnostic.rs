@@ -0,0 +1,46 @@
+//! Conversion of the compiler diagnostics into LSP diagnostics.
+
+use lsp_types::{DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Url};
+use vulpi_report::{codes, Diagnostic, Severity};
+
+use crate::position::to_range;
+
+pub fn to_diagnostic(uri: &Url, content: &str, diagnostic: &Diagnostic) -> lsp_types::Diagnostic {
+    let location = diagnostic.location();
+
+    let severity = match diagnostic.severity() {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Info => DiagnosticSeverity::INFORMATION,
+    };
+
+    let mut message = diagnostic.message().to_string();
+
+    if let Some(hint) = diagnostic.hint() {
+        message.push('\n');
+        message.push_str(&hint.to_string());
+    }
+
+    // Only the markers of the same file can be located because the others have another content.
+    let related = diagnostic
+        .secondary()
+        .into_iter()
+        .filter(|marker| marker.position.file == location.file)
+        .map(|marker| DiagnosticRelatedInformation {
+            location: Location::new(uri.clone(), to_range(content, &marker.position)),
+            message: marker.subtitle.map(|x| x.to_string()).unwrap_or_default(),
+        })
+        .collect::<Vec<_>>();
+
+    lsp_types::Diagnostic {
+        range: to_range(content, &location),
+        severity: Some(severity),
+        code: diagnostic
+            .code()
+            .map(|code| NumberOrString::String(codes::name(code))),
+        source: Some("vulpi".to_string()),
+        message,
+        related_information: (!related.is_empty()).then_some(related),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,390 @@
+//! Language server for Vulpi. It speaks the LSP protocol over stdio and checks the documents on
+//! every change using an in-memory file system, so unsaved buffers never touch the disk.
+
+use std::{
+    any::Any,
+    collections::HashSet,
+    env,
+    error::Error,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        LogMessage, Notification as _, PublishDiagnostics,
+    },
+    request::{GotoDefinition, HoverRequest, Request as _},
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DidSaveTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, InitializeParams, Location, LogMessageParams,
+    MarkupContent, MarkupKind, MessageType, OneOf, PublishDiagnosticsParams, ServerCapabilities,
+    TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use vulpi_build::{
+    manifest::{self, Graph},
+    memory::MemoryFileSystem,
+    Checked, ProjectCompiler,
+};
+use vulpi_intern::Symbol;
+use vulpi_location::{Byte, FileId};
+use vulpi_vfs::FileSystem;
+
+pub mod diagnostic;
+pub mod position;
+
+pub type Result<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
+
+/// The state of the language server.
+pub struct Server {
+    connection: Connection,
+    root: PathBuf,
+    compiler: ProjectCompiler<MemoryFileSystem>,
+
+    /// The source directories of the packages that the project depends on.
+    packages: Vec<(Symbol, PathBuf)>,
+
+    /// Files that received diagnostics in the last check. They need to be cleared in the next one
+    /// if the errors are gone.
+    published: HashSet<PathBuf>,
+
+    /// Result of the last check. It's used to answer hover and go to definition requests.
+    checked: Option<Checked>,
+}
+
+impl Server {
+    pub fn new(connection: Connection, root: PathBuf, name: Symbol) -> Self {
+        let fs = MemoryFileSystem::new(name.clone(), root.clone(), root.join("build"));
+
+        Self {
+            connection,
+            root,
+            compiler: ProjectCompiler {
+                name,
+                fs,
+                reporter: vulpi_report::hash_reporter(),
+            },
+            packages: vec![],
+            published: HashSet::new(),
+            checked: None,
+        }
+    }
+
+    /// Makes the modules of another package available to the project.
+    pub fn add_package(&mut self, name: Symbol, source: PathBuf) {
+        self.compiler.fs.add_package(name.clone(), source.clone());
+        self.packages.push((name, source));
+    }
+
+    /// Main loop of the server. It returns when the client asks for a shutdown.
+    pub fn run(&mut self) -> Result<()> {
+        let receiver = self.connection.receiver.clone();
+
+        for message in &receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+
+                    self.request(request)?;
+                }
+                Message::Notification(notification) => self.notification(notification)?,
+                Message::Response(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn request(&mut self, request: Request) -> Result<()> {
+        let response = match request.method.as_str() {
+            HoverRequest::METHOD => {
+                let params: HoverParams = serde_json::from_value(request.params)?;
+                let hover = self.hover(params.text_document_position_params);
+                Response::new_ok(request.id, hover)
+            }
+            GotoDefinition::METHOD => {
+                let params: GotoDefinitionParams = serde_json::from_value(request.params)?;
+                let definition = self.definition(params.text_document_position_params);
+                Response::new_ok(request.id, definition)
+            }
+            _ => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unknown request '{}'", request.method),
+            ),
+        };
+
+        self.connection.sender.send(Message::Response(response))?;
+
+        Ok(())
+    }
+
+    /// Finds the file and the byte that a LSP position points to.
+    fn locate(&mut self, params: &TextDocumentPositionParams) -> Option<(FileId, String, Byte)> {
+        let path = params.text_document.uri.to_file_path().ok()?;
+        let id = self.compiler.fs.load(path).ok()?;
+        let content = self.compiler.fs.read(id).ok()?;
+        let place = position::to_byte(&content, params.position);
+        Some((id, content, place))
+    }
+
+    /// Shows the type of the innermost expression or binding under the cursor.
+    fn hover(&mut self, params: TextDocumentPositionParams) -> Option<Hover> {
+        let (id, content, place) = self.locate(&params)?;
+        let entry = self.checked.as_ref()?.types.find(id, place)?;
+
+        let value = match &entry.name {
+            Some(name) => format!("```vulpi\n{} : {}\n```", name.name.get(), entry.show()),
+            None => format!("```vulpi\n{}\n```", entry.show()),
+        };
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(position::to_range(&content, &entry.span)),
+        })
+    }
+
+    /// Jumps from the use of a name to the place where it was declared.
+    fn definition(&mut self, params: TextDocumentPositionParams) -> Option<GotoDefinitionResponse> {
+        let (id, _, place) = self.locate(&params)?;
+
+        let names = &self.checked.as_ref()?.names;
+        let (_, name) = names.reference(id, place)?;
+        let span = names.declaration(name)?;
+
+        let uri = Url::from_file_path(self.compiler.fs.path(span.file).ok()?).ok()?;
+        let content = self.compiler.fs.read(span.file).ok()?;
+
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            uri,
+            position::to_range(&content, span),
+        )))
+    }
+
+    fn notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+
+                self.update(&params.text_document.uri, params.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+
+                // We only ask for full synchronization so the last change has the entire text.
+                match params.content_changes.into_iter().last() {
+                    Some(change) => self.update(&params.text_document.uri, change.text),
+                    None => Ok(()),
+                }
+            }
+            DidSaveTextDocument::METHOD => {
+                let params: DidSaveTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+
+                match params.text {
+                    Some(text) => self.update(&params.text_document.uri, text),
+                    None => Ok(()),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+
+                if let Ok(path) = params.text_document.uri.to_file_path() {
+                    let path = self.compiler.fs.get_path(path);
+
+                    // The next check doesn't see the file anymore, so its diagnostics have to go
+                    // now or they would count as errors of the project.
+                    if let Some((id, _)) = self.compiler.fs.files().find(|(_, x)| **x == path) {
+                        self.compiler.reporter.clear(id);
+                    }
+
+                    self.compiler.fs.close(path);
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Replaces the content of a document and checks it again.
+    fn update(&mut self, uri: &Url, text: String) -> Result<()> {
+        let Ok(path) = uri.to_file_path() else {
+            return Ok(());
+        };
+
+        self.compiler.fs.open(path.clone(), text);
+        self.check(path)
+    }
+
+    /// Runs the front-end of the compiler over a document and publishes the diagnostics of every
+    /// file that was loaded by it.
+    fn check(&mut self, path: PathBuf) -> Result<()> {
+        let module = self.module_path(&path);
+
+        for (id, _) in self.compiler.fs.files() {
+            self.compiler.reporter.clear(id);
+        }
+
+        // The compiler still panics on some unfinished features and it should not kill the
+        // entire session, so the panic is shown to the user and the check is dropped.
+        let compiler = &mut self.compiler;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| compiler.check(module, path)));
+
+        self.checked = match result {
+            Ok(checked) => Some(checked),
+            Err(payload) => {
+                self.log_panic(payload)?;
+                None
+            }
+        };
+
+        let mut published = HashSet::new();
+        let mut notifications = vec![];
+
+        for (id, path) in self.compiler.fs.files() {
+            let diagnostics = self.compiler.reporter.diagnostics(id);
+
+            if diagnostics.is_empty() && !self.published.contains(path) {
+                continue;
+            }
+
+            let Ok(uri) = Url::from_file_path(path) else {
+                continue;
+            };
+
+            let Ok(content) = self.compiler.fs.read(id) else {
+                continue;
+            };
+
+            let diagnostics = diagnostics
+                .iter()
+                .map(|x| diagnostic::to_diagnostic(&uri, &content, x))
+                .collect::<Vec<_>>();
+
+            if !diagnostics.is_empty() {
+                published.insert(path.clone());
+            }
+
+            notifications.push(PublishDiagnosticsParams::new(uri, diagnostics, None));
+        }
+
+        self.published = published;
+
+        for params in notifications {
+            let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+            self.connection
+                .sender
+                .send(Message::Notification(notification))?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends the message of a panic of the compiler to the client, so it shows up in its logs
+    /// instead of being lost.
+    fn log_panic(&self, payload: Box<dyn Any + Send>) -> Result<()> {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+
+        let params = LogMessageParams {
+            typ: MessageType::ERROR,
+            message: format!("internal compiler error: {message}"),
+        };
+
+        let notification = Notification::new(LogMessage::METHOD.to_string(), params);
+        self.connection
+            .sender
+            .send(Message::Notification(notification))?;
+
+        Ok(())
+    }
+
+    /// Builds the module path of a file from its place inside of the project, e.g. `Data/List.vp`
+    /// turns into `Package.Data.List`. Files of the dependencies are named after their package.
+    fn module_path(&self, path: &Path) -> vulpi_vfs::Path {
+        let path = self.compiler.fs.get_path(path.to_path_buf());
+
+        let (name, root) = self
+            .packages
+            .iter()
+            .find(|(_, source)| path.starts_with(source))
+            .map(|(name, source)| (name.clone(), source))
+            .unwrap_or((self.compiler.name.clone(), &self.root));
+
+        let relative = path.strip_prefix(root).unwrap_or(&path).with_extension("");
+
+        let mut segments = vec![name];
+
+        segments.extend(
+            relative
+                .components()
+                .map(|x| Symbol::intern(&x.as_os_str().to_string_lossy())),
+        );
+
+        vulpi_vfs::Path { segments }
+    }
+}
+
+/// Starts the language server over stdio. If the workspace folder has a `vulpi.toml`, the package
+/// and its dependencies come from it. Otherwise the package name defaults to the name of the
+/// workspace folder.
+pub fn start(package: Option<String>) -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = serde_json::to_value(ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    })?;
+
+    let params = connection.initialize(capabilities)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    let root = match params.root_uri.and_then(|x| x.to_file_path().ok()) {
+        Some(root) => root,
+        None => env::current_dir()?,
+    };
+
+    let root = root.canonicalize().unwrap_or(root);
+
+    let mut server = if root.join(manifest::FILE_NAME).exists() {
+        let graph = Graph::load(&root).map_err(|err| err.to_string())?;
+
+        let name = Symbol::intern(graph.root.name());
+        let mut server = Server::new(connection, graph.root.source(), name);
+
+        for dependency in &graph.dependencies {
+            server.add_package(Symbol::intern(dependency.name()), dependency.source());
+        }
+
+        server
+    } else {
+        let name = package
+            .or_else(|| root.file_name().map(|x| x.to_string_lossy().to_string()))
+            .unwrap_or_else(|| "Main".to_string());
+
+        Server::new(connection, root, Symbol::intern(&name))
+    };
+
+    server.run()?;
+
+    drop(server);
+    io_threads.join()?;
+
+    Ok(())
+}
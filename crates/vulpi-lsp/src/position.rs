@@ -0,0 +1,47 @@
+//! Conversion between byte offsets and LSP positions. LSP columns are counted in UTF-16 code units
+//! instead of bytes.
+
+use lsp_types::{Position, Range};
+use vulpi_location::{Byte, Span};
+
+pub fn to_position(content: &str, place: Byte) -> Position {
+    let mut place = place.0.min(content.len());
+
+    while !content.is_char_boundary(place) {
+        place -= 1;
+    }
+
+    let before = &content[..place];
+    let line = before.matches('\n').count();
+    let start = before.rfind('\n').map_or(0, |x| x + 1);
+    let character = before[start..].encode_utf16().count();
+
+    Position::new(line as u32, character as u32)
+}
+
+pub fn to_range(content: &str, span: &Span) -> Range {
+    Range::new(
+        to_position(content, span.start.clone()),
+        to_position(content, span.end.clone()),
+    )
+}
+
+pub fn to_byte(content: &str, position: Position) -> Byte {
+    let start = content
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(|line| line.len())
+        .sum::<usize>();
+
+    let mut units = 0;
+
+    for (i, chr) in content[start..].char_indices() {
+        if units >= position.character as usize || chr == '\n' {
+            return Byte(start + i);
+        }
+
+        units += chr.len_utf16();
+    }
+
+    Byte(content.len())
+}
@@ -8,6 +8,12 @@ pub enum ParserError {
 }
 
 impl IntoDiagnostic for ParserError {
+    fn code(&self) -> Option<usize> {
+        match self {
+            ParserError::UnexpectedToken(_, _) => Some(100),
+        }
+    }
+
     fn message(&self) -> vulpi_report::Text {
         match self {
             ParserError::UnexpectedToken(token, _) => {
@@ -97,9 +97,29 @@ impl<'a> Parser<'a> {
         })
     }
 
+    pub fn interpolation_expr(&mut self) -> Result<InterpolationExpr> {
+        let start = self.expect(TokenData::StringStart)?;
+        let mut parts = Vec::new();
+
+        loop {
+            let expr = self.expr()?;
+
+            if self.at(TokenData::StringMiddle) {
+                parts.push((expr, self.bump()));
+            } else {
+                let end = self.expect(TokenData::StringEnd)?;
+                parts.push((expr, end));
+                break;
+            }
+        }
+
+        Ok(InterpolationExpr { start, parts })
+    }
+
     pub fn expr_atom_kind(&mut self) -> Result<ExprKind> {
         match self.token() {
             TokenData::LBracket => Ok(ExprKind::List(self.list_expr()?)),
+            TokenData::StringStart => Ok(ExprKind::Interpolation(self.interpolation_expr()?)),
             TokenData::Less => Ok(ExprKind::HtmlNode(self.html_node()?)),
             TokenData::UpperIdent | TokenData::LowerIdent => {
                 let path = self.path_ident()?;
@@ -365,12 +385,45 @@ impl<'a> Parser<'a> {
         }))
     }
 
+    pub fn handler_expr(&mut self) -> Result<Box<Expr>> {
+        let handle = self.expect(TokenData::Handle)?;
+        let expr = self.expr()?;
+        let with = self.expect(TokenData::With)?;
+        let handler = self.expr()?;
+
+        let range = self.with_span(handle.value.span.clone());
+
+        Ok(Box::new(Spanned {
+            span: range,
+            data: ExprKind::Handler(HandlerExpr {
+                handle,
+                expr,
+                with,
+                handler,
+            }),
+        }))
+    }
+
+    pub fn cases_expr(&mut self) -> Result<Box<Expr>> {
+        let cases = self.expect(TokenData::Cases)?;
+        let arms = self.block(Self::pattern_arm)?;
+
+        let range = self.with_span(cases.value.span.clone());
+
+        Ok(Box::new(Spanned {
+            span: range,
+            data: ExprKind::Cases(CasesExpr { cases, arms }),
+        }))
+    }
+
     pub fn expr_part(&mut self) -> Result<Box<Expr>> {
         match self.token() {
             TokenData::BackSlash => self.lambda_expr(),
             TokenData::Let => self.let_expr(),
             TokenData::Do => self.expr_do(),
             TokenData::When => self.when_expr(),
+            TokenData::Handle => self.handler_expr(),
+            TokenData::Cases => self.cases_expr(),
             _ => self.expr_annotation(),
         }
     }
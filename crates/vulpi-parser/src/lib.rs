@@ -6,7 +6,7 @@ use vulpi_lexer::Lexer;
 use vulpi_location::{Byte, FileId, Span, Spanned};
 use vulpi_report::{Diagnostic, Report};
 
-use vulpi_syntax::concrete::tree::Program;
+use vulpi_syntax::concrete::tree::{Expr, Program, Type};
 use vulpi_syntax::concrete::Parenthesis;
 use vulpi_syntax::tokens::{Token, TokenData};
 
@@ -238,6 +238,22 @@ impl<'a> Parser<'a> {
 
         Ok(Parenthesis { left, data, right })
     }
+
+    /// Runs a parser that must consume the entire input and reports the error if it fails.
+    fn whole<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Option<T> {
+        let result = parse(self).and_then(|data| {
+            self.expect(TokenData::Eof)?;
+            Ok(data)
+        });
+
+        match result {
+            Ok(data) => Some(data),
+            Err(err) => {
+                self.report(err);
+                None
+            }
+        }
+    }
 }
 
 /// The entrypoint of the parsing, it parses a string into a Program.
@@ -246,3 +262,17 @@ pub fn parse(reporter: Report, file_id: FileId, source: &str) -> Program {
     let mut parser = Parser::new(lexer, file_id, reporter);
     parser.program()
 }
+
+/// Parses a string that contains a single expression, like the ones typed in the REPL.
+pub fn parse_expr(reporter: Report, file_id: FileId, source: &str) -> Option<Box<Expr>> {
+    let lexer = Lexer::new(source, file_id, reporter.clone());
+    let mut parser = Parser::new(lexer, file_id, reporter);
+    parser.whole(Parser::expr)
+}
+
+/// Parses a string that contains a single type.
+pub fn parse_type(reporter: Report, file_id: FileId, source: &str) -> Option<Box<Type>> {
+    let lexer = Lexer::new(source, file_id, reporter.clone());
+    let mut parser = Parser::new(lexer, file_id, reporter);
+    parser.whole(Parser::typ)
+}
@@ -1,5 +1,6 @@
+use vulpi_location::Spanned;
 use vulpi_syntax::{
-    concrete::{pattern::*, Either},
+    concrete::{pattern::*, Either, Path, Upper},
     tokens::TokenData,
 };
 
@@ -13,6 +14,9 @@ impl<'a> Parser<'a> {
             TokenData::UpperIdent => {
                 let path = self.path_ident()?;
                 match path.diferentiate() {
+                    Either::Left(upper) if self.at(TokenData::LBrace) => {
+                        self.pattern_record(upper).map(PatternKind::Record)
+                    }
                     Either::Left(upper) => Ok(PatternKind::Constructor(upper)),
                     Either::Right(_) => todo!(),
                 }
@@ -20,28 +24,118 @@ impl<'a> Parser<'a> {
             TokenData::LPar => self
                 .parenthesis(Self::pattern)
                 .map(PatternKind::Parenthesis),
+            TokenData::LBrace => self.pattern_effect().map(PatternKind::Effect),
+            TokenData::LBracket => self.pattern_list().map(PatternKind::List),
             _ => self.literal().map(PatternKind::Literal),
         }
     }
 
-    pub fn pattern_atom(&mut self) -> Result<Box<Pattern>> {
-        self.spanned(Self::pattern_atom_kind).map(Box::new)
+    pub fn pattern_record_field(&mut self) -> Result<PatRecordField> {
+        let name = self.lower()?;
+
+        let pat = if self.at(TokenData::Equal) {
+            let eq = self.bump();
+            let pat = self.pattern()?;
+            Some((eq, pat))
+        } else {
+            None
+        };
+
+        Ok(PatRecordField { name, pat })
+    }
+
+    pub fn pattern_record(&mut self, name: Path<Upper>) -> Result<PatRecord> {
+        let left_brace = self.expect(TokenData::LBrace)?;
+        let fields = self.sep_by(TokenData::Comma, Self::pattern_record_field)?;
+
+        let rest = if self.at(TokenData::DotDot) {
+            Some(self.bump())
+        } else {
+            None
+        };
+
+        let right_brace = self.expect(TokenData::RBrace)?;
+
+        Ok(PatRecord {
+            name,
+            left_brace,
+            fields,
+            rest,
+            right_brace,
+        })
     }
 
-    pub fn pattern_application_kind(&mut self) -> Result<PatApplication> {
-        let func = self.path_upper()?;
+    pub fn pattern_list(&mut self) -> Result<PatList> {
+        let left_bracket = self.expect(TokenData::LBracket)?;
+        let values = self.sep_by(TokenData::Comma, Self::pattern)?;
+
+        let rest = if self.at(TokenData::DotDot) {
+            let dot_dot = self.bump();
+
+            let pat = if self.at(TokenData::RBracket) {
+                None
+            } else {
+                Some(self.pattern_atom()?)
+            };
+
+            Some(PatListRest { dot_dot, pat })
+        } else {
+            None
+        };
+
+        let right_bracket = self.expect(TokenData::RBracket)?;
+
+        Ok(PatList {
+            left_bracket,
+            values,
+            rest,
+            right_bracket,
+        })
+    }
+
+    pub fn pattern_effect(&mut self) -> Result<PatEffect> {
+        let left_brace = self.expect(TokenData::LBrace)?;
+        let func = self.path_lower()?;
         let args = self.many(Self::pattern_atom)?;
-        Ok(PatApplication { func, args })
+
+        let cont = if self.at(TokenData::RightArrow) {
+            let arrow = self.bump();
+            let name = self.lower()?;
+            Some((arrow, name))
+        } else {
+            None
+        };
+
+        let right_brace = self.expect(TokenData::RBrace)?;
+
+        Ok(PatEffect {
+            left_brace,
+            func,
+            args,
+            cont,
+            right_brace,
+        })
+    }
+
+    pub fn pattern_atom(&mut self) -> Result<Box<Pattern>> {
+        self.spanned(Self::pattern_atom_kind).map(Box::new)
     }
 
     pub fn pattern_application(&mut self) -> Result<Box<Pattern>> {
         if self.at(TokenData::UpperIdent) {
             self.spanned(|this| {
-                let result = this.pattern_application_kind()?;
-                if result.args.is_empty() {
-                    Ok(PatternKind::Constructor(result.func))
+                let func = this.path_upper()?;
+
+                if this.at(TokenData::LBrace) {
+                    return this.pattern_record(func).map(PatternKind::Record);
+                }
+
+                let args = this.many(Self::pattern_atom)?;
+
+                if args.is_empty() {
+                    Ok(PatternKind::Constructor(func))
                 } else {
-                    Ok(PatternKind::Application(result))
+                    Ok(PatternKind::Application(PatApplication { func, args }))
                 }
             })
             .map(Box::new)
@@ -51,6 +145,18 @@ impl<'a> Parser<'a> {
     }
 
     pub fn pattern(&mut self) -> Result<Box<Pattern>> {
-        self.pattern_application()
+        let left = self.pattern_application()?;
+
+        if self.at(TokenData::Bar) {
+            let pipe = self.bump();
+            let right = self.pattern()?;
+
+            Ok(Box::new(Spanned {
+                span: self.with_span(left.span.clone()),
+                data: PatternKind::Or(PatOr { left, pipe, right }),
+            }))
+        } else {
+            Ok(left)
+        }
     }
 }
@@ -97,6 +97,37 @@ impl<'a> Parser<'a> {
         })
     }
 
+    fn effect_field(&mut self) -> Result<EffectField> {
+        let visibility = self.visibility()?;
+        let name = self.lower()?;
+        let args = self.many(Self::type_atom)?;
+        let colon = self.expect(TokenData::Colon)?;
+        let ret = self.typ()?;
+        Ok(EffectField {
+            visibility,
+            name,
+            args,
+            colon,
+            ret,
+        })
+    }
+
+    fn effect_decl(&mut self, visibility: Visibility) -> Result<EffectDecl> {
+        let effect = self.expect(TokenData::Effect)?;
+        let name = self.upper()?;
+        let binders = self.many(Self::type_binder)?;
+        let where_ = self.expect(TokenData::Where)?;
+        let fields = self.block(Self::effect_field)?;
+        Ok(EffectDecl {
+            visibility,
+            effect,
+            name,
+            binders,
+            where_,
+            fields,
+        })
+    }
+
     fn trait_impl(&mut self) -> Result<TraitImpl> {
         let impl_ = self.expect(TokenData::Impl)?;
         let supers = self.many(Self::trait_binder)?;
@@ -177,10 +208,7 @@ impl<'a> Parser<'a> {
     pub fn command_decl(&mut self) -> Result<CommandDecl> {
         let command = self.expect(TokenData::Command)?;
         let name = self.expect(TokenData::String)?;
-        Ok(CommandDecl {
-            command: command.symbol(),
-            name: name.symbol(),
-        })
+        Ok(CommandDecl { command, name })
     }
 
     pub fn record_decl(&mut self) -> Result<RecordDecl> {
@@ -199,7 +227,7 @@ impl<'a> Parser<'a> {
         match self.token() {
             TokenData::Bar => self.sum_decl().map(TypeDef::Sum),
             TokenData::LBrace => self.record_decl().map(TypeDef::Record),
-            _ => self.type_atom().map(TypeDef::Synonym),
+            _ => self.typ().map(TypeDef::Synonym),
         }
     }
 
@@ -309,6 +337,7 @@ impl<'a> Parser<'a> {
             TokenData::Use => self.use_decl(vis).map(Box::new).map(TopLevel::Use),
             TokenData::Impl => self.trait_impl().map(Box::new).map(TopLevel::Impl),
             TokenData::Trait => self.trait_decl(vis).map(Box::new).map(TopLevel::Trait),
+            TokenData::Effect => self.effect_decl(vis).map(Box::new).map(TopLevel::Effect),
             TokenData::Mod => self.mod_decl(vis).map(Box::new).map(TopLevel::Module),
             TokenData::Command => self.command_decl().map(Box::new).map(TopLevel::Command),
             TokenData::External => self
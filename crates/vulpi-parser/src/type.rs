@@ -105,8 +105,27 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn type_effect(&mut self) -> Result<TypeEffect> {
+        let left_brace = self.expect(TokenData::LBrace)?;
+        let effects = self.sep_by(TokenData::Comma, Self::typ)?;
+        let right_brace = self.expect(TokenData::RBrace)?;
+        let typ = self.type_application()?;
+
+        Ok(TypeEffect {
+            left_brace,
+            effects,
+            right_brace,
+            typ,
+        })
+    }
+
     fn type_arrow(&mut self) -> Result<Box<Type>> {
-        let left = self.type_application()?;
+        let left = if self.at(TokenData::LBrace) {
+            self.spanned(|x| x.type_effect().map(TypeKind::Effect))
+                .map(Box::new)?
+        } else {
+            self.type_application()?
+        };
 
         if self.at(TokenData::RightArrow) {
             let arrow = self.bump();
@@ -0,0 +1,72 @@
+//! Stable codes of the diagnostics and their long explanations. The codes are grouped by the step of
+//! the compiler that reports them: `E00xx` for the lexer, `E01xx` for the parser, `E02xx` for the
+//! resolver, `E03xx` for the type checker and `E04xx` for the backends. A code is never reused,
+//! even if its error is removed.
+
+/// The explanation of each code, written in markdown.
+static EXPLANATIONS: &[(usize, &str)] = &[
+    (1, include_str!("../explanations/E0001.md")),
+    (100, include_str!("../explanations/E0100.md")),
+    (200, include_str!("../explanations/E0200.md")),
+    (201, include_str!("../explanations/E0201.md")),
+    (202, include_str!("../explanations/E0202.md")),
+    (203, include_str!("../explanations/E0203.md")),
+    (204, include_str!("../explanations/E0204.md")),
+    (205, include_str!("../explanations/E0205.md")),
+    (206, include_str!("../explanations/E0206.md")),
+    (207, include_str!("../explanations/E0207.md")),
+    (208, include_str!("../explanations/E0208.md")),
+    (300, include_str!("../explanations/E0300.md")),
+    (301, include_str!("../explanations/E0301.md")),
+    (302, include_str!("../explanations/E0302.md")),
+    (303, include_str!("../explanations/E0303.md")),
+    (304, include_str!("../explanations/E0304.md")),
+    (305, include_str!("../explanations/E0305.md")),
+    (306, include_str!("../explanations/E0306.md")),
+    (307, include_str!("../explanations/E0307.md")),
+    (308, include_str!("../explanations/E0308.md")),
+    (309, include_str!("../explanations/E0309.md")),
+    (310, include_str!("../explanations/E0310.md")),
+    (311, include_str!("../explanations/E0311.md")),
+    (312, include_str!("../explanations/E0312.md")),
+    (313, include_str!("../explanations/E0313.md")),
+    (314, include_str!("../explanations/E0314.md")),
+    (315, include_str!("../explanations/E0315.md")),
+    (316, include_str!("../explanations/E0316.md")),
+    (317, include_str!("../explanations/E0317.md")),
+    (318, include_str!("../explanations/E0318.md")),
+    (319, include_str!("../explanations/E0319.md")),
+    (320, include_str!("../explanations/E0320.md")),
+    (321, include_str!("../explanations/E0321.md")),
+    (322, include_str!("../explanations/E0322.md")),
+    (323, include_str!("../explanations/E0323.md")),
+    (324, include_str!("../explanations/E0324.md")),
+    (325, include_str!("../explanations/E0325.md")),
+    (326, include_str!("../explanations/E0326.md")),
+    (327, include_str!("../explanations/E0327.md")),
+    (328, include_str!("../explanations/E0328.md")),
+    (329, include_str!("../explanations/E0329.md")),
+    (330, include_str!("../explanations/E0330.md")),
+    (331, include_str!("../explanations/E0331.md")),
+    (400, include_str!("../explanations/E0400.md")),
+    (401, include_str!("../explanations/E0401.md")),
+];
+
+/// Formats a code in the way that it's shown to the user, like `E0302`.
+pub fn name(code: usize) -> String {
+    format!("E{:04}", code)
+}
+
+/// Parses a code written by the user. The `E` and the leading zeroes are optional.
+pub fn parse(code: &str) -> Option<usize> {
+    let code = code.strip_prefix(['E', 'e']).unwrap_or(code);
+    code.parse().ok()
+}
+
+/// Gets the long explanation of a code with an example.
+pub fn explain(code: usize) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(other, _)| *other == code)
+        .map(|(_, explanation)| *explanation)
+}
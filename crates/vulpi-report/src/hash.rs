@@ -1,6 +1,6 @@
 //! Simple reporter for diagnostics using a hashmap to store things.
 
-use crate::{Diagnostic, Reporter};
+use crate::{Diagnostic, Reporter, Severity};
 use std::collections::HashMap;
 use vulpi_location::FileId;
 
@@ -8,17 +8,32 @@ use vulpi_location::FileId;
 pub struct HashReporter {
     map: HashMap<FileId, Vec<Diagnostic>>,
     errored: bool,
+    deny_warnings: bool,
 }
 
 impl HashReporter {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Makes the reporter treat every warning as an error.
+    pub fn deny_warnings(mut self) -> Self {
+        self.deny_warnings = true;
+        self
+    }
 }
 
 impl Reporter for HashReporter {
     fn report(&mut self, diagnostic: Diagnostic) {
-        self.errored = true;
+        let diagnostic = match diagnostic.severity() {
+            Severity::Warning if self.deny_warnings => diagnostic.deny(),
+            _ => diagnostic,
+        };
+
+        if let Severity::Error = diagnostic.severity() {
+            self.errored = true;
+        }
+
         self.map
             .entry(diagnostic.location().file)
             .or_default()
@@ -31,6 +46,12 @@ impl Reporter for HashReporter {
 
     fn clear(&mut self, file: FileId) {
         self.map.remove(&file);
+
+        self.errored = self
+            .map
+            .values()
+            .flatten()
+            .any(|diagnostic| matches!(diagnostic.severity(), Severity::Error));
     }
 
     fn all_diagnostics(&self) -> Vec<Diagnostic> {
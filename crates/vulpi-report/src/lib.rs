@@ -1,21 +1,35 @@
 //! Module for handling errors that can occur during the compilation process. It's used to report
 //! errors to the user.
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, fmt::Display, rc::Rc};
 
-use renderer::{classic::Classic, Renderer};
+use renderer::{classic::Classic, json::Json, sarif::Sarif, Renderer};
 use vulpi_location::{FileId, Span};
 
+pub mod codes;
 pub mod hash;
 pub mod renderer;
 
-/// A type for representing the severity of a [Diagnostic].
+/// A type for representing the severity of a [Diagnostic]. Only errors stop the compilation, unless
+/// the reporter is configured to deny warnings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Severity {
     Error,
     Warning,
     Info,
 }
 
+impl Severity {
+    /// The lowercase name of the severity used by machine-readable outputs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
+
 /// A type for representing the color of a [Word]. It's all numerated because it's easier to change
 /// the color of a word according to what the user wants.
 pub enum Color {
@@ -56,6 +70,30 @@ impl From<String> for Text {
     }
 }
 
+/// Writes the text without any style or color. It's used by places that cannot render colors like
+/// the LSP.
+impl Display for Text {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Text::Phrase(words) => {
+                for (i, Word(_, _, word)) in words.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " ")?;
+                    }
+
+                    write!(f, "{}", word)?;
+                }
+
+                Ok(())
+            }
+            Text::Styled(_, text) | Text::Colored(_, text) | Text::Text(text) => {
+                write!(f, "{}", text)
+            }
+            Text::Break => writeln!(f),
+        }
+    }
+}
+
 /// A position in the source code that has or not a message. It's used to generate underlined parts
 /// with messages.
 pub struct Marker {
@@ -63,6 +101,38 @@ pub struct Marker {
     pub subtitle: Option<Text>,
 }
 
+impl Marker {
+    pub fn new(position: Span, subtitle: impl Into<Text>) -> Self {
+        Self {
+            position,
+            subtitle: Some(subtitle.into()),
+        }
+    }
+}
+
+/// How much a [Suggestion] can be trusted by tools that apply it automatically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Applicability {
+    /// The replacement is exactly what the user should write.
+    MachineApplicable,
+
+    /// The replacement has parts that the user has to fill.
+    HasPlaceholders,
+}
+
+/// A change in the source code that fixes a diagnostic. The text inside of `position` is replaced
+/// by `replacement`, so an empty position inserts the replacement.
+pub struct Suggestion {
+    pub message: Text,
+    pub position: Span,
+    pub replacement: String,
+    pub applicability: Applicability,
+
+    /// A place whose column is the indentation of the lines of `replacement` after the first one,
+    /// because the layout of the code depends on it.
+    pub indent: Option<Span>,
+}
+
 /// Errors that can occur during the compilation process.
 pub trait IntoDiagnostic {
     fn code(&self) -> Option<usize> {
@@ -78,6 +148,23 @@ pub trait IntoDiagnostic {
     fn severity(&self) -> Severity;
 
     fn location(&self) -> Span;
+
+    /// The marker that is put at [IntoDiagnostic::location].
+    fn primary(&self) -> Marker {
+        Marker {
+            position: self.location(),
+            subtitle: None,
+        }
+    }
+
+    /// Other places that explain the diagnostic, like where an expected type comes from.
+    fn secondary(&self) -> Vec<Marker> {
+        Vec::new()
+    }
+
+    fn suggestions(&self) -> Vec<Suggestion> {
+        Vec::new()
+    }
 }
 
 /// A diagnostic with reference counting. It is a wrapper around a [IntoDiagnostic] trait object.
@@ -108,6 +195,60 @@ impl Diagnostic {
     pub fn location(&self) -> Span {
         self.0.location()
     }
+
+    pub fn primary(&self) -> Marker {
+        self.0.primary()
+    }
+
+    pub fn secondary(&self) -> Vec<Marker> {
+        self.0.secondary()
+    }
+
+    pub fn suggestions(&self) -> Vec<Suggestion> {
+        self.0.suggestions()
+    }
+
+    /// Turns a warning into an error. It's used when warnings are denied.
+    pub fn deny(self) -> Self {
+        Self::new(Denied(self))
+    }
+}
+
+/// A warning that was promoted to an error.
+struct Denied(Diagnostic);
+
+impl IntoDiagnostic for Denied {
+    fn code(&self) -> Option<usize> {
+        self.0.code()
+    }
+
+    fn hint(&self) -> Option<Text> {
+        self.0.hint()
+    }
+
+    fn message(&self) -> Text {
+        self.0.message()
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn location(&self) -> Span {
+        self.0.location()
+    }
+
+    fn primary(&self) -> Marker {
+        self.0.primary()
+    }
+
+    fn secondary(&self) -> Vec<Marker> {
+        self.0.secondary()
+    }
+
+    fn suggestions(&self) -> Vec<Suggestion> {
+        self.0.suggestions()
+    }
 }
 
 /// A reporter is a structure that gets and record errors. It's used to store and report errors to
@@ -160,14 +301,36 @@ impl Report {
     }
 
     pub fn to_stderr(&self, ctx: Classic) {
-        if self.has_errors() {
+        let diagnostics = self.all_diagnostics();
+
+        if !diagnostics.is_empty() {
             eprintln!();
 
-            for diagnostic in self.all_diagnostics().iter().rev() {
+            for diagnostic in diagnostics.iter().rev() {
                 diagnostic.render(&ctx, &mut std::io::stderr()).unwrap();
             }
         }
     }
+
+    /// Writes every diagnostic as a JSON object in its own line.
+    pub fn to_json_lines(
+        &self,
+        ctx: Json,
+        writer: &mut impl std::io::Write,
+    ) -> std::io::Result<()> {
+        for diagnostic in self.all_diagnostics().iter().rev() {
+            diagnostic.render(&ctx, writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes all the diagnostics as a single SARIF log.
+    pub fn to_sarif(&self, ctx: Sarif, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+        let mut diagnostics = self.all_diagnostics();
+        diagnostics.reverse();
+        diagnostics.render(&ctx, writer)
+    }
 }
 
 pub fn hash_reporter() -> Report {
@@ -1,9 +1,10 @@
 use std::path::PathBuf;
 
+use vulpi_location::FileId;
 use vulpi_vfs::FileSystem;
 use yansi::Paint;
 
-use crate::{renderer::LineGuide, Color, Diagnostic, Style, Text, Word};
+use crate::{codes, renderer::LineGuide, Color, Diagnostic, Marker, Severity, Style, Text, Word};
 
 use super::Renderer;
 
@@ -82,81 +83,187 @@ impl<'a> Renderer<Classic<'a>> for Text {
     }
 }
 
-impl<'a> Renderer<Classic<'a>> for Diagnostic {
-    fn render(&self, ctx: &Classic<'a>, writer: &mut impl std::io::Write) -> std::io::Result<()> {
-        // At this point we are probably sure that the file exists, so we can unwrap.
-        let path = ctx.fs.path(self.location().file).unwrap();
-        let relative = path.strip_prefix(&ctx.cwd).unwrap();
+/// Writes the lines of a file around the markers with each one of them underlined. The primary marker
+/// uses `^` and the color of the severity and the secondary ones use `-`.
+fn render_snippet(
+    ctx: &Classic,
+    writer: &mut impl std::io::Write,
+    file: FileId,
+    markers: &[(&Marker, bool)],
+    color: yansi::Color,
+) -> std::io::Result<()> {
+    // At this point we are probably sure that the file exists, so we can unwrap.
+    let path = ctx.fs.path(file).unwrap();
+    let relative = path.strip_prefix(&ctx.cwd).unwrap_or(path);
+
+    let content = ctx.fs.read(file).unwrap();
+    let line_guide = LineGuide::new(&content);
+
+    let positions = markers
+        .iter()
+        .map(|(marker, primary)| {
+            let start = line_guide
+                .to_line_and_column(marker.position.start.clone())
+                .unwrap();
+            let end = line_guide
+                .to_line_and_column(marker.position.end.clone())
+                .unwrap();
+            (start, end, *marker, *primary)
+        })
+        .collect::<Vec<_>>();
+
+    let guide = Paint::new("┌─>").fg(yansi::Color::Cyan).dimmed();
+    let (start, _, _, _) = positions[0];
+
+    writeln!(
+        writer,
+        "      {guide} {}:{}:{} ",
+        relative.display(),
+        start.0 + 1,
+        start.1 + 1
+    )?;
+
+    let vbar = Paint::new("│").fg(yansi::Color::Cyan).dimmed();
+
+    writeln!(writer, "      {vbar} ")?;
+
+    let lines = content.lines().collect::<Vec<_>>();
+
+    // Ranges of lines that are shown, each one with two lines of context around the markers.
+    let mut ranges = positions
+        .iter()
+        .map(|(start, end, _, _)| (start.0.saturating_sub(2), (end.0 + 2).min(lines.len())))
+        .collect::<Vec<_>>();
+
+    ranges.sort();
+
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    for (i, (minimum, maximum)) in merged.into_iter().enumerate() {
+        if i != 0 {
+            writeln!(writer, "  ... {vbar} ")?;
+        }
+
+        for (line_number, line) in lines.iter().enumerate().take(maximum).skip(minimum) {
+            writeln!(writer, "  {:>3} {vbar} {}", line_number + 1, line)?;
+
+            let mut here = positions
+                .iter()
+                .filter(|(start, _, _, _)| start.0 == line_number)
+                .collect::<Vec<_>>();
+
+            here.sort_by_key(|(start, _, _, _)| start.1);
+
+            for (start, end, marker, primary) in here {
+                let last = if end.0 == start.0 { end.1 } else { line.len() };
+                let width = last.saturating_sub(start.1).max(1);
 
-        let content = ctx.fs.read(self.location().file).unwrap();
+                let (symbol, paint) = if *primary {
+                    ("^", color)
+                } else {
+                    ("-", yansi::Color::Blue)
+                };
 
-        let range = self.location();
+                write!(
+                    writer,
+                    "      {vbar} {}{}",
+                    " ".repeat(start.1),
+                    Paint::new(symbol.repeat(width)).bold().fg(paint)
+                )?;
+
+                if let Some(subtitle) = &marker.subtitle {
+                    write!(writer, " {}", Paint::new(subtitle.to_string()).fg(paint))?;
+                }
 
-        let line_guide = LineGuide::new(&content);
+                writeln!(writer)?;
+            }
+        }
+    }
 
-        let start = line_guide.to_line_and_column(range.start).unwrap();
-        let end = line_guide.to_line_and_column(range.end).unwrap();
+    Ok(())
+}
+
+impl<'a> Renderer<Classic<'a>> for Diagnostic {
+    fn render(&self, ctx: &Classic<'a>, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+        let (label, color) = match self.severity() {
+            Severity::Error => (" ERROR ", yansi::Color::Red),
+            Severity::Warning => (" WARNING ", yansi::Color::Yellow),
+            Severity::Info => (" INFO ", yansi::Color::Blue),
+        };
 
         write!(
             writer,
             "  {} ",
-            yansi::Color::White
-                .style()
-                .bg(yansi::Color::Red)
-                .paint(" ERROR ")
+            yansi::Color::White.style().bg(color).paint(label)
         )?;
 
-        self.message().render(ctx, writer)?;
+        if let Some(code) = self.code() {
+            write!(
+                writer,
+                "{} ",
+                Paint::new(format!("[{}]", codes::name(code))).dimmed()
+            )?;
+        }
 
-        let guide = Paint::new("┌─>").fg(yansi::Color::Cyan).dimmed();
+        self.message().render(ctx, writer)?;
 
         writeln!(writer)?;
         writeln!(writer)?;
-        writeln!(
-            writer,
-            "      {guide} {}:{}:{} ",
-            relative.display(),
-            start.0 + 1,
-            start.1 + 1
-        )?;
 
-        let vbar = Paint::new("│").fg(yansi::Color::Cyan).dimmed();
+        let primary = self.primary();
+        let secondary = self.secondary();
 
-        writeln!(writer, "      {vbar} ")?;
+        let markers = std::iter::once((&primary, true))
+            .chain(secondary.iter().map(|marker| (marker, false)))
+            .collect::<Vec<_>>();
 
-        let is_inline = start.0 == end.0;
+        // Markers are grouped by file, starting with the file of the primary one.
+        let mut files = Vec::new();
 
-        let lines = content.lines().collect::<Vec<_>>();
+        for (marker, _) in &markers {
+            if !files.contains(&marker.position.file) {
+                files.push(marker.position.file);
+            }
+        }
 
-        let minimum = start.0.saturating_sub(2);
-        let maximum = (end.0 + 2).min(lines.len());
+        for (i, file) in files.into_iter().enumerate() {
+            if i != 0 {
+                writeln!(writer)?;
+            }
 
-        for (i, line) in lines[minimum..maximum].iter().enumerate() {
-            let line_number = minimum + i + 1;
+            let in_file = markers
+                .iter()
+                .filter(|(marker, _)| marker.position.file == file)
+                .cloned()
+                .collect::<Vec<_>>();
 
-            write!(writer, "  {:>3} {vbar} ", line_number)?;
+            render_snippet(ctx, writer, file, &in_file, color)?;
+        }
 
-            if is_inline && line_number == start.0 + 1 {
-                let line = line.to_string();
+        let vbar = Paint::new("│").fg(yansi::Color::Cyan).dimmed();
 
-                writeln!(writer, "{}", line)?;
+        for suggestion in self.suggestions() {
+            writeln!(writer, "      {vbar} ")?;
+            writeln!(
+                writer,
+                "      {} {}",
+                Paint::new("help:").fg(yansi::Color::Green).bold(),
+                suggestion.message
+            )?;
 
+            for line in suggestion.replacement.trim_matches('\n').lines() {
                 writeln!(
                     writer,
-                    "      {vbar} {}{}",
-                    " ".repeat(start.1),
-                    Paint::new("^".repeat(end.1 - start.1))
-                        .bold()
-                        .fg(yansi::Color::Red)
+                    "      {vbar}   {}",
+                    Paint::new(line).fg(yansi::Color::Green)
                 )?;
-            } else if is_inline && line_number == end.0 + 1 {
-                let mut line = line.to_string();
-
-                line.insert(end.1 + 1, '^');
-
-                writeln!(writer, "{}", line)?;
-            } else {
-                writeln!(writer, "{}", line)?;
             }
         }
 
@@ -0,0 +1,124 @@
+//! Renderer that writes diagnostics as JSON objects, one per line, so other tools can read them.
+
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+use vulpi_location::Span;
+use vulpi_vfs::FileSystem;
+
+use crate::{codes, renderer::LineGuide, Applicability, Diagnostic, Suggestion};
+
+use super::Renderer;
+
+pub struct Json<'a> {
+    fs: &'a dyn FileSystem<Path = PathBuf>,
+    cwd: PathBuf,
+}
+
+/// A place in the source code with lines and columns starting at one. The end column points to the
+/// character after the last one.
+pub struct Location {
+    pub file: String,
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl<'a> Json<'a> {
+    pub fn new(fs: &'a (dyn FileSystem<Path = PathBuf> + 'static), cwd: PathBuf) -> Self {
+        Self { fs, cwd }
+    }
+
+    /// The replacement of a suggestion with its new lines indented like the source code.
+    pub fn replacement(&self, suggestion: &Suggestion) -> String {
+        match &suggestion.indent {
+            Some(span) => {
+                let column = self.location(span.clone()).start.1 - 1;
+                let indent = format!("\n{}", " ".repeat(column));
+                suggestion.replacement.replace('\n', &indent)
+            }
+            None => suggestion.replacement.clone(),
+        }
+    }
+
+    pub fn location(&self, span: Span) -> Location {
+        // At this point we are probably sure that the file exists, so we can unwrap.
+        let path = self.fs.path(span.file).unwrap();
+        let relative = path.strip_prefix(&self.cwd).unwrap_or(path);
+
+        let content = self.fs.read(span.file).unwrap();
+        let line_guide = LineGuide::new(&content);
+
+        let start = line_guide.to_line_and_column(span.start).unwrap();
+        let end = line_guide.to_line_and_column(span.end).unwrap();
+
+        Location {
+            file: relative.display().to_string(),
+            start: (start.0 + 1, start.1 + 1),
+            end: (end.0 + 1, end.1 + 1),
+        }
+    }
+}
+
+impl Location {
+    pub fn range(&self) -> Value {
+        json!({
+            "start": { "line": self.start.0, "column": self.start.1 },
+            "end": { "line": self.end.0, "column": self.end.1 },
+        })
+    }
+}
+
+impl Diagnostic {
+    pub fn to_json(&self, ctx: &Json) -> Value {
+        let location = ctx.location(self.location());
+
+        let markers = self
+            .secondary()
+            .into_iter()
+            .map(|marker| {
+                let location = ctx.location(marker.position);
+                json!({
+                    "file": location.file,
+                    "range": location.range(),
+                    "label": marker.subtitle.map(|subtitle| subtitle.to_string()),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let suggestions = self
+            .suggestions()
+            .into_iter()
+            .map(|suggestion| {
+                let location = ctx.location(suggestion.position.clone());
+                json!({
+                    "message": suggestion.message.to_string(),
+                    "file": location.file,
+                    "range": location.range(),
+                    "replacement": ctx.replacement(&suggestion),
+                    "applicability": match suggestion.applicability {
+                        Applicability::MachineApplicable => "machine-applicable",
+                        Applicability::HasPlaceholders => "has-placeholders",
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "file": location.file,
+            "range": location.range(),
+            "severity": self.severity().name(),
+            "code": self.code().map(codes::name),
+            "message": self.message().to_string(),
+            "label": self.primary().subtitle.map(|subtitle| subtitle.to_string()),
+            "hint": self.hint().map(|hint| hint.to_string()),
+            "markers": markers,
+            "suggestions": suggestions,
+        })
+    }
+}
+
+impl<'a> Renderer<Json<'a>> for Diagnostic {
+    fn render(&self, ctx: &Json<'a>, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+        writeln!(writer, "{}", self.to_json(ctx))
+    }
+}
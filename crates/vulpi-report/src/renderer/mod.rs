@@ -1,6 +1,8 @@
 //! Simple renderer for diagnostics.
 
 pub mod classic;
+pub mod json;
+pub mod sarif;
 
 use vulpi_location::Byte;
 
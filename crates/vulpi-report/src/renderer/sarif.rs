@@ -0,0 +1,125 @@
+//! Renderer that writes all the diagnostics as a single [SARIF](https://sarifweb.azurewebsites.net/)
+//! log. It's the format that code scanning tools use to annotate pull requests.
+
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+use vulpi_vfs::FileSystem;
+
+use crate::{codes, Diagnostic, Severity};
+
+use super::{
+    json::{Json, Location},
+    Renderer,
+};
+
+pub struct Sarif<'a>(Json<'a>);
+
+impl<'a> Sarif<'a> {
+    pub fn new(fs: &'a (dyn FileSystem<Path = PathBuf> + 'static), cwd: PathBuf) -> Self {
+        Self(Json::new(fs, cwd))
+    }
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "note",
+    }
+}
+
+fn region(location: &Location) -> Value {
+    json!({
+        "startLine": location.start.0,
+        "startColumn": location.start.1,
+        "endLine": location.end.0,
+        "endColumn": location.end.1,
+    })
+}
+
+fn result(ctx: &Json, diagnostic: &Diagnostic) -> Value {
+    let location = ctx.location(diagnostic.location());
+
+    let related = diagnostic
+        .secondary()
+        .into_iter()
+        .enumerate()
+        .map(|(id, marker)| {
+            let location = ctx.location(marker.position);
+            json!({
+                "id": id,
+                "message": { "text": marker.subtitle.map(|x| x.to_string()).unwrap_or_default() },
+                "physicalLocation": {
+                    "artifactLocation": { "uri": location.file },
+                    "region": region(&location),
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let fixes = diagnostic
+        .suggestions()
+        .into_iter()
+        .map(|suggestion| {
+            let location = ctx.location(suggestion.position.clone());
+            json!({
+                "description": { "text": suggestion.message.to_string() },
+                "artifactChanges": [{
+                    "artifactLocation": { "uri": location.file },
+                    "replacements": [{
+                        "deletedRegion": region(&location),
+                        "insertedContent": { "text": ctx.replacement(&suggestion) },
+                    }],
+                }],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut message = diagnostic.message().to_string();
+
+    if let Some(hint) = diagnostic.hint() {
+        message.push_str(&format!("\n{}", hint));
+    }
+
+    let mut result = json!({
+        "level": level(diagnostic.severity()),
+        "message": { "text": message },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": { "uri": location.file },
+                "region": region(&location),
+            },
+        }],
+        "relatedLocations": related,
+        "fixes": fixes,
+    });
+
+    if let Some(code) = diagnostic.code() {
+        result["ruleId"] = json!(codes::name(code));
+    }
+
+    result
+}
+
+impl<'a> Renderer<Sarif<'a>> for Vec<Diagnostic> {
+    fn render(&self, ctx: &Sarif<'a>, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+        let results: Vec<_> = self.iter().map(|diag| result(&ctx.0, diag)).collect();
+
+        let log = json!({
+            "version": "2.1.0",
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "vulpi",
+                        "informationUri": "https://github.com/lang-vulpi/vulpi",
+                    },
+                },
+                "results": results,
+            }],
+        });
+
+        writeln!(writer, "{:#}", log)
+    }
+}
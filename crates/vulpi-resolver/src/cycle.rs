@@ -12,7 +12,7 @@ use crate::error::ResolverError;
 pub struct DepHolder {
     nodes: HashMap<Qualified, NodeIndex<u32>>,
     graph: DiGraph<(), ()>,
-    spans: HashMap<Qualified, Span>,
+    uses: HashMap<(Qualified, Qualified), Span>,
 }
 
 impl DepHolder {
@@ -31,7 +31,8 @@ impl DepHolder {
                         .or_insert_with(|| self.graph.add_node(()));
 
                     self.graph.add_edge(from, *to, ());
-                    self.spans.insert(to_.clone(), span.clone());
+                    self.uses
+                        .insert((let_.signature.name.clone(), to_.clone()), span.clone());
                 }
             }
         }
@@ -54,13 +55,25 @@ impl DepHolder {
                     .collect::<Vec<_>>();
                 cycle.sort_by_key(|k| k.to_string());
 
-                let first = cycle[0].clone();
+                // Each constant points to the place where it uses the next one of the cycle.
+                let mut uses = Vec::new();
 
-                let span = self.spans[&first].clone();
+                for from in &cycle {
+                    let to = cycle
+                        .iter()
+                        .find(|to| self.uses.contains_key(&(from.clone(), (*to).clone())));
+
+                    if let Some(to) = to {
+                        let span = self.uses[&(from.clone(), to.clone())].clone();
+                        uses.push((from.clone(), to.clone(), span));
+                    }
+                }
+
+                let span = uses[0].2.clone();
 
                 report.report(Diagnostic::new(ResolverError {
                     span,
-                    kind: crate::error::ResolverErrorKind::CycleBetweenConstants(cycle),
+                    kind: crate::error::ResolverErrorKind::CycleBetweenConstants(cycle, uses),
                 }))
             }
         }
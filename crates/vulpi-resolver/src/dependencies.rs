@@ -1,9 +1,10 @@
+use serde::{Deserialize, Serialize};
 use vulpi_intern::Symbol;
 use vulpi_location::Span;
 use vulpi_syntax::concrete::{self, tree::TopLevel, Upper};
 use vulpi_vfs::path::Path;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Dependencies {
     pub declared: Vec<Path>,
     pub imported: Vec<(Path, Span)>,
@@ -1,6 +1,6 @@
 use vulpi_intern::Symbol;
 use vulpi_location::Span;
-use vulpi_report::IntoDiagnostic;
+use vulpi_report::{IntoDiagnostic, Marker};
 use vulpi_syntax::r#abstract::Qualified;
 
 pub enum ResolverErrorKind {
@@ -8,9 +8,11 @@ pub enum ResolverErrorKind {
     ListIsNotAvailable,
     InvalidPath(Vec<Symbol>),
     DuplicatePattern(Symbol),
+    MissingInOrPattern(Symbol),
     PrivateDefinition,
-    CycleBetweenConstants(Vec<Qualified>),
+    CycleBetweenConstants(Vec<Qualified>, Vec<(Qualified, Qualified, Span)>),
     NotImplemented(Symbol, Symbol),
+    DuplicateLangItem(Symbol),
 }
 
 pub struct ResolverError {
@@ -19,6 +21,22 @@ pub struct ResolverError {
 }
 
 impl IntoDiagnostic for ResolverError {
+    fn code(&self) -> Option<usize> {
+        let code = match self.kind {
+            ResolverErrorKind::NotFound(_) => 200,
+            ResolverErrorKind::ListIsNotAvailable => 201,
+            ResolverErrorKind::InvalidPath(_) => 202,
+            ResolverErrorKind::DuplicatePattern(_) => 203,
+            ResolverErrorKind::MissingInOrPattern(_) => 204,
+            ResolverErrorKind::PrivateDefinition => 205,
+            ResolverErrorKind::CycleBetweenConstants(_, _) => 206,
+            ResolverErrorKind::NotImplemented(_, _) => 207,
+            ResolverErrorKind::DuplicateLangItem(_) => 208,
+        };
+
+        Some(code)
+    }
+
     fn message(&self) -> vulpi_report::Text {
         match &self.kind {
             ResolverErrorKind::NotImplemented(name, feature) => format!(
@@ -37,8 +55,16 @@ impl IntoDiagnostic for ResolverError {
             ResolverErrorKind::DuplicatePattern(name) => {
                 format!("duplicate pattern: {}", name.get()).into()
             }
+            ResolverErrorKind::MissingInOrPattern(name) => format!(
+                "the variable '{}' is not bound in every alternative of the or-pattern",
+                name.get()
+            )
+            .into(),
             ResolverErrorKind::PrivateDefinition => "private definition".into(),
-            ResolverErrorKind::CycleBetweenConstants(cycle) => {
+            ResolverErrorKind::DuplicateLangItem(name) => {
+                format!("the lang item '{}' is already defined", name.get()).into()
+            }
+            ResolverErrorKind::CycleBetweenConstants(cycle, _) => {
                 let mut cycle = cycle.iter().map(|q| q.to_string()).collect::<Vec<_>>();
                 cycle.sort_by_key(|k| k.to_string());
 
@@ -54,4 +80,31 @@ impl IntoDiagnostic for ResolverError {
     fn location(&self) -> Span {
         self.span.clone()
     }
+
+    fn primary(&self) -> Marker {
+        match &self.kind {
+            ResolverErrorKind::CycleBetweenConstants(_, uses) => {
+                let (from, to, span) = &uses[0];
+                Marker::new(span.clone(), uses_message(from, to))
+            }
+            _ => Marker {
+                position: self.span.clone(),
+                subtitle: None,
+            },
+        }
+    }
+
+    fn secondary(&self) -> Vec<Marker> {
+        match &self.kind {
+            ResolverErrorKind::CycleBetweenConstants(_, uses) => uses[1..]
+                .iter()
+                .map(|(from, to, span)| Marker::new(span.clone(), uses_message(from, to)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn uses_message(from: &Qualified, to: &Qualified) -> String {
+    format!("'{}' uses '{}' here", from.name.get(), to.name.get())
 }
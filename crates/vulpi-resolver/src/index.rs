@@ -0,0 +1,45 @@
+//! Index of the resolved names. It maps every place where a name is used to its qualified name and
+//! every qualified name to the place where it was declared. It's used by tools like the language
+//! server to implement go to definition.
+
+use std::collections::HashMap;
+
+use vulpi_location::{Byte, FileId, Span};
+use vulpi_syntax::r#abstract::Qualified;
+
+#[derive(Default)]
+pub struct Index {
+    references: HashMap<FileId, Vec<(Span, Qualified)>>,
+    declarations: HashMap<Qualified, Span>,
+}
+
+impl Index {
+    /// Records that a name was used in a place of the source code.
+    pub fn add_reference(&mut self, span: Span, name: Qualified) {
+        // Desugared nodes have empty spans and cannot be pointed at.
+        if span.start == span.end {
+            return;
+        }
+
+        self.references.entry(span.file).or_default().push((span, name));
+    }
+
+    /// Records the place where a name was declared.
+    pub fn add_declaration(&mut self, name: Qualified, span: Span) {
+        self.declarations.insert(name, span);
+    }
+
+    /// Finds the innermost name that is used in a position of a file.
+    pub fn reference(&self, file: FileId, place: Byte) -> Option<&(Span, Qualified)> {
+        self.references
+            .get(&file)?
+            .iter()
+            .filter(|(span, _)| span.start <= place && place <= span.end)
+            .min_by_key(|(span, _)| span.end.0 - span.start.0)
+    }
+
+    /// Gets the place where a name was declared.
+    pub fn declaration(&self, name: &Qualified) -> Option<&Span> {
+        self.declarations.get(name)
+    }
+}
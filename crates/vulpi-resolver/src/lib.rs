@@ -7,6 +7,7 @@ use std::{cell::RefCell, rc::Rc};
 
 use petgraph::prelude::DiGraph;
 use petgraph::stable_graph::NodeIndex;
+use serde::{Deserialize, Serialize};
 
 use vulpi_intern::Symbol;
 use vulpi_location::{Span, Spanned};
@@ -17,9 +18,12 @@ use vulpi_syntax::r#abstract as abs;
 use vulpi_syntax::r#abstract::Visibility;
 use vulpi_vfs::path::{Path, Qualified};
 
+use index::Index;
+
 pub mod cycle;
 pub mod dependencies;
 mod error;
+pub mod index;
 
 pub enum Either<L, R> {
     Left(L),
@@ -40,7 +44,7 @@ pub enum DefinitionKind {
 }
 
 /// Definition bag is a bag of definitions. It is used to store the definitions of a module.
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Bag<V> {
     pub types: V,
     pub values: V,
@@ -60,6 +64,7 @@ impl<V> Bag<V> {
 pub type Alias = (Qualified, abs::Visibility);
 
 /// Namespace of a module.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Namespace {
     name: Path,
     declared: Bag<HashMap<Symbol, abs::Visibility>>,
@@ -70,8 +75,15 @@ pub struct Namespace {
     modules: HashMap<Symbol, (Path, abs::Visibility)>,
     submodules: HashMap<Symbol, Module>,
     opened: HashMap<Path, Visibility>,
+
+    /// The types of this module that back a feature of the language, like the type of the list
+    /// literals. They are registered with commands like `#list "List"`.
+    lang_items: HashMap<Symbol, Symbol>,
 }
 
+/// The commands that register a type as a lang item.
+const LANG_ITEMS: &[&str] = &["list"];
+
 pub fn from_upper_path(path: &concrete::Path<concrete::Upper>) -> Path {
     let mut path_result = Path { segments: vec![] };
 
@@ -115,6 +127,19 @@ pub fn from_constructor_upper_path(path: &concrete::Path<concrete::Upper>) -> Qu
 #[derive(Clone)]
 pub struct Module(Rc<RefCell<Namespace>>);
 
+impl Serialize for Module {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.borrow().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Module {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let namespace = Namespace::deserialize(deserializer)?;
+        Ok(Module(Rc::new(RefCell::new(namespace))))
+    }
+}
+
 /// Getters for the namespace.
 impl Module {
     pub fn modules_mut(&self) -> RefMut<'_, HashMap<Symbol, (Path, abs::Visibility)>> {
@@ -129,6 +154,10 @@ impl Module {
         std::cell::Ref::map(self.borrow(), |this| &this.name)
     }
 
+    pub fn submodules(&self) -> Vec<Module> {
+        self.borrow().submodules.values().cloned().collect()
+    }
+
     fn declared(&self) -> Ref<'_, Bag<HashMap<Symbol, abs::Visibility>>> {
         std::cell::Ref::map(self.borrow(), |this| &this.declared)
     }
@@ -162,6 +191,7 @@ impl Module {
             submodules: Default::default(),
             opened: Default::default(),
             modules: Default::default(),
+            lang_items: Default::default(),
         })))
     }
 
@@ -375,6 +405,7 @@ pub struct Context {
     scope: RefCell<Bag<im_rc::HashSet<Symbol>>>,
     reporter: Report,
     available: Rc<RefCell<HashMap<Path, Module>>>,
+    index: Rc<RefCell<Index>>,
 
     in_head: bool,
     constant: Option<abs::Qualified>,
@@ -410,8 +441,24 @@ impl Context {
         self.in_head = false;
     }
 
+    /// Records in the index that a name was used in a place.
+    pub fn add_reference(&self, span: Span, name: abs::Qualified) {
+        self.index.borrow_mut().add_reference(span, name);
+    }
+
+    /// Records in the index the place where a name of the current module was declared.
+    pub fn add_declaration(&self, name: Symbol, span: Span) {
+        let name = abs::Qualified {
+            path: self.module.name().symbol(),
+            name,
+        };
+
+        self.index.borrow_mut().add_declaration(name, span);
+    }
+
     pub fn new(
         available: Rc<RefCell<HashMap<Path, Module>>>,
+        index: Rc<RefCell<Index>>,
         name: Path,
         report: Report,
     ) -> Context {
@@ -419,6 +466,7 @@ impl Context {
             module: Module::new(name),
             scope: Default::default(),
             available,
+            index,
             reporter: report,
 
             in_head: false,
@@ -542,11 +590,71 @@ impl Context {
             scope,
             reporter: self.reporter.clone(),
             available: self.available.clone(),
+            index: self.index.clone(),
             in_head: self.in_head,
             constant: self.constant.clone(),
         }
     }
 
+    /// Searches the current module and then every other one for the type that was registered as
+    /// the lang item `name`. There is at most one, because the ones that come after it are
+    /// reported as duplicates.
+    pub fn lang_item(&self, name: &str) -> Option<Path> {
+        let name = Symbol::intern(name);
+        let available = self.available();
+
+        std::iter::once(&self.module)
+            .chain(available.values())
+            .find_map(|module| {
+                let module = module.borrow();
+                let item = module.lang_items.get(&name)?;
+                Some(module.name.with(item.clone()))
+            })
+    }
+
+    /// The `Nil` and `Cons` constructors that build lists. They come from the `list` lang item
+    /// or, if there's none, from a `List` type that is in scope. They are looked up without
+    /// reporting why they are missing, so the only error is that the list is not available.
+    pub fn list_constructors(&self, span: Span) -> Option<(abs::Qualified, abs::Qualified)> {
+        let quiet = Context {
+            reporter: vulpi_report::hash_reporter(),
+            ..self.clone()
+        };
+
+        let path = self.lang_item("list").unwrap_or_else(|| Path {
+            segments: vec![Symbol::intern("List")],
+        });
+
+        let nil = quiet.resolve(
+            DefinitionKind::Value,
+            span.clone(),
+            Qualified {
+                path: path.clone(),
+                name: Symbol::intern("Nil"),
+            },
+        );
+
+        let cons = quiet.resolve(
+            DefinitionKind::Value,
+            span.clone(),
+            Qualified {
+                path,
+                name: Symbol::intern("Cons"),
+            },
+        );
+
+        let constructors = nil.zip(cons);
+
+        if constructors.is_none() {
+            self.reporter.report(Diagnostic::new(error::ResolverError {
+                span,
+                kind: error::ResolverErrorKind::ListIsNotAvailable,
+            }));
+        }
+
+        constructors
+    }
+
     pub fn scoped<T>(&self, fun: impl FnOnce(&mut Context) -> T) -> T {
         let mut ctx = self.clone();
         fun(&mut ctx)
@@ -616,10 +724,35 @@ pub mod top_level {
             External(ext) => Some(resolve_external(ctx, *ext).map(abs::TopLevel::External)),
             Use(use_decl) => Some(resolve_use(ctx, *use_decl).map(|_| abs::TopLevel::Use)),
             Trait(trait_) => Some(resolve_trait(ctx, *trait_).map(abs::TopLevel::Trait)),
+            Effect(effect) => Some(resolve_effect(ctx, *effect).map(abs::TopLevel::Effect)),
             Impl(impl_) => Some(resolve_impl(ctx, *impl_).map(abs::TopLevel::Impl)),
-            Command(cmd) => Some(Solver::new(move |_| {
-                abs::TopLevel::Command(cmd.name.clone(), cmd.command.clone())
-            })),
+            Command(cmd) => {
+                let command = cmd.command.symbol();
+                let name = cmd.name.symbol();
+
+                if LANG_ITEMS.contains(&command.get().as_str()) {
+                    let path = ctx.module.borrow().name.with(name.clone());
+
+                    match ctx.lang_item(&command.get()) {
+                        Some(other) if other != path => {
+                            ctx.reporter.report(Diagnostic::new(error::ResolverError {
+                                span: cmd.command.value.span.clone().mix(cmd.name.value.span),
+                                kind: error::ResolverErrorKind::DuplicateLangItem(command.clone()),
+                            }))
+                        }
+                        _ => {
+                            ctx.module
+                                .borrow_mut()
+                                .lang_items
+                                .insert(command.clone(), name.clone());
+                        }
+                    }
+                }
+
+                Some(Solver::new(move |_| {
+                    abs::TopLevel::Command(name.clone(), command.clone())
+                }))
+            }
             Error(_) => None,
         }
     }
@@ -631,6 +764,8 @@ pub mod top_level {
         ctx.module
             .define(DefinitionKind::Type, decl.visibility.clone(), name.clone());
 
+        ctx.add_declaration(name.clone(), decl.name.0.value.span.clone());
+
         ctx.module.traits().insert(
             name.clone(),
             decl.body
@@ -664,12 +799,23 @@ pub mod top_level {
                     .map(|x| transform_type(ctx, *x.typ))
                     .collect::<Vec<_>>();
 
-                let body = body.into_iter().map(|x| x.eval(ctx.clone())).collect();
+                // The signatures are declared inside of the namespace of the trait, so their names
+                // have to point to it while the types inside of them use the scope of the module.
+                let namespace = submodule.module.name().symbol();
+
+                let body = body
+                    .into_iter()
+                    .map(|x| {
+                        let mut signature = x.eval(ctx.clone());
+                        signature.name.path = namespace.clone();
+                        signature
+                    })
+                    .collect();
 
                 abs::TraitDecl {
                     name,
                     supers,
-                    namespace: submodule.module.name().symbol(),
+                    namespace,
                     binders,
                     body,
                     span: decl.name.0.value.span.clone(),
@@ -696,10 +842,22 @@ pub mod top_level {
             .map(|x| resolve_let(ctx.clone(), x, false))
             .collect::<Vec<_>>();
 
+        let span = decl.name.span.clone();
+
         Solver::new(move |ctx| {
             let path = from_constructor_upper_path(&decl.name);
             let searched = ctx.get_path(DefinitionKind::Type, decl.name.span.clone(), path, true);
 
+            if let Some(searched) = &searched {
+                ctx.add_reference(
+                    decl.name.span.clone(),
+                    abs::Qualified {
+                        path: searched.path.symbol(),
+                        name: searched.name.clone(),
+                    },
+                );
+            }
+
             ctx.scoped(|ctx| {
                 let binders = decl
                     .types
@@ -707,38 +865,47 @@ pub mod top_level {
                     .map(|x| transform_type(ctx, *x))
                     .collect::<Vec<_>>();
 
+                let supers = decl
+                    .supers
+                    .into_iter()
+                    .map(|x| transform_type(ctx, *x.typ))
+                    .collect::<Vec<_>>();
+
                 let body = body.into_iter().map(|x| x.eval(ctx.clone())).collect();
 
                 if let Some(searched) = searched {
                     let module = ctx.available().get(&searched.path).cloned().unwrap();
-                    let values = module.traits().get(&searched.name).cloned().unwrap();
-
-                    let not_declared = let_names
-                        .iter()
-                        .filter(|x| !values.contains_key(x.0))
-                        .collect::<Vec<_>>();
-
-                    let over_declared = values
-                        .iter()
-                        .filter(|x| !let_names.contains_key(x.0))
-                        .map(|(name, _)| (name.clone(), decl.name.span.clone()))
-                        .collect::<Vec<_>>();
-
-                    for (name, span) in over_declared {
-                        ctx.reporter.report(Diagnostic::new(ResolverError {
-                            span: span.clone(),
-                            kind: error::ResolverErrorKind::NotFound(name.clone()),
-                        }));
-                    }
 
-                    for (name, span) in not_declared {
-                        ctx.reporter.report(Diagnostic::new(ResolverError {
-                            span: span.clone(),
-                            kind: error::ResolverErrorKind::NotImplemented(
-                                searched.name.clone(),
-                                name.clone(),
-                            ),
-                        }));
+                    // Implementations of things that are not traits are reported by the type
+                    // checker.
+                    if let Some(values) = module.traits().get(&searched.name).cloned() {
+                        let not_declared = let_names
+                            .iter()
+                            .filter(|x| !values.contains_key(x.0))
+                            .collect::<Vec<_>>();
+
+                        let over_declared = values
+                            .iter()
+                            .filter(|x| !let_names.contains_key(x.0))
+                            .map(|(name, _)| (name.clone(), decl.name.span.clone()))
+                            .collect::<Vec<_>>();
+
+                        for (name, span) in over_declared {
+                            ctx.reporter.report(Diagnostic::new(ResolverError {
+                                span: span.clone(),
+                                kind: error::ResolverErrorKind::NotFound(name.clone()),
+                            }));
+                        }
+
+                        for (name, span) in not_declared {
+                            ctx.reporter.report(Diagnostic::new(ResolverError {
+                                span: span.clone(),
+                                kind: error::ResolverErrorKind::NotImplemented(
+                                    searched.name.clone(),
+                                    name.clone(),
+                                ),
+                            }));
+                        }
                     }
 
                     Some(abs::TraitImpl {
@@ -746,8 +913,11 @@ pub mod top_level {
                             path: searched.path.symbol(),
                             name: searched.name,
                         },
+                        supers,
+                        namespace: ctx.module.name().symbol(),
                         binders,
                         body,
+                        span,
                     })
                 } else {
                     None
@@ -769,6 +939,8 @@ pub mod top_level {
         ctx.module
             .define(DefinitionKind::Value, sig.visibility.clone(), name.clone());
 
+        ctx.add_declaration(name.clone(), span.clone());
+
         Solver::new(move |ctx| {
             ctx.scoped(|ctx| {
                 let binders = sig
@@ -809,6 +981,8 @@ pub mod top_level {
                 decl.signature.visibility.clone(),
                 name.clone(),
             );
+
+            ctx.add_declaration(name.clone(), span.clone());
         }
 
         Solver::new(move |ctx| {
@@ -872,24 +1046,28 @@ pub mod top_level {
         ctx.module
             .define(DefinitionKind::Type, decl.visibility.clone(), name.clone());
 
+        ctx.add_declaration(name.clone(), decl.name.0.value.span.clone());
+
         match &decl.def {
             None => {}
             Some((_, tree::TypeDef::Record(record))) => {
                 for (field, _) in &record.fields {
                     let name = field.name.symbol();
                     let vis = into_field_visiblity(field.visibility.clone().into());
+                    submodule.add_declaration(name.clone(), field.name.0.value.span.clone());
                     submodule.module.define(DefinitionKind::Value, vis, name);
                 }
             }
             Some((_, tree::TypeDef::Sum(sum))) => {
                 for cons in &sum.constructors {
                     let name = cons.name.symbol();
+                    submodule.add_declaration(name.clone(), cons.name.0.value.span.clone());
                     submodule
                         .module
                         .define(DefinitionKind::Value, Visibility::Public, name);
                 }
             }
-            Some((_, tree::TypeDef::Synonym(_synonym))) => todo!(),
+            Some((_, tree::TypeDef::Synonym(_))) => {}
         }
 
         let namespace = submodule.module.name().clone();
@@ -959,7 +1137,9 @@ pub mod top_level {
 
                         abs::TypeDef::Sum(abs::SumDecl { constructors })
                     }
-                    Some((_, tree::TypeDef::Synonym(_synonym))) => todo!(),
+                    Some((_, tree::TypeDef::Synonym(synonym))) => {
+                        abs::TypeDef::Synonym(transform_type(ctx, *synonym))
+                    }
                 };
 
                 abs::TypeDecl {
@@ -976,6 +1156,72 @@ pub mod top_level {
         })
     }
 
+    /// Resolve an effect declaration and returns the solver for it. The operations are declared
+    /// inside of the namespace of the effect like the constructors of a type.
+    pub fn resolve_effect(ctx: Context, decl: tree::EffectDecl) -> Solver<abs::EffectDecl> {
+        let name = decl.name.symbol();
+        let submodule = ctx.fork(decl.name.symbol());
+
+        ctx.module
+            .define(DefinitionKind::Type, decl.visibility.clone(), name.clone());
+
+        ctx.add_declaration(name.clone(), decl.name.0.value.span.clone());
+
+        for field in &decl.fields {
+            let name = field.name.symbol();
+            submodule.add_declaration(name.clone(), field.name.0.value.span.clone());
+            submodule
+                .module
+                .define(DefinitionKind::Value, field.visibility.clone(), name);
+        }
+
+        let namespace = submodule.module.name().symbol();
+
+        Solver::new(move |ctx| {
+            ctx.scoped(|ctx| {
+                let binders = decl
+                    .binders
+                    .into_iter()
+                    .map(|x| transform_type_binder(ctx, x))
+                    .collect::<Vec<_>>();
+
+                for binder in &binders {
+                    ctx.with(DefinitionKind::Type, binder.name().clone())
+                }
+
+                let fields = decl
+                    .fields
+                    .into_iter()
+                    .map(|field| abs::EffectField {
+                        name: abs::Qualified {
+                            path: namespace.clone(),
+                            name: field.name.symbol(),
+                        },
+                        visibility: field.visibility.into(),
+                        args: field
+                            .args
+                            .into_iter()
+                            .map(|x| transform_type(ctx, *x))
+                            .collect(),
+                        ret: transform_type(ctx, *field.ret),
+                    })
+                    .collect();
+
+                abs::EffectDecl {
+                    visibility: decl.visibility.into(),
+                    name: abs::Qualified {
+                        path: ctx.module.name().symbol(),
+                        name,
+                    },
+                    namespace,
+                    binders,
+                    fields,
+                    span: decl.name.0.value.span.clone(),
+                }
+            })
+        })
+    }
+
     /// Resolve an external declaration and returns the solver for it.
     pub fn resolve_external(ctx: Context, decl: tree::ExtDecl) -> Solver<abs::ExtDecl> {
         let name = decl.name.symbol();
@@ -983,6 +1229,8 @@ pub mod top_level {
         ctx.module
             .define(DefinitionKind::Value, decl.visibility.clone(), name.clone());
 
+        ctx.add_declaration(name.clone(), decl.name.0.value.span.clone());
+
         let namespace = ctx.module.name().clone();
 
         Solver::new(move |module| abs::ExtDecl {
@@ -993,6 +1241,7 @@ pub mod top_level {
             namespace: namespace.symbol(),
             visibility: decl.visibility.into(),
             typ: transform_type(&module, *decl.typ),
+            span: decl.str.value.span.clone(),
             ret: decl.str.symbol(),
         })
     }
@@ -1021,6 +1270,7 @@ pub mod top_level {
                         abs::TopLevel::Module(x) => program.modules.push(x),
                         abs::TopLevel::External(x) => program.externals.push(x),
                         abs::TopLevel::Trait(t) => program.traits.push(t),
+                        abs::TopLevel::Effect(e) => program.effects.push(e),
                         abs::TopLevel::Impl(Some(t)) => program.impls.push(t),
                         abs::TopLevel::Impl(None) => (),
                         abs::TopLevel::Command(name, symbol) => {
@@ -1108,10 +1358,13 @@ pub mod pattern {
                     from_constructor_upper_path(&x),
                 );
                 match func {
-                    Some(res) => abs::PatternKind::Application(abs::PatApplication {
-                        func: res,
-                        args: vec![],
-                    }),
+                    Some(res) => {
+                        ctx.add_reference(pattern.span.clone(), res.clone());
+                        abs::PatternKind::Application(abs::PatApplication {
+                            func: res,
+                            args: vec![],
+                        })
+                    }
                     None => abs::PatternKind::Error,
                 }
             }
@@ -1154,6 +1407,8 @@ pub mod pattern {
 
                 match func {
                     Some(func) => {
+                        ctx.add_reference(app.func.span.clone(), func.clone());
+
                         let args = app
                             .args
                             .into_iter()
@@ -1165,6 +1420,154 @@ pub mod pattern {
                     None => abs::PatternKind::Error,
                 }
             }
+            tree::PatternKind::Effect(eff) => {
+                let func = ctx.resolve(
+                    DefinitionKind::Value,
+                    eff.func.span.clone(),
+                    from_lower_path(&eff.func),
+                );
+
+                match func {
+                    Some(func) => {
+                        ctx.add_reference(eff.func.span.clone(), func.clone());
+
+                        let args = eff
+                            .args
+                            .into_iter()
+                            .map(|x| transform_pat(ctx, *x, vars))
+                            .collect();
+
+                        let cont = eff.cont.map(|(_, name)| {
+                            vars.insert(name.symbol());
+                            name.symbol()
+                        });
+
+                        abs::PatternKind::Effect(abs::PatEffect { func, args, cont })
+                    }
+                    None => abs::PatternKind::Error,
+                }
+            }
+            tree::PatternKind::Record(record) => {
+                let name = ctx.resolve(
+                    DefinitionKind::Type,
+                    record.name.span.clone(),
+                    from_constructor_upper_path(&record.name),
+                );
+
+                match name {
+                    Some(name) => {
+                        ctx.add_reference(record.name.span.clone(), name.clone());
+
+                        // The fields are values inside of the namespace of the record.
+                        let namespace = from_upper_path(&record.name);
+
+                        let fields = record
+                            .fields
+                            .into_iter()
+                            .filter_map(|(field, _)| {
+                                let span = field.name.0.value.span.clone();
+
+                                let qualified = ctx.resolve(
+                                    DefinitionKind::Value,
+                                    span.clone(),
+                                    Qualified {
+                                        path: namespace.clone(),
+                                        name: field.name.symbol(),
+                                    },
+                                )?;
+
+                                ctx.add_reference(span.clone(), qualified.clone());
+
+                                let pat = match field.pat {
+                                    Some((_, pat)) => transform_pat(ctx, *pat, vars),
+                                    None => transform_pat(
+                                        ctx,
+                                        Spanned::new(
+                                            tree::PatternKind::Variable(field.name),
+                                            span.clone(),
+                                        ),
+                                        vars,
+                                    ),
+                                };
+
+                                Some((span, qualified, pat))
+                            })
+                            .collect();
+
+                        abs::PatternKind::Record(abs::PatRecord {
+                            name,
+                            fields,
+                            rest: record.rest.is_some(),
+                        })
+                    }
+                    None => abs::PatternKind::Error,
+                }
+            }
+            tree::PatternKind::List(list) => {
+                // The sub-patterns are resolved even when the list is not available, so their
+                // variables are still bound in the body of the arm.
+                let values: Vec<_> = list
+                    .values
+                    .into_iter()
+                    .map(|(value, _)| transform_pat(ctx, *value, vars))
+                    .collect();
+
+                let rest = list.rest.map(|rest| match rest.pat {
+                    Some(pat) => transform_pat(ctx, *pat, vars),
+                    None => Box::new(Spanned::new(
+                        abs::PatternKind::Wildcard,
+                        rest.dot_dot.value.span.clone(),
+                    )),
+                });
+
+                match ctx.list_constructors(pattern.span.clone()) {
+                    Some((nil, cons)) => {
+                        let tail = rest.unwrap_or_else(|| {
+                            Box::new(Spanned::new(
+                                abs::PatternKind::Application(abs::PatApplication {
+                                    func: nil,
+                                    args: vec![],
+                                }),
+                                list.right_bracket.value.span.clone(),
+                            ))
+                        });
+
+                        let list = values.into_iter().rfold(tail, |acc, value| {
+                            let span = value.span.clone().mix(acc.span.clone());
+                            Box::new(Spanned::new(
+                                abs::PatternKind::Application(abs::PatApplication {
+                                    func: cons.clone(),
+                                    args: vec![value, acc],
+                                }),
+                                span,
+                            ))
+                        });
+
+                        list.data
+                    }
+                    None => abs::PatternKind::Error,
+                }
+            }
+            tree::PatternKind::Or(or) => {
+                let mut left_vars = vars.clone();
+                let left = transform_pat(ctx, *or.left, &mut left_vars);
+
+                let mut right_vars = vars.clone();
+                let right = transform_pat(ctx, *or.right, &mut right_vars);
+
+                // Both alternatives have to bind the same variables, otherwise some of them would
+                // not have a value after the match.
+                for name in left_vars.clone().symmetric_difference(right_vars.clone()) {
+                    ctx.reporter.report(Diagnostic::new(error::ResolverError {
+                        span: pattern.span.clone(),
+                        kind: error::ResolverErrorKind::MissingInOrPattern(name),
+                    }));
+                }
+
+                *vars = left_vars.union(right_vars);
+
+                abs::PatternKind::Or(abs::PatOr { left, right })
+            }
             tree::PatternKind::Parenthesis(x) => {
                 return transform_pat(ctx, *x.data, vars);
             }
@@ -1269,8 +1672,6 @@ pub mod pattern {
 /// Expressions are the ones that can be used in a function body.
 pub mod expr {
     use super::*;
-    use crate::error::ResolverError;
-    use vulpi_syntax::r#abstract::SttmKind::Expr;
 
     /// Transforms an expression into an abstract expression.
     pub fn transform(ctx: &mut Context, expr: concrete::tree::Expr) -> abs::Expr {
@@ -1328,6 +1729,7 @@ pub mod expr {
 
                     match searched {
                         Some(res) => {
+                            ctx.add_reference(expr.span.clone(), res.clone());
                             ctx.insert_constant(res.clone(), expr.span.clone());
                             abs::ExprKind::Function(res)
                         }
@@ -1342,6 +1744,7 @@ pub mod expr {
                     from_constructor_upper_path(&x),
                 ) {
                     Some(res) => {
+                        ctx.add_reference(expr.span.clone(), res.clone());
                         ctx.insert_constant(res.clone(), expr.span.clone());
                         abs::ExprKind::Constructor(res)
                     }
@@ -1353,6 +1756,7 @@ pub mod expr {
                 let searched = ctx.resolve(DefinitionKind::Value, expr.span.clone(), qualified);
                 match searched {
                     Some(res) => {
+                        ctx.add_reference(expr.span.clone(), res.clone());
                         ctx.insert_constant(res.clone(), expr.span.clone());
 
                         abs::ExprKind::Function(res)
@@ -1405,6 +1809,8 @@ pub mod expr {
                 );
 
                 if let Some(path) = path {
+                    ctx.add_reference(bin.op.get_span(), path.clone());
+
                     abs::ExprKind::Application(abs::ApplicationExpr {
                         app: abs::AppKind::Infix,
                         func: Box::new(Spanned::new(
@@ -1442,6 +1848,20 @@ pub mod expr {
                         .collect(),
                 })
             }
+            Handler(handler) => {
+                ctx.in_head = false;
+                abs::ExprKind::Handler(abs::HandlerExpr {
+                    expr: transform(ctx, *handler.expr),
+                    handler: transform(ctx, *handler.handler),
+                })
+            }
+            Cases(cases) => abs::ExprKind::Cases(abs::CasesExpr {
+                arms: cases
+                    .arms
+                    .into_iter()
+                    .map(|x| pattern::transform_pattern_arm(ctx, x))
+                    .collect(),
+            }),
             Do(do_expr) => ctx.scoped(|ctx| {
                 abs::ExprKind::Do(abs::Block {
                     sttms: do_expr
@@ -1468,18 +1888,22 @@ pub mod expr {
                 );
 
                 match path {
-                    Some(name) => abs::ExprKind::RecordInstance(abs::RecordInstance {
-                        name,
-                        fields: record_instance
-                            .fields
-                            .into_iter()
-                            .map(|(field, _)| {
-                                let name = field.name.symbol();
-                                let expr = transform(ctx, *field.expr);
-                                (field.name.0.value.span, name, expr)
-                            })
-                            .collect(),
-                    }),
+                    Some(name) => {
+                        ctx.add_reference(record_instance.name.span.clone(), name.clone());
+
+                        abs::ExprKind::RecordInstance(abs::RecordInstance {
+                            name,
+                            fields: record_instance
+                                .fields
+                                .into_iter()
+                                .map(|(field, _)| {
+                                    let name = field.name.symbol();
+                                    let expr = transform(ctx, *field.expr);
+                                    (field.name.0.value.span, name, expr)
+                                })
+                                .collect(),
+                        })
+                    }
                     None => abs::ExprKind::Error,
                 }
             }
@@ -1512,6 +1936,9 @@ pub mod expr {
             HtmlNode(node) => {
                 transform_html(ctx, expr.span.clone(), node).data
             }
+            Interpolation(interpolation) => {
+                transform_interpolation(ctx, expr.span.clone(), interpolation)
+            }
         };
 
         Box::new(Spanned {
@@ -1566,30 +1993,76 @@ pub mod expr {
         })
     }
 
-    fn fold_list(ctx: &mut Context, span: Span, values: Vec<abs::Expr>) -> abs::ExprKind {
-        let nil = ctx.resolve(
-            DefinitionKind::Value,
-            span.clone(),
-            Qualified {
-                path: Path {
-                    segments: vec![Symbol::intern("List")],
-                },
-                name: Symbol::intern("Nil"),
-            },
-        );
+    /// Desugars an interpolated string into calls to the `concat` function of the prelude, so all
+    /// the interpolated expressions must be strings.
+    fn transform_interpolation(
+        ctx: &mut Context,
+        span: Span,
+        interpolation: tree::InterpolationExpr,
+    ) -> abs::ExprKind {
+        ctx.in_head = false;
 
-        let cons = ctx.resolve(
+        let concat = ctx.resolve(
             DefinitionKind::Value,
-            span.clone(),
+            span,
             Qualified {
                 path: Path {
-                    segments: vec![Symbol::intern("List")],
+                    segments: vec![Symbol::intern("Prelude")],
                 },
-                name: Symbol::intern("Cons"),
+                name: Symbol::intern("concat"),
             },
         );
 
-        if let Some((nil, cons)) = nil.zip(cons) {
+        let Some(concat) = concat else {
+            return abs::ExprKind::Error;
+        };
+
+        let text = |token: &vulpi_syntax::tokens::Token| {
+            Box::new(Spanned::new(
+                abs::ExprKind::Literal(Box::new(Spanned::new(
+                    abs::LiteralKind::String(token.symbol()),
+                    token.value.span.clone(),
+                ))),
+                token.value.span.clone(),
+            ))
+        };
+
+        // The text before the first interpolation is always kept so the result is a string even
+        // if the whole string is a single interpolation.
+        let mut parts = vec![text(&interpolation.start)];
+
+        for (expr, token) in interpolation.parts {
+            parts.push(transform(ctx, *expr));
+
+            if !token.data().is_empty() {
+                parts.push(text(&token));
+            }
+        }
+
+        let mut parts = parts.into_iter();
+        let first = parts.next().unwrap();
+
+        let result = parts.fold(first, |acc, part| {
+            let span = acc.span.clone().mix(part.span.clone());
+
+            Box::new(Spanned::new(
+                abs::ExprKind::Application(abs::ApplicationExpr {
+                    app: abs::AppKind::Infix,
+                    func: Box::new(Spanned::new(
+                        abs::ExprKind::Function(concat.clone()),
+                        part.span.clone(),
+                    )),
+                    args: vec![acc, part],
+                }),
+                span,
+            ))
+        });
+
+        result.data
+    }
+
+    fn fold_list(ctx: &mut Context, span: Span, values: Vec<abs::Expr>) -> abs::ExprKind {
+        if let Some((nil, cons)) = ctx.list_constructors(span.clone()) {
             ctx.insert_constant(nil.clone(), span.clone());
             ctx.insert_constant(cons.clone(), span.clone());
 
@@ -1606,14 +2079,38 @@ pub mod expr {
                     })
                 })
         } else {
-            ctx.reporter.report(Diagnostic::new(ResolverError {
-                span: span.clone(),
-                kind: error::ResolverErrorKind::ListIsNotAvailable,
-            }));
-
             abs::ExprKind::Error
         }
     }
+
+    /// Transforms an expression that is the whole body of the value `name`, like a let without
+    /// binders. It also returns the constants that the expression uses, or nothing if the value
+    /// is not a constant.
+    pub fn transform_constant(
+        ctx: &mut Context,
+        name: abs::Qualified,
+        expr: concrete::tree::Expr,
+    ) -> (abs::Expr, Option<HashMap<abs::Qualified, Span>>) {
+        ctx.set_constant(name.clone());
+        let expr = transform(ctx, expr);
+
+        let constant = if ctx.constant.is_some() {
+            Some(
+                ctx.module
+                    .borrow_mut()
+                    .constants
+                    .remove(&name)
+                    .unwrap_or_default(),
+            )
+        } else {
+            ctx.module.borrow_mut().constants.remove(&name);
+            None
+        };
+
+        ctx.reset_constant();
+
+        (expr, constant)
+    }
 }
 
 /// The super module can access all the names in the module of an struct, so this is useful
@@ -1671,7 +2168,10 @@ pub fn transform_type(ctx: &Context, concrete_type: tree::Type) -> abs::Type {
                 from_constructor_upper_path(&typ),
             );
             match path {
-                Some(res) => abs::TypeKind::Type(res),
+                Some(res) => {
+                    ctx.add_reference(concrete_type.span.clone(), res.clone());
+                    abs::TypeKind::Type(res)
+                }
                 None => abs::TypeKind::Error,
             }
         }
@@ -1680,6 +2180,14 @@ pub fn transform_type(ctx: &Context, concrete_type: tree::Type) -> abs::Type {
             left: transform_type(ctx, *x.left),
             right: transform_type(ctx, *x.right),
         }),
+        tree::TypeKind::Effect(eff) => abs::TypeKind::Effect(abs::TypeEffect {
+            effects: eff
+                .effects
+                .into_iter()
+                .map(|x| transform_type(ctx, *x.0))
+                .collect(),
+            typ: transform_type(ctx, *eff.typ),
+        }),
         tree::TypeKind::Application(app) => {
             let func = transform_type(ctx, *app.func);
             let args = app
@@ -1776,6 +2284,7 @@ pub fn resolve(ctx: &Context, program: tree::Program) -> Solver<abs::Program> {
                 abs::TopLevel::Module(x) => program.modules.push(x),
                 abs::TopLevel::External(x) => program.externals.push(x),
                 abs::TopLevel::Trait(x) => program.traits.push(x),
+                abs::TopLevel::Effect(x) => program.effects.push(x),
                 abs::TopLevel::Impl(Some(t)) => program.impls.push(t),
                 abs::TopLevel::Command(name, symbol) => program.commands.push((name, symbol)),
                 abs::TopLevel::Impl(None) => (),
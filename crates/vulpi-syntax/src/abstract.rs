@@ -1,5 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
+use serde::{Deserialize, Serialize};
 use vulpi_intern::Symbol;
 use vulpi_location::{Span, Spanned};
 use vulpi_macros::Show;
@@ -18,6 +19,7 @@ impl Qualified {
         format!("{}${}", self.path.get(), self.name.get())
             .replace('.', "$")
             .replace('?', "INT")
+            .replace('!', "BANG")
     }
 
     pub fn to_string(&self) -> String {
@@ -25,6 +27,29 @@ impl Qualified {
     }
 }
 
+/// Qualified names are stored in their dotted form, so they can be used as keys of maps in formats
+/// like JSON. Names never contain dots, so the last one always separates the name from the path.
+impl Serialize for Qualified {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Qualified {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+
+        let Some((path, name)) = string.rsplit_once('.') else {
+            return Err(serde::de::Error::custom("expected a qualified name"));
+        };
+
+        Ok(Qualified {
+            path: Symbol::intern(path),
+            name: Symbol::intern(name),
+        })
+    }
+}
+
 impl Show for Qualified {
     fn show(&self) -> TreeDisplay {
         TreeDisplay::label("Qualified")
@@ -78,9 +103,16 @@ pub struct TypeForall {
     pub body: Type,
 }
 
+#[derive(Show)]
+pub struct TypeEffect {
+    pub effects: Vec<Type>,
+    pub typ: Type,
+}
+
 #[derive(Show)]
 pub enum TypeKind {
     Arrow(PiType),
+    Effect(TypeEffect),
     Tuple(Vec<Type>),
     Application(TypeApplication),
     Forall(TypeForall),
@@ -102,6 +134,15 @@ impl TypeKind {
 
                 set
             }
+            TypeKind::Effect(eff) => {
+                let mut set = eff.typ.data.free_variables();
+
+                for effect in &eff.effects {
+                    set.extend(effect.data.free_variables());
+                }
+
+                set
+            }
             TypeKind::Tuple(t) => {
                 let mut set = HashSet::new();
 
@@ -142,6 +183,40 @@ impl TypeKind {
             _ => HashSet::new(),
         }
     }
+
+    /// The names of the types that are used inside of the type, in the order that they appear.
+    pub fn type_names(&self) -> Vec<Qualified> {
+        match self {
+            TypeKind::Arrow(pi) => {
+                let mut names = pi.left.data.type_names();
+                names.extend(pi.right.data.type_names());
+                names
+            }
+            TypeKind::Effect(eff) => {
+                let mut names = Vec::new();
+
+                for effect in &eff.effects {
+                    names.extend(effect.data.type_names());
+                }
+
+                names.extend(eff.typ.data.type_names());
+                names
+            }
+            TypeKind::Tuple(t) => t.iter().flat_map(|typ| typ.data.type_names()).collect(),
+            TypeKind::Application(app) => {
+                let mut names = app.func.data.type_names();
+
+                for arg in &app.args {
+                    names.extend(arg.data.type_names());
+                }
+
+                names
+            }
+            TypeKind::Forall(f) => f.body.data.type_names(),
+            TypeKind::Type(name) => vec![name.clone()],
+            _ => Vec::new(),
+        }
+    }
 }
 
 // Literal
@@ -199,6 +274,20 @@ pub struct PatApplication {
     pub args: Vec<Pattern>,
 }
 
+#[derive(Show)]
+pub struct PatRecord {
+    pub name: Qualified,
+    pub fields: Vec<(Span, Qualified, Pattern)>,
+    pub rest: bool,
+}
+
+#[derive(Show)]
+pub struct PatEffect {
+    pub func: Qualified,
+    pub args: Vec<Pattern>,
+    pub cont: Option<Symbol>,
+}
+
 #[derive(Show)]
 pub enum PatternKind {
     Wildcard,
@@ -208,6 +297,8 @@ pub enum PatternKind {
     Ascription(PatAscription),
     Or(PatOr),
     Application(PatApplication),
+    Effect(PatEffect),
+    Record(PatRecord),
 
     Error,
 }
@@ -252,6 +343,17 @@ pub struct WhenExpr {
     pub arms: Vec<PatternArm>,
 }
 
+#[derive(Show)]
+pub struct HandlerExpr {
+    pub expr: Expr,
+    pub handler: Expr,
+}
+
+#[derive(Show)]
+pub struct CasesExpr {
+    pub arms: Vec<PatternArm>,
+}
+
 #[derive(Show)]
 pub struct AnnotationExpr {
     pub expr: Expr,
@@ -295,6 +397,8 @@ pub enum ExprKind {
     Let(LetExpr),
     When(WhenExpr),
     Do(Block),
+    Handler(HandlerExpr),
+    Cases(CasesExpr),
     Literal(Literal),
 
     Annotation(AnnotationExpr),
@@ -328,7 +432,7 @@ impl ExprKind {
 
 pub type Expr = Box<Spanned<ExprKind>>;
 
-#[derive(Show, Clone, PartialEq, Eq)]
+#[derive(Show, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Visibility {
     Public,
     Super,
@@ -388,8 +492,29 @@ pub struct TraitDecl {
 #[derive(Show)]
 pub struct TraitImpl {
     pub name: Qualified,
+    pub supers: Vec<Type>,
+    pub namespace: Symbol,
     pub binders: Vec<Type>,
     pub body: Vec<LetDecl>,
+    pub span: Span,
+}
+
+#[derive(Show)]
+pub struct EffectField {
+    pub name: Qualified,
+    pub visibility: Visibility,
+    pub args: Vec<Type>,
+    pub ret: Type,
+}
+
+#[derive(Show)]
+pub struct EffectDecl {
+    pub visibility: Visibility,
+    pub name: Qualified,
+    pub namespace: Symbol,
+    pub binders: Vec<TypeBinder>,
+    pub fields: Vec<EffectField>,
+    pub span: Span,
 }
 
 #[derive(Show)]
@@ -447,6 +572,9 @@ pub struct ExtDecl {
     pub namespace: Symbol,
     pub typ: Type,
     pub ret: Symbol,
+
+    /// The place of the binding, like `"console.log"`.
+    pub span: Span,
 }
 
 pub enum TopLevel {
@@ -455,6 +583,7 @@ pub enum TopLevel {
     Module(ModuleDecl),
     External(ExtDecl),
     Trait(TraitDecl),
+    Effect(EffectDecl),
     Impl(Option<TraitImpl>),
     Command(Symbol, Symbol),
     Use,
@@ -467,6 +596,7 @@ pub struct Program {
     pub modules: Vec<ModuleDecl>,
     pub traits: Vec<TraitDecl>,
     pub impls: Vec<TraitImpl>,
+    pub effects: Vec<EffectDecl>,
     pub externals: Vec<ExtDecl>,
     pub commands: Vec<(Symbol, Symbol)>
 }
@@ -71,6 +71,14 @@ pub struct ListExpr {
     pub right_bracket: Token,
 }
 
+/// A string with expressions inside of it like `"Count: {count}"`. Each expression is followed by
+/// the text that comes after it.
+#[derive(Show, Clone)]
+pub struct InterpolationExpr {
+    pub start: Token,
+    pub parts: Vec<(Box<Expr>, Token)>,
+}
+
 #[derive(Show, Clone)]
 pub struct ApplicationExpr {
     pub func: Box<Expr>,
@@ -117,6 +125,20 @@ pub struct WhenExpr {
     pub arms: Vec<PatternArm>,
 }
 
+#[derive(Show, Clone)]
+pub struct HandlerExpr {
+    pub handle: Token,
+    pub expr: Box<Expr>,
+    pub with: Token,
+    pub handler: Box<Expr>,
+}
+
+#[derive(Show, Clone)]
+pub struct CasesExpr {
+    pub cases: Token,
+    pub arms: Vec<PatternArm>,
+}
+
 #[derive(Show, Clone)]
 pub struct AnnotationExpr {
     pub expr: Box<Expr>,
@@ -182,6 +204,7 @@ pub type Tuple = Parenthesis<Vec<(Box<Spanned<ExprKind>>, Option<Token>)>>;
 pub enum ExprKind {
     Lambda(LambdaExpr),
     List(ListExpr),
+    Interpolation(InterpolationExpr),
     Application(ApplicationExpr),
     HtmlNode(HtmlNode),
 
@@ -194,6 +217,8 @@ pub enum ExprKind {
     Let(LetExpr),
     When(WhenExpr),
     Do(DoExpr),
+    Handler(HandlerExpr),
+    Cases(CasesExpr),
     Literal(Literal),
 
     Annotation(AnnotationExpr),
@@ -12,12 +12,57 @@ pub struct PatAscription {
     pub right: Box<Type>,
 }
 
+#[derive(Show, Clone)]
+pub struct PatOr {
+    pub left: Box<Pattern>,
+    pub pipe: Token,
+    pub right: Box<Pattern>,
+}
+
 #[derive(Show, Clone)]
 pub struct PatApplication {
     pub func: Path<Upper>,
     pub args: Vec<Box<Pattern>>,
 }
 
+#[derive(Show, Clone)]
+pub struct PatRecordField {
+    pub name: Lower,
+    pub pat: Option<(Token, Box<Pattern>)>,
+}
+
+#[derive(Show, Clone)]
+pub struct PatRecord {
+    pub name: Path<Upper>,
+    pub left_brace: Token,
+    pub fields: Vec<(PatRecordField, Option<Token>)>,
+    pub rest: Option<Token>,
+    pub right_brace: Token,
+}
+
+#[derive(Show, Clone)]
+pub struct PatListRest {
+    pub dot_dot: Token,
+    pub pat: Option<Box<Pattern>>,
+}
+
+#[derive(Show, Clone)]
+pub struct PatList {
+    pub left_bracket: Token,
+    pub values: Vec<(Box<Pattern>, Option<Token>)>,
+    pub rest: Option<PatListRest>,
+    pub right_bracket: Token,
+}
+
+#[derive(Show, Clone)]
+pub struct PatEffect {
+    pub left_brace: Token,
+    pub func: Path<Lower>,
+    pub args: Vec<Box<Pattern>>,
+    pub cont: Option<(Token, Lower)>,
+    pub right_brace: Token,
+}
+
 #[derive(Show, Clone)]
 pub enum PatternKind {
     Wildcard(Token),
@@ -27,6 +72,10 @@ pub enum PatternKind {
     Annotation(PatAscription),
     Tuple(Vec<(Pattern, Option<Token>)>),
     Application(PatApplication),
+    Effect(PatEffect),
+    Record(PatRecord),
+    List(PatList),
+    Or(PatOr),
     Parenthesis(Parenthesis<Box<Pattern>>),
 }
 
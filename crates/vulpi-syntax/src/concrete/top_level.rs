@@ -144,6 +144,25 @@ pub struct TypeDecl {
     pub def: Option<(Token, TypeDef)>,
 }
 
+#[derive(Show, Clone)]
+pub struct EffectField {
+    pub visibility: Visibility,
+    pub name: Lower,
+    pub args: Vec<Box<Type>>,
+    pub colon: Token,
+    pub ret: Box<Type>,
+}
+
+#[derive(Show, Clone)]
+pub struct EffectDecl {
+    pub visibility: Visibility,
+    pub effect: Token,
+    pub name: Upper,
+    pub binders: Vec<TypeBinder>,
+    pub where_: Token,
+    pub fields: Vec<EffectField>,
+}
+
 #[derive(Show, Clone)]
 pub struct UseAlias {
     pub as_: Token,
@@ -224,8 +243,8 @@ pub struct ExtDecl {
 
 #[derive(Show, Clone)]
 pub struct CommandDecl {
-    pub name: Symbol,
-    pub command: Symbol,
+    pub name: Token,
+    pub command: Token,
 }
 
 #[derive(Show, Clone)]
@@ -235,6 +254,7 @@ pub enum TopLevel {
     Use(Box<UseDecl>),
     Impl(Box<TraitImpl>),
     Trait(Box<TraitDecl>),
+    Effect(Box<EffectDecl>),
     Module(Box<ModuleDecl>),
     Error(Vec<Token>),
     External(Box<ExtDecl>),
@@ -27,6 +27,14 @@ pub struct TypeForall {
     pub body: Box<Type>,
 }
 
+#[derive(Show, Clone)]
+pub struct TypeEffect {
+    pub left_brace: Token,
+    pub effects: Vec<(Box<Type>, Option<Token>)>,
+    pub right_brace: Token,
+    pub typ: Box<Type>,
+}
+
 #[derive(Show, Clone)]
 pub enum TypeKind {
     Parenthesis(Parenthesis<(Box<Type>, Option<Token>)>),
@@ -36,6 +44,7 @@ pub enum TypeKind {
     Arrow(TypeArrow),
     Application(TypeApplication),
     Forall(TypeForall),
+    Effect(TypeEffect),
     Unit(Token),
 }
 
@@ -1,12 +1,14 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
+use serde::{Deserialize, Serialize};
 use vulpi_intern::Symbol;
 use vulpi_location::{Span, Spanned};
 use vulpi_macros::Show;
+use vulpi_show::{Show, TreeDisplay};
 
-use crate::r#abstract::Qualified;
+use crate::r#abstract::{Qualified, Visibility};
 
-#[derive(Show, PartialEq, Eq, Hash, Clone, Debug)]
+#[derive(Show, PartialEq, Eq, Hash, Clone, Debug, Serialize, Deserialize)]
 pub enum LiteralKind {
     String(Symbol),
     Integer(Symbol),
@@ -34,7 +36,7 @@ pub type Statement<T> = SttmKind<T>;
 
 pub type Block<T> = Vec<Statement<T>>;
 
-#[derive(Show, Clone)]
+#[derive(Show, Clone, Debug)]
 pub struct PatOr {
     pub left: Pattern,
     pub right: Pattern,
@@ -46,6 +48,14 @@ pub struct PatApplication {
     pub args: Vec<Pattern>,
 }
 
+/// A record pattern has a pattern for every field of the record in the order of the declaration,
+/// the fields that were not written are wildcards.
+#[derive(Show, Clone, Debug)]
+pub struct PatRecord {
+    pub name: Qualified,
+    pub fields: Vec<(Qualified, Pattern)>,
+}
+
 #[derive(Show, Clone, Debug)]
 pub enum PatternKind {
     Wildcard,
@@ -53,6 +63,8 @@ pub enum PatternKind {
     Literal(Literal),
     Application(PatApplication),
     Tuple(Vec<Pattern>),
+    Record(PatRecord),
+    Or(PatOr),
     Error,
 }
 
@@ -121,6 +133,64 @@ pub struct Tuple<T> {
     pub exprs: Vec<Expr<T>>,
 }
 
+/// Runs a computation with a handler for one of its effects. The handler receives the requests of
+/// the effect and the final value of the computation.
+#[derive(Show, Clone)]
+pub struct HandleExpr<T> {
+    pub effect: Qualified,
+    pub body: Expr<T>,
+    pub handler: Expr<T>,
+
+    /// If the handler returns a computation that has to be performed after it.
+    pub effectful: bool,
+}
+
+/// A dictionary is the runtime proof that a type implements a trait. It contains the
+/// implementation of the super traits followed by the implementation of the methods.
+#[derive(Show, Clone)]
+pub enum Dictionary {
+    /// A dictionary that was received as a parameter.
+    Variable(Symbol),
+
+    /// A global instance applied to the dictionaries that its context requires.
+    Instance(Qualified, Vec<Dictionary>),
+
+    /// The dictionary of a super trait that lives inside of another dictionary.
+    Super(Box<Dictionary>, usize),
+
+    Error,
+}
+
+/// A dictionary that is only known after the constraints of the declaration are solved.
+#[derive(Clone)]
+pub struct Evidence(pub Rc<RefCell<Dictionary>>);
+
+impl Evidence {
+    pub fn new() -> Self {
+        Self(Rc::new(RefCell::new(Dictionary::Error)))
+    }
+
+    pub fn fill(&self, dictionary: Dictionary) {
+        *self.0.borrow_mut() = dictionary;
+    }
+
+    pub fn get(&self) -> Dictionary {
+        self.0.borrow().clone()
+    }
+}
+
+impl Default for Evidence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Show for Evidence {
+    fn show(&self) -> TreeDisplay {
+        self.0.borrow().show()
+    }
+}
+
 #[derive(Show, Clone)]
 pub enum ExprKind<T> {
     Lambda(LambdaExpr<T>),
@@ -140,6 +210,16 @@ pub enum ExprKind<T> {
     RecordUpdate(RecordUpdate<T>),
     Tuple(Tuple<T>),
 
+    Dictionary(Evidence),
+
+    /// Delays an expression that performs effects.
+    Computation(Expr<T>),
+
+    /// Runs a computation inside of the current one.
+    Perform(Expr<T>),
+
+    Handle(HandleExpr<T>),
+
     Error,
 }
 
@@ -148,6 +228,8 @@ pub type Expr<T> = Spanned<Box<ExprKind<T>>>;
 #[derive(Show, Clone)]
 pub struct LetDecl<T> {
     pub name: Qualified,
+    pub span: Span,
+    pub visibility: Visibility,
     pub binders: Vec<(Pattern, T)>,
     pub body: Vec<PatternArm<T>>,
     pub constants: Option<HashMap<Qualified, Span>>,
@@ -165,6 +247,44 @@ pub struct ExternalDecl<T> {
     pub name: Qualified,
     pub typ: T,
     pub binding: Symbol,
+    pub span: Span,
+}
+
+#[derive(Show, Clone)]
+pub struct TraitDecl {
+    pub supers: usize,
+    pub methods: Vec<Qualified>,
+}
+
+/// The operations of an effect together with the number of arguments of each one of them.
+#[derive(Show, Clone)]
+pub struct EffectDecl {
+    pub operations: Vec<(Qualified, usize)>,
+}
+
+/// The name of the type of the requests that a handler receives.
+pub fn request_type() -> Qualified {
+    Qualified {
+        path: Symbol::intern("Prelude"),
+        name: Symbol::intern("Request"),
+    }
+}
+
+/// The request that a handler receives when the computation finishes with a value. The requests
+/// of the operations of an effect come after it.
+pub fn return_constructor() -> Qualified {
+    Qualified {
+        path: Symbol::intern("Prelude.Request"),
+        name: Symbol::intern("Return"),
+    }
+}
+
+#[derive(Show, Clone)]
+pub struct InstanceDecl {
+    pub name: Qualified,
+    pub context: Vec<Symbol>,
+    pub supers: Vec<Dictionary>,
+    pub methods: Vec<Qualified>,
 }
 
 #[derive(Show, Clone)]
@@ -173,6 +293,9 @@ pub struct Program<T> {
     pub lets: HashMap<Qualified, LetDecl<T>>,
     pub types: HashMap<Qualified, TypeDecl>,
     pub externals: HashMap<Qualified, ExternalDecl<T>>,
+    pub traits: HashMap<Qualified, TraitDecl>,
+    pub instances: HashMap<Qualified, InstanceDecl>,
+    pub effects: HashMap<Qualified, EffectDecl>,
     pub commands: Vec<(Symbol, Symbol)>,
 }
 
@@ -183,6 +306,9 @@ impl<T> Default for Program<T> {
             lets: HashMap::new(),
             types: HashMap::new(),
             externals: HashMap::new(),
+            traits: HashMap::new(),
+            instances: HashMap::new(),
+            effects: HashMap::new(),
             commands: Vec::new(),
         }
     }
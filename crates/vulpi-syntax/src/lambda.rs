@@ -1,12 +1,16 @@
 use std::{collections::HashMap};
 
+use serde::{Deserialize, Serialize};
 use vulpi_intern::Symbol;
 use vulpi_location::Span;
 use vulpi_macros::Show;
 
-use crate::{r#abstract::Qualified, elaborated::Literal};
+use crate::{
+    elaborated::Literal,
+    r#abstract::{Qualified, Visibility},
+};
 
-#[derive(Show, Clone)]
+#[derive(Show, Clone, Serialize, Deserialize)]
 pub enum ConsDef {
     Enumerated(Qualified, usize),
     Heavy(Qualified, usize, usize),
@@ -14,26 +18,50 @@ pub enum ConsDef {
     Tuple,
 }
 
-#[derive(Clone, Show, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Show, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Case {
     Tuple(usize),
     Constructor(Qualified, usize),
+    Record(Qualified, Vec<Qualified>),
     Literal(Literal),
 }
 
-#[derive(Show, Clone)]
+#[derive(Show, Clone, Serialize, Deserialize)]
 pub enum Stmt {
     Let(Symbol, Expr),
     Expr(Expr),
+
+    /// Adds a reference to the value of a variable. The instructions that count references are
+    /// only added by the Perceus pass, for backends that manage the memory themselves.
+    Dup(Symbol),
+
+    /// Removes a reference to the value of a variable and frees it, together with the references
+    /// that it holds, if it was the last one.
+    Drop(Symbol),
+
+    /// Removes a reference to an object that is known to have other references, so it's never
+    /// freed.
+    Decref(Symbol),
+
+    /// Frees the memory of an object without removing the references that it holds.
+    Free(Symbol),
+
+    /// Sets a reuse token to the memory of an object whose references were already removed, or
+    /// to an empty token.
+    Token(Symbol, Option<Symbol>),
+
+    /// Runs the first statements if the variable holds the only reference to its value, and the
+    /// second ones otherwise.
+    Unique(Symbol, Vec<Stmt>, Vec<Stmt>),
 }
 
-#[derive(Show, Clone)]
+#[derive(Show, Clone, Serialize, Deserialize)]
 pub enum Tree {
     Leaf(usize),
-    Switch(Expr, Vec<(Case, TagType, Tree)>),
+    Switch(Expr, Vec<(Case, TagType, Tree)>, Option<Box<Tree>>),
 }
 
-#[derive(Show, Clone)]
+#[derive(Show, Clone, Serialize, Deserialize)]
 pub enum TagType {
     Field(usize),
     Number(usize),
@@ -41,16 +69,22 @@ pub enum TagType {
     None
 }
 
-#[derive(Show, Clone)]
+#[derive(Show, Clone, Serialize, Deserialize)]
 pub enum ExprKind {
     Lambda(Vec<Symbol>, Expr),
-    Application(Expr, Vec<Expr>),
+
+    /// Calls a function. The span is the place of the call in the source code, if it came from
+    /// there.
+    Application(Expr, Vec<Expr>, Option<Span>),
 
     Variable(Symbol),
     Constructor(Qualified),
     Function(Qualified),
     Object(usize, Vec<Expr>),
 
+    /// Creates an object in the memory of a reuse token, or in new memory if the token is empty.
+    Reuse(Symbol, usize, Vec<Expr>),
+
     Projection(Qualified, Expr),
     Access(Expr, usize),
 
@@ -63,36 +97,59 @@ pub enum ExprKind {
     Tuple(Vec<Expr>),
 
     Switch(Symbol, Tree, Vec<Expr>),
-    
+
+    /// A suspended expression that can perform effects when it runs.
+    Computation(Expr),
+
+    /// Runs a computation and returns its result.
+    Perform(Expr),
+
+    /// Runs the computation with the handler of an effect. The flag says if the handler returns
+    /// a computation, and the span is the place of the handler in the source code.
+    Handle(Qualified, Expr, Expr, bool, Option<Span>),
+
+    /// Sends the request of an operation to the closest handler of the effect.
+    Request(Qualified, usize, Vec<Expr>),
 }
 
 pub type Expr = Box<ExprKind>;
 
-#[derive(Show, Clone)]
+#[derive(Show, Clone, Serialize, Deserialize)]
 pub struct LetDecl {
     pub name: Qualified,
+
+    /// The name of the declaration in the source code. Declarations created by the compiler
+    /// don't have one.
+    pub span: Option<Span>,
+
+    /// Declarations created by the compiler are private.
+    pub visibility: Visibility,
+
     pub body: Expr,
     pub is_in_source_code: bool,
     pub constants: Option<HashMap<Qualified, Span>>,
 }
 
-#[derive(Show, Clone)]
+#[derive(Show, Clone, Serialize, Deserialize)]
 pub enum TypeDecl {
     Abstract,
     Enum(Vec<(Qualified, usize)>),
     Record(Vec<Qualified>),
 }
 
-#[derive(Show, Clone)]
+#[derive(Show, Clone, Serialize, Deserialize)]
 pub struct ExternalDecl {
     pub name: Qualified,
     pub binding: Symbol,
+
+    /// The binding in the source code, so the backends that don't have it can point to it.
+    pub span: Span,
 }
 
-#[derive(Show, Clone, Default)]
+#[derive(Show, Clone, Default, Serialize, Deserialize)]
 pub struct Program {
     pub lets: Vec<(Qualified, LetDecl)>,
-    pub externals: Vec<(Qualified, Symbol)>,
+    pub externals: Vec<ExternalDecl>,
     pub commands: Vec<(Symbol, Symbol)>,
     pub definitions: HashMap<Qualified, (ConsDef, usize)>,
 }
\ No newline at end of file
@@ -32,10 +32,13 @@ pub enum TokenData {
     Trait,    // 'trait' keyword
     Impl,     // 'impl' keyword
 
-    String, // String literal
-    Int,    // Integer literal
-    Float,  // Float Literal
-    Char,   // Char literal
+    String,       // String literal
+    StringStart,  // Start of an interpolated string until the first '{'
+    StringMiddle, // Part of an interpolated string between a '}' and a '{'
+    StringEnd,    // End of an interpolated string after the last '}'
+    Int,          // Integer literal
+    Float,        // Float Literal
+    Char,         // Char literal
 
     LBrace,     // '{'
     RBrace,     // '}'
@@ -57,6 +60,7 @@ pub enum TokenData {
     Semicolon,   // ';'
     Comma,       // ','
     Dot,         // '.'
+    DotDot,      // '..'
     Exclamation, // '!'
     Equal,       // '='
     Bar,         // '|'
@@ -138,6 +142,9 @@ impl ToString for Token {
 
         match self.kind {
             String => format!("\"{}\"", self.value.data.get()),
+            StringStart => format!("\"{}{{", self.value.data.get()),
+            StringMiddle => format!("}}{}{{", self.value.data.get()),
+            StringEnd => format!("}}{}\"", self.value.data.get()),
             Int => format!("int({})", self.value.data.get()),
             Float => format!("float({})", self.value.data.get()),
             LowerIdent => format!("lower {}", self.value.data.get()),
@@ -146,6 +153,7 @@ impl ToString for Token {
             Semicolon => ";".to_string(),
             Comma => ",".to_string(),
             Dot => ".".to_string(),
+            DotDot => "..".to_string(),
             Exclamation => "!".to_string(),
             Equal => "=".to_string(),
             Bar => "|".to_string(),
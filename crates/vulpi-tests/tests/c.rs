@@ -0,0 +1,132 @@
+//! Tests for the C backend, that compile projects to C files and run them with the C compiler of
+//! the system when there is one.
+
+use std::{
+    fs,
+    path::{self, PathBuf},
+    process::Command,
+};
+
+use vulpi_build::{real::RealFileSystem, ProjectCompiler};
+use vulpi_intern::Symbol;
+use vulpi_report::{hash::HashReporter, Report};
+use vulpi_vfs::path::Path;
+
+const PRELUDE: &str = include_str!("../prelude/Prelude.vp");
+
+/// Compiles the `Main` module of a project to `Main.c`, and returns the codes of the errors.
+fn compile(directory: &path::Path, main: &str) -> Vec<String> {
+    let _ = fs::remove_dir_all(directory);
+    fs::create_dir_all(directory.join("build")).unwrap();
+    fs::write(directory.join("Prelude.vp"), PRELUDE).unwrap();
+    fs::write(directory.join("Main.vp"), main).unwrap();
+
+    let package = Symbol::intern("Tc");
+
+    let mut compiler = ProjectCompiler {
+        fs: RealFileSystem::new(
+            package.clone(),
+            directory.to_path_buf(),
+            directory.join("build"),
+        ),
+        reporter: Report::new(HashReporter::new()),
+        name: package.clone(),
+    };
+
+    let root = Path {
+        segments: vec![package, Symbol::intern("Main")],
+    };
+
+    compiler.compile_c(root, PathBuf::from("Main.vp"), directory.join("Main.c"));
+
+    compiler
+        .reporter
+        .all_diagnostics()
+        .iter()
+        .filter_map(|diagnostic| diagnostic.code())
+        .map(vulpi_report::codes::name)
+        .collect()
+}
+
+fn directory(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("vulpi-c-{name}-{}", std::process::id()))
+}
+
+#[test]
+fn compiles_and_runs() {
+    let directory = directory("run");
+
+    let errors = compile(
+        &directory,
+        "use Prelude\n\
+         let sum (n: Int) (acc: Int) : Int =\n    \
+             when n is\n        \
+                 0 => acc\n        \
+                 _ => sum (sub n 1) (add acc n)\n\
+         let main : () = log (sum 100 0)\n",
+    );
+
+    assert!(errors.is_empty(), "{errors:?}");
+    assert!(directory.join("vulpi.h").is_file());
+
+    let binary = directory.join("main");
+
+    let compiled = Command::new("cc")
+        .arg(directory.join("Main.c"))
+        .arg("-o")
+        .arg(&binary)
+        .status();
+
+    // The generated code is still checked when there is no C compiler.
+    if compiled.is_ok_and(|status| status.success()) {
+        let output = Command::new(&binary).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "5050\n");
+    }
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn effects_are_errors() {
+    let directory = directory("effects");
+
+    let errors = compile(
+        &directory,
+        "use Prelude\n\
+         pub effect Log where\n    \
+             pub log String : ()\n\
+         let run! : Request Log a -> a =\n    \
+             cases\n        \
+                 { Log.log s -> k } => handle k () with run!\n        \
+                 other => other\n\
+         let greet : {Log} () = Log.log \"hello\"\n\
+         let main : () = handle greet with run!\n",
+    );
+
+    let written = directory.join("Main.c").exists();
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(
+        !errors.is_empty() && errors.iter().all(|code| code == "E0400"),
+        "{errors:?}"
+    );
+    assert!(!written);
+}
+
+#[test]
+fn externals_without_primitives_are_errors() {
+    let directory = directory("externals");
+
+    let errors = compile(
+        &directory,
+        "use Prelude\n\
+         external now : () -> Int = \"Date.now\"\n\
+         let main : () = log (now ())\n",
+    );
+
+    let written = directory.join("Main.c").exists();
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(errors, ["E0401"]);
+    assert!(!written);
+}
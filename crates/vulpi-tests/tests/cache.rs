@@ -0,0 +1,87 @@
+//! Tests for the incremental compilation, that need more than one compilation of the same
+//! project.
+
+use std::{
+    fs,
+    path::{self, PathBuf},
+};
+
+use vulpi_build::{real::RealFileSystem, ProjectCompiler};
+use vulpi_eval::Interpreter;
+use vulpi_intern::Symbol;
+use vulpi_report::{hash::HashReporter, Report};
+use vulpi_vfs::path::Path;
+
+const PRELUDE: &str = include_str!("../prelude/Prelude.vp");
+
+/// Compiles a module of the project with the cache in its build directory, and returns the
+/// output of its `main` or the messages of its errors.
+fn eval(directory: &path::Path, module: &str) -> String {
+    let package = Symbol::intern("Tc");
+
+    let mut compiler = ProjectCompiler {
+        fs: RealFileSystem::new(
+            package.clone(),
+            directory.to_path_buf(),
+            directory.join("build"),
+        ),
+        reporter: Report::new(HashReporter::new()),
+        name: package.clone(),
+    };
+
+    let root = Path {
+        segments: vec![package, Symbol::intern(module)],
+    };
+
+    let Some((main, programs)) = compiler.eval(root, PathBuf::from(format!("{module}.vp"))) else {
+        return compiler
+            .reporter
+            .all_diagnostics()
+            .iter()
+            .map(|diagnostic| format!("{}\n", diagnostic.message()))
+            .collect();
+    };
+
+    let mut out = Vec::new();
+    if let Err(err) = Interpreter::new(&programs, &mut out).run(&main) {
+        return format!("{err}\n");
+    }
+
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn changed_interfaces_invalidate_importers() {
+    let directory = std::env::temp_dir().join(format!("vulpi-cache-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(directory.join("build")).unwrap();
+
+    let write = |name: &str, content: &str| fs::write(directory.join(name), content).unwrap();
+
+    write("Prelude.vp", PRELUDE);
+    write("B.vp", "use Prelude\npub let val : Int = 1\n");
+    write(
+        "A.vp",
+        "use Prelude\nuse B\npub let get : Int = add B.val 41\n",
+    );
+    write("Main.vp", "use Prelude\nuse A\nlet main : () = log A.get\n");
+    write(
+        "Other.vp",
+        "use Prelude\nuse B\nlet main : () = log B.val\n",
+    );
+
+    assert_eq!(eval(&directory, "Main"), "42\n");
+
+    // `Other` stores the new `B` in the cache, but the `A` in the cache was checked against the
+    // old one.
+    write(
+        "B.vp",
+        "use Prelude\npub let val : String = \"not an int\"\n",
+    );
+    assert_eq!(eval(&directory, "Other"), "not an int\n");
+
+    let errors = eval(&directory, "Main");
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(errors.contains("type mismatch"), "{errors}");
+}
@@ -0,0 +1,234 @@
+//! Tests for the diagnostics themselves: their severities, the formats that other tools read and
+//! their codes.
+
+use std::{fs, path::PathBuf};
+
+use serde_json::Value;
+use vulpi_build::{memory::MemoryFileSystem, ProjectCompiler};
+use vulpi_intern::Symbol;
+use vulpi_report::{
+    codes,
+    hash::HashReporter,
+    renderer::{json::Json, sarif::Sarif},
+    Applicability, Diagnostic, Report, Severity,
+};
+use vulpi_vfs::path::Path;
+
+const PRELUDE: &str = include_str!("../prelude/Prelude.vp");
+
+/// A module with an arm that can never be reached, that is only a warning.
+const UNREACHABLE: &str = "use Prelude\n\
+                           let pick (x: Bool) : Int =\n    \
+                               when x is\n        \
+                                   _ => 0\n        \
+                                   Bool.True => 1\n\
+                           let main : () = log (pick Bool.True)\n";
+
+/// A module whose declaration has a body of the wrong type.
+const MISMATCH: &str = "use Prelude\n\
+                        let value : Int = \"text\"\n";
+
+/// A module with a match that misses one of the constructors.
+const NON_EXHAUSTIVE: &str = "use Prelude\n\
+                              let pick (x: Bool) : Int =\n    \
+                                  when x is\n        \
+                                      Bool.True => 1\n";
+
+/// Checks and lowers a `Main` module that only exists in memory, and returns the compiler
+/// together with whether the module could run.
+fn compile(main: &str, reporter: HashReporter) -> (ProjectCompiler<MemoryFileSystem>, bool) {
+    let package = Symbol::intern("Td");
+    let directory = PathBuf::from("/diagnostics");
+
+    let mut fs = MemoryFileSystem::new(package.clone(), directory.clone(), directory.join("build"));
+    fs.open(PathBuf::from("Prelude.vp"), PRELUDE.to_string());
+    fs.open(PathBuf::from("Main.vp"), main.to_string());
+
+    let mut compiler = ProjectCompiler {
+        fs,
+        reporter: Report::new(reporter),
+        name: package.clone(),
+    };
+
+    let root = Path {
+        segments: vec![package, Symbol::intern("Main")],
+    };
+
+    let runs = compiler.eval(root, PathBuf::from("Main.vp")).is_some();
+    (compiler, runs)
+}
+
+fn diagnostics(compiler: &ProjectCompiler<MemoryFileSystem>) -> Vec<Diagnostic> {
+    compiler.reporter.all_diagnostics()
+}
+
+#[test]
+fn warnings_do_not_stop_the_build() {
+    let (compiler, runs) = compile(UNREACHABLE, HashReporter::new());
+
+    let diagnostics = diagnostics(&compiler);
+    assert_eq!(diagnostics.len(), 1);
+    assert!(matches!(diagnostics[0].severity(), Severity::Warning));
+    assert!(!compiler.reporter.has_errors());
+    assert!(runs);
+}
+
+#[test]
+fn denied_warnings_are_errors() {
+    let (compiler, runs) = compile(UNREACHABLE, HashReporter::new().deny_warnings());
+
+    let diagnostics = diagnostics(&compiler);
+    assert_eq!(diagnostics.len(), 1);
+    assert!(matches!(diagnostics[0].severity(), Severity::Error));
+    assert!(compiler.reporter.has_errors());
+    assert!(!runs);
+}
+
+#[test]
+fn json_lines() {
+    let (compiler, _) = compile(MISMATCH, HashReporter::new());
+
+    let mut out = Vec::new();
+    let ctx = Json::new(&compiler.fs, PathBuf::from("/diagnostics"));
+    compiler.reporter.to_json_lines(ctx, &mut out).unwrap();
+
+    let lines: Vec<Value> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(lines.len(), 1);
+
+    let diagnostic = &lines[0];
+    assert_eq!(diagnostic["file"], "Main.vp");
+    assert_eq!(diagnostic["severity"], "error");
+    assert_eq!(diagnostic["code"], "E0302");
+    assert_eq!(diagnostic["message"], "type mismatch: String != Int");
+    assert_eq!(diagnostic["range"]["start"]["line"], 2);
+    assert_eq!(diagnostic["range"]["start"]["column"], 19);
+    assert_eq!(
+        diagnostic["markers"][0]["label"],
+        "expected Int because of this"
+    );
+}
+
+#[test]
+fn sarif() {
+    let (compiler, _) = compile(MISMATCH, HashReporter::new());
+
+    let mut out = Vec::new();
+    let ctx = Sarif::new(&compiler.fs, PathBuf::from("/diagnostics"));
+    compiler.reporter.to_sarif(ctx, &mut out).unwrap();
+
+    let log: Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(log["version"], "2.1.0");
+
+    let results = log["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+
+    let result = &results[0];
+    assert_eq!(result["ruleId"], "E0302");
+    assert_eq!(result["level"], "error");
+
+    let location = &result["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "Main.vp");
+    assert_eq!(location["region"]["startLine"], 2);
+}
+
+#[test]
+fn mismatches_point_at_the_expected_type() {
+    let (compiler, _) = compile(MISMATCH, HashReporter::new());
+
+    let diagnostics = diagnostics(&compiler);
+    assert_eq!(diagnostics.len(), 1);
+
+    let secondary = diagnostics[0].secondary();
+    assert_eq!(secondary.len(), 1);
+
+    // The marker is at the `Int` of the signature.
+    let span = &secondary[0].position;
+    assert_eq!(&MISMATCH[span.start.0..span.end.0], "Int");
+    assert_eq!(
+        secondary[0].subtitle.as_ref().unwrap().to_string(),
+        "expected Int because of this"
+    );
+}
+
+/// Gets the replacement of the only suggestion of the only diagnostic of a module, indented like
+/// the source code.
+fn suggestion(main: &str) -> String {
+    let (compiler, _) = compile(main, HashReporter::new());
+
+    let diagnostics = diagnostics(&compiler);
+    assert_eq!(diagnostics.len(), 1);
+
+    let suggestions = diagnostics[0].suggestions();
+    assert_eq!(suggestions.len(), 1);
+    assert!(matches!(
+        suggestions[0].applicability,
+        Applicability::HasPlaceholders
+    ));
+
+    let ctx = Json::new(&compiler.fs, PathBuf::from("/diagnostics"));
+    ctx.replacement(&suggestions[0])
+}
+
+#[test]
+fn non_exhaustive_matches_suggest_an_arm() {
+    assert_eq!(suggestion(NON_EXHAUSTIVE), "\n        Bool.False => ...");
+}
+
+#[test]
+fn non_exhaustive_cases_suggest_an_arm() {
+    let main = "use Prelude\n\
+                let pick : Bool -> Int\n  \
+                    | Bool.True => 1\n";
+
+    assert_eq!(suggestion(main), "\n  | Bool.False => ...");
+}
+
+#[test]
+fn codes_are_parsed_like_they_are_shown() {
+    assert_eq!(codes::name(302), "E0302");
+    assert_eq!(codes::name(1), "E0001");
+
+    assert_eq!(codes::parse("E0302"), Some(302));
+    assert_eq!(codes::parse("e302"), Some(302));
+    assert_eq!(codes::parse("0302"), Some(302));
+    assert_eq!(codes::parse("E03x2"), None);
+}
+
+#[test]
+fn reported_codes_are_explained() {
+    let suite = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("suite");
+    let mut found = 0;
+
+    for entry in fs::read_dir(suite).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path
+            .extension()
+            .is_none_or(|extension| extension != "expect")
+        {
+            continue;
+        }
+
+        let content = fs::read_to_string(&path).unwrap();
+
+        for (start, _) in content.match_indices("[E") {
+            let code = &content[start + 1..start + 6];
+            let parsed = codes::parse(code).unwrap();
+
+            assert!(
+                codes::explain(parsed).is_some(),
+                "{code} of {} has no explanation",
+                path.display()
+            );
+
+            found += 1;
+        }
+    }
+
+    assert!(found > 0);
+}
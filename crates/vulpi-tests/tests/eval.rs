@@ -0,0 +1,99 @@
+//! Tests for the interpreter, that can't run every program that the JavaScript backend runs.
+
+use std::{
+    fs,
+    path::{self, PathBuf},
+};
+
+use vulpi_build::{real::RealFileSystem, ProjectCompiler};
+use vulpi_eval::Interpreter;
+use vulpi_intern::Symbol;
+use vulpi_report::{hash::HashReporter, Report};
+use vulpi_vfs::path::Path;
+
+const PRELUDE: &str = include_str!("../prelude/Prelude.vp");
+
+/// Runs the `Main` module of a project with the interpreter, and returns its output or the codes
+/// of the errors.
+fn eval(directory: &path::Path, main: &str) -> Result<String, Vec<String>> {
+    let _ = fs::remove_dir_all(directory);
+    fs::create_dir_all(directory.join("build")).unwrap();
+    fs::write(directory.join("Prelude.vp"), PRELUDE).unwrap();
+    fs::write(directory.join("Main.vp"), main).unwrap();
+
+    let package = Symbol::intern("Tc");
+
+    let mut compiler = ProjectCompiler {
+        fs: RealFileSystem::new(
+            package.clone(),
+            directory.to_path_buf(),
+            directory.join("build"),
+        ),
+        reporter: Report::new(HashReporter::new()),
+        name: package.clone(),
+    };
+
+    let root = Path {
+        segments: vec![package, Symbol::intern("Main")],
+    };
+
+    let result = compiler.eval(root, PathBuf::from("Main.vp"));
+    fs::remove_dir_all(directory).unwrap();
+
+    let Some((main, programs)) = result else {
+        return Err(compiler
+            .reporter
+            .all_diagnostics()
+            .iter()
+            .filter_map(|diagnostic| diagnostic.code())
+            .map(vulpi_report::codes::name)
+            .collect());
+    };
+
+    let mut out = Vec::new();
+    if let Err(err) = Interpreter::new(&programs, &mut out).run(&main) {
+        panic!("{err}");
+    }
+
+    Ok(String::from_utf8(out).unwrap())
+}
+
+fn directory(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("vulpi-eval-{name}-{}", std::process::id()))
+}
+
+#[test]
+fn runs_programs_without_effects() {
+    let output = eval(
+        &directory("pure"),
+        "use Prelude\n\
+         let main : () = log (add 40 2)\n",
+    );
+
+    assert_eq!(output, Ok("42\n".to_string()));
+}
+
+#[test]
+fn effects_are_rejected_before_running() {
+    let output = eval(
+        &directory("effects"),
+        "use Prelude\n\
+         pub effect Log where\n    \
+             pub log String : ()\n\
+         let run! : Request Log a -> a =\n    \
+             cases\n        \
+                 { Log.log s -> k } => handle k () with run!\n        \
+                 other => other\n\
+         let greet : {Log} () = Log.log \"hello\"\n\
+         let main : () = do\n    \
+             log 1\n    \
+             handle greet with run!\n",
+    );
+
+    // The `log 1` before the handler doesn't run, because the program is rejected as a whole.
+    let errors = output.unwrap_err();
+    assert!(
+        !errors.is_empty() && errors.iter().all(|code| code == "E0400"),
+        "{errors:?}"
+    );
+}
@@ -0,0 +1,201 @@
+//! Tests for the JavaScript backend, that compile projects with the prelude of the examples and
+//! run them with Node.js when there is one.
+
+use std::{
+    fs,
+    path::{self, PathBuf},
+    process::Command,
+};
+
+use vulpi_build::{real::RealFileSystem, ProjectCompiler};
+use vulpi_intern::Symbol;
+use vulpi_report::{hash::HashReporter, Report};
+use vulpi_vfs::path::Path;
+
+const PRELUDE: &str = include_str!("../../../example/Prelude.vp");
+
+/// Two effects and a program that performs both of them.
+const LOG_AND_ASK: &str = "use Prelude\n\
+                           pub effect Log e where\n    \
+                               pub log e : ()\n\
+                           pub effect Ask where\n    \
+                               pub ask : Int\n\
+                           let program : {Log String, Ask} Int = do\n    \
+                               Log.log \"a\"\n    \
+                               let n = Ask.ask\n    \
+                               Log.log \"b\"\n    \
+                               add n 1\n";
+
+/// The type of the requests that handlers receive, that the prelude must have to use effects.
+const REQUEST: &str = "\npub type Request e a\n";
+
+/// Writes a project with the prelude and a `Main` module, and returns a compiler for it together
+/// with the path of `Main`.
+fn project(directory: &path::Path, main: &str) -> (ProjectCompiler<RealFileSystem>, Path) {
+    let _ = fs::remove_dir_all(directory);
+    fs::create_dir_all(directory.join("build")).unwrap();
+    fs::write(directory.join("Prelude.vp"), format!("{PRELUDE}{REQUEST}")).unwrap();
+    fs::write(directory.join("Main.vp"), main).unwrap();
+
+    let package = Symbol::intern("Tj");
+
+    let compiler = ProjectCompiler {
+        fs: RealFileSystem::new(
+            package.clone(),
+            directory.to_path_buf(),
+            directory.join("build"),
+        ),
+        reporter: Report::new(HashReporter::new()),
+        name: package.clone(),
+    };
+
+    let root = Path {
+        segments: vec![package, Symbol::intern("Main")],
+    };
+
+    (compiler, root)
+}
+
+/// Compiles the `Main` module of a project to `Main.js` and returns the codes of the diagnostics.
+fn compile(directory: &path::Path, main: &str) -> Vec<usize> {
+    let (mut compiler, root) = project(directory, main);
+    compiler.compile(root, PathBuf::from("Main.vp"), directory.join("Main.js"));
+
+    compiler
+        .reporter
+        .all_diagnostics()
+        .iter()
+        .filter_map(|diagnostic| diagnostic.code())
+        .collect()
+}
+
+/// Compiles a project and returns what it prints. Nothing is returned if there is no Node.js to
+/// run it.
+fn run(directory: &path::Path, main: &str) -> Option<String> {
+    let codes = compile(directory, main);
+    assert!(codes.is_empty(), "{codes:?}");
+
+    let output = Command::new("node").arg(directory.join("Main.js")).output();
+    fs::remove_dir_all(directory).unwrap();
+
+    // The generated code is still checked when there is no Node.js.
+    let output = output.ok()?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+fn directory(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("vulpi-js-{name}-{}", std::process::id()))
+}
+
+#[test]
+fn handlers_resume_and_nest() {
+    let main = format!(
+        "{LOG_AND_ASK}\
+         let printAll! : Request (Log String) a -> {{Ask}} a =\n    \
+             cases\n        \
+                 {{ Log.log s -> k }} => do\n            \
+                     log s\n            \
+                     handle k () with printAll!\n        \
+                 other => other\n\
+         let answer! : Request Ask a -> a =\n    \
+             cases\n        \
+                 {{ Ask.ask -> k }} => handle k 42 with answer!\n        \
+                 other => other\n\
+         let main : () = log (handle (handle program with printAll!) with answer!)\n"
+    );
+
+    if let Some(output) = run(&directory("effects"), &main) {
+        assert_eq!(output, "a\nb\n43\n");
+    }
+}
+
+#[test]
+fn handlers_must_allow_the_effects_that_go_through_them() {
+    let main = format!(
+        "{LOG_AND_ASK}\
+         let silence! : Request (Log String) a -> a =\n    \
+             cases\n        \
+                 {{ Log.log _ -> k }} => handle k () with silence!\n        \
+                 other => other\n\
+         let asking : {{Ask}} Int = handle program with silence!\n"
+    );
+
+    let directory = directory("through");
+    let codes = compile(&directory, &main);
+    fs::remove_dir_all(directory).unwrap();
+
+    assert_eq!(codes, vec![331]);
+}
+
+#[test]
+fn tail_calls_run_in_constant_stack() {
+    let main = "use Prelude\n\
+                type List a =\n    \
+                    | Nil\n    \
+                    | Cons a (List a)\n\
+                let range (acc: List Int) (n: Int) : List Int =\n    \
+                    when eq n 0 is\n        \
+                        True => acc\n        \
+                        False => range (List.Cons n acc) (sub n 1)\n\
+                let foldl (f: b -> a -> b) (acc: b) (list: List a) : b =\n    \
+                    when list is\n        \
+                        List.Nil => acc\n        \
+                        List.Cons x xs => foldl f (f acc x) xs\n\
+                let isEven (n: Int) : Bool =\n    \
+                    when eq n 0 is\n        \
+                        True => True\n        \
+                        False => isOdd (sub n 1)\n\
+                let isOdd (n: Int) : Bool =\n    \
+                    when eq n 0 is\n        \
+                        True => False\n        \
+                        False => isEven (sub n 1)\n\
+                let main : () = do\n    \
+                    log (foldl (\\a b => add a b) 0 (range List.Nil 1000000))\n    \
+                    log (isOdd 1000001)\n";
+
+    if let Some(output) = run(&directory("tail"), main) {
+        assert_eq!(output, "500000500000\n1\n");
+    }
+}
+
+#[test]
+fn dictionaries_come_after_their_super_traits() {
+    let main = "use Prelude\n\
+                trait Eq a where\n    \
+                    let equal (x: a) (y: a) : Bool\n\
+                trait [Eq a] Ord a where\n    \
+                    let less (x: a) (y: a) : Bool\n\
+                impl Eq Int where\n    \
+                    let equal (x: Int) (y: Int) : Bool = eq x y\n\
+                impl Ord Int where\n    \
+                    let less (x: Int) (y: Int) : Bool = False\n\
+                let both [Ord a] (x: a) (y: a) : Bool =\n    \
+                    when Ord.less x y is\n        \
+                        True => True\n        \
+                        False => Eq.equal x y\n\
+                let main : () = log (both 1 1)\n";
+
+    if let Some(output) = run(&directory("supers"), main) {
+        assert_eq!(output, "1\n");
+    }
+}
+
+#[test]
+fn modules_are_written_to_a_directory_that_does_not_exist_yet() {
+    let directory = directory("esm");
+    let (mut compiler, root) = project(&directory, "use Prelude\nlet main : () = log 1\n");
+
+    let output = directory.join("out").join("esm");
+    compiler.compile_esm(root, PathBuf::from("Main.vp"), output.clone());
+
+    let written = output.join("runtime.mjs").exists() && output.join("Tj/Main.mjs").exists();
+    fs::remove_dir_all(directory).unwrap();
+
+    assert!(written);
+}
@@ -0,0 +1,169 @@
+//! Tests for the passes over the lambda IR, that look at the text that `--emit=lambda` prints.
+
+use std::path::PathBuf;
+
+use vulpi_build::{memory::MemoryFileSystem, ProjectCompiler};
+use vulpi_intern::Symbol;
+use vulpi_report::{hash::HashReporter, Report};
+use vulpi_vfs::path::Path;
+
+const PRELUDE: &str = include_str!("../prelude/Prelude.vp");
+
+/// A list with a function that consumes it and functions that only read it.
+const LISTS: &str = "use Prelude\n\
+                     type List a =\n    \
+                         | Nil\n    \
+                         | Cons a (List a)\n\
+                     let map (f: a -> b) (list: List a) : List b =\n    \
+                         when list is\n        \
+                             List.Nil => List.Nil\n        \
+                             List.Cons x xs => List.Cons (f x) (map f xs)\n\
+                     let length (list: List a) : Int =\n    \
+                         when list is\n        \
+                             List.Nil => 0\n        \
+                             List.Cons _ xs => add 1 (length xs)\n";
+
+/// Returns the lambda IR of the `Main` module, without the modules that it uses.
+fn emit(main: &str) -> String {
+    let package = Symbol::intern("Tl");
+    let directory = PathBuf::from("/lambda");
+
+    let mut fs = MemoryFileSystem::new(package.clone(), directory.clone(), directory.join("build"));
+    fs.open(PathBuf::from("Prelude.vp"), PRELUDE.to_string());
+    fs.open(PathBuf::from("Main.vp"), main.to_string());
+
+    let mut compiler = ProjectCompiler {
+        fs,
+        reporter: Report::new(HashReporter::new()),
+        name: package.clone(),
+    };
+
+    let root = Path {
+        segments: vec![package, Symbol::intern("Main")],
+    };
+
+    let Some(out) = compiler.emit_lambda(root, PathBuf::from("Main.vp")) else {
+        let errors: Vec<_> = compiler
+            .reporter
+            .all_diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.message().to_string())
+            .collect();
+
+        panic!("{errors:?}");
+    };
+
+    let start = out.find("// module Tl.Main").unwrap();
+    let end = out[start + 1..]
+        .find("// module")
+        .map_or(out.len(), |end| start + 1 + end);
+
+    out[start..end].to_string()
+}
+
+/// Returns the definition of a value in the output of [emit].
+fn definition<'a>(out: &'a str, name: &str) -> &'a str {
+    let start = out.find(&format!("let Tl.Main.{name} = ")).unwrap();
+    let end = out[start..]
+        .find("\n\n")
+        .map_or(out.len(), |end| start + end);
+
+    &out[start..end]
+}
+
+#[test]
+fn parameters_that_are_only_read_are_borrowed() {
+    let out = emit(LISTS);
+
+    let length = definition(&out, "length.uncurried");
+    assert!(length.contains("fn(&list)"), "{length}");
+    assert!(!length.contains("drop list"), "{length}");
+
+    let map = definition(&out, "map.uncurried");
+    assert!(map.contains("fn(&f, list)"), "{map}");
+}
+
+#[test]
+fn consumed_cells_are_reused() {
+    let out = emit(LISTS);
+
+    let map = definition(&out, "map.uncurried");
+    assert!(map.contains("if unique list"), "{map}");
+    assert!(map.contains("dup xs"), "{map}");
+    assert!(map.contains("reuse"), "{map}");
+}
+
+#[test]
+fn owned_values_are_dropped_after_their_last_use() {
+    let out = emit(&format!(
+        "{LISTS}\
+         let main : () = log (length (map (\\x => add x 1) (List.Cons 1 List.Nil)))\n"
+    ));
+
+    let main = definition(&out, "main");
+    assert!(main.contains("drop"), "{main}");
+}
+
+#[test]
+fn lambdas_that_are_called_right_away_are_reduced() {
+    let out = emit(
+        "use Prelude\n\
+         let beta : Int = (\\x => add x 1) 4\n",
+    );
+
+    let beta = definition(&out, "beta");
+    assert!(!beta.contains("fn("), "{beta}");
+    assert!(beta.contains("Prelude.add(4)"), "{beta}");
+}
+
+#[test]
+fn switches_over_known_values_take_their_branch() {
+    let out = emit(
+        "use Prelude\n\
+         let known : Int =\n    \
+             when Option.Some 2 is\n        \
+                 Option.Some x => x\n        \
+                 Option.None => 0\n\
+         let literal : Int =\n    \
+             when 1 is\n        \
+                 1 => 10\n        \
+                 _ => 20\n",
+    );
+
+    let known = definition(&out, "known");
+    assert!(!known.contains("switch"), "{known}");
+    assert!(!known.contains('#'), "{known}");
+
+    assert_eq!(definition(&out, "literal"), "let Tl.Main.literal = 10");
+}
+
+#[test]
+fn switches_over_switches_go_inside_of_them() {
+    let out = emit(
+        "use Prelude\n\
+         let pick (b: Bool) : Int = do\n    \
+             let o = when b is\n                \
+                         True => Option.Some 1\n                \
+                         False => Option.None\n    \
+             when o is\n        \
+                 Option.Some x => x\n        \
+                 Option.None => 0\n",
+    );
+
+    let pick = definition(&out, "pick.uncurried");
+    assert_eq!(pick.matches("switch").count(), 1, "{pick}");
+    assert!(!pick.contains('#'), "{pick}");
+}
+
+#[test]
+fn unused_pure_lets_are_removed() {
+    let out = emit(
+        "use Prelude\n\
+         let unused (x: Int) : Int = do\n    \
+             let z = 3\n    \
+             x\n",
+    );
+
+    let unused = definition(&out, "unused.uncurried");
+    assert!(!unused.contains("let z"), "{unused}");
+}
@@ -0,0 +1,159 @@
+//! Tests for the language server, that talk to it through a connection in memory.
+
+use std::{fs, path::PathBuf, thread, time::Duration};
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidOpenTextDocument, Exit, LogMessage, PublishDiagnostics,
+    },
+    request::{Request as _, Shutdown},
+    DidChangeTextDocumentParams, DidOpenTextDocumentParams, LogMessageParams, MessageType,
+    PublishDiagnosticsParams, TextDocumentContentChangeEvent, TextDocumentItem, Url,
+    VersionedTextDocumentIdentifier,
+};
+use vulpi_intern::Symbol;
+use vulpi_lsp::Server;
+
+/// A client that edits the `Main.vp` of a project and reads what the server answers.
+struct Client {
+    connection: Connection,
+    uri: Url,
+    version: i32,
+    server: thread::JoinHandle<()>,
+    directory: PathBuf,
+}
+
+impl Client {
+    fn start(name: &str) -> Client {
+        let directory =
+            std::env::temp_dir().join(format!("vulpi-lsp-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        let (server, connection) = Connection::memory();
+        let root = directory.clone();
+
+        let server = thread::spawn(move || {
+            Server::new(server, root, Symbol::intern("Tl"))
+                .run()
+                .unwrap();
+        });
+
+        Client {
+            connection,
+            uri: Url::from_file_path(directory.join("Main.vp")).unwrap(),
+            version: 0,
+            server,
+            directory,
+        }
+    }
+
+    fn notify<N: lsp_types::notification::Notification>(&self, params: N::Params) {
+        let notification = Notification::new(N::METHOD.to_string(), params);
+        self.connection
+            .sender
+            .send(Message::Notification(notification))
+            .unwrap();
+    }
+
+    fn open(&mut self, text: &str) {
+        self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                self.uri.clone(),
+                "vulpi".to_string(),
+                self.version,
+                text.to_string(),
+            ),
+        });
+    }
+
+    fn change(&mut self, text: &str) {
+        self.version += 1;
+
+        self.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(self.uri.clone(), self.version),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: text.to_string(),
+            }],
+        });
+    }
+
+    /// Waits for the next notification with a method, and skips the others.
+    fn wait<N: lsp_types::notification::Notification>(&self) -> N::Params {
+        loop {
+            let message = self
+                .connection
+                .receiver
+                .recv_timeout(Duration::from_secs(30))
+                .unwrap();
+
+            if let Message::Notification(notification) = message {
+                if notification.method == N::METHOD {
+                    return serde_json::from_value(notification.params).unwrap();
+                }
+            }
+        }
+    }
+
+    /// Waits for the diagnostics of `Main.vp` and returns their messages.
+    fn diagnostics(&self) -> Vec<String> {
+        loop {
+            let params: PublishDiagnosticsParams = self.wait::<PublishDiagnostics>();
+
+            if params.uri == self.uri {
+                return params
+                    .diagnostics
+                    .into_iter()
+                    .map(|diagnostic| diagnostic.message)
+                    .collect();
+            }
+        }
+    }
+
+    fn shutdown(self) {
+        let request = Request::new(RequestId::from(0), Shutdown::METHOD.to_string(), ());
+        self.connection
+            .sender
+            .send(Message::Request(request))
+            .unwrap();
+        self.notify::<Exit>(());
+        self.server.join().unwrap();
+        fs::remove_dir_all(&self.directory).unwrap();
+    }
+}
+
+#[test]
+fn fixed_errors_are_cleared() {
+    let mut client = Client::start("clear");
+
+    client.open("let value : Missing = 1\n");
+    assert!(!client.diagnostics().is_empty());
+
+    client.change("type Missing\n");
+    assert_eq!(client.diagnostics(), Vec::<String>::new());
+
+    client.shutdown();
+}
+
+#[test]
+fn panics_are_logged_and_the_session_goes_on() {
+    let mut client = Client::start("panic");
+
+    client.open("type Wrapper (a : Unknown) = | Wrapper\n");
+
+    let log: LogMessageParams = client.wait::<LogMessage>();
+    assert_eq!(log.typ, MessageType::ERROR);
+    assert!(
+        log.message.starts_with("internal compiler error"),
+        "{}",
+        log.message
+    );
+
+    client.change("let value : Missing = 1\n");
+    assert!(!client.diagnostics().is_empty());
+
+    client.shutdown();
+}
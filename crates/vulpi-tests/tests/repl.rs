@@ -0,0 +1,64 @@
+//! Tests for the sessions of the REPL, that run the code of each input with Node.js when there is
+//! one.
+
+use std::{fs, path::PathBuf, process::Command};
+
+use vulpi_build::repl::Session;
+use vulpi_intern::Symbol;
+
+const PRELUDE: &str = include_str!("../../../example/Prelude.vp");
+
+fn session() -> Session {
+    let mut session = Session::new(Symbol::intern("Tr"), PathBuf::from("/repl"));
+
+    session
+        .compiler
+        .fs
+        .open(PathBuf::from("Prelude.vp"), PRELUDE.to_string());
+
+    assert!(session.declare("use Prelude").is_some());
+    session
+}
+
+/// Runs the code of an input and returns what it prints. Nothing is returned if there is no
+/// Node.js to run it.
+fn execute(name: &str, code: &str) -> Option<String> {
+    let file = std::env::temp_dir().join(format!("vulpi-repl-{name}-{}.js", std::process::id()));
+    fs::write(&file, code).unwrap();
+
+    let output = Command::new("node").arg(&file).output();
+    fs::remove_file(&file).unwrap();
+
+    let output = output.ok()?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn expressions_run_only_when_they_are_evaluated() {
+    let mut session = session();
+
+    let (_, first) = session.evaluate("log \"hi\"").unwrap();
+    let (typ, second) = session.evaluate("1").unwrap();
+    let (_, third) = session.evaluate("2").unwrap();
+
+    assert_eq!(typ, "Int");
+    assert!(!second.contains("console.log(\"hi\")"), "{second}");
+
+    if let Some(output) = execute("first", &first) {
+        assert!(output.starts_with("hi\n"), "{output}");
+    }
+
+    if let Some(output) = execute("second", &second) {
+        assert_eq!(output, "1\n");
+    }
+
+    if let Some(output) = execute("third", &third) {
+        assert_eq!(output, "2\n");
+    }
+}
@@ -1,6 +1,134 @@
 #![feature(custom_test_frameworks)]
 #![test_runner(vulpi_tests::test_runner)]
 
+use std::{fs, path::PathBuf, process::Command};
+
+use vulpi_build::{memory::MemoryFileSystem, ProjectCompiler};
+use vulpi_eval::Interpreter;
+use vulpi_intern::Symbol;
+use vulpi_report::{hash::HashReporter, renderer::classic::Classic, renderer::Renderer, Report};
 use vulpi_tests::test;
+use vulpi_vfs::path::Path;
+
+/// The interpreter recurses when the programs do, so they run in a thread with a bigger stack.
+const STACK_SIZE: usize = 1 << 28;
+
+/// The code of the errors of things that a backend cannot run, like effects in the interpreter.
+const UNSUPPORTED: usize = 400;
+
+/// The module that every case can import with `use Prelude`. It lives outside of the suite so it
+/// is not run as a case.
+const PRELUDE: &str = include_str!("../prelude/Prelude.vp");
+
+/// Checks the case as a module of the package `Suite`, and writes its diagnostics followed by the
+/// output of its `main`, if it has one and compiles. The interpreter has no handlers, so the cases
+/// that use effects are compiled to JavaScript and run with Node.js instead.
+fn run(path: PathBuf) -> String {
+    yansi::Paint::disable();
+
+    let directory = path.parent().unwrap().to_path_buf();
+    let file_name = PathBuf::from(path.file_name().unwrap());
+    let module = path.file_stem().unwrap().to_string_lossy().to_string();
+
+    let package = Symbol::intern("Suite");
+
+    let mut fs = MemoryFileSystem::new(package.clone(), directory.clone(), directory.join("build"));
+    fs.open(PathBuf::from("Prelude.vp"), PRELUDE.to_string());
+
+    let mut compiler = ProjectCompiler {
+        fs,
+        reporter: Report::new(HashReporter::new()),
+        name: package.clone(),
+    };
+
+    let root = Path {
+        segments: vec![package, Symbol::intern(&module)],
+    };
+
+    let mut program = compiler.eval(root.clone(), file_name.clone());
+
+    let diagnostics = compiler.reporter.all_diagnostics();
+    let uses_effects = !diagnostics.is_empty()
+        && diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.code() == Some(UNSUPPORTED));
+
+    let javascript =
+        std::env::temp_dir().join(format!("vulpi-suite-{module}-{}.js", std::process::id()));
+
+    if uses_effects {
+        compiler.reporter = Report::new(HashReporter::new());
+        compiler.compile(root, file_name, javascript.clone());
+        program = None;
+    }
+
+    let mut out = Vec::new();
+    let ctx = Classic::new(&compiler.fs, directory);
+
+    for diagnostic in compiler.reporter.all_diagnostics().iter().rev() {
+        diagnostic.render(&ctx, &mut out).unwrap();
+    }
+
+    if uses_effects && javascript.exists() {
+        out.extend(node(&javascript).bytes());
+    }
+
+    if let Some((main, programs)) = program {
+        let has_main = programs
+            .iter()
+            .any(|program| program.lets.iter().any(|(name, _)| *name == main));
+
+        if has_main {
+            let result = Interpreter::new(&programs, &mut out).run(&main);
+
+            match result {
+                Ok(()) => (),
+                Err(vulpi_eval::Error::Exit(code)) => {
+                    out.extend(format!("[exit {code}]\n").bytes())
+                }
+                Err(err) => out.extend(format!("[Error]: {err}\n").bytes()),
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Runs a compiled case and returns what it prints, followed by what it threw.
+fn node(javascript: &std::path::Path) -> String {
+    let output = Command::new("node").arg(javascript).output();
+
+    let mut map = javascript.as_os_str().to_owned();
+    map.push(".map");
+
+    fs::remove_file(javascript).unwrap();
+    let _ = fs::remove_file(map);
+
+    match output {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).to_string()
+        }
+        Ok(output) => format!(
+            "{}[Error]: {}\n",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .find(|line| line.contains("Error"))
+                .unwrap_or_default()
+        ),
+        Err(_) => "[Error]: Node.js is needed to run the cases that use effects\n".to_string(),
+    }
+}
+
+test!("/suite", |path| {
+    // The symbols are interned by each thread, so the whole case runs in the new one.
+    let thread = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || run(path))
+        .unwrap();
 
-test!("/suite", |_file_name| { todo!() });
+    match thread.join() {
+        Ok(out) => out,
+        Err(err) => std::panic::resume_unwind(err),
+    }
+});
@@ -3,7 +3,7 @@
 use vulpi_location::Spanned;
 use vulpi_syntax::{elaborated, r#abstract::Expr, r#abstract::ExprKind, r#abstract::Sttm};
 
-use crate::{context::Context, real::Real, Env, Type, TypeKind, Virtual};
+use crate::{context::Context, effects::Scope, real::Real, Env, Type, TypeKind, Virtual};
 
 use super::Check;
 use crate::infer::Infer;
@@ -20,7 +20,17 @@ impl Check for Expr {
     ) -> Self::Return {
         env.set_current_span(self.span.clone());
 
+        // The expected span is only for this expression and not for the ones inside of it.
+        let expected = env.expected.take();
+
         let elem = match (&self.data, typ.deref().as_ref()) {
+            (_, TypeKind::Effect(effects, ret)) => {
+                let (elab, _) = ctx.effect_scope(Scope::Closed(effects.clone()), |ctx| {
+                    self.check(ret.clone(), (ctx, env.expecting(expected.clone())))
+                });
+
+                Box::new(elaborated::ExprKind::Computation(elab))
+            }
             (ExprKind::Do(block), _) => {
                 let mut stmts = Vec::new();
 
@@ -28,7 +38,7 @@ impl Check for Expr {
                     for (i, stmt) in block.sttms.iter().enumerate() {
                         let is_last = i == block.sttms.len() - 1;
                         let (elab, new_env) = if is_last {
-                            stmt.check(typ.clone(), (ctx, env.clone()))
+                            stmt.check(typ.clone(), (ctx, env.expecting(expected.clone())))
                         } else {
                             let (_, new_env, elab) = stmt.infer((ctx, &mut env.clone()));
                             (elab, new_env)
@@ -42,17 +52,41 @@ impl Check for Expr {
 
                 Box::new(elaborated::ExprKind::Do(stmts))
             }
+            // Lambdas are only checked against computations, because the effects of the body cannot
+            // be collected in the type of the lambda otherwise.
+            (ExprKind::Lambda(lam), TypeKind::Arrow(pi))
+                if matches!(pi.body.deref().as_ref(), TypeKind::Effect(_, _)) =>
+            {
+                let mut hashmap = Default::default();
+                let elab_pat = lam.param.check(pi.typ.clone(), (ctx, &mut hashmap, env.clone()));
+
+                for binding in hashmap {
+                    env.add_var(binding.0, binding.1)
+                }
+
+                let elab_body = lam
+                    .body
+                    .check(pi.body.clone(), (ctx, env.expecting(expected)));
+
+                Box::new(elaborated::ExprKind::Lambda(elaborated::LambdaExpr {
+                    param: elab_pat,
+                    body: elab_body,
+                }))
+            }
             (_, TypeKind::Forall(l)) => {
                 let lvl_ty = Type::new(TypeKind::Bound(env.level));
                 self.check(
                     l.body.apply_local(Some(l.name.clone()), lvl_ty.clone()),
-                    (ctx, env.add(Some(l.name.clone()), lvl_ty)),
+                    (
+                        ctx,
+                        env.add(Some(l.name.clone()), lvl_ty).expecting(expected),
+                    ),
                 )
                 .data
             }
             _ => {
                 let (expr_ty, elab_expr) = self.infer((ctx, env.clone()));
-                ctx.subsumes(env, expr_ty, typ);
+                ctx.subsumes(env.expecting(expected), expr_ty, typ);
                 elab_expr.data
             }
         };
@@ -66,12 +100,14 @@ impl Check for Sttm {
 
     type Context<'a> = (&'a mut Context, Env);
 
-    fn check(&self, ann_ty: Type<Virtual>, (ctx, env): Self::Context<'_>) -> Self::Return {
+    fn check(&self, ann_ty: Type<Virtual>, (ctx, mut env): Self::Context<'_>) -> Self::Return {
         env.set_current_span(self.span.clone());
 
+        let expected = env.expected.take();
+
         let (typ, env, elab) = self.infer((ctx, &mut env.clone()));
 
-        ctx.subsumes(env.clone(), typ, ann_ty);
+        ctx.subsumes(env.expecting(expected), typ, ann_ty);
         (elab, env)
     }
 }
@@ -18,6 +18,9 @@ impl Check for PatternArm {
     type Context<'a> = (&'a mut Context, Env);
 
     fn check(&self, mut typ: Type<Virtual>, (ctx, mut env): Self::Context<'_>) -> Self::Return {
+        // The patterns are checked against the arguments, so only the body uses the expected span.
+        let expected = env.expected.take();
+
         let mut map = Default::default();
 
         let mut elaborated_patterns = Vec::new();
@@ -46,7 +49,7 @@ impl Check for PatternArm {
             env.add_var(binding.0, binding.1);
         }
 
-        let elab_expr = self.expr.check(typ, (ctx, env.clone()));
+        let elab_expr = self.expr.check(typ, (ctx, env.expecting(expected)));
 
         let guard = self.guard.as_ref().map(|g| g.infer((ctx, env.clone())));
 
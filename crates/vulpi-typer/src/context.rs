@@ -3,11 +3,19 @@
 
 use vulpi_intern::Symbol;
 use vulpi_report::{Diagnostic, Report};
-use vulpi_syntax::{elaborated, r#abstract::Qualified};
+use vulpi_syntax::{
+    elaborated::{self, Dictionary},
+    r#abstract::{self, Qualified},
+};
 
 use crate::{
+    effects::Scope,
+    entail::Wanted,
     errors::{TypeError, TypeErrorKind},
-    module::Modules,
+    eval::Quote,
+    index::Index,
+    infer::Infer,
+    module::{Def, Modules},
     r#virtual::Env,
     r#virtual::Pi,
     r#virtual::Virtual,
@@ -23,7 +31,21 @@ pub struct Context {
     pub modules: Modules,
     pub elaborated: elaborated::Program<Type<Real>>,
     pub errored: bool,
-    }
+    pub index: Index,
+
+    /// Constraints that must be proved at the end of the current declaration.
+    pub wanted: Vec<Wanted>,
+
+    /// Constraints that are given by the signature of the current declaration together with the
+    /// dictionaries that prove them.
+    pub given: Vec<(Type<Virtual>, Dictionary)>,
+
+    /// The scopes that decide what happens with the effects performed by the current expression.
+    pub effects: Vec<Scope>,
+
+    /// The namespace of the constructors of the `list` lang item, so the errors show them as lists.
+    pub list: Option<Symbol>,
+}
 
 impl Context {
     pub fn new(reporter: Report) -> Self {
@@ -33,9 +55,28 @@ impl Context {
             modules: Default::default(),
             elaborated: Default::default(),
             errored: false,
+            index: Default::default(),
+            wanted: Default::default(),
+            given: Default::default(),
+            effects: Default::default(),
+            list: None,
         }
     }
 
+    /// Infers the type of an expression that is outside of every declaration, like the ones typed
+    /// in the REPL.
+    pub fn infer_expr(&mut self, env: Env, expr: &r#abstract::Expr) -> Type<Real> {
+        let (typ, _) = expr.infer((self, env.clone()));
+        self.solve_wanted();
+        typ.quote(env.level)
+    }
+
+    /// Infers the kind of a type that is outside of every declaration.
+    pub fn infer_kind(&mut self, env: Env, typ: &r#abstract::Type) -> Type<Real> {
+        let (_, kind) = typ.infer((self, env.clone()));
+        kind.quote(env.level)
+    }
+
     pub fn report(&mut self, env: &Env, kind: TypeErrorKind) {
         self.errored = true;
         self.reporter.report(Diagnostic::new(TypeError {
@@ -44,6 +85,14 @@ impl Context {
         }));
     }
 
+    /// Reports a diagnostic that does not stop the compilation.
+    pub fn warn(&mut self, env: &Env, kind: TypeErrorKind) {
+        self.reporter.report(Diagnostic::new(TypeError {
+            span: env.span.borrow().clone(),
+            kind,
+        }));
+    }
+
     fn inc_counter(&mut self) -> usize {
         self.counter += 1;
         self.counter - 1
@@ -60,11 +109,22 @@ impl Context {
         }
     }
 
+    /// Checks if a type is a synonym, so it has to be expanded instead of used as a type.
+    pub fn is_synonym(&mut self, name: &Qualified) -> bool {
+        matches!(self.modules.typ(name).def, Def::Synonym(_))
+    }
+
     /// Creates a new name with the prefix `t_` and a unique number.
     pub fn new_name(&mut self) -> Symbol {
         Symbol::intern(&format!("t_{}", self.inc_counter()))
     }
 
+    /// Creates a new name for a variable that is introduced by the elaboration. The `$` avoids
+    /// collisions with the names that are written by the user.
+    pub fn new_variable(&mut self) -> Symbol {
+        Symbol::intern(&format!("x${}", self.inc_counter()))
+    }
+
     /// Creates a new hole that is a type that is not yet known
     pub fn hole<S: State>(&mut self, env: &Env, kind: Type<Virtual>) -> Type<S> {
         env.hole(kind, self.new_name())
@@ -5,12 +5,16 @@ use std::fmt::Display;
 
 use im_rc::HashSet;
 
+use vulpi_intern::Symbol;
 use vulpi_syntax::{
-    elaborated::{Literal, LiteralKind, Pattern, PatternArm, PatternKind},
-    r#abstract::Qualified,
+    elaborated::{self, Literal, LiteralKind, Pattern, PatternArm, PatternKind},
+    r#abstract::{self, Qualified},
 };
 
-use crate::{context::Context, eval::Eval, real::Real, Env, Type, TypeKind, Virtual};
+use crate::{
+    context::Context, errors::TypeErrorKind, eval::Eval, module::Def, real::Real, Env, Type,
+    TypeKind, Virtual,
+};
 
 #[derive(Clone, Debug)]
 pub enum Pat {
@@ -18,6 +22,12 @@ pub enum Pat {
     Constructor(Qualified, Vec<Pat>),
     Wildcard,
     Literal(Literal),
+    Or(Box<Pat>, Box<Pat>),
+
+    /// The values of a list and the pattern of its tail, or none if it ends there. They are only
+    /// made by [Pat::with_lists], so the patterns in the errors look like the ones that the user
+    /// writes.
+    List(Vec<Pat>, Option<Box<Pat>>),
 }
 
 impl Display for Pat {
@@ -34,10 +44,15 @@ impl Display for Pat {
                 write!(f, ")")
             }
             Pat::Constructor(name, args) => {
+                // Constructors live in the namespace of their type, so they are written as
+                // `List.Cons`, like they are used outside of it.
+                let path = name.path.get();
+                let typ = path.rsplit('.').next().unwrap_or_default();
+
                 if args.is_empty() {
-                    write!(f, "{}", name.name.get())
+                    write!(f, "{}.{}", typ, name.name.get())
                 } else {
-                    write!(f, "({}", name.name.get())?;
+                    write!(f, "({}.{}", typ, name.name.get())?;
                     for arg in args.iter() {
                         write!(f, " {}", arg)?;
                     }
@@ -52,6 +67,18 @@ impl Display for Pat {
                 LiteralKind::Char(c) => write!(f, "'{}'", c.get()),
                 LiteralKind::Unit => write!(f, "()"),
             },
+            Pat::Or(left, right) => write!(f, "({} | {})", left, right),
+            Pat::List(values, tail) => {
+                let mut parts: Vec<_> = values.iter().map(|value| value.to_string()).collect();
+
+                match tail.as_deref() {
+                    None => (),
+                    Some(Pat::Wildcard) => parts.push("..".to_string()),
+                    Some(tail) => parts.push(format!("..{}", tail)),
+                }
+
+                write!(f, "[{}]", parts.join(", "))
+            }
         }
     }
 }
@@ -74,22 +101,107 @@ impl Pat {
                     .map(Pat::from_pattern)
                     .collect::<Option<Vec<_>>>()?,
             )),
+            PatternKind::Record(record) => Some(Pat::Constructor(
+                record.name.clone(),
+                record
+                    .fields
+                    .iter()
+                    .map(|(_, pat)| Pat::from_pattern(pat))
+                    .collect::<Option<Vec<_>>>()?,
+            )),
+            PatternKind::Or(or) => Some(Pat::Or(
+                Box::new(Pat::from_pattern(&or.left)?),
+                Box::new(Pat::from_pattern(&or.right)?),
+            )),
             PatternKind::Error => None,
         }
     }
 
+    /// Writes the constructors of the list lang item, that live in the namespace `list`, as list
+    /// patterns.
+    pub fn with_lists(self, list: &Symbol) -> Pat {
+        match self {
+            Pat::Constructor(name, args) if name.path == *list => {
+                let mut args = args.into_iter().map(|arg| arg.with_lists(list));
+
+                match (args.next(), args.next()) {
+                    (Some(head), Some(Pat::List(mut values, tail))) => {
+                        values.insert(0, head);
+                        Pat::List(values, tail)
+                    }
+                    (Some(head), Some(tail)) => Pat::List(vec![head], Some(Box::new(tail))),
+                    _ => Pat::List(vec![], None),
+                }
+            }
+            Pat::Constructor(name, args) => Pat::Constructor(
+                name,
+                args.into_iter().map(|arg| arg.with_lists(list)).collect(),
+            ),
+            Pat::Tuple(args) => {
+                Pat::Tuple(args.into_iter().map(|arg| arg.with_lists(list)).collect())
+            }
+            Pat::Or(left, right) => Pat::Or(
+                Box::new(left.with_lists(list)),
+                Box::new(right.with_lists(list)),
+            ),
+            pat => pat,
+        }
+    }
+
     pub fn is_wildcard(&self) -> bool {
-        matches!(self, Pat::Wildcard)
+        match self {
+            Pat::Wildcard => true,
+            Pat::Or(left, right) => left.is_wildcard() || right.is_wildcard(),
+            _ => false,
+        }
     }
 
-    pub fn constructor(&self) -> Option<Qualified> {
+    /// Expands the or-patterns inside of a pattern into all of its alternatives.
+    pub fn alternatives(&self) -> Vec<Pat> {
         match self {
-            Pat::Constructor(name, _) => Some(name.clone()),
-            _ => None,
+            Pat::Or(left, right) => {
+                let mut alternatives = left.alternatives();
+                alternatives.extend(right.alternatives());
+                alternatives
+            }
+            Pat::Tuple(args) => product(args).into_iter().map(Pat::Tuple).collect(),
+            Pat::Constructor(name, args) => product(args)
+                .into_iter()
+                .map(|args| Pat::Constructor(name.clone(), args))
+                .collect(),
+            _ => vec![self.clone()],
+        }
+    }
+
+    pub fn constructors(&self) -> Vec<Qualified> {
+        match self {
+            Pat::Constructor(name, _) => vec![name.clone()],
+            Pat::Or(left, right) => {
+                let mut constructors = left.constructors();
+                constructors.extend(right.constructors());
+                constructors
+            }
+            _ => vec![],
         }
     }
 }
 
+fn product(pats: &[Pat]) -> Vec<Vec<Pat>> {
+    pats.iter().fold(vec![vec![]], |rows, pat| {
+        let alternatives = pat.alternatives();
+
+        rows.into_iter()
+            .flat_map(|row| {
+                alternatives.iter().map(move |alt| {
+                    let mut row = row.clone();
+                    row.push(alt.clone());
+                    row
+                })
+            })
+            .collect()
+    })
+}
+
 /// A line in the problem matrix. It's used to indicate that there's an answer to a open pattern
 /// problem.
 #[derive(Clone)]
@@ -124,6 +236,11 @@ impl<T: Clone> Row<T> {
         self.0.get(0).unwrap()
     }
 
+    /// Replaces the first column of the row
+    pub fn replace_first(&self, item: T) -> Self {
+        self.pop_front().preppend(item)
+    }
+
     pub fn inline(&self, mut other: Vec<T>) -> Self {
         let mut copied = self.clone();
         copied.0.pop_front();
@@ -133,7 +250,42 @@ impl<T: Clone> Row<T> {
 }
 
 impl Row<Pat> {
+    /// Writes the constructors of the list lang item of the row as list patterns, if there is one.
+    pub fn with_lists(self, list: Option<&Symbol>) -> Self {
+        match list {
+            Some(list) => Row(self.0.into_iter().map(|pat| pat.with_lists(list)).collect()),
+            None => self,
+        }
+    }
+
+    /// Writes the patterns of the row separated by commas, in the same way as the patterns of an arm.
+    pub fn to_patterns(&self) -> String {
+        self.0
+            .iter()
+            .map(|pat| pat.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Splits a row that starts with an or-pattern in one row for each alternative.
+    fn alternatives(&self) -> Option<Vec<Row<Pat>>> {
+        match self.first() {
+            Pat::Or(left, right) => Some(vec![
+                self.replace_first(*left.clone()),
+                self.replace_first(*right.clone()),
+            ]),
+            _ => None,
+        }
+    }
+
     pub fn specialize(&self, useful: Pat) -> Vec<Row<Pat>> {
+        if let Some(rows) = self.alternatives() {
+            return rows
+                .into_iter()
+                .flat_map(|row| row.specialize(useful.clone()))
+                .collect();
+        }
+
         let first = &self.0[0];
         match (useful, first) {
             (Pat::Wildcard, Pat::Tuple(args)) => vec![self.inline(wildcards(args.len()))],
@@ -151,13 +303,21 @@ impl Row<Pat> {
                 vec![self.inline(b.to_vec())]
             }
 
+            (Pat::Tuple(a), Pat::Wildcard) => vec![self.inline(wildcards(a.len()))],
+
             (Pat::Literal(n), Pat::Literal(m)) if n == *m => vec![self.pop_front()],
 
+            (Pat::Literal(_), Pat::Wildcard) => vec![self.pop_front()],
+
             (_, _) => vec![],
         }
     }
 
     pub fn default_row(self) -> Vec<Row<Pat>> {
+        if let Some(rows) = self.alternatives() {
+            return rows.into_iter().flat_map(|row| row.default_row()).collect();
+        }
+
         let first = &self.0[0];
         match first {
             Pat::Wildcard => vec![self.pop_front()],
@@ -169,8 +329,8 @@ impl Row<Pat> {
         self.0[0].is_wildcard()
     }
 
-    pub fn used_constructor(&self) -> Option<Qualified> {
-        self.first().constructor()
+    pub fn used_constructors(&self) -> Vec<Qualified> {
+        self.first().constructors()
     }
 }
 
@@ -184,7 +344,7 @@ impl Matrix<Pat> {
     }
 
     pub fn used_constructors(&self) -> HashSet<Qualified> {
-        self.0.iter().flat_map(|x| x.used_constructor()).collect()
+        self.0.iter().flat_map(|x| x.used_constructors()).collect()
     }
 
     pub fn specialize(self, useful: Pat) -> Matrix<Pat> {
@@ -205,6 +365,22 @@ fn wildcards(n: usize) -> Vec<Pat> {
     vec![Pat::Wildcard; n]
 }
 
+/// The fields of a record. A record pattern is treated as the only constructor of the record type
+/// and the fields are its arguments.
+fn record_fields(ctx: &mut Context, name: &Qualified) -> Option<Vec<Qualified>> {
+    match &ctx.modules.get(&name.path).types.get(&name.name)?.def {
+        Def::Record(fields) => Some(fields.clone()),
+        _ => None,
+    }
+}
+
+fn arity(ctx: &mut Context, name: &Qualified) -> usize {
+    match record_fields(ctx, name) {
+        Some(fields) => fields.len(),
+        None => ctx.modules.constructor(name).1,
+    }
+}
+
 pub enum Witness {
     Ok,
     NonExhaustive(Row<Pat>),
@@ -372,7 +548,31 @@ impl Problem {
         case_pats: Vec<Pat>,
         args: Vec<Type<Virtual>>,
     ) -> Witness {
-        let (signature, _, _) = ctx.modules.constructor(&name);
+        if let Some(fields) = record_fields(ctx, &name) {
+            let spine = fields
+                .iter()
+                .map(|field| {
+                    let typ = ctx.modules.field(field).eval(&env);
+                    ctx.instantiate_with_arguments(&typ, args.clone())
+                })
+                .collect();
+
+            let case = Pat::Constructor(name, case_pats.clone());
+            return self.specialize(ctx, env, spine, case_pats, case);
+        }
+
+        let (signature, _, type_name) = ctx.modules.constructor(&name);
+
+        // The operations of an effect are instantiated with the arguments of the effect and the
+        // type of the value of the request.
+        let args = if let Def::Effect(_) = ctx.modules.typ(&type_name).def {
+            let mut effect_args = args[0].deref().application_spine().1;
+            effect_args.push(args[1].clone());
+            effect_args
+        } else {
+            args
+        };
+
         let signature = ctx.instantiate_with_arguments(&signature.eval(&env), args);
 
         let spine = signature.arrow_spine();
@@ -392,10 +592,44 @@ impl Problem {
         }
     }
 
-    pub fn is_complete_signature(&self, ctx: &mut Context, type_name: Qualified) -> Completeness {
-        let signature = ctx.modules.typ(&type_name);
+    /// The constructors of a type. The requests of a handler are built by the return constructor
+    /// and the operations of the effect.
+    fn constructors(
+        ctx: &mut Context,
+        type_name: &Qualified,
+        type_spine: &[Type<Virtual>],
+    ) -> Option<Vec<Qualified>> {
+        if *type_name == elaborated::request_type() {
+            let (head, _) = type_spine.first()?.deref().application_spine();
+            let head = head.deref();
+
+            let TypeKind::Variable(effect) = head.as_ref() else {
+                return None;
+            };
+
+            let Def::Effect(operations) = ctx.modules.typ(effect).def else {
+                return None;
+            };
+
+            let mut constructors = vec![elaborated::return_constructor()];
+            constructors.extend(operations);
+            Some(constructors)
+        } else {
+            match ctx.modules.typ(type_name).def {
+                Def::Enum(constructors) => Some(constructors),
+                Def::Record(_) => Some(vec![type_name.clone()]),
+                _ => None,
+            }
+        }
+    }
 
-        if let crate::module::Def::Enum(constructors) = signature.def {
+    pub fn is_complete_signature(
+        &self,
+        ctx: &mut Context,
+        type_name: Qualified,
+        type_spine: &[Type<Virtual>],
+    ) -> Completeness {
+        if let Some(constructors) = Self::constructors(ctx, &type_name, type_spine) {
             Completeness::check(
                 constructors.into_iter().collect(),
                 self.matrix.used_constructors(),
@@ -406,8 +640,8 @@ impl Problem {
     }
 
     pub fn synthetize(&self, ctx: &mut Context, name: Qualified) -> Pat {
-        let (_, args, _) = ctx.modules.constructor(&name);
-        Pat::Constructor(name.clone(), wildcards(args))
+        let args = arity(ctx, &name);
+        Pat::Constructor(name, wildcards(args))
     }
 
     pub fn exhaustiveness_wildcard(
@@ -420,7 +654,7 @@ impl Problem {
         if self.matrix.is_wildcard() {
             self.specialize_wildcard(ctx, env)
         } else {
-            match self.is_complete_signature(ctx, type_name.clone()) {
+            match self.is_complete_signature(ctx, type_name.clone(), &type_spine) {
                 Completeness::Complete(_) => self.split(ctx, env, type_name, type_spine),
                 Completeness::Incomplete(Finitude::Finite(cons)) => {
                     let name = cons.into_iter().collect::<Vec<_>>()[0].clone();
@@ -428,10 +662,7 @@ impl Problem {
                     let witness = self.default_matrix().exaustive(ctx, env);
                     witness.preppend(pat)
                 }
-                Completeness::Incomplete(Finitude::Infinite) => {
-                    let witness = self.specialize_wildcard(ctx, env);
-                    witness.preppend(Pat::Wildcard)
-                }
+                Completeness::Incomplete(Finitude::Infinite) => self.specialize_wildcard(ctx, env),
             }
         }
     }
@@ -443,11 +674,9 @@ impl Problem {
         type_name: Qualified,
         type_spine: Vec<Type<Virtual>>,
     ) -> Witness {
-        let typ = ctx.modules.typ(&type_name);
-
-        if let crate::module::Def::Enum(constructors) = typ.def {
+        if let Some(constructors) = Self::constructors(ctx, &type_name, &type_spine) {
             for constructor in constructors {
-                let (_, size, _) = ctx.modules.constructor(&constructor);
+                let size = arity(ctx, &constructor);
 
                 let witness = self.clone().specialize_cons(
                     ctx,
@@ -508,7 +737,7 @@ impl Problem {
 
             (Pat::Wildcard, _) => self.specialize_wildcard(ctx, env),
 
-            (Pat::Constructor(n, pats), TypeKind::Application(_, _)) => {
+            (Pat::Constructor(n, pats), TypeKind::Application(_, _) | TypeKind::Variable(_)) => {
                 let args = current.application_spine().1;
                 let name = n.clone();
                 let pats = pats.clone();
@@ -531,3 +760,107 @@ impl Problem {
         }
     }
 }
+
+impl Context {
+    /// Warns about the arms of a pattern matching that can never match.
+    pub fn check_redundancy(
+        &mut self,
+        env: &Env,
+        arms: &[r#abstract::PatternArm],
+        elab_arms: &[PatternArm<Type<Real>>],
+        types: &[Type<Virtual>],
+    ) {
+        let span = |arm: &r#abstract::PatternArm| {
+            let (first, last) = (arm.patterns.first()?, arm.patterns.last()?);
+            Some(first.span.clone().mix(last.span.clone()))
+        };
+
+        for (i, subsumer) in redundant_arms(self, env, elab_arms, types) {
+            if let Some(redundant) = span(&arms[i]) {
+                let subsumer =
+                    subsumer.and_then(|(j, pats)| span(&arms[j]).map(|subsumer| (pats, subsumer)));
+
+                env.set_current_span(redundant);
+                self.warn(env, TypeErrorKind::RedundantArm(subsumer));
+            }
+        }
+    }
+}
+
+/// Checks if there's a value that matches the patterns of a row but none of the rows of a matrix.
+fn is_useful(
+    ctx: &mut Context,
+    env: &Env,
+    types: &[Type<Virtual>],
+    row: &[Pat],
+    matrix: &[Row<Pat>],
+) -> bool {
+    product(row).into_iter().any(|alternative| {
+        let problem = Problem {
+            types: Row(types[0..alternative.len()].iter().cloned().collect()),
+            case: Row(alternative.into()),
+            matrix: Matrix(matrix.to_vec()),
+        };
+
+        problem.exaustive(ctx, env.clone()).non_exaustive()
+    })
+}
+
+/// The index of an arm together with its patterns.
+type Arm = (usize, Vec<Pat>);
+
+/// Finds the arms that can never match because the arms before them already match every value that
+/// they do. Each one of them comes with the index and the patterns of an earlier arm that matches
+/// everything on its own, if there is one. Arms with guards can be redundant but they never make
+/// other arms redundant.
+pub fn redundant_arms(
+    ctx: &mut Context,
+    env: &Env,
+    arms: &[PatternArm<Type<Real>>],
+    types: &[Type<Virtual>],
+) -> Vec<(usize, Option<Arm>)> {
+    let mut previous: Vec<Arm> = vec![];
+    let mut redundant = vec![];
+
+    for (i, arm) in arms.iter().enumerate() {
+        let Some(row) = arm
+            .patterns
+            .iter()
+            .map(Pat::from_pattern)
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+
+        let matrix = previous
+            .iter()
+            .map(|(_, row)| Row(row.iter().cloned().collect()))
+            .collect::<Vec<_>>();
+
+        if !is_useful(ctx, env, types, &row, &matrix) {
+            let subsumer = previous
+                .iter()
+                .zip(&matrix)
+                .find(|(_, other)| !is_useful(ctx, env, types, &row, std::slice::from_ref(other)))
+                .map(|((j, pats), _)| {
+                    let pats = match &ctx.list {
+                        Some(list) => pats
+                            .iter()
+                            .map(|pat| pat.clone().with_lists(list))
+                            .collect(),
+                        None => pats.clone(),
+                    };
+
+                    (*j, pats)
+                });
+
+            redundant.push((i, subsumer));
+        }
+
+        if arm.guard.is_none() {
+            previous.push((i, row));
+        }
+    }
+
+    redundant
+}
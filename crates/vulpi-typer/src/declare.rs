@@ -1,10 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use vulpi_intern::Symbol;
 use vulpi_syntax::{
     elaborated::{self},
     r#abstract::{
-        LetBinder, Qualified, TraitDecl, {ExtDecl, LetDecl, TypeDef}, {Program, TypeDecl},
+        EffectDecl, LetBinder, Qualified, TraitDecl, TraitImpl, {ExtDecl, LetDecl, TypeDef},
+        {Program, TypeDecl},
     },
 };
 
@@ -12,14 +13,15 @@ use crate::{
     check::Check,
     context::Context,
     coverage::{Problem, Witness},
-    errors::TypeErrorKind,
+    entail,
+    errors::{MissingArm, TypeErrorKind},
     eval::Eval,
     eval::Quote,
     infer::Infer,
-    module::{Def, LetDef, TraitData, TypeData},
+    module::{Def, InstanceData, LetDef, TraitData, TypeData},
     r#virtual::Virtual,
     real::{Forall, Real},
-    Env, Index, Kind, Type,
+    Env, Index, Kind, Type, TypeKind,
 };
 
 fn free_variables(let_sig: &vulpi_syntax::r#abstract::LetSignature) -> HashSet<Symbol> {
@@ -83,17 +85,15 @@ impl<T: Declare> Declare for Option<T> {
 }
 
 impl Declare for TraitDecl {
-    type Return = ();
+    type Return = (Qualified, elaborated::TraitDecl);
 
-    fn declare(&self, (ctx, mut env): (&mut Context, Env)) {        
+    fn declare(&self, (ctx, mut env): (&mut Context, Env)) {
         env.set_current_span(self.span.clone());
-        let vec = &self.binders;
 
         let mut names = Vec::new();
         let mut binders = Vec::new();
-        let mut fvs = Vec::new();
 
-        for binder in vec {
+        for binder in &self.binders {
             let (n, binder) = binder.infer((ctx, env.clone()));
             let value = binder.eval(&env);
             binders.push(value.clone());
@@ -101,105 +101,433 @@ impl Declare for TraitDecl {
             env = env.add(Some(n), value);
         }
 
-        fvs.extend(self.binders.iter().map(|x| x.name().clone()));
-
         let kind = Type::<Virtual>::function(binders.clone(), Type::constraint());
 
-        let mut supers = vec![];
-
         ctx.modules.get(&self.name.path).types.insert(
             self.name.name.clone(),
             TypeData {
-                kind: kind.clone(),
-                binders: names.into_iter().zip(binders.clone()).collect(),
+                kind,
+                binders: names.into_iter().zip(binders).collect(),
                 module: self.namespace.clone(),
                 def: Def::Constraint,
             },
         );
+    }
+
+    fn define(&self, (ctx, mut env): (&mut Context, Env)) -> Self::Return {
+        let start_env = env.clone();
+
+        let type_data = ctx.modules.typ(&self.name);
+
+        for (name, binder) in &type_data.binders {
+            env = env.add(Some(name.clone()), binder.clone());
+        }
+
+        let mut supers = Vec::new();
 
         for super_ in &self.supers {
             env.set_current_span(super_.span.clone());
-            let (value, typ) = super_.infer((ctx, env.clone()));
-            ctx.subsumes(env.clone(), typ, Type::constraint());
+            let (value, kind) = super_.infer((ctx, env.clone()));
+            ctx.subsumes(env.clone(), kind, Type::constraint());
             supers.push(value);
         }
 
+        let trait_fvs = type_data
+            .binders
+            .iter()
+            .map(|x| x.0.clone())
+            .collect::<HashSet<_>>();
+
         let mut signatures = Vec::new();
 
         for let_signature in &self.body {
             let mut env = env.clone();
 
-            let free_variables = &free_variables(let_signature);
-            let diff = HashSet::from_iter(fvs.iter().cloned());
-            let signature_fvs = free_variables.difference(&diff);
-
             let mut unbound = Vec::new();
 
-            for fv in fvs.iter() {
-                let typ = ctx.hole(&env, Type::typ());
-                env = env.add(Some(fv.clone()), typ.clone());
-                unbound.push((fv, typ.quote(env.level)))
-            }            
-
-            for fv in signature_fvs {
+            for fv in free_variables(let_signature).difference(&trait_fvs) {
                 let typ = ctx.hole(&env, Type::typ());
                 env = env.add(Some(fv.clone()), typ.clone());
-                unbound.push((fv, typ.quote(env.level)))
+                unbound.push((fv.clone(), typ.quote(env.level)))
             }
 
             let mut args = Vec::new();
+            let mut constraints = Vec::new();
 
-            for arg in &let_signature.binders {
-                let (typ, kind) = arg.typ().infer((ctx, env.clone()));
-                env.set_current_span(arg.typ().span.clone());
-                ctx.subsumes(env.clone(), kind, Kind::typ());    
-                args.push(typ);
+            for binder in &let_signature.binders {
+                let (typ, kind) = binder.typ().infer((ctx, env.clone()));
+                env.set_current_span(binder.typ().span.clone());
+
+                match binder {
+                    LetBinder::Param(_) => {
+                        ctx.subsumes(env.clone(), kind, Kind::typ());
+                        args.push(typ);
+                    }
+                    LetBinder::Trait(_) => {
+                        ctx.subsumes(env.clone(), kind, Kind::constraint());
+                        constraints.push(typ);
+                    }
+                }
             }
 
             let ret = if let Some(ret) = &let_signature.ret {
                 let (typ, kind) = ret.infer((ctx, env.clone()));
                 env.set_current_span(ret.span.clone());
                 ctx.subsumes(env.clone(), kind, Kind::typ());
-    
+
                 typ
             } else {
                 ctx.hole(&env, Kind::typ())
             };
-            
-            let mut typ = Type::<Real>::function(args.clone(), ret.clone());
-    
-            let fvs = fvs.iter().map(|x| {
-                let Some((index, _, _)) = env.find(x) else { unreachable!() };
-                Type::bound(Index(index))
-            }).collect();
+
+            let mut typ = Type::<Real>::function(args, ret);
+
+            for constraint in constraints.into_iter().rev() {
+                typ = Type::qualified(constraint, typ);
+            }
+
+            // The constraint of the trait itself is always the first one, so the implementations
+            // can skip it.
+            let fvs = type_data
+                .binders
+                .iter()
+                .map(|(name, _)| {
+                    let Some((index, _, _)) = env.find(name) else {
+                        unreachable!()
+                    };
+                    Type::bound(Index(index))
+                })
+                .collect();
 
             let constraint = Type::<Real>::application(Type::variable(self.name.clone()), fvs);
 
             typ = Type::qualified(constraint, typ);
 
-            for (name, kind) in unbound.iter().rev().cloned() {
+            for (name, kind) in unbound.into_iter().rev() {
                 typ = Type::forall(Forall {
-                    name: name.clone(),
+                    name,
                     kind,
                     body: typ,
                 });
             }
 
-            signatures.push((self.name.clone(), typ));
+            for (name, kind) in type_data.binders.iter().rev() {
+                typ = Type::forall(Forall {
+                    name: name.clone(),
+                    kind: kind.quote(env.level),
+                    body: typ,
+                });
+            }
+
+            let value = typ.eval(&start_env);
+
+            ctx.modules.get(&let_signature.name.path).variables.insert(
+                let_signature.name.name.clone(),
+                LetDef {
+                    typ: value.clone(),
+                    unbound: vec![],
+                    constraints: vec![],
+                    args: vec![],
+                    ret: value,
+                },
+            );
+
+            signatures.push((let_signature.name.clone(), typ));
         }
 
+        let methods = signatures.iter().map(|x| x.0.clone()).collect();
+
         ctx.modules.get(&self.name.path).traits.insert(
             self.name.name.clone(),
             TraitData {
-                kind,
-                binders,
-                supers,
+                kind: type_data.kind,
+                binders: type_data.binders.into_iter().map(|x| x.1).collect(),
+                supers: supers.clone(),
                 signatures,
             },
         );
+
+        (
+            self.name.clone(),
+            elaborated::TraitDecl {
+                supers: supers.len(),
+                methods,
+            },
+        )
     }
+}
 
-    fn define(&self, _context: (&mut Context, Env)) -> Self::Return {}
+/// The name of the dictionary of an instance. It uses the place of the instance because they do
+/// not have a name in the source code.
+fn instance_name(decl: &TraitImpl) -> Qualified {
+    Qualified {
+        path: decl.namespace.clone(),
+        name: Symbol::intern(&format!("{}${}", decl.name.name.get(), decl.span.start.0)),
+    }
+}
+
+/// Checks the implementation of a method against the signature in the trait instantiated with the
+/// types of the instance.
+fn define_method(
+    ctx: &mut Context,
+    mut env: Env,
+    types: &[Type<Virtual>],
+    context: &[Type<Virtual>],
+    signature: &Type<Real>,
+    decl: &LetDecl,
+    name: Qualified,
+) -> elaborated::LetDecl<Type<Real>> {
+    env.set_current_span(decl.signature.span.clone());
+
+    let mut typ = ctx.instantiate_with_arguments(&signature.eval(&Env::default()), types.to_vec());
+
+    // The variables that are bound by the method itself are rigid inside of the body.
+    loop {
+        let current = typ.deref();
+
+        let TypeKind::Forall(forall) = current.as_ref() else {
+            break;
+        };
+
+        let bound = Type::bound(env.level);
+        env = env.add(Some(forall.name.clone()), forall.kind.clone());
+        typ = forall
+            .body
+            .apply(Some(forall.name.clone()), bound, forall.kind.clone());
+    }
+
+    let mut constraints = context.to_vec();
+
+    if let TypeKind::Qualified(_, body) = typ.deref().as_ref() {
+        typ = body.clone();
+    }
+
+    loop {
+        let current = typ.deref();
+
+        let TypeKind::Qualified(constraint, body) = current.as_ref() else {
+            break;
+        };
+
+        constraints.push(constraint.clone());
+        typ = body.clone();
+    }
+
+    let mut annotation_env = env.clone();
+
+    for fv in free_variables(&decl.signature) {
+        if annotation_env.find(&fv).is_none() {
+            let kind = ctx.hole(&env, Type::typ());
+            let hole = ctx.hole(&env, kind.clone());
+            annotation_env = annotation_env.define(Some(fv), hole, kind);
+        }
+    }
+
+    let params = decl
+        .signature
+        .binders
+        .iter()
+        .filter(|x| matches!(x, LetBinder::Param(_)))
+        .count();
+
+    let mut args = Vec::new();
+
+    for binder in &decl.signature.binders {
+        let LetBinder::Param(binder) = binder else {
+            continue;
+        };
+
+        let current = typ.deref();
+
+        let TypeKind::Arrow(pi) = current.as_ref() else {
+            let expected = args.len() + typ.arrow_spine().len() - 1;
+            ctx.report(&env, TypeErrorKind::WrongArity(expected, params));
+            break;
+        };
+
+        let (annotation, kind) = binder.typ.infer((ctx, annotation_env.clone()));
+        env.set_current_span(binder.typ.span.clone());
+        ctx.subsumes(env.clone(), kind, Kind::typ());
+        ctx.subsumes(
+            env.clone(),
+            pi.typ.clone(),
+            annotation.eval(&annotation_env),
+        );
+
+        args.push(pi.typ.quote(env.level));
+        typ = pi.body.clone();
+    }
+
+    if let Some(ret) = &decl.signature.ret {
+        let (annotation, kind) = ret.infer((ctx, annotation_env.clone()));
+        env.set_current_span(ret.span.clone());
+        ctx.subsumes(env.clone(), kind, Kind::typ());
+        ctx.subsumes(env.clone(), annotation.eval(&annotation_env), typ.clone());
+    }
+
+    define_body(ctx, env, decl, name, &args, typ, constraints)
+}
+
+impl Declare for TraitImpl {
+    type Return = Option<(
+        elaborated::InstanceDecl,
+        Vec<(Qualified, elaborated::LetDecl<Type<Real>>)>,
+    )>;
+
+    fn declare(&self, (ctx, mut env): (&mut Context, Env)) {
+        env.set_current_span(self.span.clone());
+
+        let type_data = ctx.modules.typ(&self.name);
+
+        if !matches!(type_data.def, Def::Constraint) {
+            ctx.report(&env, TypeErrorKind::NotATrait(self.name.clone()));
+            return;
+        }
+
+        if type_data.binders.len() != self.binders.len() {
+            ctx.report(
+                &env,
+                TypeErrorKind::WrongArity(type_data.binders.len(), self.binders.len()),
+            );
+            return;
+        }
+
+        let mut fvs = HashSet::new();
+
+        for typ in self.binders.iter().chain(self.supers.iter()) {
+            fvs.extend(typ.data.free_variables());
+        }
+
+        let mut binders = Vec::new();
+
+        for fv in fvs {
+            let kind = ctx.hole(&env, Type::typ());
+            env = env.add(Some(fv.clone()), kind.clone());
+            binders.push((fv, kind));
+        }
+
+        let mut types = Vec::new();
+
+        for (typ, (_, kind)) in self.binders.iter().zip(type_data.binders.iter()) {
+            let (typ, typ_kind) = typ.infer((ctx, env.clone()));
+            ctx.subsumes(env.clone(), typ_kind, kind.clone());
+            types.push(typ);
+        }
+
+        let mut context = Vec::new();
+
+        for super_ in &self.supers {
+            let (typ, kind) = super_.infer((ctx, env.clone()));
+            ctx.subsumes(env.clone(), kind, Kind::constraint());
+            context.push(typ);
+        }
+
+        let instance = InstanceData {
+            name: instance_name(self),
+            binders,
+            types,
+            context,
+        };
+
+        env.set_current_span(self.span.clone());
+
+        let instances = ctx.modules.instances(&self.name);
+
+        if ctx.overlaps_with(&env, &instance, &instances) {
+            let head = Type::<Virtual>::application(
+                Type::variable(self.name.clone()),
+                instance.types.eval(&env),
+            );
+
+            ctx.report(
+                &env,
+                TypeErrorKind::OverlappingInstance(env.clone(), head.quote(env.level)),
+            );
+
+            return;
+        }
+
+        ctx.modules
+            .get(&self.name.path)
+            .instances
+            .entry(self.name.name.clone())
+            .or_default()
+            .push(instance);
+    }
+
+    fn define(&self, (ctx, mut env): (&mut Context, Env)) -> Self::Return {
+        let name = instance_name(self);
+
+        let instance = ctx
+            .modules
+            .instances(&self.name)
+            .into_iter()
+            .find(|x| x.name == name)?;
+
+        let trait_data = ctx.modules.trait_data(&self.name)?;
+
+        for (name, kind) in &instance.binders {
+            env = env.add(Some(name.clone()), kind.clone());
+        }
+
+        env.set_current_span(self.span.clone());
+
+        let types = instance.types.eval(&env);
+        let context = instance.context.eval(&env);
+
+        let trait_env = entail::substitution(&trait_data.binders, types.clone());
+
+        let given = ctx.add_given(&env, context.clone());
+
+        let evidences = trait_data
+            .supers
+            .iter()
+            .map(|super_| ctx.wanted(&env, super_.eval(&trait_env)))
+            .collect::<Vec<_>>();
+
+        ctx.solve_wanted();
+
+        let mut methods = Vec::new();
+        let mut lets = Vec::new();
+
+        for (method, signature) in &trait_data.signatures {
+            let Some(decl) = self
+                .body
+                .iter()
+                .find(|x| x.signature.name.name == method.name)
+            else {
+                continue;
+            };
+
+            let let_name = Qualified {
+                path: name.path.clone(),
+                name: Symbol::intern(&format!("{}${}", name.name.get(), method.name.get())),
+            };
+
+            let elab = define_method(
+                ctx,
+                env.clone(),
+                &types,
+                &context,
+                signature,
+                decl,
+                let_name.clone(),
+            );
+
+            methods.push(let_name.clone());
+            lets.push((let_name, elab));
+        }
+
+        Some((
+            elaborated::InstanceDecl {
+                name,
+                context: given.into_iter().map(|x| x.0).collect(),
+                supers: evidences.into_iter().map(|x| x.get()).collect(),
+                methods,
+            },
+            lets,
+        ))
+    }
 }
 
 impl Declare for TypeDecl {
@@ -319,14 +647,177 @@ impl Declare for TypeDecl {
 
                 elaborated::TypeDecl::Record(names)
             }
-            TypeDef::Synonym(_) => todo!(),
-            TypeDef::Abstract => elaborated::TypeDecl::Abstract,
+            // Synonyms are defined before everything else by [define_synonyms] and have no
+            // representation after type checking.
+            TypeDef::Synonym(_) => elaborated::TypeDecl::Abstract,
+            TypeDef::Abstract => {
+                // The requests that are received by handlers are the only abstract type with a
+                // constructor, the one for the final value of the computation.
+                if self.name == elaborated::request_type() && type_decl.binders.len() == 2 {
+                    let mut cons_typ =
+                        Type::<Real>::function(vec![Type::bound(Index(0))], ret_type.clone());
+
+                    for (name, binder) in type_decl.binders.iter().rev() {
+                        cons_typ = Type::forall(Forall {
+                            name: name.clone(),
+                            kind: binder.clone().quote(env.level),
+                            body: cons_typ,
+                        });
+                    }
+
+                    let name = elaborated::return_constructor();
+
+                    ctx.modules
+                        .get(&name.path)
+                        .constructors
+                        .insert(name.name.clone(), (cons_typ, 1, self.name.clone()));
+                }
+
+                elaborated::TypeDecl::Abstract
+            }
         };
 
         (self.name.clone(), decl)
     }
 }
 
+impl Declare for EffectDecl {
+    type Return = (Qualified, elaborated::EffectDecl);
+
+    fn declare(&self, (ctx, env): (&mut Context, Env)) {
+        env.set_current_span(self.span.clone());
+
+        let mut names = Vec::new();
+        let mut binders = Vec::new();
+
+        for binder in &self.binders {
+            let (n, binder) = binder.infer((ctx, env.clone()));
+            binders.push(binder.eval(&env));
+            names.push(n);
+        }
+
+        let kind = Type::<Virtual>::function(binders.clone(), Type::typ());
+
+        let operations = self.fields.iter().map(|x| x.name.clone()).collect();
+
+        ctx.modules.get(&self.name.path).types.insert(
+            self.name.name.clone(),
+            TypeData {
+                kind,
+                binders: names.into_iter().zip(binders).collect(),
+                module: self.namespace.clone(),
+                def: Def::Effect(operations),
+            },
+        );
+    }
+
+    fn define(&self, (ctx, mut env): (&mut Context, Env)) -> Self::Return {
+        env.set_current_span(self.span.clone());
+
+        let start_env = env.clone();
+
+        let type_data = ctx.modules.typ(&self.name);
+
+        for (name, binder) in &type_data.binders {
+            env = env.add(Some(name.clone()), binder.clone());
+        }
+
+        let request = ctx.find_prelude_type("Request", env.clone());
+
+        let effect = Type::<Real>::application(
+            Type::variable(self.name.clone()),
+            (0..type_data.binders.len())
+                .rev()
+                .map(|x| Type::bound(Index(x)))
+                .collect(),
+        );
+
+        let value = Type::<Virtual>::bound(env.level);
+        let value_env = env.add(Some(Symbol::intern("a")), Type::typ());
+
+        let mut operations = Vec::new();
+
+        for field in &self.fields {
+            let mut args = Vec::new();
+
+            for arg in &field.args {
+                env.set_current_span(arg.span.clone());
+                let (typ, kind) = arg.infer((ctx, env.clone()));
+                ctx.subsumes(env.clone(), kind, Kind::typ());
+                args.push(typ);
+            }
+
+            env.set_current_span(field.ret.span.clone());
+            let (ret, kind) = field.ret.infer((ctx, env.clone()));
+            ctx.subsumes(env.clone(), kind, Kind::typ());
+
+            // The operation itself is a computation that performs the effect.
+            let mut typ = Type::<Real>::function(
+                args.clone(),
+                Type::effect(vec![effect.clone()], ret.clone()),
+            );
+
+            // The request of the operation carries its arguments and the continuation.
+            let cont = Type::<Virtual>::function(
+                vec![ret.eval(&env)],
+                Type::effect(vec![effect.eval(&env)], value.clone()),
+            );
+
+            let mut params: Vec<_> = args.iter().map(|x| x.eval(&env)).collect();
+            params.push(cont);
+
+            let request = Type::<Virtual>::application(
+                request.clone(),
+                vec![effect.eval(&env), value.clone()],
+            );
+
+            let mut cons_typ = Type::forall(Forall {
+                name: Symbol::intern("a"),
+                kind: Type::typ(),
+                body: Type::<Virtual>::function(params, request).quote(value_env.level),
+            });
+
+            for (name, kind) in type_data.binders.iter().rev() {
+                typ = Type::forall(Forall {
+                    name: name.clone(),
+                    kind: kind.quote(env.level),
+                    body: typ,
+                });
+
+                cons_typ = Type::forall(Forall {
+                    name: name.clone(),
+                    kind: kind.quote(env.level),
+                    body: cons_typ,
+                });
+            }
+
+            let value = typ.eval(&start_env);
+
+            let module = ctx.modules.get(&field.name.path);
+
+            module.variables.insert(
+                field.name.name.clone(),
+                LetDef {
+                    typ: value.clone(),
+                    unbound: vec![],
+                    constraints: vec![],
+                    args: vec![],
+                    ret: value,
+                },
+            );
+
+            module.constructors.insert(
+                field.name.name.clone(),
+                (cons_typ, field.args.len() + 1, self.name.clone()),
+            );
+
+            operations.push((field.name.clone(), field.args.len()));
+        }
+
+        (self.name.clone(), elaborated::EffectDecl { operations })
+    }
+}
+
 fn get_definition_of_type(type_def: &TypeDef) -> Def {
     match type_def {
         TypeDef::Sum(cons) => Def::Enum(cons.constructors.iter().map(|x| x.name.clone()).collect()),
@@ -362,6 +853,7 @@ impl Declare for ExtDecl {
             LetDef {
                 typ: typ.clone(),
                 unbound,
+                constraints: vec![],
                 ret: typ.clone(),
                 args: vec![],
             },
@@ -373,6 +865,7 @@ impl Declare for ExtDecl {
                 name: self.name.clone(),
                 typ: typ.quote(env.level),
                 binding: self.ret.clone(),
+                span: self.span.clone(),
             },
         );
     }
@@ -417,14 +910,22 @@ impl Declare for LetDecl {
         }
 
         let mut args = Vec::new();
+        let mut constraints = Vec::new();
 
         for arg in &self.signature.binders {
             let (typ, kind) = arg.typ().infer((ctx, env.clone()));
             env.set_current_span(arg.typ().span.clone());
 
-            ctx.subsumes(env.clone(), kind, Kind::typ());
-
-            args.push(typ);
+            match arg {
+                LetBinder::Param(_) => {
+                    ctx.subsumes(env.clone(), kind, Kind::typ());
+                    args.push(typ);
+                }
+                LetBinder::Trait(_) => {
+                    ctx.subsumes(env.clone(), kind, Kind::constraint());
+                    constraints.push(typ);
+                }
+            }
         }
 
         let ret = if let Some(ret) = &self.signature.ret {
@@ -441,6 +942,10 @@ impl Declare for LetDecl {
 
         let mut typ = Type::<Real>::function(args.clone(), ret.clone());
 
+        for constraint in constraints.iter().rev().cloned() {
+            typ = Type::qualified(constraint, typ);
+        }
+
         for (name, kind) in unbound.iter().rev().cloned() {
             typ = Type::forall(Forall {
                 name,
@@ -457,6 +962,7 @@ impl Declare for LetDecl {
                 LetDef {
                     typ: typ.eval(&start_env),
                     unbound,
+                    constraints,
                     ret: ret.eval(&env),
                     args: func_args,
                 },
@@ -468,62 +974,189 @@ impl Declare for LetDecl {
 
         let let_decl = ctx.modules.let_decl(&self.signature.name).clone();
 
+        ctx.index.add(
+            self.signature.span.clone(),
+            Some(self.signature.name.clone()),
+            let_decl.typ.quote(env.level),
+            env.clone(),
+        );
+
         for (fv, typ) in &let_decl.unbound {
             env = env.add(Some(fv.clone()), typ.eval(&env).clone());
         }
 
-        let mut binders = Default::default();
-        let mut elab_binders = Vec::new();
+        let constraints = let_decl.constraints.eval(&env);
 
-        for (binder, typ) in self.signature.binders.iter().zip(let_decl.args.iter()) {
-            if let LetBinder::Param(binder) = binder {
-                let pat = binder
-                    .pat
-                    .check(typ.eval(&env), (ctx, &mut binders, env.clone()));
+        (
+            self.signature.name.clone(),
+            define_body(
+                ctx,
+                env,
+                self,
+                self.signature.name.clone(),
+                &let_decl.args,
+                let_decl.ret,
+                constraints,
+            ),
+        )
+    }
+}
 
-                elab_binders.push((pat, typ.clone()));
-            }
-        }
+/// Checks the body of a let declaration against the types of the arguments and the return type.
+/// Each constraint is received as a dictionary before the arguments.
+fn define_body(
+    ctx: &mut Context,
+    mut env: Env,
+    decl: &LetDecl,
+    name: Qualified,
+    args: &[Type<Real>],
+    typ: Type<Virtual>,
+    constraints: Vec<Type<Virtual>>,
+) -> elaborated::LetDecl<Type<Real>> {
+    let mut binders = Default::default();
+    let mut elab_binders = Vec::new();
+
+    for (dictionary, typ) in ctx.add_given(&env, constraints) {
+        elab_binders.push((Box::new(elaborated::PatternKind::Variable(dictionary)), typ));
+    }
 
-        for binder in binders {
-            env.add_var(binder.0, binder.1);
-        }
+    let params = decl.signature.binders.iter().filter_map(|x| match x {
+        LetBinder::Param(binder) => Some(binder),
+        LetBinder::Trait(_) => None,
+    });
+
+    for (binder, typ) in params.zip(args.iter()) {
+        let pat = binder
+            .pat
+            .check(typ.eval(&env), (ctx, &mut binders, env.clone()));
+
+        elab_binders.push((pat, typ.clone()));
+    }
 
-        let typ = let_decl.ret.clone();
-        let binders = elab_binders;
+    for binder in binders {
+        env.add_var(binder.0, binder.1);
+    }
+
+    let binders = elab_binders;
 
-        ctx.errored = false;
+    ctx.errored = false;
 
-        let body = self.body.check(typ.clone(), (ctx, env.clone()));
-        let types = typ.arrow_spine();
+    let ret = decl.signature.ret.as_ref().map(|ret| ret.span.clone());
+    let body = decl.body.check(typ.clone(), (ctx, env.expecting(ret)));
+    let types = typ.arrow_spine();
 
-        if !ctx.errored {
-            let problem = Problem::exhaustiveness(&body, types);
-            let patterns = &self.body.last().unwrap().patterns;
+    if !ctx.errored {
+        let problem = Problem::exhaustiveness(&body, types.clone());
+        let patterns = &decl.body.last().unwrap().patterns;
 
-            if patterns.first().is_some() {
-                env.set_current_span(patterns
+        if !patterns.is_empty() {
+            env.set_current_span(
+                patterns
                     .first()
                     .unwrap()
                     .span
                     .clone()
-                    .mix(patterns.last().unwrap().span.clone()));
+                    .mix(patterns.last().unwrap().span.clone()),
+            );
 
-                if let Witness::NonExhaustive(case) = problem.exaustive(ctx, env.clone()) {
-                    ctx.report(&env, TypeErrorKind::NonExhaustive(case));
-                };
-            }
+            if let Witness::NonExhaustive(case) = problem.exaustive(ctx, env.clone()) {
+                let missing = MissingArm::after(&decl.body, "| ");
+                ctx.report(
+                    &env,
+                    TypeErrorKind::NonExhaustive(case.with_lists(ctx.list.as_ref()), missing),
+                );
+            };
         }
 
-        (
-            self.signature.name.clone(),
-            elaborated::LetDecl {
-                name: self.signature.name.clone(),
-                binders,
-                body,
-                constants: self.constant.clone(),
-            },
-        )
+        ctx.check_redundancy(&env, &decl.body, &body, &types);
+    }
+
+    ctx.solve_wanted();
+
+    elaborated::LetDecl {
+        name,
+        span: decl.signature.span.clone(),
+        visibility: decl.signature.visibility.clone(),
+        binders,
+        body,
+        constants: decl.constant.clone(),
+    }
+}
+
+/// Defines a synonym after the synonyms that it uses. The ones that use themselves are reported
+/// and replaced by an error, so their expansion always ends.
+fn define_synonym(
+    ctx: &mut Context,
+    env: Env,
+    synonyms: &[&TypeDecl],
+    visited: &mut HashMap<Qualified, bool>,
+    decl: &TypeDecl,
+) {
+    let TypeDef::Synonym(body) = &decl.def else {
+        return;
+    };
+
+    match visited.get(&decl.name) {
+        Some(true) => return,
+        Some(false) => {
+            env.set_current_span(body.span.clone());
+            ctx.report(&env, TypeErrorKind::CyclicSynonym(decl.name.clone()));
+
+            visited.insert(decl.name.clone(), true);
+            ctx.modules.get(&decl.name.path).types.get_mut(&decl.name.name).unwrap().def =
+                Def::Synonym(Type::error());
+
+            return;
+        }
+        None => {
+            visited.insert(decl.name.clone(), false);
+        }
+    }
+
+    for name in body.data.type_names() {
+        if let Some(used) = synonyms.iter().find(|x| x.name == name) {
+            define_synonym(ctx, env.clone(), synonyms, visited, used);
+        }
+    }
+
+    // It was part of a cycle and got replaced by an error.
+    if visited.get(&decl.name) == Some(&true) {
+        return;
+    }
+
+    let mut type_data = ctx.modules.typ(&decl.name);
+    let mut body_env = env.clone();
+
+    for (name, binder) in &type_data.binders {
+        body_env = body_env.add(Some(name.clone()), binder.clone());
+    }
+
+    let (typ, kind) = body.infer((ctx, body_env));
+
+    let binders = type_data.binders.iter().map(|x| x.1.clone()).collect();
+    type_data.kind = Type::<Virtual>::function(binders, kind);
+    type_data.def = Def::Synonym(typ);
+
+    ctx.modules
+        .get(&decl.name.path)
+        .types
+        .insert(decl.name.name.clone(), type_data);
+
+    visited.insert(decl.name.clone(), true);
+}
+
+/// Defines all the type synonyms, so they can be expanded in the declarations of everything else.
+fn define_synonyms(ctx: &mut Context, env: Env, programs: &[Program]) {
+    let synonyms: Vec<_> = programs
+        .iter()
+        .flat_map(|program| program.types.iter())
+        .filter(|decl| matches!(decl.def, TypeDef::Synonym(_)))
+        .collect();
+
+    let mut visited = HashMap::new();
+
+    for decl in &synonyms {
+        define_synonym(ctx, env.clone(), &synonyms, &mut visited, decl);
     }
 }
 
@@ -535,6 +1168,26 @@ impl Declare for Programs {
     fn declare(&self, (ctx, env): (&mut Context, Env)) {
         for program in self.0.iter() {
             program.types.declare((ctx, env.clone()));
+
+            let list = program
+                .commands
+                .iter()
+                .filter(|(_, command)| command.get() == "list")
+                .find_map(|(name, _)| program.types.iter().find(|decl| decl.name.name == *name));
+
+            if let Some(decl) = list {
+                ctx.list = Some(decl.namespace.clone());
+            }
+        }
+
+        define_synonyms(ctx, env.clone(), &self.0);
+
+        for program in self.0.iter() {
+            program.effects.declare((ctx, env.clone()));
+        }
+
+        for program in self.0.iter() {
+            program.traits.declare((ctx, env.clone()));
         }
 
         for program in self.0.iter() {
@@ -546,7 +1199,7 @@ impl Declare for Programs {
         }
 
         for program in self.0.iter() {
-            program.traits.declare((ctx, env.clone()));
+            program.impls.declare((ctx, env.clone()));
         }
     }
 
@@ -558,6 +1211,16 @@ impl Declare for Programs {
             programs[i].types = typ.into_iter().collect();
         }
 
+        for (i, program) in self.0.iter().enumerate() {
+            let effect_decl = program.effects.define((context, env.clone()));
+            programs[i].effects = effect_decl.into_iter().collect();
+        }
+
+        for (i, program) in self.0.iter().enumerate() {
+            let trait_decl = program.traits.define((context, env.clone()));
+            programs[i].traits = trait_decl.into_iter().collect();
+        }
+
         for (i, program) in self.0.iter().enumerate() {
             let let_decl = program.lets.define((context, env.clone()));
             programs[i].lets = let_decl.into_iter().collect();
@@ -569,7 +1232,15 @@ impl Declare for Programs {
         }
 
         for (i, program) in self.0.iter().enumerate() {
-            let _trait_decl = program.traits.define((context, env.clone()));
+            let impls = program.impls.define((context, env.clone()));
+
+            for (instance, lets) in impls.into_iter().flatten() {
+                programs[i].lets.extend(lets);
+                programs[i]
+                    .instances
+                    .insert(instance.name.clone(), instance);
+            }
+
             programs[i].commands = program.commands.clone();
         }
 
@@ -0,0 +1,152 @@
+//! Module for the effects that are performed by expressions. Every expression is checked inside of
+//! a stack of [Scope]s that say what happens with the effects that it performs: they can be handled
+//! by a `handle` expression, allowed by the type of a computation or collected in the type of a
+//! lambda.
+
+use vulpi_location::Spanned;
+use vulpi_syntax::elaborated;
+
+use crate::{
+    context::Context,
+    errors::TypeErrorKind,
+    eval::Quote,
+    module::Def,
+    r#virtual::{Env, Virtual},
+    real::Real,
+    Type, TypeKind,
+};
+
+pub enum Scope {
+    /// The effects allowed by the type of a computation. Performing any other effect is an error.
+    Closed(Vec<Type<Virtual>>),
+
+    /// The effects performed by the body of a lambda that has no annotation. They are added to
+    /// the type of the lambda.
+    Open(Vec<Type<Virtual>>),
+
+    /// The effect that is handled by a `handle` expression and the other effects that go through
+    /// it. The continuations run inside of the handler, so it must allow the effects that go
+    /// through it.
+    Handler(Type<Virtual>, Vec<Type<Virtual>>),
+}
+
+fn head(typ: &Type<Virtual>) -> Type<Virtual> {
+    typ.deref().application_spine().0.deref()
+}
+
+fn same_head(left: &Type<Virtual>, right: &Type<Virtual>) -> bool {
+    match (head(left).as_ref(), head(right).as_ref()) {
+        (TypeKind::Variable(x), TypeKind::Variable(y)) => x == y,
+        (TypeKind::Bound(x), TypeKind::Bound(y)) => x == y,
+        (TypeKind::Hole(_), _) | (_, TypeKind::Hole(_)) => true,
+        (TypeKind::Error, _) | (_, TypeKind::Error) => true,
+        _ => false,
+    }
+}
+
+/// Finds the effect of a list that has the same head of another one. Effects are identified by
+/// their heads, so `Log String` and `Log Int` cannot be in the same list.
+pub fn same_effect(effects: &[Type<Virtual>], effect: &Type<Virtual>) -> Option<Type<Virtual>> {
+    effects.iter().find(|x| same_head(x, effect)).cloned()
+}
+
+impl Context {
+    /// Checks if the head of a type is an effect or something that can be replaced by one.
+    pub fn is_effect(&mut self, typ: &Type<Real>) -> bool {
+        let (head, _) = typ.application_spine();
+
+        match head.as_ref() {
+            TypeKind::Variable(name) => matches!(self.modules.typ(name).def, Def::Effect(_)),
+            _ => true,
+        }
+    }
+
+    /// Runs a function inside of an effect scope and returns the scope after it.
+    pub fn effect_scope<T>(&mut self, scope: Scope, fun: impl FnOnce(&mut Self) -> T) -> (T, Scope) {
+        self.effects.push(scope);
+        let result = fun(self);
+        let scope = self.effects.pop().unwrap();
+        (result, scope)
+    }
+
+    /// Performs a list of effects in the current scope.
+    pub fn perform(&mut self, env: &Env, effects: &[Type<Virtual>]) {
+        for effect in effects {
+            let mut found = None;
+
+            for scope in self.effects.iter_mut().rev() {
+                match scope {
+                    Scope::Handler(handled, _) if same_head(handled, effect) => {
+                        found = Some(handled.clone());
+                        break;
+                    }
+                    Scope::Handler(_, passing) => {
+                        if same_effect(passing, effect).is_none() {
+                            passing.push(effect.clone());
+                        }
+                    }
+                    Scope::Closed(allowed) => {
+                        found = same_effect(allowed, effect);
+                        break;
+                    }
+                    Scope::Open(collected) => {
+                        found = same_effect(collected, effect);
+
+                        if found.is_none() {
+                            collected.push(effect.clone());
+                            found = Some(effect.clone());
+                        }
+
+                        break;
+                    }
+                }
+            }
+
+            match found {
+                Some(other) => self.subsumes(env.clone(), effect.clone(), other),
+                None => self.report(
+                    env,
+                    TypeErrorKind::UnhandledEffect(env.clone(), effect.quote(env.level)),
+                ),
+            }
+        }
+    }
+
+    /// Performs a value if it is a computation. It happens every time that a computation is used
+    /// as an expression, so its type turns into the type of the result of the computation.
+    pub fn run_computation(
+        &mut self,
+        env: &Env,
+        typ: Type<Virtual>,
+        elab: elaborated::Expr<Type<Real>>,
+    ) -> (Type<Virtual>, Box<elaborated::ExprKind<Type<Real>>>) {
+        let instantiated = self.instantiate_all(env, &typ);
+
+        if let TypeKind::Effect(effects, ret) = instantiated.deref().as_ref() {
+            self.perform(env, effects);
+            (ret.clone(), Box::new(elaborated::ExprKind::Perform(elab)))
+        } else {
+            (typ, elab.data)
+        }
+    }
+
+    /// Turns the result of an expression that was checked in an open scope into a computation if
+    /// it performed any effect.
+    pub fn computation(
+        &mut self,
+        scope: Scope,
+        typ: Type<Virtual>,
+        elab: elaborated::Expr<Type<Real>>,
+    ) -> (Type<Virtual>, elaborated::Expr<Type<Real>>) {
+        match scope {
+            Scope::Open(effects) if !effects.is_empty() => {
+                let span = elab.span.clone();
+                (
+                    Type::effect(effects, typ),
+                    Spanned::new(Box::new(elaborated::ExprKind::Computation(elab)), span),
+                )
+            }
+            _ => (typ, elab),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Module for entailment of constraints. Every use of a declaration with a qualified type creates
+//! a wanted constraint with an empty [Evidence]. At the end of the declaration the constraints are
+//! solved using the constraints that were given by the signature and the instances, and the
+//! evidence is filled with the dictionary that proves the constraint.
+
+#![allow(clippy::result_large_err)]
+
+use vulpi_intern::Symbol;
+use vulpi_syntax::elaborated::{Dictionary, Evidence};
+
+use crate::{
+    context::Context,
+    errors::TypeErrorKind,
+    eval::{Eval, Quote},
+    module::InstanceData,
+    r#virtual::{Env, Virtual},
+    real::Real,
+    Type, TypeKind,
+};
+
+/// The maximum depth of the search for instances. It avoids infinite loops with instances that
+/// require bigger constraints than the ones that they prove.
+const MAX_DEPTH: usize = 32;
+
+type Result<T = Dictionary> = std::result::Result<T, TypeErrorKind>;
+
+/// A constraint that has to be proved at the end of the current declaration.
+pub struct Wanted {
+    pub env: Env,
+    pub constraint: Type<Virtual>,
+    pub evidence: Evidence,
+}
+
+fn is_ambiguous(typ: &Type<Virtual>) -> bool {
+    match typ.deref().as_ref() {
+        TypeKind::Hole(_) => true,
+        TypeKind::Arrow(pi) => is_ambiguous(&pi.typ) || is_ambiguous(&pi.body),
+        TypeKind::Tuple(types) => types.iter().any(is_ambiguous),
+        TypeKind::Application(func, arg) => is_ambiguous(func) || is_ambiguous(arg),
+        TypeKind::Qualified(from, to) => is_ambiguous(from) || is_ambiguous(to),
+        TypeKind::Effect(effects, typ) => effects.iter().any(is_ambiguous) || is_ambiguous(typ),
+        _ => false,
+    }
+}
+
+fn has_error(typ: &Type<Virtual>) -> bool {
+    match typ.deref().as_ref() {
+        TypeKind::Error => true,
+        TypeKind::Arrow(pi) => has_error(&pi.typ) || has_error(&pi.body),
+        TypeKind::Tuple(types) => types.iter().any(has_error),
+        TypeKind::Application(func, arg) => has_error(func) || has_error(arg),
+        TypeKind::Qualified(from, to) => has_error(from) || has_error(to),
+        TypeKind::Effect(effects, typ) => effects.iter().any(has_error) || has_error(typ),
+        _ => false,
+    }
+}
+
+/// Creates an environment where the bound variables of a declaration are replaced by the types in
+/// `args`, so the types of the declaration can be evaluated with them.
+pub fn substitution(binders: &[Type<Virtual>], args: Vec<Type<Virtual>>) -> Env {
+    binders
+        .iter()
+        .zip(args)
+        .fold(Env::default(), |env, (kind, arg)| {
+            env.define(None, arg, kind.clone())
+        })
+}
+
+impl Context {
+    /// Creates a constraint that must be proved at the end of the current declaration and returns
+    /// the place where its dictionary will be stored.
+    pub fn wanted(&mut self, env: &Env, constraint: Type<Virtual>) -> Evidence {
+        let evidence = Evidence::new();
+
+        self.wanted.push(Wanted {
+            env: env.clone(),
+            constraint,
+            evidence: evidence.clone(),
+        });
+
+        evidence
+    }
+
+    /// Instantiates a type that contains constraints and creates a wanted constraint for each one
+    /// of them. Types without constraints are returned untouched so higher rank types keep being
+    /// instantiated lazily.
+    pub fn instantiate_constraints(
+        &mut self,
+        env: &Env,
+        typ: Type<Virtual>,
+    ) -> (Type<Virtual>, Vec<Evidence>) {
+        let mut instantiated = self.instantiate_all(env, &typ);
+
+        if !matches!(instantiated.deref().as_ref(), TypeKind::Qualified(_, _)) {
+            return (typ, vec![]);
+        }
+
+        let mut evidences = vec![];
+
+        while let TypeKind::Qualified(constraint, body) = instantiated.deref().as_ref() {
+            evidences.push(self.wanted(env, constraint.clone()));
+            let body = body.clone();
+            instantiated = self.instantiate_all(env, &body);
+        }
+
+        (instantiated, evidences)
+    }
+
+    /// Makes the constraints of a declaration available to the entailment. Each one of them is
+    /// received as a parameter with the returned name, and the super traits of them are available
+    /// through the same parameter.
+    pub fn add_given(
+        &mut self,
+        env: &Env,
+        constraints: Vec<Type<Virtual>>,
+    ) -> Vec<(Symbol, Type<Real>)> {
+        let mut params = vec![];
+
+        for constraint in constraints {
+            let name = Symbol::intern(&format!("dict${}", self.given.len()));
+            params.push((name.clone(), constraint.quote(env.level)));
+            self.add_given_with_supers(constraint, Dictionary::Variable(name), 0);
+        }
+
+        params
+    }
+
+    fn add_given_with_supers(
+        &mut self,
+        constraint: Type<Virtual>,
+        dictionary: Dictionary,
+        depth: usize,
+    ) {
+        self.given.push((constraint.clone(), dictionary.clone()));
+
+        if depth > MAX_DEPTH {
+            return;
+        }
+
+        let (head, args) = constraint.application_spine();
+
+        let head = head.deref();
+
+        let TypeKind::Variable(name) = head.as_ref() else {
+            return;
+        };
+
+        let Some(trait_data) = self.modules.trait_data(name) else {
+            return;
+        };
+
+        let env = substitution(&trait_data.binders, args);
+
+        for (i, super_) in trait_data.supers.iter().enumerate() {
+            let dictionary = Dictionary::Super(Box::new(dictionary.clone()), i);
+            self.add_given_with_supers(super_.eval(&env), dictionary, depth + 1);
+        }
+    }
+
+    /// Proves every wanted constraint and forgets the given ones. It should be called at the end of
+    /// each declaration.
+    pub fn solve_wanted(&mut self) {
+        let wanted = std::mem::take(&mut self.wanted);
+
+        for wanted in wanted {
+            match self.entail(&wanted.env, wanted.constraint, 0) {
+                Ok(dictionary) => wanted.evidence.fill(dictionary),
+                Err(kind) => self.report(&wanted.env, kind),
+            }
+        }
+
+        self.given.clear();
+    }
+
+    fn entail(&mut self, env: &Env, constraint: Type<Virtual>, depth: usize) -> Result {
+        if has_error(&constraint) {
+            return Ok(Dictionary::Error);
+        }
+
+        if is_ambiguous(&constraint) {
+            return Err(TypeErrorKind::AmbiguousInstance(
+                env.clone(),
+                constraint.quote(env.level),
+            ));
+        }
+
+        let no_instance = || TypeErrorKind::NoInstance(env.clone(), constraint.quote(env.level));
+
+        if depth > MAX_DEPTH {
+            return Err(no_instance());
+        }
+
+        for (given, dictionary) in self.given.clone() {
+            if self.overlaps(env.clone(), given, constraint.clone()) {
+                return Ok(dictionary);
+            }
+        }
+
+        let (head, args) = constraint.application_spine();
+
+        let head = head.deref();
+
+        let TypeKind::Variable(name) = head.as_ref() else {
+            return Err(no_instance());
+        };
+
+        let mut candidates = vec![];
+
+        for instance in self.modules.instances(name) {
+            let (types, context) = self.instantiate_instance(env, &instance);
+
+            let matches = types
+                .into_iter()
+                .zip(args.iter().cloned())
+                .all(|(typ, arg)| self.overlaps(env.clone(), typ, arg));
+
+            if matches {
+                candidates.push((instance.name, context));
+            }
+        }
+
+        match candidates.len() {
+            0 => Err(no_instance()),
+            1 => {
+                let (name, context) = candidates.pop().unwrap();
+
+                let dictionaries = context
+                    .into_iter()
+                    .map(|constraint| self.entail(env, constraint, depth + 1))
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(Dictionary::Instance(name, dictionaries))
+            }
+            _ => Err(TypeErrorKind::OverlappingInstance(
+                env.clone(),
+                constraint.quote(env.level),
+            )),
+        }
+    }
+
+    /// Replaces the variables of an instance by new holes and returns the types and the context of
+    /// the instance.
+    fn instantiate_instance(
+        &mut self,
+        env: &Env,
+        instance: &InstanceData,
+    ) -> (Vec<Type<Virtual>>, Vec<Type<Virtual>>) {
+        let kinds = instance
+            .binders
+            .iter()
+            .map(|(_, kind)| kind.clone())
+            .collect::<Vec<_>>();
+
+        let holes = kinds
+            .iter()
+            .map(|kind| self.hole(env, kind.clone()))
+            .collect();
+
+        let env = substitution(&kinds, holes);
+
+        (instance.types.eval(&env), instance.context.eval(&env))
+    }
+
+    /// Checks if an instance can be used for the same constraints of the other ones. The
+    /// unification fills the holes of both instances, so they are instantiated again for every
+    /// comparison.
+    pub fn overlaps_with(
+        &mut self,
+        env: &Env,
+        instance: &InstanceData,
+        others: &[InstanceData],
+    ) -> bool {
+        others.iter().any(|other| {
+            let (types, _) = self.instantiate_instance(env, instance);
+            let (other_types, _) = self.instantiate_instance(env, other);
+
+            types
+                .iter()
+                .zip(other_types)
+                .all(|(typ, other)| self.overlaps(env.clone(), typ.clone(), other))
+        })
+    }
+}
@@ -1,9 +1,9 @@
 //! Module for definition of errors that can occur during type checking.
 
 use vulpi_intern::Symbol;
-use vulpi_location::Span;
-use vulpi_report::{IntoDiagnostic, Text};
-use vulpi_syntax::r#abstract::Qualified;
+use vulpi_location::{Byte, Span};
+use vulpi_report::{Applicability, IntoDiagnostic, Marker, Suggestion, Text};
+use vulpi_syntax::r#abstract::{PatternArm, Qualified};
 
 use crate::{
     coverage::{Pat, Row},
@@ -31,7 +31,51 @@ pub enum TypeErrorKind {
     NotFoundField,
     NotARecord,
     MissingField(Symbol),
-    NonExhaustive(Row<Pat>),
+    NonExhaustive(Row<Pat>, Option<MissingArm>),
+    RedundantArm(Option<(Vec<Pat>, Span)>),
+    NotATrait(Qualified),
+    NoInstance(Env, Type<Real>),
+    AmbiguousInstance(Env, Type<Real>),
+    OverlappingInstance(Env, Type<Real>),
+    NotAnEffect(Env, Type<Real>),
+    UnhandledEffect(Env, Type<Real>),
+    EffectThroughHandler(Env, Type<Real>),
+    NotAHandler(Env, Type<Real>),
+    NotAnOperation(Qualified),
+    CyclicSynonym(Qualified),
+    PartialSynonym(Qualified, usize, usize),
+}
+
+/// Where an arm for the patterns that are not covered by a pattern match can be added.
+pub struct MissingArm {
+    /// The end of the last arm.
+    pub after: Span,
+
+    /// The start of the last arm, where the new one has to start too.
+    pub start: Span,
+
+    /// What comes before the patterns of an arm, like the bar of the cases of a let.
+    pub prefix: &'static str,
+}
+
+impl MissingArm {
+    pub fn after(arms: &[PatternArm], prefix: &'static str) -> Option<Self> {
+        let last = arms.last()?;
+        let end = last.expr.span.clone();
+        let pattern = last.patterns.first()?.span.clone();
+
+        Some(MissingArm {
+            after: Span {
+                start: end.end.clone(),
+                ..end
+            },
+            start: Span {
+                start: Byte(pattern.start.0.saturating_sub(prefix.len())),
+                ..pattern
+            },
+            prefix,
+        })
+    }
 }
 
 pub struct TypeError {
@@ -40,6 +84,45 @@ pub struct TypeError {
 }
 
 impl IntoDiagnostic for TypeError {
+    fn code(&self) -> Option<usize> {
+        let code = match &self.kind {
+            TypeErrorKind::EmptyCase => 300,
+            TypeErrorKind::UnboundTypeVariable(_) => 301,
+            TypeErrorKind::TypeMismatch(_, _, _) => 302,
+            TypeErrorKind::KindMismatch(_, _, _) => 303,
+            TypeErrorKind::InfiniteType => 304,
+            TypeErrorKind::CannotFind(_) => 305,
+            TypeErrorKind::AtLeastOneArgument => 306,
+            TypeErrorKind::EscapingScope => 307,
+            TypeErrorKind::NotAFunctionKind => 308,
+            TypeErrorKind::WrongArity(_, _) => 309,
+            TypeErrorKind::NotAFunction(_, _) => 310,
+            TypeErrorKind::NotImplemented => 311,
+            TypeErrorKind::MissingLabel(_) => 312,
+            TypeErrorKind::InvalidLabels(_) => 313,
+            TypeErrorKind::PatternsNotAllowedHere => 314,
+            TypeErrorKind::DuplicatedField => 315,
+            TypeErrorKind::NotFoundField => 316,
+            TypeErrorKind::NotARecord => 317,
+            TypeErrorKind::MissingField(_) => 318,
+            TypeErrorKind::NonExhaustive(_, _) => 319,
+            TypeErrorKind::RedundantArm(_) => 320,
+            TypeErrorKind::NotATrait(_) => 321,
+            TypeErrorKind::NoInstance(_, _) => 322,
+            TypeErrorKind::AmbiguousInstance(_, _) => 323,
+            TypeErrorKind::OverlappingInstance(_, _) => 324,
+            TypeErrorKind::NotAnEffect(_, _) => 325,
+            TypeErrorKind::UnhandledEffect(_, _) => 326,
+            TypeErrorKind::NotAHandler(_, _) => 327,
+            TypeErrorKind::NotAnOperation(_) => 328,
+            TypeErrorKind::CyclicSynonym(_) => 329,
+            TypeErrorKind::PartialSynonym(_, _, _) => 330,
+            TypeErrorKind::EffectThroughHandler(_, _) => 331,
+        };
+
+        Some(code)
+    }
+
     fn message(&self) -> Text {
         match &self.kind {
             TypeErrorKind::TypeMismatch(env, left, right) => Text::from(format!(
@@ -93,17 +176,123 @@ impl IntoDiagnostic for TypeError {
                 Text::from("at least one argument is required".to_string())
             }
 
-            TypeErrorKind::NonExhaustive(row) => {
-                Text::from(format!("non-exhaustive patterns: {}", row))
+            TypeErrorKind::NonExhaustive(row, _) => {
+                Text::from(format!("non-exhaustive patterns: {}", row.to_patterns()))
+            }
+            TypeErrorKind::RedundantArm(Some((pats, _))) => Text::from(format!(
+                "unreachable arm, everything that it matches is already matched by the arm '{}'",
+                pats.iter()
+                    .map(|pat| pat.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            TypeErrorKind::RedundantArm(None) => Text::from(
+                "unreachable arm, everything that it matches is already matched by the arms before it"
+                    .to_string(),
+            ),
+            TypeErrorKind::NotATrait(name) => {
+                Text::from(format!("not a trait: {}", name.name.get()))
             }
+            TypeErrorKind::NoInstance(env, constraint) => {
+                Text::from(format!("no instance found for: {}", constraint.show(env)))
+            }
+            TypeErrorKind::AmbiguousInstance(env, constraint) => Text::from(format!(
+                "ambiguous constraint, cannot choose an instance for: {}",
+                constraint.show(env)
+            )),
+            TypeErrorKind::OverlappingInstance(env, constraint) => Text::from(format!(
+                "overlapping instances for: {}",
+                constraint.show(env)
+            )),
+            TypeErrorKind::NotAnEffect(env, typ) => {
+                Text::from(format!("not an effect: {}", typ.show(env)))
+            }
+            TypeErrorKind::UnhandledEffect(env, effect) => Text::from(format!(
+                "the effect {} is not handled or allowed here",
+                effect.show(env)
+            )),
+            TypeErrorKind::EffectThroughHandler(env, effect) => Text::from(format!(
+                "the effect {} goes through the handler, so the handler must allow it",
+                effect.show(env)
+            )),
+            TypeErrorKind::NotAnOperation(name) => {
+                Text::from(format!("not an operation of an effect: {}", name.name.get()))
+            }
+            TypeErrorKind::NotAHandler(env, typ) => Text::from(format!(
+                "not a handler, expected a function from a request: {}",
+                typ.show(env)
+            )),
+            TypeErrorKind::CyclicSynonym(name) => {
+                Text::from(format!("the type synonym {} refers to itself", name.name.get()))
+            }
+            TypeErrorKind::PartialSynonym(name, expected, found) => Text::from(format!(
+                "the type synonym {} expects {} arguments but got {}",
+                name.name.get(),
+                expected,
+                found
+            )),
         }
     }
 
     fn severity(&self) -> vulpi_report::Severity {
-        vulpi_report::Severity::Error
+        match self.kind {
+            TypeErrorKind::RedundantArm(_) => vulpi_report::Severity::Warning,
+            _ => vulpi_report::Severity::Error,
+        }
     }
 
     fn location(&self) -> Span {
         self.span.clone()
     }
+
+    fn primary(&self) -> Marker {
+        let subtitle = match &self.kind {
+            TypeErrorKind::TypeMismatch(env, left, _) if env.expected.is_some() => {
+                Some(Text::from(format!("found {}", left.show(env))))
+            }
+            TypeErrorKind::NonExhaustive(row, _) => Some(Text::from(format!(
+                "'{}' is not covered",
+                row.to_patterns()
+            ))),
+            _ => None,
+        };
+
+        Marker {
+            position: self.span.clone(),
+            subtitle,
+        }
+    }
+
+    fn secondary(&self) -> Vec<Marker> {
+        match &self.kind {
+            TypeErrorKind::TypeMismatch(env, _, right) => env
+                .expected
+                .iter()
+                .map(|span| {
+                    Marker::new(
+                        span.clone(),
+                        format!("expected {} because of this", right.show(env)),
+                    )
+                })
+                .collect(),
+            TypeErrorKind::RedundantArm(Some((_, span))) => vec![Marker::new(
+                span.clone(),
+                "this arm matches everything that the unreachable one does",
+            )],
+            _ => Vec::new(),
+        }
+    }
+
+    fn suggestions(&self) -> Vec<Suggestion> {
+        match &self.kind {
+            TypeErrorKind::NonExhaustive(row, Some(missing)) => vec![Suggestion {
+                message: Text::from("add an arm for the missing patterns"),
+                position: missing.after.clone(),
+                replacement: format!("\n{}{} => ...", missing.prefix, row.to_patterns()),
+                applicability: Applicability::HasPlaceholders,
+                indent: Some(missing.start.clone()),
+            }],
+            _ => Vec::new(),
+        }
+    }
 }
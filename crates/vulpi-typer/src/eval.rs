@@ -46,6 +46,12 @@ impl Eval<Type<Virtual>> for Type<Real> {
                 Type::new(TypeKind::Qualified(from, to))
             }
             TypeKind::Constraint => Type::new(TypeKind::Constraint),
+            TypeKind::Effect(effects, typ) => {
+                Type::new(TypeKind::Effect(effects.eval(env), typ.eval(env)))
+            }
+            TypeKind::Synonym(synonym, typ) => {
+                Type::new(TypeKind::Synonym(synonym.eval(env), typ.eval(env)))
+            }
         }
     }
 }
@@ -74,10 +80,7 @@ pub trait Quote<T> {
 
 impl Quote<Type<Real>> for Hole<Virtual> {
     fn quote(&self, depth: Level) -> Type<Real> {
-        match &*self.0.borrow() {
-            HoleInner::Empty(_, _, _) => Type::new(TypeKind::Hole(self.clone())),
-            HoleInner::Filled(f) => f.clone().quote(depth),
-        }
+        quote_hole(self, depth, false)
     }
 }
 
@@ -89,36 +92,70 @@ impl Quote<Vec<Type<Real>>> for Vec<Type<Virtual>> {
 
 impl Quote<Type<Real>> for Type<Virtual> {
     fn quote(&self, depth: Level) -> Type<Real> {
-        match self.as_ref() {
-            TypeKind::Type => Type::new(TypeKind::Type),
-            TypeKind::Arrow(pi) => Type::new(TypeKind::Arrow(real::Arrow {
-                typ: pi.typ.clone().quote(depth),
-                body: pi.body.clone().quote(depth),
-            })),
-            TypeKind::Forall(f) => Type::new(TypeKind::Forall(real::Forall {
-                name: f.name.clone(),
-                kind: f.kind.clone().quote(depth),
-                body: f
-                    .body
-                    .apply_local(Some(f.name.clone()), Type::new(TypeKind::Bound(depth)))
-                    .quote(depth.inc()),
-            })),
-            TypeKind::Hole(h) => h.quote(depth),
-            TypeKind::Variable(v) => Type::new(TypeKind::Variable(v.clone())),
-            TypeKind::Bound(i) => Type::new(TypeKind::Bound(Level::to_index(depth, *i))),
-            TypeKind::Tuple(p) => Type::new(TypeKind::Tuple(p.quote(depth))),
-            TypeKind::Application(func, arg) => {
-                let func = func.quote(depth);
-                let arg = arg.quote(depth);
-                Type::new(TypeKind::Application(func, arg))
-            }
-            TypeKind::Error => Type::new(TypeKind::Error),
-            TypeKind::Qualified(from, to) => {
-                let from = from.clone().quote(depth);
-                let to = to.clone().quote(depth);
-                Type::new(TypeKind::Qualified(from, to))
-            }
-            TypeKind::Constraint => Type::new(TypeKind::Constraint),
+        quote(self, depth, false)
+    }
+}
+
+impl Type<Virtual> {
+    /// Quotes a type keeping the synonyms that it was written with. It's only used for messages,
+    /// because the rest of the compiler expects the definitions of the synonyms.
+    pub fn quote_named(&self, depth: Level) -> Type<Real> {
+        quote(self, depth, true)
+    }
+}
+
+fn quote_hole(hole: &Hole<Virtual>, depth: Level, named: bool) -> Type<Real> {
+    match &*hole.0.borrow() {
+        HoleInner::Empty(_, _, _) => Type::new(TypeKind::Hole(hole.clone())),
+        HoleInner::Filled(f) => quote(f, depth, named),
+    }
+}
+
+fn quote_many(types: &[Type<Virtual>], depth: Level, named: bool) -> Vec<Type<Real>> {
+    types.iter().map(|typ| quote(typ, depth, named)).collect()
+}
+
+fn quote(typ: &Type<Virtual>, depth: Level, named: bool) -> Type<Real> {
+    match typ.as_ref() {
+        TypeKind::Type => Type::new(TypeKind::Type),
+        TypeKind::Arrow(pi) => Type::new(TypeKind::Arrow(real::Arrow {
+            typ: quote(&pi.typ, depth, named),
+            body: quote(&pi.body, depth, named),
+        })),
+        TypeKind::Forall(f) => Type::new(TypeKind::Forall(real::Forall {
+            name: f.name.clone(),
+            kind: quote(&f.kind, depth, named),
+            body: quote(
+                &f.body
+                    .apply_local(Some(f.name.clone()), Type::new(TypeKind::Bound(depth))),
+                depth.inc(),
+                named,
+            ),
+        })),
+        TypeKind::Hole(h) => quote_hole(h, depth, named),
+        TypeKind::Variable(v) => Type::new(TypeKind::Variable(v.clone())),
+        TypeKind::Bound(i) => Type::new(TypeKind::Bound(Level::to_index(depth, *i))),
+        TypeKind::Tuple(p) => Type::new(TypeKind::Tuple(quote_many(p, depth, named))),
+        TypeKind::Application(func, arg) => {
+            let func = quote(func, depth, named);
+            let arg = quote(arg, depth, named);
+            Type::new(TypeKind::Application(func, arg))
+        }
+        TypeKind::Error => Type::new(TypeKind::Error),
+        TypeKind::Qualified(from, to) => {
+            let from = quote(from, depth, named);
+            let to = quote(to, depth, named);
+            Type::new(TypeKind::Qualified(from, to))
         }
+        TypeKind::Constraint => Type::new(TypeKind::Constraint),
+        TypeKind::Effect(effects, typ) => Type::new(TypeKind::Effect(
+            quote_many(effects, depth, named),
+            quote(typ, depth, named),
+        )),
+        TypeKind::Synonym(synonym, typ) if named => Type::new(TypeKind::Synonym(
+            quote(synonym, depth, named),
+            quote(typ, depth, named),
+        )),
+        TypeKind::Synonym(_, typ) => quote(typ, depth, named),
     }
 }
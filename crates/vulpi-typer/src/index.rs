@@ -0,0 +1,59 @@
+//! Index of the types that were inferred for expressions and bindings. It's used by tools like the
+//! language server to show the type of the thing under the cursor.
+
+use std::collections::HashMap;
+
+use vulpi_location::{Byte, FileId, Span};
+use vulpi_syntax::r#abstract::Qualified;
+
+use crate::{
+    real::{Real, Show},
+    Env, Type,
+};
+
+/// The type of a piece of code and the name that it refers to, if it refers to one.
+#[derive(Clone)]
+pub struct Entry {
+    pub span: Span,
+    pub name: Option<Qualified>,
+    pub typ: Type<Real>,
+    env: Env,
+}
+
+impl Entry {
+    /// Pretty prints the type. Holes are printed with the value that they have at the time of the
+    /// call, so it should be called after the type checking finishes.
+    pub fn show(&self) -> Show {
+        self.typ.show(&self.env)
+    }
+}
+
+#[derive(Default)]
+pub struct Index {
+    entries: HashMap<FileId, Vec<Entry>>,
+}
+
+impl Index {
+    pub fn add(&mut self, span: Span, name: Option<Qualified>, typ: Type<Real>, env: Env) {
+        // Desugared nodes have empty spans and cannot be pointed at.
+        if span.start == span.end {
+            return;
+        }
+
+        self.entries.entry(span.file).or_default().push(Entry {
+            span,
+            name,
+            typ,
+            env,
+        });
+    }
+
+    /// Finds the innermost entry that contains a position of a file.
+    pub fn find(&self, file: FileId, place: Byte) -> Option<&Entry> {
+        self.entries
+            .get(&file)?
+            .iter()
+            .filter(|entry| entry.span.start <= place && place <= entry.span.end)
+            .min_by_key(|entry| entry.span.end.0 - entry.span.start.0)
+    }
+}
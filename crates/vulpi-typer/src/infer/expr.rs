@@ -2,6 +2,7 @@
 
 use crate::coverage::Problem;
 use crate::coverage::Witness;
+use crate::effects::Scope;
 use crate::r#virtual;
 use crate::real::Real;
 use crate::TypeKind;
@@ -20,7 +21,12 @@ use vulpi_syntax::{
 
 use crate::eval::Eval;
 use crate::eval::Quote;
-use crate::{context::Context, errors::TypeErrorKind, r#virtual::Virtual, Env, Type};
+use crate::{
+    context::Context,
+    errors::{MissingArm, TypeErrorKind},
+    r#virtual::Virtual,
+    Env, Type,
+};
 
 use super::Infer;
 
@@ -41,7 +47,7 @@ impl Infer for Expr {
                     env.set_current_span(arg.span.clone());
 
                     if let Some((left, right)) = ctx.as_function(&env, typ.deref()) {
-                        let arg = arg.check(left, (ctx, env.clone()));
+                        let arg = arg.check(left, (ctx, env.expecting(app.func.span.clone())));
                         elab_args.push(arg);
                         typ = right;
                     } else {
@@ -55,27 +61,31 @@ impl Infer for Expr {
                         );
                     }
                 }
-                
 
-                (
-                    typ.clone(),
-                    elab_args.into_iter().fold(func_elab, |acc, arg| {
-                        Spanned::new(
-                            Box::new(elaborated::ExprKind::Application(
-                                elaborated::ApplicationExpr {
-                                    typ: typ.quote(env.level),
-                                    func: acc,
-                                    args: arg,
-                                },
-                            )),
-                            self.span.clone(),
-                        )
-                    }).data,
-                )
+                let elab = elab_args.into_iter().fold(func_elab, |acc, arg| {
+                    Spanned::new(
+                        Box::new(elaborated::ExprKind::Application(
+                            elaborated::ApplicationExpr {
+                                typ: typ.quote(env.level),
+                                func: acc,
+                                args: arg,
+                            },
+                        )),
+                        self.span.clone(),
+                    )
+                });
+
+                env.set_current_span(self.span.clone());
+                ctx.run_computation(&env, typ, elab)
             }
-            ExprKind::Variable(m) => (
-                env.vars.get(m).unwrap().clone(),
-                Box::new(elaborated::ExprKind::Variable(m.clone())),
+            // The variables of patterns that have errors are not bound, and they were reported.
+            ExprKind::Variable(m) => ctx.run_computation(
+                &env,
+                env.vars.get(m).cloned().unwrap_or_else(Type::error),
+                Spanned::new(
+                    Box::new(elaborated::ExprKind::Variable(m.clone())),
+                    self.span.clone(),
+                ),
             ),
             ExprKind::Constructor(n) => (
                 ctx.modules.constructor(n).0.eval(&env),
@@ -84,13 +94,33 @@ impl Infer for Expr {
                     n.clone(),
                 )),
             ),
-            ExprKind::Function(n) => (
-                ctx.modules.let_decl(n).typ.clone(),
-                Box::new(elaborated::ExprKind::Function(
+            ExprKind::Function(n) => {
+                let typ = ctx.modules.let_decl(n).typ.clone();
+
+                let func = Box::new(elaborated::ExprKind::Function(
                     n.clone(),
-                    ctx.modules.let_decl(n).typ.clone().quote(env.level),
-                )),
-            ),
+                    typ.quote(env.level),
+                ));
+
+                // Each constraint of the function turns into a dictionary that is passed before
+                // the arguments.
+                let (typ, evidences) = ctx.instantiate_constraints(&env, typ);
+
+                let func = evidences.into_iter().fold(func, |acc, evidence| {
+                    Box::new(elaborated::ExprKind::Application(
+                        elaborated::ApplicationExpr {
+                            typ: typ.quote(env.level),
+                            func: Spanned::new(acc, self.span.clone()),
+                            args: Spanned::new(
+                                Box::new(elaborated::ExprKind::Dictionary(evidence)),
+                                self.span.clone(),
+                            ),
+                        },
+                    ))
+                });
+
+                ctx.run_computation(&env, typ, Spanned::new(func, self.span.clone()))
+            }
             ExprKind::Let(e) => {
                 let (val_ty, body_elab) = e.body.infer((ctx, env.clone()));
 
@@ -155,13 +185,28 @@ impl Infer for Expr {
                 }
 
                 if perform {
-                    let arms = arms.iter().map(|x| ctx.instantiate(&env, x)).collect();
+                    let arms: Vec<_> = arms.iter().map(|x| ctx.instantiate(&env, x)).collect();
 
-                    let problem = Problem::exhaustiveness(&elab_arms, arms);
+                    let problem = Problem::exhaustiveness(&elab_arms, arms.clone());
 
                     if let Witness::NonExhaustive(case) = problem.exaustive(ctx, env.clone()) {
-                        ctx.report(&env, TypeErrorKind::NonExhaustive(case));
+                        if let (Some(first), Some(last)) =
+                            (when.scrutinee.first(), when.scrutinee.last())
+                        {
+                            env.set_current_span(first.span.clone().mix(last.span.clone()));
+                        }
+
+                        let missing = MissingArm::after(&when.arms, "");
+                        ctx.report(
+                            &env,
+                            TypeErrorKind::NonExhaustive(
+                                case.with_lists(ctx.list.as_ref()),
+                                missing,
+                            ),
+                        );
                     };
+
+                    ctx.check_redundancy(&env, &when.arms, &elab_arms, &arms);
                 }
 
                 (
@@ -186,15 +231,17 @@ impl Infer for Expr {
 
                 (typ, Box::new(elaborated::ExprKind::Do(stmts)))
             }
+            ExprKind::Handler(handler) => handler.infer((ctx, env.clone())),
+            ExprKind::Cases(cases) => cases.infer((ctx, env.clone())),
             ExprKind::Literal(n) => {
-                let (typ, elab) = n.infer((ctx, env));
+                let (typ, elab) = n.infer((ctx, env.clone()));
                 (typ, Box::new(elaborated::ExprKind::Literal(elab)))
             }
             ExprKind::Annotation(ann) => {
                 let (expr_typ, elab_expr) = ann.expr.infer((ctx, env.clone()));
                 let (typ, _) = ann.typ.infer((ctx, env.clone()));
                 let right = typ.eval(&env);
-                ctx.subsumes(env.clone(), expr_typ, right.clone());
+                ctx.subsumes(env.expecting(ann.typ.span.clone()), expr_typ, right.clone());
                 (right, elab_expr.data)
             }
             ExprKind::Lambda(lam) => {
@@ -205,7 +252,11 @@ impl Infer for Expr {
                     env.add_var(binding.0, binding.1)
                 }
 
-                let (body, elab_body) = lam.body.infer((ctx, env.clone()));
+                let ((body, elab_body), scope) = ctx.effect_scope(Scope::Open(vec![]), |ctx| {
+                    lam.body.infer((ctx, env.clone()))
+                });
+
+                let (body, elab_body) = ctx.computation(scope, body, elab_body);
 
                 (
                     Type::new(TypeKind::Arrow(r#virtual::Pi { typ: pat_ty, body })),
@@ -405,6 +456,14 @@ impl Infer for Expr {
             }
         };
 
+        let name = match &self.data {
+            ExprKind::Function(name) | ExprKind::Constructor(name) => Some(name.clone()),
+            _ => None,
+        };
+
+        ctx.index
+            .add(self.span.clone(), name, elem.0.quote(env.level), env.clone());
+
         (elem.0, Spanned::new(elem.1, self.span.clone()))
     }
 }
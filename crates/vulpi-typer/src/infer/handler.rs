@@ -0,0 +1,259 @@
+//! Inference of handlers and `cases` expressions.
+
+use vulpi_location::Spanned;
+use vulpi_syntax::{
+    elaborated,
+    r#abstract::{CasesExpr, HandlerExpr, PatternArm, PatternKind},
+};
+
+use crate::{
+    check::Check,
+    context::Context,
+    coverage::{Problem, Witness},
+    effects::{same_effect, Scope},
+    errors::{MissingArm, TypeErrorKind},
+    eval::Quote,
+    r#virtual::Virtual,
+    real::Real,
+    Env, Type, TypeKind,
+};
+
+use super::Infer;
+
+type Elaborated = Box<elaborated::ExprKind<Type<Real>>>;
+
+impl Infer for HandlerExpr {
+    type Return = (Type<Virtual>, Elaborated);
+
+    type Context<'a> = (&'a mut Context, Env);
+
+    fn infer(&self, (ctx, env): Self::Context<'_>) -> Self::Return {
+        let span = env.span.borrow().clone();
+
+        let (handler_ty, elab_handler) = self.handler.infer((ctx, env.clone()));
+
+        let Some((param, ret)) = ctx.as_function(&env, handler_ty.clone()) else {
+            ctx.report(
+                &env,
+                TypeErrorKind::NotAHandler(env.clone(), handler_ty.quote(env.level)),
+            );
+            return (Type::error(), Box::new(elaborated::ExprKind::Error));
+        };
+
+        let request = ctx.find_prelude_type("Request", env.clone());
+        let effect = ctx.hole(&env, Type::typ());
+        let value = ctx.hole(&env, Type::typ());
+
+        ctx.subsumes(
+            env.clone(),
+            Type::<Virtual>::application(request, vec![effect.clone(), value.clone()]),
+            param,
+        );
+
+        let effect = effect.deref();
+        let (head, _) = effect.application_spine();
+
+        let name = match head.deref().as_ref() {
+            TypeKind::Variable(name) => name.clone(),
+            TypeKind::Error => return (Type::error(), Box::new(elaborated::ExprKind::Error)),
+            _ => {
+                ctx.report(
+                    &env,
+                    TypeErrorKind::NotAHandler(env.clone(), handler_ty.quote(env.level)),
+                );
+                return (Type::error(), Box::new(elaborated::ExprKind::Error));
+            }
+        };
+
+        let (elab_body, scope) = ctx.effect_scope(Scope::Handler(effect, vec![]), |ctx| {
+            self.expr.check(value, (ctx, env.clone()))
+        });
+
+        let ret = ctx.instantiate_all(&env, &ret);
+
+        env.set_current_span(span.clone());
+
+        let (allowed, typ, effectful) = match ret.deref().as_ref() {
+            TypeKind::Effect(effects, typ) => {
+                ctx.perform(&env, effects);
+                (effects.clone(), typ.clone(), true)
+            }
+            _ => (vec![], ret.clone(), false),
+        };
+
+        if let Scope::Handler(_, passing) = scope {
+            for effect in passing {
+                match same_effect(&allowed, &effect) {
+                    Some(other) => ctx.subsumes(env.clone(), effect, other),
+                    None => ctx.report(
+                        &env,
+                        TypeErrorKind::EffectThroughHandler(env.clone(), effect.quote(env.level)),
+                    ),
+                }
+            }
+        }
+
+        let body = Spanned::new(
+            Box::new(elaborated::ExprKind::Computation(elab_body)),
+            span.clone(),
+        );
+
+        let handle = elaborated::ExprKind::Handle(elaborated::HandleExpr {
+            effect: name,
+            body,
+            handler: elab_handler,
+            effectful,
+        });
+
+        (
+            typ,
+            Box::new(elaborated::ExprKind::Perform(Spanned::new(
+                Box::new(handle),
+                span,
+            ))),
+        )
+    }
+}
+
+/// Infers the arms of a handler. Each one of them receives a request that is either an operation of
+/// the handled effect or the final value of the computation.
+fn infer_handler_arms(
+    ctx: &mut Context,
+    env: Env,
+    arms: &[PatternArm],
+) -> (
+    Type<Virtual>,
+    Type<Virtual>,
+    Vec<elaborated::PatternArm<Type<Real>>>,
+) {
+    let request = ctx.find_prelude_type("Request", env.clone());
+    let effect = ctx.hole(&env, Type::typ());
+    let value = ctx.hole(&env, Type::typ());
+
+    let scrutinee = Type::<Virtual>::application(request, vec![effect, value.clone()]);
+    let ret = ctx.hole(&env, Type::typ());
+
+    let mut elab_arms = Vec::new();
+
+    for arm in arms {
+        if arm.patterns.len() != 1 {
+            ctx.report(&env, TypeErrorKind::WrongArity(1, arm.patterns.len()));
+            return (Type::error(), Type::error(), vec![]);
+        }
+
+        let mut map = Default::default();
+        let pat = &arm.patterns[0];
+
+        let elab_pat = if let PatternKind::Effect(_) = &pat.data {
+            let (typ, elab) = pat.infer((ctx, &mut map, env.clone()));
+            ctx.subsumes(env.clone(), typ, scrutinee.clone());
+            elab
+        } else {
+            let elab = pat.check(value.clone(), (ctx, &mut map, env.clone()));
+            Box::new(elaborated::PatternKind::Application(
+                elaborated::PatApplication {
+                    func: elaborated::return_constructor(),
+                    args: vec![elab],
+                },
+            ))
+        };
+
+        let mut env = env.clone();
+
+        for binding in map {
+            env.add_var(binding.0, binding.1);
+        }
+
+        let (typ, elab_expr) = arm.expr.infer((ctx, env.clone()));
+        ctx.subsumes(env.clone(), typ, ret.clone());
+
+        let elab_guard = arm.guard.as_ref().map(|guard| {
+            let bool = ctx.find_prelude_type("Bool", env.clone());
+            guard.check(bool, (ctx, env.clone()))
+        });
+
+        elab_arms.push(elaborated::PatternArm {
+            patterns: vec![elab_pat],
+            guard: elab_guard,
+            expr: elab_expr,
+        });
+    }
+
+    (scrutinee, ret, elab_arms)
+}
+
+impl Infer for CasesExpr {
+    type Return = (Type<Virtual>, Elaborated);
+
+    type Context<'a> = (&'a mut Context, Env);
+
+    fn infer(&self, (ctx, env): Self::Context<'_>) -> Self::Return {
+        let span = env.span.borrow().clone();
+
+        let is_handler = self.arms.iter().any(|arm| {
+            arm.patterns
+                .iter()
+                .any(|pat| matches!(pat.data, PatternKind::Effect(_)))
+        });
+
+        ctx.errored = false;
+
+        let ((types, ret, elab_arms), scope) = ctx.effect_scope(Scope::Open(vec![]), |ctx| {
+            if is_handler {
+                let (scrutinee, ret, elab_arms) = infer_handler_arms(ctx, env.clone(), &self.arms);
+                (vec![scrutinee], ret, elab_arms)
+            } else {
+                let (_, types, ret, elab_arms) = self.arms.infer((ctx, env.clone()));
+                (types, ret, elab_arms)
+            }
+        });
+
+        if !ctx.errored {
+            let types: Vec<_> = types.iter().map(|x| ctx.instantiate(&env, x)).collect();
+
+            let problem = Problem::exhaustiveness(&elab_arms, types.clone());
+
+            if let Witness::NonExhaustive(case) = problem.exaustive(ctx, env.clone()) {
+                let missing = MissingArm::after(&self.arms, "");
+                ctx.report(
+                    &env,
+                    TypeErrorKind::NonExhaustive(case.with_lists(ctx.list.as_ref()), missing),
+                );
+            };
+
+            ctx.check_redundancy(&env, &self.arms, &elab_arms, &types);
+        }
+
+        let names: Vec<_> = types.iter().map(|_| ctx.new_variable()).collect();
+
+        let body = Spanned::new(
+            Box::new(elaborated::ExprKind::When(elaborated::WhenExpr {
+                scrutinee: names
+                    .iter()
+                    .map(|name| {
+                        Spanned::new(
+                            Box::new(elaborated::ExprKind::Variable(name.clone())),
+                            span.clone(),
+                        )
+                    })
+                    .collect(),
+                arms: elab_arms,
+            })),
+            span.clone(),
+        );
+
+        let (ret, body) = ctx.computation(scope, ret, body);
+
+        let elab = names.into_iter().rev().fold(body, |body, name| {
+            Spanned::new(
+                Box::new(elaborated::ExprKind::Lambda(elaborated::LambdaExpr {
+                    param: Box::new(elaborated::PatternKind::Variable(name)),
+                    body,
+                })),
+                span.clone(),
+            )
+        });
+
+        (Type::<Virtual>::function(types, ret), elab.data)
+    }
+}
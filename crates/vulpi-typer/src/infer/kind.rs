@@ -18,7 +18,7 @@ impl Infer for Kind {
 
         match &self.data {
             KindType::Star => Type::typ(),
-            KindType::Constraint => todo!(),
+            KindType::Constraint => Type::constraint(),
             KindType::Arrow(l, r) => {
                 let l = l.infer(context.clone());
                 let r = r.infer(context);
@@ -4,6 +4,7 @@
 use vulpi_location::Spanned;
 
 pub mod expr;
+pub mod handler;
 pub mod kind;
 pub mod literal;
 pub mod pat;
@@ -4,18 +4,21 @@ use std::collections::HashMap;
 
 use vulpi_intern::Symbol;
 use vulpi_syntax::{
-    elaborated::{self, PatApplication},
+    elaborated::{self, PatApplication, PatOr, PatRecord},
     r#abstract::Pattern,
     r#abstract::PatternArm,
     r#abstract::PatternKind,
+    r#abstract::Qualified,
 };
 
 use crate::{
+    check::Check,
     context::Context,
     errors::TypeErrorKind,
+    module::Def,
     real::Real,
     Env, Kind, Type,
-    {eval::Eval, r#virtual::Virtual},
+    {eval::Eval, eval::Quote, r#virtual::Virtual},
 };
 
 use super::Infer;
@@ -125,6 +128,41 @@ impl Infer for Vec<PatternArm> {
     }
 }
 
+/// Infers the type of a constructor applied to patterns that were already inferred.
+fn infer_application(
+    ctx: &mut Context,
+    env: Env,
+    func: &Qualified,
+    args: Vec<(Type<Virtual>, elaborated::Pattern)>,
+) -> (Type<Virtual>, elaborated::Pattern) {
+    let (typ, _, _) = ctx.modules.constructor(func);
+
+    // Constructors without arguments are never applied, so the type has to be instantiated here.
+    let mut typ = ctx.instantiate_all(&env, &typ.eval(&env));
+
+    let mut elab_args = Vec::new();
+
+    for (arg_ty, elab_arg) in args {
+        elab_args.push(elab_arg);
+
+        let Some((param_ty, rest)) = ctx.as_function(&env, typ) else {
+            unreachable!()
+        };
+
+        typ = rest;
+
+        ctx.subsumes(env.clone(), arg_ty, param_ty);
+    }
+
+    (
+        typ,
+        Box::new(elaborated::PatternKind::Application(PatApplication {
+            func: func.clone(),
+            args: elab_args,
+        })),
+    )
+}
+
 impl Infer for Pattern {
     type Return = (Type<Virtual>, elaborated::Pattern);
 
@@ -156,6 +194,9 @@ impl Infer for Pattern {
             PatternKind::Variable(symbol) => {
                 let value = ctx.hole(&env, Type::typ());
 
+                ctx.index
+                    .add(self.span.clone(), None, value.quote(env.level), env.clone());
+
                 if let Some(typ) = map.get(symbol) {
                     ctx.subsumes(env, typ.clone(), value.clone());
                 } else {
@@ -178,45 +219,138 @@ impl Infer for Pattern {
                 ctx.subsumes(env, eval_typ.clone(), value);
                 (eval_typ, pat)
             }
-            PatternKind::Or(_) => {
-                unimplemented!("Or patterns are not yet implemented")
+            PatternKind::Or(or) => {
+                // The variables are shared between the alternatives, so the ones that are bound in
+                // both of them get the same type.
+                let (left_ty, left) = or.left.infer((ctx, map, env.clone()));
+                let (right_ty, right) = or.right.infer((ctx, map, env.clone()));
+
+                env.set_current_span(self.span.clone());
+                ctx.subsumes(env, right_ty, left_ty.clone());
+
+                (
+                    left_ty,
+                    Box::new(elaborated::PatternKind::Or(PatOr { left, right })),
+                )
             }
             PatternKind::Application(app) => {
-                let (typ, arity, _) = ctx.modules.constructor(&app.func);
-
-                let mut typ = typ.eval(&env);
+                let (_, arity, _) = ctx.modules.constructor(&app.func);
 
                 if arity != app.args.len() {
                     ctx.report(&env, TypeErrorKind::WrongArity(arity, app.args.len()));
                     return (Type::error(), Box::new(elaborated::PatternKind::Error));
                 }
 
-                let mut types = Vec::new();
-                let mut args = Vec::new();
+                let args = app
+                    .args
+                    .iter()
+                    .map(|arg| arg.infer((ctx, map, env.clone())))
+                    .collect();
 
-                for arg in &app.args {
-                    let (arg_ty, elab_arg) = arg.infer((ctx, map, env.clone()));
+                infer_application(ctx, env, &app.func, args)
+            }
+            PatternKind::Record(record) => {
+                let typ = ctx.modules.typ(&record.name);
 
-                    types.push(arg_ty.clone());
-                    args.push(elab_arg);
+                let Def::Record(fields) = typ.def else {
+                    ctx.report(&env, TypeErrorKind::NotARecord);
+                    return (Type::error(), Box::new(elaborated::PatternKind::Error));
+                };
 
-                    let Some((param_ty, rest)) = ctx.as_function(&env, typ) else {
-                        unreachable!()
-                    };
+                let binders = typ
+                    .binders
+                    .iter()
+                    .map(|x| ctx.hole::<Virtual>(&env, x.1.clone()))
+                    .collect::<Vec<_>>();
+
+                let ret_type = Type::<Virtual>::application(
+                    Type::variable(record.name.clone()),
+                    binders.clone(),
+                );
 
-                    typ = rest;
+                let mut patterns = HashMap::new();
 
-                    ctx.subsumes(env.clone(), arg_ty, param_ty);
+                for (span, name, pat) in &record.fields {
+                    env.set_current_span(span.clone());
+
+                    if !fields.contains(name) {
+                        ctx.report(&env, TypeErrorKind::NotFoundField);
+                        continue;
+                    }
+
+                    if patterns.contains_key(name) {
+                        ctx.report(&env, TypeErrorKind::DuplicatedField);
+                        continue;
+                    }
+
+                    let field = ctx.modules.field(name).eval(&env);
+                    let field = ctx.instantiate_with_arguments(&field, binders.clone());
+
+                    let elab_pat = pat.check(field, (ctx, map, env.clone()));
+                    patterns.insert(name.clone(), elab_pat);
+                }
+
+                env.set_current_span(self.span.clone());
+
+                let mut elab_fields = Vec::new();
+
+                for field in fields {
+                    let pat = match patterns.remove(&field) {
+                        Some(pat) => pat,
+                        None => {
+                            if !record.rest {
+                                ctx.report(&env, TypeErrorKind::MissingField(field.name.clone()));
+                            }
+
+                            Box::new(elaborated::PatternKind::Wildcard)
+                        }
+                    };
+
+                    elab_fields.push((field, pat));
                 }
 
                 (
-                    typ,
-                    Box::new(elaborated::PatternKind::Application(PatApplication {
-                        func: app.func.clone(),
-                        args,
+                    ret_type,
+                    Box::new(elaborated::PatternKind::Record(PatRecord {
+                        name: record.name.clone(),
+                        fields: elab_fields,
                     })),
                 )
             }
+            PatternKind::Effect(eff) => {
+                let module = ctx.modules.get(&eff.func.path);
+
+                let Some((_, arity, _)) = module.constructors.get(&eff.func.name).cloned() else {
+                    ctx.report(&env, TypeErrorKind::NotAnOperation(eff.func.clone()));
+                    return (Type::error(), Box::new(elaborated::PatternKind::Error));
+                };
+
+                // The continuation is the last argument of the request.
+                if arity != eff.args.len() + 1 {
+                    ctx.report(&env, TypeErrorKind::WrongArity(arity - 1, eff.args.len()));
+                    return (Type::error(), Box::new(elaborated::PatternKind::Error));
+                }
+
+                let mut args: Vec<_> = eff
+                    .args
+                    .iter()
+                    .map(|arg| arg.infer((ctx, map, env.clone())))
+                    .collect();
+
+                let cont = ctx.hole(&env, Type::typ());
+
+                let elab_cont = match &eff.cont {
+                    Some(name) => {
+                        map.insert(name.clone(), cont.clone());
+                        elaborated::PatternKind::Variable(name.clone())
+                    }
+                    None => elaborated::PatternKind::Wildcard,
+                };
+
+                args.push((cont, Box::new(elab_cont)));
+
+                infer_application(ctx, env, &eff.func, args)
+            }
             PatternKind::Error => (Type::error(), Box::new(elaborated::PatternKind::Error)),
         }
     }
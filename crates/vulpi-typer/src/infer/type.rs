@@ -2,16 +2,106 @@
 
 use crate::{
     context::Context,
+    entail::substitution,
     errors::TypeErrorKind,
     eval::{Eval, Quote},
     r#virtual::Env,
     r#virtual::Virtual,
     real::{self, Real},
+    module::Def,
     Index, Kind, Type,
 };
 
 use super::Infer;
-use vulpi_syntax::{r#abstract, r#abstract::TypeKind};
+use vulpi_syntax::{r#abstract, r#abstract::Qualified, r#abstract::TypeKind};
+
+/// Infers the application of a type to arguments with the kind of the type.
+fn infer_application(
+    ctx: &mut Context,
+    env: Env,
+    typ: Type<Real>,
+    mut kind: Kind<Virtual>,
+    args: &[r#abstract::Type],
+) -> (Type<Real>, Kind<Virtual>) {
+    let mut types = Vec::new();
+
+    for arg in args {
+        env.set_current_span(arg.span.clone());
+
+        let (arg_ty, arg_kind) = arg.infer((ctx, env.clone()));
+
+        types.push(arg_ty);
+
+        if let Some((left, right)) = ctx.as_function(&env, kind.deref()) {
+            ctx.subsumes(env.clone(), arg_kind, left);
+            kind = right;
+        } else {
+            ctx.report(
+                &env,
+                TypeErrorKind::NotAFunction(env.clone(), kind.quote(env.level)),
+            );
+            return (Type::error(), Kind::error());
+        }
+    }
+
+    (Type::<Real>::application(typ, types), kind)
+}
+
+/// Replaces a type synonym by its definition. Synonyms have to receive all of their parameters,
+/// and the arguments after them are applied to the result. The synonym is kept next to its
+/// definition, so the messages show the type as it was written.
+fn expand_synonym(
+    ctx: &mut Context,
+    env: Env,
+    name: &Qualified,
+    args: &[r#abstract::Type],
+) -> (Type<Real>, Kind<Virtual>) {
+    let data = ctx.modules.typ(name);
+
+    let Def::Synonym(body) = data.def else {
+        unreachable!()
+    };
+
+    if args.len() < data.binders.len() {
+        ctx.report(
+            &env,
+            TypeErrorKind::PartialSynonym(name.clone(), data.binders.len(), args.len()),
+        );
+        return (Type::error(), Kind::error());
+    }
+
+    let (params, rest) = args.split_at(data.binders.len());
+
+    let mut kinds = Vec::new();
+    let mut types = Vec::new();
+    let mut values = Vec::new();
+
+    for ((_, kind), param) in data.binders.iter().zip(params) {
+        env.set_current_span(param.span.clone());
+
+        let (typ, param_kind) = param.infer((ctx, env.clone()));
+        ctx.subsumes(env.clone(), param_kind, kind.clone());
+
+        kinds.push(kind.clone());
+        values.push(typ.eval(&env));
+        types.push(typ);
+    }
+
+    let definition = body.eval(&substitution(&kinds, values)).quote(env.level);
+    let synonym = Type::<Real>::application(Type::variable(name.clone()), types);
+    let typ = Type::new(crate::TypeKind::Synonym(synonym, definition));
+
+    let mut kind = data.kind;
+
+    for _ in 0..data.binders.len() {
+        let Some((_, right)) = ctx.as_function(&env, kind.deref()) else {
+            unreachable!()
+        };
+        kind = right;
+    }
+
+    infer_application(ctx, env, typ, kind, rest)
+}
 
 impl Infer for r#abstract::Type {
     type Return = (Type<Real>, Kind<Virtual>);
@@ -34,6 +124,31 @@ impl Infer for r#abstract::Type {
                 let typ = Type::new(crate::TypeKind::Arrow(real::Arrow { typ, body }));
                 (typ, Kind::typ())
             }
+            TypeKind::Effect(eff) => {
+                let mut effects = Vec::new();
+
+                for effect in &eff.effects {
+                    env.set_current_span(effect.span.clone());
+
+                    let (typ, kind) = effect.infer((ctx, env.clone()));
+                    ctx.subsumes(env.clone(), kind, Kind::typ());
+
+                    if !ctx.is_effect(&typ) {
+                        ctx.report(
+                            &env,
+                            TypeErrorKind::NotAnEffect(env.clone(), typ.clone()),
+                        );
+                    }
+
+                    effects.push(typ);
+                }
+
+                let (typ, kind) = eff.typ.infer((ctx, env.clone()));
+                env.set_current_span(eff.typ.span.clone());
+                ctx.subsumes(env.clone(), kind, Kind::typ());
+
+                (Type::effect(effects, typ), Kind::typ())
+            }
             TypeKind::Tuple(t) => {
                 let mut types = Vec::new();
 
@@ -45,32 +160,15 @@ impl Infer for r#abstract::Type {
 
                 (Type::tuple(types), Kind::typ())
             }
-            TypeKind::Application(app) => {
-                let (ty, mut k) = app.func.infer((ctx, env.clone()));
-
-                let mut args = Vec::new();
-
-                for arg in &app.args {
-                    env.set_current_span(arg.span.clone());
-
-                    let (arg_ty, arg_kind) = arg.infer((ctx, env.clone()));
-
-                    args.push(arg_ty);
-
-                    if let Some((left, right)) = ctx.as_function(&env, k.deref()) {
-                        ctx.subsumes(env.clone(), arg_kind, left);
-                        k = right;
-                    } else {
-                        ctx.report(
-                            &env,
-                            TypeErrorKind::NotAFunction(env.clone(), k.quote(env.level)),
-                        );
-                        return (Type::error(), Kind::error());
-                    }
+            TypeKind::Application(app) => match &app.func.data {
+                TypeKind::Type(name) if ctx.is_synonym(name) => {
+                    expand_synonym(ctx, env, name, &app.args)
                 }
-
-                (Type::<Real>::application(ty, args), k)
-            }
+                _ => {
+                    let (ty, k) = app.func.infer((ctx, env.clone()));
+                    infer_application(ctx, env, ty, k, &app.args)
+                }
+            },
             TypeKind::Forall(forall) => {
                 let mut env = env.clone();
                 let mut names = Vec::new();
@@ -97,6 +195,7 @@ impl Infer for r#abstract::Type {
 
                 (Type::bound(Index(index)), kind)
             }
+            TypeKind::Type(name) if ctx.is_synonym(name) => expand_synonym(ctx, env, name, &[]),
             TypeKind::Type(name) => (Type::variable(name.clone()), ctx.modules.typ(name).kind),
             TypeKind::Unit => (Type::tuple(Vec::new()), Kind::typ()),
             TypeKind::Error => (Type::error(), Kind::error()),
@@ -15,12 +15,15 @@ mod errors;
 mod check;
 mod context;
 mod coverage;
+mod effects;
+mod entail;
 mod eval;
 mod infer;
-mod module;
+pub mod module;
 mod unify;
 
 pub mod declare;
+pub mod index;
 
 pub use context::Context;
 
@@ -113,6 +116,13 @@ pub enum TypeKind<S: State> {
     /// Qualified types.
     Qualified(S::Type, S::Type),
 
+    /// The type of computations that perform a set of effects before returning a value.
+    Effect(Vec<S::Type>, S::Type),
+
+    /// A synonym applied to its parameters, together with the type that it stands for. Only the
+    /// messages show the synonym, everything else looks at its definition.
+    Synonym(S::Type, S::Type),
+
     /// A type error.
     Error,
 }
@@ -160,6 +170,10 @@ impl<S: State> Type<S> {
     pub(crate) fn qualified(from: S::Type, to: S::Type) -> Type<S> {
         Type::new(TypeKind::Qualified(from, to))
     }
+
+    pub(crate) fn effect(effects: Vec<S::Type>, typ: S::Type) -> Type<S> {
+        Type::new(TypeKind::Effect(effects, typ))
+    }
 }
 
 impl<S: State> AsRef<TypeKind<S>> for Type<S> {
@@ -237,6 +251,10 @@ pub mod r#virtual {
         pub vars: im_rc::HashMap<Symbol, Type<Virtual>>,
         pub level: Level,
         pub span: RefCell<Span>,
+
+        /// Where the type that the current expression is checked against comes from. It's only
+        /// used to point at it in type mismatches.
+        pub expected: Option<Span>,
     }
 
     impl Env {
@@ -249,6 +267,13 @@ pub mod r#virtual {
             *self.span.borrow_mut() = span;
         }
 
+        /// Sets where the expected type of the next checked expression comes from.
+        pub fn expecting(&self, span: impl Into<Option<Span>>) -> Self {
+            let mut clone = self.clone();
+            clone.expected = span.into();
+            clone
+        }
+
         pub fn find(&self, name: &Symbol) -> Option<(usize, Type<Virtual>, Type<Virtual>)> {
             self.names
                 .iter()
@@ -351,7 +376,7 @@ pub mod r#virtual {
 
             spine.reverse();
 
-            (current, spine)
+            (current.deref(), spine)
         }
 
         pub fn arrow_spine(&self) -> Vec<Self> {
@@ -374,6 +399,7 @@ pub mod r#virtual {
                     HoleInner::Filled(typ) => typ.deref(),
                     _ => self.clone(),
                 },
+                TypeKind::Synonym(_, typ) => typ.deref(),
                 _ => self.clone(),
             }
         }
@@ -446,13 +472,21 @@ pub mod real {
     }
 
     impl Type<Real> {
+        /// The type that a synonym stands for, or the type itself if it isn't a synonym.
+        pub fn unfold(&self) -> Self {
+            match self.as_ref() {
+                TypeKind::Synonym(_, typ) => typ.unfold(),
+                _ => self.clone(),
+            }
+        }
+
         pub(crate) fn application_spine(&self) -> (Self, Vec<Self>) {
             let mut spine = Vec::new();
-            let mut current = self.clone();
+            let mut current = self.unfold();
 
-            while let TypeKind::Application(left, right) = current.as_ref() {
+            while let TypeKind::Application(left, right) = current.clone().as_ref() {
                 spine.push(right.clone());
-                current = left.clone();
+                current = left.unfold();
             }
 
             spine.reverse();
@@ -462,11 +496,11 @@ pub mod real {
 
         pub(crate) fn forall_spine(&self) -> (Vec<(Symbol, Self)>, Self) {
             let mut spine = Vec::new();
-            let mut current = self.clone();
+            let mut current = self.unfold();
 
-            while let TypeKind::Forall(Forall { name, kind, body }) = current.as_ref() {
+            while let TypeKind::Forall(Forall { name, kind, body }) = current.clone().as_ref() {
                 spine.push((name.clone(), kind.clone()));
-                current = body.clone();
+                current = body.unfold();
             }
 
             (spine, current)
@@ -474,11 +508,11 @@ pub mod real {
 
         pub fn arrow_spine(&self) -> Vec<Self> {
             let mut spine = Vec::new();
-            let mut current = self.clone();
+            let mut current = self.unfold();
 
-            while let TypeKind::Arrow(pi) = current.as_ref() {
+            while let TypeKind::Arrow(pi) = current.clone().as_ref() {
                 spine.push(pi.typ.clone());
-                current = pi.body.clone();
+                current = pi.body.unfold();
             }
 
             spine.push(current);
@@ -587,6 +621,18 @@ pub mod real {
                     to.format(env, f)?;
                     write!(f, ")")
                 }
+                TypeKind::Effect(effects, typ) => {
+                    write!(f, "{{")?;
+                    for (i, effect) in effects.iter().enumerate() {
+                        effect.format(env, f)?;
+                        if i != effects.len() - 1 {
+                            write!(f, ", ")?;
+                        }
+                    }
+                    write!(f, "}} ")?;
+                    typ.format(env, f)
+                }
+                TypeKind::Synonym(synonym, _) => synonym.format(env, f),
             }
         }
     }
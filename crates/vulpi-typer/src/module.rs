@@ -4,21 +4,28 @@
 
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
 use vulpi_intern::Symbol;
 use vulpi_syntax::r#abstract::Qualified;
 
-use crate::{r#virtual::Virtual, real::Real, Type};
+use crate::{
+    eval::{Eval, Quote},
+    r#virtual::{Env, Virtual},
+    real::{self, Real},
+    Hole, HoleInner, Index, Level, Type, TypeKind,
+};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Def {
     Enum(Vec<Qualified>),
     Record(Vec<Qualified>),
     Effect(Vec<Qualified>),
+    Synonym(Type<Real>),
     Type,
     Constraint
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TypeData {
     pub kind: Type<Virtual>,
     pub binders: Vec<(Symbol, Type<Virtual>)>,
@@ -26,25 +33,63 @@ pub struct TypeData {
     pub def: Def,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TraitData {
     pub kind: Type<Virtual>,
     pub binders: Vec<Type<Virtual>>,
     pub supers: Vec<Type<Real>>,
     pub signatures: Vec<(Qualified, Type<Real>)>,
+}
 
-
+/// An implementation of a trait. The types and the context are bound by the binders, so they
+/// have to be instantiated before being compared with a constraint.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InstanceData {
+    pub name: Qualified,
+    pub binders: Vec<(Symbol, Type<Virtual>)>,
+    pub types: Vec<Type<Real>>,
+    pub context: Vec<Type<Real>>,
 }
 
 #[derive(Clone)]
 pub struct LetDef {
     pub typ: Type<Virtual>,
     pub unbound: Vec<(Symbol, Type<Real>)>,
+    pub constraints: Vec<Type<Real>>,
     pub args: Vec<Type<Real>>,
     pub ret: Type<Virtual>,
 }
 
-#[derive(Default)]
+impl LetDef {
+    /// The type of the declaration as it's written in the source code.
+    pub fn signature(&self) -> Type<Real> {
+        self.typ.quote(Level(0))
+    }
+}
+
+/// Only the type of a declaration is stored. The rest is only used to check the body of the
+/// declaration, and the bodies of stored modules are not checked again.
+impl Serialize for LetDef {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.typ.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LetDef {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let typ = Type::<Virtual>::deserialize(deserializer)?;
+
+        Ok(LetDef {
+            typ: typ.clone(),
+            unbound: vec![],
+            constraints: vec![],
+            args: vec![],
+            ret: typ,
+        })
+    }
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Interface {
     /// The types of the functions.
     pub variables: HashMap<Symbol, LetDef>,
@@ -60,6 +105,46 @@ pub struct Interface {
 
     /// Traits.
     pub traits: HashMap<Symbol, TraitData>,
+
+    /// The instances of the traits of the module.
+    pub instances: HashMap<Symbol, Vec<InstanceData>>,
+}
+
+impl Interface {
+    /// Describes everything that the module declares, one declaration per line and sorted by
+    /// name, like `let map : ...` or `type List : (Type -> Type)`.
+    pub fn describe(&self) -> Vec<String> {
+        fn sorted<T>(map: &HashMap<Symbol, T>) -> Vec<(&Symbol, &T)> {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(name, _)| name.get());
+            entries
+        }
+
+        let env = Env::default();
+        let mut lines = vec![];
+
+        for (name, data) in sorted(&self.types) {
+            let keyword = match data.def {
+                Def::Effect(_) => "effect",
+                Def::Constraint => "trait",
+                _ => "type",
+            };
+
+            let kind = data.kind.quote(env.level);
+            lines.push(format!("{} {} : {}", keyword, name.get(), kind.show(&env)));
+        }
+
+        for (name, (typ, _, _)) in sorted(&self.constructors) {
+            lines.push(format!("{} : {}", name.get(), typ.show(&env)));
+        }
+
+        for (name, def) in sorted(&self.variables) {
+            let typ = def.signature();
+            lines.push(format!("let {} : {}", name.get(), typ.show(&env)));
+        }
+
+        lines
+    }
 }
 
 #[derive(Default)]
@@ -90,6 +175,20 @@ impl Modules {
         module.variables.get_mut(&qualified.name).unwrap()
     }
 
+    pub fn trait_data(&mut self, qualified: &Qualified) -> Option<TraitData> {
+        let module = self.get(&qualified.path);
+        module.traits.get(&qualified.name).cloned()
+    }
+
+    pub fn instances(&mut self, qualified: &Qualified) -> Vec<InstanceData> {
+        let module = self.get(&qualified.path);
+        module
+            .instances
+            .get(&qualified.name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
     pub fn field(&mut self, qualified: &Qualified) -> Type<Real> {
         let module = self.get(&qualified.path);
         module.fields.get(&qualified.name).unwrap().clone()
@@ -98,4 +197,186 @@ impl Modules {
     pub fn get(&mut self, id: &Symbol) -> &mut Interface {
         self.modules.entry(id.clone()).or_default()
     }
+
+    /// Takes everything that was declared by the modules in `names`. Instances are stored in the
+    /// module of their trait, so they are taken apart from the interfaces.
+    pub fn export(&self, names: &[Symbol]) -> Exported {
+        let interfaces = names
+            .iter()
+            .filter_map(|name| {
+                let interface = self.modules.get(name)?;
+
+                let interface = Interface {
+                    variables: interface.variables.clone(),
+                    constructors: interface.constructors.clone(),
+                    types: interface.types.clone(),
+                    fields: interface.fields.clone(),
+                    traits: interface.traits.clone(),
+                    instances: Default::default(),
+                };
+
+                Some((name.clone(), interface))
+            })
+            .collect();
+
+        let mut instances = vec![];
+
+        for (module, interface) in &self.modules {
+            for (name, list) in &interface.instances {
+                for instance in list {
+                    if names.contains(&instance.name.path) {
+                        let trait_name = Qualified {
+                            path: module.clone(),
+                            name: name.clone(),
+                        };
+
+                        instances.push((trait_name, instance.clone()));
+                    }
+                }
+            }
+        }
+
+        // The modules are in a map, so the instances are sorted to always export them in the
+        // same order.
+        instances.sort_by_key(|(trait_name, instance)| {
+            (trait_name.to_string(), instance.name.to_string())
+        });
+
+        Exported {
+            interfaces,
+            instances,
+        }
+    }
+
+    /// Adds the declarations of modules that were exported by another compilation.
+    pub fn import(&mut self, exported: Exported) {
+        for (name, interface) in exported.interfaces {
+            self.modules.insert(name, interface);
+        }
+
+        for (trait_name, instance) in exported.instances {
+            self.get(&trait_name.path)
+                .instances
+                .entry(trait_name.name)
+                .or_default()
+                .push(instance);
+        }
+    }
+}
+
+/// The declarations of a group of modules, in a form that can be stored and loaded without checking
+/// the modules again.
+#[derive(Serialize, Deserialize)]
+pub struct Exported {
+    interfaces: Vec<(Symbol, Interface)>,
+    instances: Vec<(Qualified, InstanceData)>,
+}
+
+/// The form in which types are stored. Holes that were filled are replaced by their content, so
+/// the stored types do not share anything with the run of the compiler that created them.
+#[derive(Serialize, Deserialize)]
+enum Stored {
+    Type,
+    Constraint,
+    Arrow(Box<Stored>, Box<Stored>),
+    Forall(Symbol, Box<Stored>, Box<Stored>),
+    Hole(Symbol, Box<Stored>, usize),
+    Variable(Qualified),
+    Bound(usize),
+    Tuple(Vec<Stored>),
+    Application(Box<Stored>, Box<Stored>),
+    Qualified(Box<Stored>, Box<Stored>),
+    Effect(Vec<Stored>, Box<Stored>),
+    Error,
+}
+
+impl Stored {
+    fn from_real(typ: &Type<Real>, depth: Level) -> Stored {
+        let store = |typ: &Type<Real>| Box::new(Stored::from_real(typ, depth));
+        let many = |types: &[Type<Real>]| types.iter().map(|x| *store(x)).collect();
+
+        match typ.as_ref() {
+            TypeKind::Type => Stored::Type,
+            TypeKind::Constraint => Stored::Constraint,
+            TypeKind::Arrow(arrow) => Stored::Arrow(store(&arrow.typ), store(&arrow.body)),
+            TypeKind::Forall(forall) => Stored::Forall(
+                forall.name.clone(),
+                store(&forall.kind),
+                Box::new(Stored::from_real(&forall.body, depth.inc())),
+            ),
+            TypeKind::Hole(hole) => match &*hole.0.borrow() {
+                HoleInner::Empty(name, kind, level) => Stored::Hole(
+                    name.clone(),
+                    Box::new(Stored::from_real(&kind.quote(Level(0)), Level(0))),
+                    level.0,
+                ),
+                HoleInner::Filled(typ) => Stored::from_real(&typ.quote(depth), depth),
+            },
+            TypeKind::Variable(name) => Stored::Variable(name.clone()),
+            TypeKind::Bound(index) => Stored::Bound(index.0),
+            TypeKind::Tuple(types) => Stored::Tuple(many(types)),
+            TypeKind::Application(func, arg) => Stored::Application(store(func), store(arg)),
+            TypeKind::Qualified(from, to) => Stored::Qualified(store(from), store(to)),
+            TypeKind::Effect(effects, typ) => Stored::Effect(many(effects), store(typ)),
+            TypeKind::Error => Stored::Error,
+            TypeKind::Synonym(_, typ) => Stored::from_real(typ, depth),
+        }
+    }
+
+    fn into_real(self) -> Type<Real> {
+        let many = |types: Vec<Stored>| types.into_iter().map(Stored::into_real).collect();
+
+        Type::new(match self {
+            Stored::Type => TypeKind::Type,
+            Stored::Constraint => TypeKind::Constraint,
+            Stored::Arrow(typ, body) => TypeKind::Arrow(real::Arrow {
+                typ: typ.into_real(),
+                body: body.into_real(),
+            }),
+            Stored::Forall(name, kind, body) => TypeKind::Forall(real::Forall {
+                name,
+                kind: kind.into_real(),
+                body: body.into_real(),
+            }),
+            Stored::Hole(name, kind, level) => {
+                let kind = kind.into_real().eval(&Env::default());
+                TypeKind::Hole(Hole::empty(name, kind, Level(level)))
+            }
+            Stored::Variable(name) => TypeKind::Variable(name),
+            Stored::Bound(index) => TypeKind::Bound(Index(index)),
+            Stored::Tuple(types) => TypeKind::Tuple(many(types)),
+            Stored::Application(func, arg) => {
+                TypeKind::Application(func.into_real(), arg.into_real())
+            }
+            Stored::Qualified(from, to) => TypeKind::Qualified(from.into_real(), to.into_real()),
+            Stored::Effect(effects, typ) => TypeKind::Effect(many(effects), typ.into_real()),
+            Stored::Error => TypeKind::Error,
+        })
+    }
+}
+
+impl Serialize for Type<Real> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Stored::from_real(self, Level(0)).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Type<Real> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Stored::deserialize(deserializer)?.into_real())
+    }
+}
+
+/// Virtual types are stored quoted. Only closed types are stored, so they are evaluated again in
+/// an empty environment.
+impl Serialize for Type<Virtual> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.quote(Level(0)).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Type<Virtual> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Type::<Real>::deserialize(deserializer)?.eval(&Env::default()))
+    }
 }
@@ -2,10 +2,9 @@
 
 #![allow(clippy::only_used_in_recursion)]
 
-use crate::{context::Context, errors::TypeErrorKind};
+use crate::{context::Context, effects::same_effect, errors::TypeErrorKind};
 
 use super::{
-    eval::Quote,
     r#virtual::Pi,
     r#virtual::{Env, Virtual},
     Hole, HoleInner, Level, Type, TypeKind,
@@ -31,6 +30,20 @@ impl Context {
                     go(ctx, env.clone(), n.typ.clone(), m.typ.clone())?;
                     go(ctx, env, m.body.clone(), n.body.clone())
                 }
+                (TypeKind::Effect(e, a), TypeKind::Effect(f, b)) => {
+                    // A computation can be used where more effects are allowed.
+                    for effect in e {
+                        let Some(other) = same_effect(f, effect) else {
+                            return Err(TypeErrorKind::TypeMismatch(
+                                env.clone(),
+                                l.quote_named(env.level),
+                                r.quote_named(env.level),
+                            ));
+                        };
+                        ctx.unify(env.clone(), effect.clone(), other)?;
+                    }
+                    go(ctx, env, a.clone(), b.clone())
+                }
                 (_, TypeKind::Forall(forall)) => {
                     let lvl_ty = Type::new(TypeKind::Bound(env.level));
                     go(
@@ -56,8 +69,8 @@ impl Context {
                     &env,
                     TypeErrorKind::TypeMismatch(
                         env.clone(),
-                        left.quote(env.level),
-                        right.quote(env.level),
+                        left.quote_named(env.level),
+                        right.quote_named(env.level),
                     ),
                 ),
                 _ => self.report(&env, kind),
@@ -163,6 +176,19 @@ impl Context {
                 self.unify(env.clone(), f.clone(), f1.clone())?;
                 self.unify(env, u.clone(), u1.clone())
             }
+            (TypeKind::Effect(e, a), TypeKind::Effect(f, b)) if e.len() == f.len() => {
+                for effect in e {
+                    let Some(other) = same_effect(f, effect) else {
+                        return Err(TypeErrorKind::TypeMismatch(
+                            env.clone(),
+                            left.quote_named(env.level),
+                            right.quote_named(env.level),
+                        ));
+                    };
+                    self.unify(env.clone(), effect.clone(), other)?;
+                }
+                self.unify(env, a.clone(), b.clone())
+            }
             (TypeKind::Hole(n), TypeKind::Hole(m)) if n == m => Ok(()),
             (TypeKind::Hole(m), _) => self.unify_hole(env, m.clone(), r),
             (_, TypeKind::Hole(m)) => self.unify_hole(env, m.clone(), l),
@@ -173,8 +199,8 @@ impl Context {
             (TypeKind::Error, _) | (_, TypeKind::Error) => Ok(()),
             (_, _) => Err(TypeErrorKind::TypeMismatch(
                 env.clone(),
-                left.quote(env.level),
-                right.quote(env.level),
+                left.quote_named(env.level),
+                right.quote_named(env.level),
             )),
         }
     }
@@ -198,6 +224,12 @@ impl Context {
                 self.occurs(env.clone(), scope, hole.clone(), f.clone())?;
                 self.occurs(env, scope, hole, a.clone())
             }
+            TypeKind::Effect(effects, typ) => {
+                for effect in effects {
+                    self.occurs(env.clone(), scope, hole.clone(), effect.clone())?;
+                }
+                self.occurs(env, scope, hole, typ.clone())
+            }
             _ => Ok(()),
         }
     }
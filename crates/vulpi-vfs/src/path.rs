@@ -3,6 +3,7 @@ use std::{
     path::PathBuf,
 };
 
+use serde::{Deserialize, Serialize};
 use vulpi_intern::Symbol;
 
 #[derive(Clone, PartialEq, Eq, Hash)]
@@ -65,7 +66,29 @@ impl Display for Path {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+/// Paths are stored as their dotted form, so they can be used as keys of maps in formats like
+/// JSON.
+impl Serialize for Path {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Path {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+
+        let segments = if string.is_empty() {
+            vec![]
+        } else {
+            string.split('.').map(Symbol::intern).collect()
+        };
+
+        Ok(Path { segments })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Qualified {
     pub path: Path,
     pub name: Symbol,